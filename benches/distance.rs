@@ -0,0 +1,37 @@
+//! Brute-force scan throughput of the exact distance kernels against a naive
+//! loop. Run with `cargo bench --bench distance`.
+
+use genai_vectors::distance;
+use std::hint::black_box;
+use std::time::Instant;
+
+const DIM: usize = 768;
+const VECTORS: usize = 100_000;
+
+fn scan(name: &str, vectors: &[f32], query: &[f32], score: impl Fn(&[f32], &[f32]) -> f32) {
+    let start = Instant::now();
+    let mut best = f32::MIN;
+    for row in vectors.chunks_exact(DIM) {
+        best = best.max(score(black_box(query), black_box(row)));
+    }
+    let elapsed = start.elapsed();
+    black_box(best);
+    println!(
+        "{:<16} {:>8.1} ms per {} vectors ({:.1} M vectors/s)",
+        name,
+        elapsed.as_secs_f64() * 1000.0,
+        VECTORS,
+        VECTORS as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn main() {
+    let vectors: Vec<f32> = (0..VECTORS * DIM).map(|i| ((i * 7919) % 1000) as f32 / 1000.0).collect();
+    let query: Vec<f32> = (0..DIM).map(|i| (i % 13) as f32 / 13.0).collect();
+    println!("kernel: {}", distance::kernel());
+
+    scan("naive dot", &vectors, &query, |a, b| a.iter().zip(b).map(|(x, y)| x * y).sum());
+    scan("dot", &vectors, &query, distance::dot);
+    scan("naive l2", &vectors, &query, |a, b| -a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>());
+    scan("l2_squared", &vectors, &query, |a, b| -distance::l2_squared(a, b));
+}
@@ -0,0 +1,42 @@
+//! Cost of turning Faiss search labels into ids, against a plain copy of the
+//! same number of `(f32, i64)` pairs. Run with `cargo bench --bench label_conversion`.
+
+use faiss::Idx;
+use genai_vectors::faiss_utils::label_ids;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LABELS: usize = 1_000;
+const ROUNDS: u32 = 10_000;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    for _ in 0..ROUNDS / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    // Every tenth label empty, as in short result lists.
+    let raw: Vec<i64> = (0..LABELS as i64).map(|i| if i % 10 == 9 { -1 } else { i }).collect();
+    let labels: Vec<Idx> = raw.iter().map(|&i| Idx::from(i)).collect();
+    let distances: Vec<f32> = (0..LABELS).map(|i| i as f32).collect();
+
+    let baseline = time(|| {
+        let pairs: (Vec<f32>, Vec<i64>) = black_box(distances.clone())
+            .into_iter()
+            .zip(black_box(raw.clone()))
+            .filter(|&(_, id)| id >= 0)
+            .unzip();
+        black_box(pairs);
+    });
+    let converted = time(|| {
+        black_box(label_ids(black_box(distances.clone()), black_box(labels.clone())));
+    });
+
+    println!("{} labels: copy {:?}, label_ids {:?} per call", LABELS, baseline, converted);
+}
@@ -0,0 +1,256 @@
+//! Exact vector distances for the paths that score raw vectors themselves
+//! (reranking, hybrid scoring, duplicate detection). Kernels use AVX2+FMA on
+//! x86_64 CPUs that have it, detected at runtime, and NEON on aarch64; other
+//! targets get a scalar loop the compiler can vectorize. Slices of different
+//! lengths are compared over the shorter one.
+
+/// Inner product.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    kernels::dot(&a[..n], &b[..n])
+}
+
+/// Squared Euclidean distance.
+pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    kernels::l2_squared(&a[..n], &b[..n])
+}
+
+/// Manhattan (L1) distance. Rarely on a hot path, so left to the compiler.
+pub fn l1(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum()
+}
+
+/// Chebyshev (L-infinity) distance.
+pub fn linf(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).fold(0.0, f32::max)
+}
+
+/// Cosine similarity; 0 when either vector is all zeros.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let norms = dot(a, a).sqrt() * dot(b, b).sqrt();
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot(a, b) / norms
+    }
+}
+
+/// Name of the kernel in use, for logs and diagnostics.
+pub fn kernel() -> &'static str {
+    kernels::name()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod kernels {
+    use super::{scalar, x86};
+
+    pub fn name() -> &'static str {
+        if x86::available() { "avx2" } else { "scalar" }
+    }
+
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        if x86::available() {
+            // SAFETY: the CPU supports AVX2 and FMA, and the slices are the same length.
+            unsafe { x86::dot(a, b) }
+        } else {
+            scalar::dot(a, b)
+        }
+    }
+
+    pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+        if x86::available() {
+            // SAFETY: as above.
+            unsafe { x86::l2_squared(a, b) }
+        } else {
+            scalar::l2_squared(a, b)
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod kernels {
+    pub use super::neon::{dot, l2_squared};
+
+    pub fn name() -> &'static str {
+        "neon"
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod kernels {
+    pub use super::scalar::{dot, l2_squared};
+
+    pub fn name() -> &'static str {
+        "scalar"
+    }
+}
+
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+mod scalar {
+    const LANES: usize = 8;
+
+    /// Independent accumulators per lane, so the loop vectorizes without
+    /// `-ffast-math` style reassociation.
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let (chunks_a, chunks_b) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+        let tail: f32 = chunks_a.remainder().iter().zip(chunks_b.remainder()).map(|(x, y)| x * y).sum();
+        for (x, y) in chunks_a.zip(chunks_b) {
+            for ((acc, x), y) in acc.iter_mut().zip(x).zip(y) {
+                *acc += x * y;
+            }
+        }
+        acc.iter().sum::<f32>() + tail
+    }
+
+    pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let (chunks_a, chunks_b) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+        let tail: f32 = chunks_a.remainder().iter().zip(chunks_b.remainder()).map(|(x, y)| (x - y) * (x - y)).sum();
+        for (x, y) in chunks_a.zip(chunks_b) {
+            for ((acc, x), y) in acc.iter_mut().zip(x).zip(y) {
+                *acc += (x - y) * (x - y);
+            }
+        }
+        acc.iter().sum::<f32>() + tail
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use std::sync::OnceLock;
+
+    pub fn available() -> bool {
+        static AVAILABLE: OnceLock<bool> = OnceLock::new();
+        *AVAILABLE.get_or_init(|| is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma"))
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    fn horizontal_sum(v: __m256) -> f32 {
+        let sum = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+        let sum = _mm_add_ps(sum, _mm_movehl_ps(sum, sum));
+        let sum = _mm_add_ss(sum, _mm_shuffle_ps(sum, sum, 1));
+        _mm_cvtss_f32(sum)
+    }
+
+    /// # Safety
+    /// The CPU must support AVX2 and FMA, and `a` and `b` be the same length.
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let mut acc = _mm256_setzero_ps();
+        let mut i = 0;
+        while i + 8 <= n {
+            let x = _mm256_loadu_ps(a.as_ptr().add(i));
+            let y = _mm256_loadu_ps(b.as_ptr().add(i));
+            acc = _mm256_fmadd_ps(x, y, acc);
+            i += 8;
+        }
+        let mut sum = horizontal_sum(acc);
+        while i < n {
+            sum += a[i] * b[i];
+            i += 1;
+        }
+        sum
+    }
+
+    /// # Safety
+    /// As for [`dot`].
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let mut acc = _mm256_setzero_ps();
+        let mut i = 0;
+        while i + 8 <= n {
+            let d = _mm256_sub_ps(_mm256_loadu_ps(a.as_ptr().add(i)), _mm256_loadu_ps(b.as_ptr().add(i)));
+            acc = _mm256_fmadd_ps(d, d, acc);
+            i += 8;
+        }
+        let mut sum = horizontal_sum(acc);
+        while i < n {
+            let d = a[i] - b[i];
+            sum += d * d;
+            i += 1;
+        }
+        sum
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    /// `a` and `b` must be the same length.
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let mut i = 0;
+        // SAFETY: NEON is part of the aarch64 baseline and loads stay in bounds.
+        let mut sum = unsafe {
+            let mut acc = vdupq_n_f32(0.0);
+            while i + 4 <= n {
+                acc = vfmaq_f32(acc, vld1q_f32(a.as_ptr().add(i)), vld1q_f32(b.as_ptr().add(i)));
+                i += 4;
+            }
+            vaddvq_f32(acc)
+        };
+        while i < n {
+            sum += a[i] * b[i];
+            i += 1;
+        }
+        sum
+    }
+
+    pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let mut i = 0;
+        // SAFETY: as for `dot`.
+        let mut sum = unsafe {
+            let mut acc = vdupq_n_f32(0.0);
+            while i + 4 <= n {
+                let d = vsubq_f32(vld1q_f32(a.as_ptr().add(i)), vld1q_f32(b.as_ptr().add(i)));
+                acc = vfmaq_f32(acc, d, d);
+                i += 4;
+            }
+            vaddvq_f32(acc)
+        };
+        while i < n {
+            let d = a[i] - b[i];
+            sum += d * d;
+            i += 1;
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    fn naive_l2(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+    }
+
+    #[test]
+    fn test_kernels_match_naive_sums() {
+        // Every length up to a few lanes, so tails of each size are covered.
+        for n in 0..40 {
+            let a: Vec<f32> = (0..n).map(|i| (i as f32 * 0.37).sin()).collect();
+            let b: Vec<f32> = (0..n).map(|i| (i as f32 * 0.11).cos()).collect();
+            assert!((dot(&a, &b) - naive_dot(&a, &b)).abs() < 1e-4, "dot, n={} ({})", n, kernel());
+            assert!((l2_squared(&a, &b) - naive_l2(&a, &b)).abs() < 1e-4, "l2, n={} ({})", n, kernel());
+            assert!((scalar::dot(&a, &b) - naive_dot(&a, &b)).abs() < 1e-4);
+            assert!((scalar::l2_squared(&a, &b) - naive_l2(&a, &b)).abs() < 1e-4);
+        }
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[3.0, 4.0]), 11.0);
+        assert!((cosine(&[1.0, 0.0], &[1.0, 1.0]) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+        assert_eq!(l1(&[1.0, -2.0], &[3.0, 1.0]), 5.0);
+        assert_eq!(linf(&[1.0, -2.0], &[3.0, 1.0]), 3.0);
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// How per-shard result lists are merged into one ranking.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(tag = "strategy", rename_all = "camelCase")]
+pub enum Fusion {
+    /// Merge on score. When shards were built with different algorithms their
+    /// scores are first min-max scaled to `[0, 1]` per algorithm, since exact
+    /// HNSW-Flat and quantized IVF-PQ distances are not on the same scale.
+    #[default]
+    Score,
+    /// Reciprocal rank fusion: a hit at rank `r` (from 1) of its shard scores
+    /// `1 / (k + r)`, ignoring raw scores altogether.
+    Rrf {
+        #[serde(default = "default_rrf_k")]
+        k: u32,
+    },
+}
+
+fn default_rrf_k() -> u32 {
+    60
+}
+
+/// Results of one shard, best first, with the algorithm its index was built with.
+pub struct ShardHits<T> {
+    pub algorithm: String,
+    pub hits: Vec<(T, f32)>,
+}
+
+/// Result order: higher score first, equal scores by key, so identical queries
+/// rank ties the same way whichever shard or replica answered first.
+pub fn rank(a_score: f32, a_key: &str, b_score: f32, b_key: &str) -> Ordering {
+    b_score.total_cmp(&a_score).then_with(|| a_key.cmp(b_key))
+}
+
+/// Merge shard results into one list, best first, with the scores they were
+/// ranked on; `key` gives the tie-breaking key of a hit.
+pub fn fuse<T>(fusion: Fusion, shards: Vec<ShardHits<T>>, key: impl Fn(&T) -> &str) -> Vec<(T, f32)> {
+    let mut merged: Vec<(T, f32)> = match fusion {
+        Fusion::Score => {
+            let mut ranges: HashMap<&str, (f32, f32)> = HashMap::new();
+            for shard in &shards {
+                let range = ranges.entry(shard.algorithm.as_str()).or_insert((f32::INFINITY, f32::NEG_INFINITY));
+                for (_, score) in &shard.hits {
+                    *range = (range.0.min(*score), range.1.max(*score));
+                }
+            }
+            let ranges: HashMap<String, (f32, f32)> = ranges.into_iter().map(|(a, r)| (a.to_string(), r)).collect();
+            let mixed = ranges.len() > 1;
+            shards
+                .into_iter()
+                .flat_map(|shard| {
+                    let (min, max) = ranges[&shard.algorithm];
+                    shard.hits.into_iter().map(move |(hit, score)| match mixed {
+                        true if max > min => (hit, (score - min) / (max - min)),
+                        true => (hit, 1.0),
+                        false => (hit, score),
+                    })
+                })
+                .collect()
+        }
+        Fusion::Rrf { k } => shards
+            .into_iter()
+            .flat_map(|shard| {
+                shard.hits.into_iter().enumerate().map(move |(i, (hit, _))| (hit, 1.0 / (k as f32 + i as f32 + 1.0)))
+            })
+            .collect(),
+    };
+    merged.sort_by(|a, b| rank(a.1, key(&a.0), b.1, key(&b.0)));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key<'a>(id: &'a &str) -> &'a str {
+        id
+    }
+
+    fn shard(algorithm: &str, hits: &[(&'static str, f32)]) -> ShardHits<&'static str> {
+        ShardHits { algorithm: algorithm.to_string(), hits: hits.to_vec() }
+    }
+
+    #[test]
+    fn test_score_fusion_normalizes_mixed_algorithms() {
+        let single = fuse(Fusion::Score, vec![shard("ivfpq", &[("a", 3.0)]), shard("ivfpq", &[("b", 5.0)])], key);
+        assert_eq!(single, vec![("b", 5.0), ("a", 3.0)]);
+
+        let mixed = fuse(
+            Fusion::Score,
+            vec![
+                shard("hnsw_flat", &[("h1", 0.9), ("h2", 0.5)]),
+                shard("ivfpq", &[("p1", 40.0), ("p2", 30.0), ("p3", 20.0)]),
+            ],
+            key,
+        );
+        let order: Vec<_> = mixed.iter().map(|(id, _)| *id).collect();
+        assert_eq!(order[2..], ["p2", "h2", "p3"]);
+        assert_eq!(mixed[3].1, 0.0);
+    }
+
+    #[test]
+    fn test_ties_break_by_key() {
+        let shards = |first: &[(&'static str, f32)], second: &[(&'static str, f32)]| {
+            vec![shard("ivfpq", first), shard("ivfpq", second)]
+        };
+        let a = fuse(Fusion::Score, shards(&[("k2", 1.0)], &[("k1", 1.0), ("k0", 0.5)]), key);
+        let b = fuse(Fusion::Score, shards(&[("k1", 1.0), ("k0", 0.5)], &[("k2", 1.0)]), key);
+        assert_eq!(a, b);
+        assert_eq!(a[0].0, "k1");
+    }
+
+    #[test]
+    fn test_rrf_ranks_by_position() {
+        let fused = fuse(
+            Fusion::Rrf { k: 60 },
+            vec![shard("hnsw_flat", &[("h1", 0.1), ("h2", 0.05)]), shard("ivfpq", &[("p1", 99.0)])],
+            key,
+        );
+        assert_eq!(fused[2], ("h2", 1.0 / 62.0));
+        let parsed: Fusion = serde_json::from_value(serde_json::json!({"strategy": "rrf"})).unwrap();
+        assert_eq!(parsed, Fusion::Rrf { k: 60 });
+    }
+}
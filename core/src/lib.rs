@@ -0,0 +1,11 @@
+//! GenAI Vector Database core
+//!
+//! Distance kernels, metadata filters, BM25 text indexes and result fusion:
+//! the search logic that needs neither tokio, storage nor Faiss, so it builds
+//! for `wasm32` and other constrained targets. The server, CLI and bindings
+//! use it through `genai_vectors`, which re-exports these modules.
+
+pub mod distance;
+pub mod fusion;
+pub mod metadata_filter;
+pub mod text_index;
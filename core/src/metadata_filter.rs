@@ -0,0 +1,483 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Advanced metadata filtering engine supporting range queries, regex, and boolean logic
+#[derive(Debug, Clone)]
+pub struct MetadataFilter {
+    conditions: Vec<FilterCondition>,
+    operator: BooleanOperator,
+}
+
+#[derive(Debug, Clone)]
+pub enum BooleanOperator {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub enum FilterCondition {
+    Equals { field: String, value: Value },
+    NotEquals { field: String, value: Value },
+    In { field: String, values: Vec<Value> },
+    NotIn { field: String, values: Vec<Value> },
+    Range { field: String, min: Option<f64>, max: Option<f64> },
+    Contains { field: String, substring: String },
+    Regex { field: String, pattern: String },
+    Exists { field: String },
+    NotExists { field: String },
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self {
+            conditions: Vec::new(),
+            operator: BooleanOperator::And,
+        }
+    }
+
+    pub fn and(mut self) -> Self {
+        self.operator = BooleanOperator::And;
+        self
+    }
+
+    pub fn or(mut self) -> Self {
+        self.operator = BooleanOperator::Or;
+        self
+    }
+
+    pub fn equals(mut self, field: String, value: Value) -> Self {
+        self.conditions.push(FilterCondition::Equals { field, value });
+        self
+    }
+
+    pub fn not_equals(mut self, field: String, value: Value) -> Self {
+        self.conditions.push(FilterCondition::NotEquals { field, value });
+        self
+    }
+
+    pub fn in_values(mut self, field: String, values: Vec<Value>) -> Self {
+        self.conditions.push(FilterCondition::In { field, values });
+        self
+    }
+
+    pub fn not_in_values(mut self, field: String, values: Vec<Value>) -> Self {
+        self.conditions.push(FilterCondition::NotIn { field, values });
+        self
+    }
+
+    pub fn range(mut self, field: String, min: Option<f64>, max: Option<f64>) -> Self {
+        self.conditions.push(FilterCondition::Range { field, min, max });
+        self
+    }
+
+    pub fn contains(mut self, field: String, substring: String) -> Self {
+        self.conditions.push(FilterCondition::Contains { field, substring });
+        self
+    }
+
+    pub fn regex(mut self, field: String, pattern: String) -> Self {
+        self.conditions.push(FilterCondition::Regex { field, pattern });
+        self
+    }
+
+    pub fn exists(mut self, field: String) -> Self {
+        self.conditions.push(FilterCondition::Exists { field });
+        self
+    }
+
+    pub fn not_exists(mut self, field: String) -> Self {
+        self.conditions.push(FilterCondition::NotExists { field });
+        self
+    }
+
+    /// Apply filter to metadata and return whether it matches
+    pub fn matches(&self, metadata: &Value) -> bool {
+        if self.conditions.is_empty() {
+            return true; // No filter means all match
+        }
+
+        let results: Vec<bool> = self.conditions.iter()
+            .map(|condition| self.evaluate_condition(condition, metadata))
+            .collect();
+
+        match self.operator {
+            BooleanOperator::And => results.iter().all(|&x| x),
+            BooleanOperator::Or => results.iter().any(|&x| x),
+        }
+    }
+
+    fn evaluate_condition(&self, condition: &FilterCondition, metadata: &Value) -> bool {
+        match condition {
+            FilterCondition::Equals { field, value } => {
+                self.get_field_value(metadata, field)
+                    .map(|v| v == value)
+                    .unwrap_or(false)
+            }
+            FilterCondition::NotEquals { field, value } => {
+                self.get_field_value(metadata, field)
+                    .map(|v| v != value)
+                    .unwrap_or(true)
+            }
+            FilterCondition::In { field, values } => {
+                self.get_field_value(metadata, field)
+                    .map(|v| values.contains(v))
+                    .unwrap_or(false)
+            }
+            FilterCondition::NotIn { field, values } => {
+                self.get_field_value(metadata, field)
+                    .map(|v| !values.contains(v))
+                    .unwrap_or(true)
+            }
+            FilterCondition::Range { field, min, max } => {
+                self.get_field_value(metadata, field)
+                    .and_then(|v| v.as_f64())
+                    .map(|num| {
+                        let min_ok = min.map(|m| num >= m).unwrap_or(true);
+                        let max_ok = max.map(|m| num <= m).unwrap_or(true);
+                        min_ok && max_ok
+                    })
+                    .unwrap_or(false)
+            }
+            FilterCondition::Contains { field, substring } => {
+                self.get_field_value(metadata, field)
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.contains(substring))
+                    .unwrap_or(false)
+            }
+            FilterCondition::Regex { field, pattern } => {
+                self.get_field_value(metadata, field)
+                    .and_then(|v| v.as_str())
+                    .map(|s| {
+                        // Simple regex matching - in production, use proper regex crate
+                        match regex::Regex::new(pattern) {
+                            Ok(re) => re.is_match(s),
+                            Err(_) => false,
+                        }
+                    })
+                    .unwrap_or(false)
+            }
+            FilterCondition::Exists { field } => {
+                self.get_field_value(metadata, field).is_some()
+            }
+            FilterCondition::NotExists { field } => {
+                self.get_field_value(metadata, field).is_none()
+            }
+        }
+    }
+
+    fn get_field_value<'a>(&self, metadata: &'a Value, field: &str) -> Option<&'a Value> {
+        // Support nested field access with dot notation
+        let parts: Vec<&str> = field.split('.').collect();
+        let mut current = metadata;
+
+        for part in parts {
+            current = current.get(part)?;
+        }
+
+        Some(current)
+    }
+
+    /// Pre-filter IDs before vector search to improve performance
+    pub fn pre_filter_ids(&self, metadata_map: &HashMap<String, Value>) -> Vec<String> {
+        metadata_map.iter()
+            .filter_map(|(id, metadata)| {
+                if self.matches(metadata) {
+                    Some(id.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for MetadataFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a filter was refused, and where: `path` is a JSON Pointer (RFC 6901)
+/// into the filter document, e.g. `/price/$gt`, or empty for the whole of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    pub path: String,
+    pub message: String,
+}
+
+impl FilterError {
+    fn new(path: &str, message: String) -> Self {
+        FilterError { path: path.to_string(), message }
+    }
+}
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} (at {})", self.message, self.path)
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// Parse a filter document. Accepted filters follow this grammar; anything
+/// else is refused with a [`FilterError`] pointing at the offending value.
+///
+/// ```text
+/// filter    = { field : condition, ... }     all conditions must hold
+/// field     = key ( "." key )*               keys non-empty; dots descend into objects
+/// condition = string | number | boolean      equal to the value
+///           | { operator : operand, ... }    all operators must hold
+/// operator  = "$eq" | "$ne"                  operand: any JSON value, compared literally
+///           | "$in" | "$nin"                 operand: array
+///           | "$gt" | "$gte" | "$lt" | "$lte" operand: number
+///           | "$contains"                    operand: string
+///           | "$regex"                       operand: string, a valid regex
+///           | "$exists"                      operand: boolean
+/// ```
+impl TryFrom<Value> for MetadataFilter {
+    type Error = FilterError;
+
+    fn try_from(value: Value) -> Result<Self, FilterError> {
+        let Value::Object(map) = value else {
+            return Err(FilterError::new("", format!("A filter must be an object of field conditions, not {}", kind(&value))));
+        };
+        let mut filter = MetadataFilter::new();
+        for (field, condition) in map {
+            let path = pointer("", &field);
+            if field.split('.').any(str::is_empty) {
+                return Err(FilterError::new(&path, format!("Field '{}' has an empty key; keys are separated by single dots", field)));
+            }
+            filter = match condition {
+                Value::String(_) | Value::Number(_) | Value::Bool(_) => filter.equals(field, condition),
+                Value::Object(operators) => {
+                    for (op, operand) in operators {
+                        filter = parse_operator(filter, &field, &op, operand, &pointer(&path, &op))?;
+                    }
+                    filter
+                }
+                other => {
+                    let message = format!("Condition on {} must be a string, number, boolean or operator object, not {}", field, kind(&other));
+                    return Err(FilterError::new(&path, message));
+                }
+            };
+        }
+        Ok(filter)
+    }
+}
+
+fn parse_operator(filter: MetadataFilter, field: &str, op: &str, operand: Value, path: &str) -> Result<MetadataFilter, FilterError> {
+    let field = field.to_string();
+    let mismatch = |expected: &str, operand: &Value| FilterError::new(path, format!("{} requires {}, not {}", op, expected, kind(operand)));
+    Ok(match op {
+        "$eq" => filter.equals(field, operand),
+        "$ne" => filter.not_equals(field, operand),
+        "$in" | "$nin" => match operand {
+            Value::Array(values) if op == "$in" => filter.in_values(field, values),
+            Value::Array(values) => filter.not_in_values(field, values),
+            other => return Err(mismatch("an array", &other)),
+        },
+        "$gt" | "$gte" | "$lt" | "$lte" => {
+            let Some(num) = operand.as_f64() else { return Err(mismatch("a number", &operand)) };
+            match op {
+                "$gt" => filter.range(field, Some(num + f64::EPSILON), None),
+                "$gte" => filter.range(field, Some(num), None),
+                "$lt" => filter.range(field, None, Some(num - f64::EPSILON)),
+                _ => filter.range(field, None, Some(num)),
+            }
+        }
+        "$contains" => match operand {
+            Value::String(substring) => filter.contains(field, substring),
+            other => return Err(mismatch("a string", &other)),
+        },
+        "$regex" => match operand {
+            Value::String(pattern) => match regex::Regex::new(&pattern) {
+                Ok(_) => filter.regex(field, pattern),
+                Err(e) => return Err(FilterError::new(path, format!("$regex pattern is invalid: {}", e))),
+            },
+            other => return Err(mismatch("a string", &other)),
+        },
+        "$exists" => match operand {
+            Value::Bool(true) => filter.exists(field),
+            Value::Bool(false) => filter.not_exists(field),
+            other => return Err(mismatch("a boolean", &other)),
+        },
+        _ => return Err(FilterError::new(path, format!("Unknown filter operator: {}", op))),
+    })
+}
+
+/// `parent` extended by the key `token`, escaped as RFC 6901 requires.
+fn pointer(parent: &str, token: &str) -> String {
+    format!("{}/{}", parent, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    const OPERATORS: [&str; 11] = ["$eq", "$ne", "$in", "$nin", "$gt", "$gte", "$lt", "$lte", "$contains", "$regex", "$exists"];
+
+    fn parse(filter: Value) -> Result<MetadataFilter, FilterError> {
+        MetadataFilter::try_from(filter)
+    }
+
+    /// Any JSON, with keys that are often operators or dotted paths.
+    fn json_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i32>().prop_map(Value::from),
+            (-1e6..1e6f64).prop_map(Value::from),
+            "[a-z$.(\\[]{0,6}".prop_map(Value::from),
+        ];
+        let key = prop_oneof![prop::sample::select(OPERATORS.to_vec()).prop_map(str::to_string), "[a-z./~]{0,5}"];
+        leaf.prop_recursive(3, 32, 4, move |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+                prop::collection::btree_map(key.clone(), inner, 0..4).prop_map(|map| Value::Object(map.into_iter().collect())),
+            ]
+        })
+    }
+
+    /// Metadata holding `leaf` under the nested `keys`.
+    fn nest(keys: &[String], leaf: Value) -> Value {
+        keys.iter().rev().fold(leaf, |inner, key| json!({ key: inner }))
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_offending_value() {
+        let error = |filter: Value| parse(filter).unwrap_err();
+        assert_eq!(error(json!([1])).path, "");
+        assert_eq!(error(json!({"price": {"$gte": 1, "$lt": "ten"}})).path, "/price/$lt");
+        assert_eq!(error(json!({"tags": {"$in": "a"}})).message, "$in requires an array, not a string");
+        assert_eq!(error(json!({"a/b": {"$between": [1, 2]}})).path, "/a~1b/$between");
+        assert_eq!(error(json!({"user..id": 1})).path, "/user..id");
+        assert_eq!(error(json!({"seen": {"$exists": "yes"}})).path, "/seen/$exists");
+        assert!(error(json!({"name": {"$regex": "("}})).message.starts_with("$regex pattern is invalid"));
+        assert_eq!(error(json!({"n": null})).to_string(), "Condition on n must be a string, number, boolean or operator object, not null (at /n)");
+        assert!(parse(json!({"price": {"$gte": 1, "$lt": 10}, "user.id": 3, "$weird key": {"$eq": {"$gt": 1}}})).is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parsing_never_panics_and_errors_point_into_the_filter(filter in json_value()) {
+            if let Err(e) = parse(filter.clone()) {
+                prop_assert!(filter.pointer(&e.path).is_some(), "{} does not point into {}", e.path, filter);
+            }
+        }
+
+        #[test]
+        fn prop_operators_refuse_operands_of_the_wrong_type(operand in json_value(), op in prop::sample::select(OPERATORS.to_vec())) {
+            let accepted = match op {
+                "$eq" | "$ne" => true,
+                "$in" | "$nin" => operand.is_array(),
+                "$gt" | "$gte" | "$lt" | "$lte" => operand.is_number(),
+                "$contains" => operand.is_string(),
+                "$regex" => operand.as_str().is_some_and(|p| regex::Regex::new(p).is_ok()),
+                _ => operand.is_boolean(),
+            };
+            match parse(json!({"field": { op: operand }})) {
+                Ok(_) => prop_assert!(accepted),
+                Err(e) => {
+                    prop_assert!(!accepted, "{}", e);
+                    prop_assert_eq!(e.path, format!("/field/{}", op));
+                }
+            }
+        }
+
+        #[test]
+        fn prop_operator_objects_are_conjunctions(x in -1e3..1e3f64, lo in -1e3..1e3f64, hi in -1e3..1e3f64) {
+            let filter = parse(json!({"n": {"$gte": lo, "$lte": hi, "$exists": true}})).unwrap();
+            prop_assert_eq!(filter.matches(&json!({"n": x})), lo <= x && x <= hi);
+            let missing = filter.matches(&json!({"m": x}));
+            prop_assert!(!missing);
+        }
+
+        #[test]
+        fn prop_eq_compares_operator_like_objects_literally(operand in json_value()) {
+            let filter = parse(json!({"field": {"$eq": operand.clone()}})).unwrap();
+            let matched = filter.matches(&json!({"field": operand}));
+            prop_assert!(matched);
+        }
+
+        #[test]
+        fn prop_dotted_paths_descend_into_objects(keys in prop::collection::vec("[a-z]{1,4}", 1..5), leaf in any::<i64>(), other in "[A-Z]{1,4}") {
+            let path = keys.join(".");
+            let metadata = nest(&keys, json!(leaf));
+            let equal = parse(json!({ path.clone(): leaf })).unwrap().matches(&metadata);
+            let exists = parse(json!({ path.clone(): {"$exists": true} })).unwrap().matches(&metadata);
+            let deeper = parse(json!({ format!("{}.{}", path, other): {"$exists": true} })).unwrap().matches(&metadata);
+            let elsewhere = parse(json!({ format!("{}.{}", other, path): leaf })).unwrap().matches(&metadata);
+            prop_assert!(equal && exists, "{} not found in {}", path, metadata);
+            prop_assert!(!deeper && !elsewhere, "{} matched beyond {}", other, metadata);
+        }
+    }
+
+    #[test]
+    fn test_simple_equals_filter() {
+        let filter = MetadataFilter::new()
+            .equals("category".to_string(), json!("A"));
+
+        let metadata = json!({"category": "A", "value": 42});
+        assert!(filter.matches(&metadata));
+
+        let metadata2 = json!({"category": "B", "value": 42});
+        assert!(!filter.matches(&metadata2));
+    }
+
+    #[test]
+    fn test_range_filter() {
+        let filter = MetadataFilter::new()
+            .range("score".to_string(), Some(0.5), Some(1.0));
+
+        let metadata1 = json!({"score": 0.7});
+        assert!(filter.matches(&metadata1));
+
+        let metadata2 = json!({"score": 0.3});
+        assert!(!filter.matches(&metadata2));
+    }
+
+    #[test]
+    fn test_complex_filter() {
+        let filter = MetadataFilter::new()
+            .and()
+            .equals("category".to_string(), json!("tech"))
+            .range("score".to_string(), Some(0.8), None);
+
+        let metadata1 = json!({"category": "tech", "score": 0.9});
+        assert!(filter.matches(&metadata1));
+
+        let metadata2 = json!({"category": "tech", "score": 0.7});
+        assert!(!filter.matches(&metadata2));
+    }
+
+    #[test]
+    fn test_nested_field_access() {
+        let filter = MetadataFilter::new()
+            .equals("user.id".to_string(), json!(123));
+
+        let metadata = json!({
+            "user": {
+                "id": 123,
+                "name": "John"
+            }
+        });
+        assert!(filter.matches(&metadata));
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Object name of a shard's BM25 index, next to `index.faiss`.
+pub const TEXT_INDEX_FILE: &str = "text.json";
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Lowercased alphanumeric runs; everything else separates tokens.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// BM25 inverted index over the `text` of a shard's vectors, with rows in
+/// Faiss id order like the raw vector column.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TextIndex {
+    /// Tokens per row; 0 for vectors without text.
+    doc_lengths: Vec<u32>,
+    /// Term to `(row, term frequency)` pairs, in row order.
+    postings: HashMap<String, Vec<(u32, u32)>>,
+}
+
+impl TextIndex {
+    /// Index `texts`, one entry per row. `None` when no row has text, so
+    /// shards without any skip the file.
+    pub fn build(texts: &[Option<String>]) -> Option<Self> {
+        if texts.iter().all(Option::is_none) {
+            return None;
+        }
+        let mut index = TextIndex { doc_lengths: Vec::with_capacity(texts.len()), postings: HashMap::new() };
+        for (row, text) in texts.iter().enumerate() {
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for token in text.as_deref().map(tokenize).into_iter().flatten() {
+                *counts.entry(token).or_default() += 1;
+            }
+            index.doc_lengths.push(counts.values().sum());
+            for (term, tf) in counts {
+                index.postings.entry(term).or_default().push((row as u32, tf));
+            }
+        }
+        Some(index)
+    }
+
+    /// Rows matching any term of `query`, best BM25 score first, at most `limit`.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(i64, f32)> {
+        let docs = self.doc_lengths.iter().filter(|l| **l > 0).count() as f32;
+        if docs == 0.0 {
+            return Vec::new();
+        }
+        let avg_length = self.doc_lengths.iter().sum::<u32>() as f32 / docs;
+        let mut terms: Vec<String> = tokenize(query).collect();
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for postings in terms.iter().filter_map(|t| self.postings.get(t)) {
+            let df = postings.len() as f32;
+            let idf = ((docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for &(row, tf) in postings {
+                let length = self.doc_lengths[row as usize] as f32;
+                let tf = tf as f32;
+                *scores.entry(row).or_default() += idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / avg_length));
+            }
+        }
+        let mut hits: Vec<(i64, f32)> = scores.into_iter().map(|(row, score)| (row as i64, score)).collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Combine vector and BM25 scores of the same candidates. Each is min-max
+/// scaled to `[0, 1]` over the candidates first, a missing score counting as 0,
+/// then weighted `vector_weight : 1 - vector_weight`.
+pub fn blend(vector: &[Option<f32>], lexical: &[Option<f32>], vector_weight: f32) -> Vec<f32> {
+    let scale = |scores: &[Option<f32>]| -> Vec<f32> {
+        let present = scores.iter().flatten();
+        let min = present.clone().copied().fold(f32::INFINITY, f32::min);
+        let max = present.copied().fold(f32::NEG_INFINITY, f32::max);
+        scores
+            .iter()
+            .map(|s| match s {
+                Some(s) if max > min => (s - min) / (max - min),
+                Some(_) => 1.0,
+                None => 0.0,
+            })
+            .collect()
+    };
+    scale(vector)
+        .into_iter()
+        .zip(scale(lexical))
+        .map(|(v, l)| vector_weight * v + (1.0 - vector_weight) * l)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bm25_ranking() {
+        let texts = vec![
+            Some("Rust vector database".to_string()),
+            None,
+            Some("A database of databases, about the database".to_string()),
+            Some("Python notebooks".to_string()),
+        ];
+        let index = TextIndex::build(&texts).unwrap();
+        let hits = index.search("DATABASE rust", 10);
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), vec![0, 2]);
+        assert!(index.search("golang", 10).is_empty());
+        assert_eq!(index.search("database", 1).len(), 1);
+        assert!(TextIndex::build(&[None, None]).is_none());
+    }
+
+    #[test]
+    fn test_blend_scales_each_score() {
+        let vector = [Some(0.9), Some(0.5), None];
+        let lexical = [None, Some(2.0), Some(8.0)];
+        assert_eq!(blend(&vector, &lexical, 0.5), vec![0.5, 0.0, 0.5]);
+        assert_eq!(blend(&vector, &lexical, 1.0), vec![1.0, 0.0, 0.0]);
+    }
+}
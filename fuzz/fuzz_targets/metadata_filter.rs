@@ -0,0 +1,13 @@
+//! Filter documents, parsed and then matched against themselves as metadata.
+#![no_main]
+
+use genai_vectors_core::metadata_filter::MetadataFilter;
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(document) = serde_json::from_slice::<Value>(data) else { return };
+    if let Ok(filter) = MetadataFilter::try_from(document.clone()) {
+        filter.matches(&document);
+    }
+});
@@ -0,0 +1,27 @@
+//! RPC request bodies: the envelope, then the typed request of each operation.
+#![no_main]
+
+use genai_vectors::api::{
+    parse_rpc_body, S3CreateIndexRequest, S3DeleteVectorsRequest, S3GetVectorsRequest, S3ListVectorsRequest,
+    S3PutVectorsRequest, S3QueryVectorsRequest,
+};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fn decode<T: serde::de::DeserializeOwned>(body: &Value) {
+    let _ = serde_json::from_value::<T>(body.clone());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(body) = std::str::from_utf8(data) else { return };
+    let Ok((operation, body)) = parse_rpc_body(body) else { return };
+    match operation.as_str() {
+        "CreateIndex" => decode::<S3CreateIndexRequest>(&body),
+        "PutVectors" => decode::<S3PutVectorsRequest>(&body),
+        "GetVectors" => decode::<S3GetVectorsRequest>(&body),
+        "DeleteVectors" => decode::<S3DeleteVectorsRequest>(&body),
+        "ListVectors" => decode::<S3ListVectorsRequest>(&body),
+        "QueryVectors" => decode::<S3QueryVectorsRequest>(&body),
+        _ => {}
+    }
+});
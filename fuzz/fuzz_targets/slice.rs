@@ -0,0 +1,16 @@
+//! Staged slices as the indexer loads them; the first byte picks the format.
+#![no_main]
+
+use bytes::Bytes;
+use genai_vectors::indexer::decode_slice;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&format, slice)) = data.split_first() else { return };
+    let path = match format % 3 {
+        0 => "staged/fuzz/slice.parquet",
+        1 => "staged/fuzz/slice.jsonl",
+        _ => "staged/fuzz/slice.jsonl.zst",
+    };
+    let _ = decode_slice(path, Bytes::copy_from_slice(slice));
+});
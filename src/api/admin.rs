@@ -0,0 +1,161 @@
+use axum::{response::{Html, IntoResponse, Response}, Json, http::{header, StatusCode}, extract::{Query, State}};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use super::AppState;
+use crate::metrics::get_metrics_collector;
+
+/// Dashboard assets are compiled into the binary, so the UI needs no extra files
+/// in the container. `/ui` is only routed when `VEC_ADMIN_UI=true`.
+const INDEX_HTML: &str = include_str!("ui/index.html");
+const APP_JS: &str = include_str!("ui/app.js");
+
+pub fn ui_enabled() -> bool {
+    std::env::var("VEC_ADMIN_UI").map(|v| v == "true" || v == "1").unwrap_or(false)
+}
+
+pub async fn ui_index() -> impl IntoResponse {
+    Html(INDEX_HTML)
+}
+
+pub async fn ui_app_js() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/javascript")], APP_JS)
+}
+
+/// Staged slices waiting for the indexer, counted per index.
+fn pending_slices(keys: &[String]) -> HashMap<String, usize> {
+    let mut pending = HashMap::new();
+    for key in keys {
+        if let Some((index, _)) = key.strip_prefix("staged/").and_then(|k| k.split_once('/')) {
+            *pending.entry(index.to_string()).or_insert(0) += 1;
+        }
+    }
+    pending
+}
+
+/// GET /admin/stats/indexes - Buckets, indexes and their shard layouts, pending slices
+pub async fn indexes(State(state): State<AppState>) -> Response {
+    let listing = async {
+        let buckets = state.s3.list_buckets().await?;
+        let index_keys = state.s3.list_objects("indexes/").await?;
+        let staged_keys = state.s3.list_objects("staged/").await?;
+        anyhow::Ok((buckets, index_keys, staged_keys))
+    };
+    let (buckets, index_keys, staged_keys) = match listing.await {
+        Ok(listing) => listing,
+        Err(e) => {
+            let body = json!({"error": format!("Failed to list storage: {}", e)});
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response();
+        }
+    };
+    let pending = pending_slices(&staged_keys);
+    let default_bucket = std::env::var("VEC_BUCKET").unwrap_or_else(|_| "vectors".to_string());
+
+    let mut indexes = Vec::new();
+    for name in super::indices::candidate_index_names(index_keys, None, None) {
+        let config: Value = match state.s3.get_object(&format!("indexes/{}/config.json", name)).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or(Value::Null),
+            Err(_) => continue,
+        };
+        let manifest: Value = match state.s3.get_object(&format!("indexes/{}/manifest.json", name)).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or(Value::Null),
+            Err(_) => Value::Null,
+        };
+        let shards: Vec<Value> = manifest.get("shards").and_then(|s| s.as_array()).into_iter().flatten()
+            .map(|shard| json!({
+                "shardId": shard.get("shard_id"),
+                "vectorCount": shard.get("vector_count"),
+                "algorithm": shard.get("algorithm"),
+                "createdAt": shard.get("created_at"),
+                "contentEncoding": shard.get("content_encoding"),
+            }))
+            .collect();
+        indexes.push(json!({
+            "indexName": name,
+            "vectorBucketName": config.get("vector_bucket_name").and_then(|b| b.as_str()).unwrap_or(&default_bucket),
+            "dimension": config.get("dim"),
+            "metric": config.get("metric"),
+            "totalVectors": manifest.get("total_vectors").and_then(|t| t.as_u64()).unwrap_or(0),
+            "shards": shards,
+            "pendingSlices": pending.get(&name).copied().unwrap_or(0),
+        }));
+    }
+
+    (StatusCode::OK, Json(json!({ "buckets": buckets, "indexes": indexes }))).into_response()
+}
+
+/// GET /admin/stats/queries - Most recent queries served by this replica
+pub async fn queries() -> Response {
+    let queries = get_metrics_collector().recent_queries();
+    (StatusCode::OK, Json(json!({ "queries": queries }))).into_response()
+}
+
+/// GET /admin/stats/metrics - Aggregates of the metrics tracked by this replica
+pub async fn metrics() -> Response {
+    let metrics = get_metrics_collector().metric_stats();
+    (StatusCode::OK, Json(json!({ "metrics": metrics }))).into_response()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantineQuery {
+    pub index_name: Option<String>,
+}
+
+/// GET /admin/quarantine[?indexName=] - Slices the indexer could not read, with the reason
+pub async fn quarantined(State(state): State<AppState>, Query(query): Query<QuarantineQuery>) -> Response {
+    match crate::quarantine::list(&state.s3, query.index_name.as_deref()).await {
+        Ok(slices) => (StatusCode::OK, Json(json!({ "slices": slices }))).into_response(),
+        Err(e) => {
+            let body = json!({"error": format!("Failed to list quarantined slices: {}", e)});
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryQuarantinedRequest {
+    pub index_name: String,
+    /// Slice file names; all quarantined slices of the index when omitted.
+    pub slices: Option<Vec<String>>,
+}
+
+/// POST /admin/quarantine/retry - Stage quarantined slices again and index them in the background
+pub async fn retry_quarantined(State(state): State<AppState>, Json(request): Json<RetryQuarantinedRequest>) -> Response {
+    let staged = match crate::quarantine::retry(&state.s3, &request.index_name, request.slices.as_deref()).await {
+        Ok(staged) => staged,
+        Err(e) => {
+            let body = json!({"error": e.to_string(), "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    for slice_path in staged.clone() {
+        let s3 = state.s3.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::indexer::trigger_indexing_for_slice(s3, slice_path.clone()).await {
+                tracing::error!("Indexing retried slice {} failed: {}", slice_path, e);
+            }
+        });
+    }
+    (StatusCode::OK, Json(json!({ "staged": staged }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_slices_per_index() {
+        let keys = vec![
+            "staged/a/slice-1.jsonl".to_string(),
+            "staged/a/slice-2.parquet".to_string(),
+            "staged/b/slice-1.jsonl.zst".to_string(),
+            "staged/orphan".to_string(),
+        ];
+        let pending = pending_slices(&keys);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending["a"], 2);
+        assert_eq!(pending["b"], 1);
+    }
+}
@@ -0,0 +1,151 @@
+use axum::{response::{IntoResponse, Response}, Json, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use super::AppState;
+use super::schema::{AliasRequest, ListAliasesRequest, Typed, UpdateAliasRequest};
+use crate::minio::S3Client;
+
+/// Alias object stored at `aliases/<name>.json`. Switching an alias is a single
+/// object overwrite, so readers see either the old or the new index, never a mix.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexAlias {
+    pub alias_name: String,
+    pub index_name: String,
+    #[serde(default)]
+    pub previous_index_name: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn alias_key(alias: &str) -> String {
+    format!("aliases/{}.json", alias)
+}
+
+async fn load_alias(s3: &S3Client, alias: &str) -> Option<IndexAlias> {
+    let data = s3.get_object(&alias_key(alias)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Resolve an index name that may be an alias. Names without an alias object are
+/// returned unchanged, so existing clients keep addressing indexes directly.
+pub async fn resolve(s3: &S3Client, name: &str) -> String {
+    match load_alias(s3, name).await {
+        Some(alias) => alias.index_name,
+        None => name.to_string(),
+    }
+}
+
+/// UpdateAlias - Create an alias or point it at a different index
+pub async fn update(req: UpdateAliasRequest, state: AppState) -> Response {
+    if state.s3.get_object(&format!("indexes/{}/config.json", req.index_name)).await.is_err() {
+        let body = json!({"error": format!("Index not found: {}", req.index_name)});
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    if state.s3.get_object(&format!("indexes/{}/config.json", req.alias_name)).await.is_ok() {
+        let body = json!({"error": format!("Alias name {} is already used by an index", req.alias_name)});
+        return (StatusCode::CONFLICT, Json(body)).into_response();
+    }
+
+    let current = load_alias(&state.s3, &req.alias_name).await;
+    if let Some(expected) = &req.expected_index_name {
+        let actual = current.as_ref().map(|a| a.index_name.as_str());
+        if actual != Some(expected.as_str()) {
+            let body = json!({
+                "error": format!("Alias {} does not point to {}", req.alias_name, expected),
+                "currentIndexName": actual
+            });
+            return (StatusCode::CONFLICT, Json(body)).into_response();
+        }
+    }
+
+    let alias = IndexAlias {
+        alias_name: req.alias_name.clone(),
+        index_name: req.index_name.clone(),
+        previous_index_name: current.map(|a| a.index_name),
+        updated_at: chrono::Utc::now(),
+    };
+    let data = match serde_json::to_vec(&alias) {
+        Ok(data) => data,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Serialization error: {}", e)).into_response(),
+    };
+    if let Err(e) = state.s3.put_object(&alias_key(&req.alias_name), data.into()).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update alias: {}", e)).into_response();
+    }
+
+    tracing::info!("Alias {} now points to {}", alias.alias_name, alias.index_name);
+    (StatusCode::OK, Json(json!({ "alias": alias }))).into_response()
+}
+
+/// GetAlias - Show which index an alias points to
+pub async fn get(req: AliasRequest, state: AppState) -> Response {
+    match load_alias(&state.s3, &req.alias_name).await {
+        Some(alias) => (StatusCode::OK, Json(json!({ "alias": alias }))).into_response(),
+        None => {
+            let body = json!({"error": format!("Alias not found: {}", req.alias_name)});
+            (StatusCode::NOT_FOUND, Json(body)).into_response()
+        }
+    }
+}
+
+/// ListAliases - List all aliases
+pub async fn list(_req: ListAliasesRequest, state: AppState) -> Response {
+    match state.s3.list_objects("aliases/").await {
+        Ok(keys) => {
+            let mut aliases = Vec::new();
+            for key in keys {
+                if let Some(name) = key.strip_prefix("aliases/").and_then(|k| k.strip_suffix(".json")) {
+                    if let Some(alias) = load_alias(&state.s3, name).await {
+                        aliases.push(alias);
+                    }
+                }
+            }
+            (StatusCode::OK, Json(json!({ "aliases": aliases }))).into_response()
+        }
+        Err(e) => {
+            let body = json!({"error": format!("Failed to list aliases: {}", e)});
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+/// DeleteAlias - Remove an alias; the index it pointed to is untouched
+pub async fn delete(req: AliasRequest, state: AppState) -> Response {
+    match state.s3.delete_object(&alias_key(&req.alias_name)).await {
+        Ok(_) => (StatusCode::OK, Json(json!({}))).into_response(),
+        Err(e) => {
+            let body = json!({"error": format!("Failed to delete alias: {}", e)});
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+// Direct handlers for S3 API routes
+use axum::extract::State;
+
+pub async fn update_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<UpdateAliasRequest>,
+) -> impl IntoResponse {
+    update(req, state).await
+}
+
+pub async fn get_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<AliasRequest>,
+) -> impl IntoResponse {
+    get(req, state).await
+}
+
+pub async fn list_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<ListAliasesRequest>,
+) -> impl IntoResponse {
+    list(req, state).await
+}
+
+pub async fn delete_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<AliasRequest>,
+) -> impl IntoResponse {
+    delete(req, state).await
+}
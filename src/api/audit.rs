@@ -0,0 +1,120 @@
+use axum::{extract::{Request, State}, http::HeaderMap, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use super::AppState;
+use super::request_info::RequestInfo;
+
+/// Audit events are written one object per call under `audit/<yyyy>/<mm>/<dd>/`.
+/// Nothing in the service rewrites or deletes them, which keeps the trail
+/// append-only; bucket object lock can enforce that on the storage side.
+const AUDIT_PREFIX: &str = "audit/";
+
+const MUTATING_OPERATIONS: &[&str] = &[
+    "CreateVectorBucket",
+    "DeleteVectorBucket",
+    "CreateIndex",
+    "DeleteIndex",
+    "RestoreIndex",
+    "CopyIndex",
+    "ExportIndex",
+    "ShadowBuild",
+    "PutVectors",
+    "DeleteVectors",
+    "UpdateAlias",
+    "DeleteAlias",
+    "CancelJob",
+];
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub operation: String,
+    /// Access key id from the SigV4 `Authorization` header, if the call was signed.
+    pub access_key_id: Option<String>,
+    pub source_ip: Option<String>,
+    pub bucket: Option<String>,
+    pub index: Option<String>,
+    /// Vectors written or keys deleted, for PutVectors/DeleteVectors.
+    pub item_count: Option<usize>,
+    pub status: u16,
+    pub success: bool,
+}
+
+/// Audit logging is on unless `VEC_AUDIT_LOG=false`.
+pub fn enabled() -> bool {
+    std::env::var("VEC_AUDIT_LOG").map(|v| v != "false" && v != "0").unwrap_or(true)
+}
+
+pub fn access_key_id(headers: &HeaderMap) -> Option<String> {
+    let auth = headers.get("authorization")?.to_str().ok()?;
+    let credential = auth.split("Credential=").nth(1)?;
+    credential.split('/').next().filter(|id| !id.is_empty()).map(str::to_string)
+}
+
+fn source_ip(headers: &HeaderMap) -> Option<String> {
+    let forwarded = headers.get("x-forwarded-for")?.to_str().ok()?;
+    forwarded.split(',').next().map(|ip| ip.trim().to_string())
+}
+
+async fn write(state: &AppState, event: &AuditEvent) -> anyhow::Result<()> {
+    let key = format!(
+        "{}{}/{}-{}.json",
+        AUDIT_PREFIX,
+        event.timestamp.format("%Y/%m/%d"),
+        event.timestamp.format("%H%M%S%.6f"),
+        uuid::Uuid::new_v4()
+    );
+    state.s3.put_object(&key, serde_json::to_vec(event)?.into()).await
+}
+
+/// Record every mutating call with its caller and outcome, including calls
+/// rejected by access control.
+pub async fn middleware(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let info = req.extensions().get::<RequestInfo>()
+        .filter(|info| MUTATING_OPERATIONS.contains(&info.operation.as_str()))
+        .cloned();
+    let Some(info) = info.filter(|_| enabled()) else {
+        return next.run(req).await;
+    };
+    let access_key_id = access_key_id(req.headers());
+    let source_ip = source_ip(req.headers());
+
+    let response = next.run(req).await;
+
+    let event = AuditEvent {
+        timestamp: Utc::now(),
+        request_id: crate::request_id::current(),
+        operation: info.operation,
+        access_key_id,
+        source_ip,
+        bucket: info.bucket,
+        index: info.index,
+        item_count: info.item_count,
+        status: response.status().as_u16(),
+        success: response.status().is_success(),
+    };
+    if let Err(e) = write(&state, &event).await {
+        tracing::error!(operation = %event.operation, error = %e, "Failed to write audit event");
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_key_id_from_sigv4() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20250101/us-east-1/s3vectors/aws4_request, SignedHeaders=host, Signature=abc"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(access_key_id(&headers).as_deref(), Some("AKIDEXAMPLE"));
+        assert_eq!(access_key_id(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,215 @@
+use axum::{extract::Request, http::{HeaderMap, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::OnceLock;
+use super::request_info::RequestInfo;
+
+/// Roles are cumulative: a writer can do everything a reader can, an admin
+/// everything a writer can.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Writer,
+    Admin,
+}
+
+/// A role on one bucket, or one index in it. `"*"` matches any name and a
+/// grant without an index covers the whole bucket.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Grant {
+    pub role: Role,
+    pub bucket: String,
+    #[serde(default)]
+    pub index: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Principal {
+    pub name: String,
+    /// SHA-256 hex digests of the API keys accepted in the `x-api-key` header.
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// Access key ids taken from SigV4 `Authorization` headers. Signatures are
+    /// not verified here; only use these behind a gateway that does.
+    #[serde(default)]
+    pub access_key_ids: Vec<String>,
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AccessPolicy {
+    pub principals: Vec<Principal>,
+}
+
+const API_KEY_HEADER: &str = "x-api-key";
+/// Shared secret replicas send on `/internal/` calls when `VEC_CLUSTER_SECRET` is set.
+pub const CLUSTER_SECRET_HEADER: &str = "x-cluster-secret";
+
+static POLICY: OnceLock<Option<AccessPolicy>> = OnceLock::new();
+
+/// Load the policy named by `VEC_ACCESS_POLICY_FILE`. Without one the API stays
+/// open; a policy that fails to load stops startup rather than failing open.
+pub fn init() -> anyhow::Result<()> {
+    let policy = match std::env::var("VEC_ACCESS_POLICY_FILE") {
+        Ok(path) => {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read access policy {}", path))?;
+            let policy: AccessPolicy = serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse access policy {}", path))?;
+            tracing::info!(path = %path, principals = policy.principals.len(), "Loaded access policy");
+            Some(policy)
+        }
+        Err(_) => {
+            tracing::warn!("VEC_ACCESS_POLICY_FILE not set, access control disabled");
+            None
+        }
+    };
+    let _ = POLICY.set(policy);
+    Ok(())
+}
+
+fn policy() -> Option<&'static AccessPolicy> {
+    POLICY.get().and_then(Option::as_ref)
+}
+
+pub fn cluster_secret() -> Option<String> {
+    std::env::var("VEC_CLUSTER_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+/// Role an operation needs. Anything not known to be a read or a data write,
+/// including operations added later, needs admin.
+pub fn required_role(operation: &str) -> Role {
+    match operation {
+        "ListVectorBuckets" | "GetVectorBucket" | "ListIndexes" | "GetIndex" | "GetIndexStats" | "ListVectors"
+        | "GetVectors" | "ScrollVectors" | "FilterVectors" | "CountVectors" | "QueryVectors" | "GetAlias"
+        | "ListAliases" | "GetJob" | "ListJobs" | "SubscribeIndexEvents" => Role::Reader,
+        "PutVectors" | "DeleteVectors" => Role::Writer,
+        _ => Role::Admin,
+    }
+}
+
+fn matches(pattern: Option<&str>, name: Option<&str>) -> bool {
+    match pattern.unwrap_or("*") {
+        "*" => true,
+        pattern => name == Some(pattern),
+    }
+}
+
+impl Grant {
+    fn allows(&self, info: &RequestInfo) -> bool {
+        self.role >= required_role(&info.operation)
+            && matches(Some(&self.bucket), info.bucket.as_deref())
+            && matches(self.index.as_deref(), info.index.as_deref())
+    }
+}
+
+impl Principal {
+    /// A multi-index query needs a grant for every index it reads.
+    fn allows(&self, info: &RequestInfo) -> bool {
+        let granted = |info: &RequestInfo| self.grants.iter().any(|g| g.allows(info));
+        if info.indexes.is_empty() {
+            return granted(info);
+        }
+        info.indexes.iter().all(|index| granted(&RequestInfo { index: Some(index.clone()), ..info.clone() }))
+    }
+}
+
+impl AccessPolicy {
+    /// Principal presenting `headers`: an API key is preferred over a SigV4 access key id.
+    fn principal(&self, headers: &HeaderMap) -> Option<&Principal> {
+        if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            let digest = crate::integrity::sha256_hex(key.as_bytes());
+            return self.principals.iter().find(|p| p.api_keys.iter().any(|k| k.eq_ignore_ascii_case(&digest)));
+        }
+        let access_key_id = super::audit::access_key_id(headers)?;
+        self.principals.iter().find(|p| p.access_key_ids.contains(&access_key_id))
+    }
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({"error": message, "code": "AccessDeniedException"}))).into_response()
+}
+
+/// Reject calls with no grant for the operation on the target bucket and index.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    if req.uri().path().starts_with("/internal/") {
+        let secret = req.headers().get(CLUSTER_SECRET_HEADER).and_then(|v| v.to_str().ok());
+        if cluster_secret().is_some_and(|expected| secret != Some(expected.as_str())) {
+            return error(StatusCode::FORBIDDEN, "Invalid cluster secret".to_string());
+        }
+        return next.run(req).await;
+    }
+    if let Some(info) = req.extensions().get::<RequestInfo>() {
+        if let Err((status, message)) = authorize(req.headers(), info) {
+            return error(status, message);
+        }
+    }
+    next.run(req).await
+}
+
+/// Check the credentials in `headers` against the policy for `info`; always
+/// passes when no policy is loaded. Also used by the Flight endpoint.
+pub fn authorize(headers: &HeaderMap, info: &RequestInfo) -> Result<(), (StatusCode, String)> {
+    let Some(policy) = policy() else { return Ok(()) };
+    let Some(principal) = policy.principal(headers) else {
+        return Err((StatusCode::UNAUTHORIZED, "Missing or unknown credentials".to_string()));
+    };
+    if !principal.allows(info) {
+        tracing::warn!(principal = %principal.name, operation = %info.operation, "Access denied");
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("{} is not allowed to perform {}", principal.name, info.operation),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants() {
+        let request = |operation: &str, bucket: &str, index: Option<&str>| RequestInfo {
+            operation: operation.to_string(),
+            bucket: Some(bucket.to_string()),
+            index: index.map(str::to_string),
+            ..Default::default()
+        };
+        let writer = Grant { role: Role::Writer, bucket: "docs".to_string(), index: Some("en".to_string()) };
+        assert!(writer.allows(&request("QueryVectors", "docs", Some("en"))));
+        assert!(writer.allows(&request("PutVectors", "docs", Some("en"))));
+        assert!(!writer.allows(&request("PutVectors", "docs", Some("fr"))));
+        assert!(!writer.allows(&request("DeleteIndex", "docs", Some("en"))));
+        assert!(!writer.allows(&request("SomeFutureOperation", "docs", Some("en"))));
+
+        let admin = Grant { role: Role::Admin, bucket: "*".to_string(), index: None };
+        assert!(admin.allows(&request("DeleteIndex", "other", Some("x"))));
+        // A bucket-wide grant does not cover ListVectorBuckets, which names no bucket.
+        let reader = Grant { role: Role::Reader, bucket: "docs".to_string(), index: None };
+        assert!(!reader.allows(&RequestInfo { operation: "ListVectorBuckets".to_string(), ..Default::default() }));
+    }
+
+    #[test]
+    fn test_multi_index_query_needs_every_index() {
+        let grant = |index: &str| Grant { role: Role::Reader, bucket: "logs".to_string(), index: Some(index.to_string()) };
+        let principal = Principal {
+            name: "analyst".to_string(),
+            api_keys: Vec::new(),
+            access_key_ids: Vec::new(),
+            grants: vec![grant("2025-01"), grant("2025-02")],
+        };
+        let query = |indexes: &[&str]| RequestInfo {
+            operation: "QueryVectors".to_string(),
+            bucket: Some("logs".to_string()),
+            indexes: indexes.iter().map(|i| i.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(principal.allows(&query(&["2025-01", "2025-02"])));
+        assert!(!principal.allows(&query(&["2025-01", "2025-03"])));
+    }
+}
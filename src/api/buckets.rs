@@ -1,6 +1,7 @@
 use axum::{response::{IntoResponse, Response}, Json, http::StatusCode};
 use serde_json::json;
 use crate::api::AppState;
+use super::schema::{BucketRequest, ListVectorBucketsRequest, Typed};
 
 /// Create a new vector bucket
 pub async fn create(bucket: String, state: AppState) -> Response {
@@ -35,16 +36,42 @@ pub async fn create(bucket: String, state: AppState) -> Response {
     }
 }
 
-/// List all vector buckets
-pub async fn list(state: AppState) -> Response {
+/// Default and maximum page size for ListVectorBuckets, per the S3 Vectors spec.
+const MAX_LIST_BUCKETS_RESULTS: u32 = 500;
+
+/// List vector buckets, with prefix filtering and pagination
+pub async fn list(req: ListVectorBucketsRequest, state: AppState) -> Response {
+    let max_results = match req.max_results {
+        None => MAX_LIST_BUCKETS_RESULTS as usize,
+        Some(n) if (1..=MAX_LIST_BUCKETS_RESULTS).contains(&n) => n as usize,
+        Some(n) => {
+            let body = json!({"error": format!("maxResults must be between 1 and {}, got {}", MAX_LIST_BUCKETS_RESULTS, n)});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    let after = match req.next_token.as_deref().map(super::indices::decode_token) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid nextToken").into_response(),
+        Some(Some(name)) => Some(name),
+        None => None,
+    };
+
     match state.s3.client.list_buckets().send().await {
         Ok(output) => {
-            let buckets: Vec<serde_json::Value> = output.buckets()
+            let mut listed: Vec<_> = output.buckets()
                 .iter()
-                .map(|bucket| {
+                .filter_map(|bucket| Some((bucket.name()?, bucket)))
+                .filter(|(name, _)| req.prefix.as_deref().is_none_or(|p| name.starts_with(p)))
+                .filter(|(name, _)| after.as_deref().is_none_or(|a| *name > a))
+                .collect();
+            listed.sort_by_key(|(name, _)| *name);
+            let next_token = (listed.len() > max_results).then(|| super::indices::encode_token(listed[max_results - 1].0));
+            let buckets: Vec<serde_json::Value> = listed
+                .into_iter()
+                .take(max_results)
+                .map(|(name, bucket)| {
                     json!({
-                        "vectorBucketName": bucket.name().unwrap_or("unknown"),
-                        "vectorBucketArn": format!("arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/{}", bucket.name().unwrap_or("unknown")),
+                        "vectorBucketName": name,
+                        "vectorBucketArn": format!("arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/{}", name),
                         "creationTime": bucket.creation_date()
                             .map(|d| d.to_string())
                             .unwrap_or_else(|| "2025-07-01T12:34:56Z".to_string())
@@ -52,9 +79,12 @@ pub async fn list(state: AppState) -> Response {
                 }).collect();
             
             // AWS S3 Vectors ListVectorBuckets returns camelCase format
-            let body = json!({
+            let mut body = json!({
                 "vectorBuckets": buckets
             });
+            if let Some(token) = next_token {
+                body["nextToken"] = json!(token);
+            }
             (StatusCode::OK, Json(body)).into_response()
         }
         Err(e) => {
@@ -101,57 +131,33 @@ pub async fn delete(bucket: String, state: AppState) -> Response {
     }
 }
 
-// Direct handlers for S3 API routes (extract bucket from JSON body)
+// Direct handlers for S3 API routes
 use axum::extract::State;
 
 pub async fn create_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<BucketRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .or_else(|| payload.get("BucketName"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    create(bucket, state).await
+    create(req.vector_bucket_name, state).await
 }
 
 pub async fn get_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<BucketRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .or_else(|| payload.get("BucketName"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    get(bucket, state).await
+    get(req.vector_bucket_name, state).await
 }
 
 pub async fn delete_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<BucketRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .or_else(|| payload.get("BucketName"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    delete(bucket, state).await
+    delete(req.vector_bucket_name, state).await
 }
 
 pub async fn list_direct(
     State(state): State<AppState>,
-    Json(_payload): Json<serde_json::Value>
+    Typed(req): Typed<ListVectorBucketsRequest>,
 ) -> impl IntoResponse {
-    list(state).await
+    list(req, state).await
 }
@@ -0,0 +1,179 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::OnceLock;
+
+/// Headers a browser may read from responses: the request ids and the
+/// idempotent-replay marker.
+const EXPOSED_HEADERS: &str = "x-request-id, x-amzn-requestid, idempotent-replayed";
+const DEFAULT_METHODS: &str = "GET, POST, PUT, DELETE";
+/// What the API reads from requests: API keys, SigV4 signing and the
+/// request id, idempotency and query timeout headers.
+const DEFAULT_HEADERS: &str = "content-type, authorization, x-api-key, x-request-id, idempotency-key, x-query-timeout-ms, \
+                               x-amz-date, x-amz-content-sha256, x-amz-security-token, x-amz-user-agent";
+
+static CONFIG: OnceLock<Option<CorsConfig>> = OnceLock::new();
+
+/// Which browser origins may call the API, from `VEC_CORS_ALLOWED_ORIGINS`
+/// (comma-separated, or `*`); unset, responses carry no CORS headers and
+/// browsers only call the API from its own origin.
+#[derive(Debug, PartialEq)]
+struct CorsConfig {
+    /// `None` allows any origin.
+    origins: Option<Vec<String>>,
+    methods: String,
+    /// `None` allows whatever headers a preflight asks for.
+    headers: Option<String>,
+    max_age_secs: u64,
+}
+
+impl CorsConfig {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let list = |value: String| value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect::<Vec<_>>();
+        let origins = list(var("VEC_CORS_ALLOWED_ORIGINS")?);
+        if origins.is_empty() {
+            return None;
+        }
+        let any = |items: &[String]| items.iter().any(|item| item == "*");
+        let methods = var("VEC_CORS_ALLOWED_METHODS").map(list).filter(|m| !m.is_empty());
+        let headers = var("VEC_CORS_ALLOWED_HEADERS").map(list).filter(|h| !h.is_empty());
+        Some(CorsConfig {
+            origins: (!any(&origins)).then(|| origins.into_iter().map(|o| o.trim_end_matches('/').to_string()).collect()),
+            methods: methods.map_or_else(|| DEFAULT_METHODS.to_string(), |m| m.join(", ")),
+            headers: match headers {
+                Some(headers) if any(&headers) => None,
+                Some(headers) => Some(headers.join(", ")),
+                None => Some(DEFAULT_HEADERS.to_string()),
+            },
+            max_age_secs: var("VEC_CORS_MAX_AGE_SECS").and_then(|v| v.trim().parse().ok()).unwrap_or(600),
+        })
+    }
+
+    /// Value of `Access-Control-Allow-Origin` for a request from `origin`,
+    /// if it is allowed.
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            None => Some(HeaderValue::from_static("*")),
+            Some(origins) => {
+                let origin_str = origin.to_str().ok()?;
+                origins.iter().any(|o| o.eq_ignore_ascii_case(origin_str)).then(|| origin.clone())
+            }
+        }
+    }
+
+    /// Answer to a preflight from an allowed origin; requested headers are
+    /// echoed back when any are allowed, since `*` doesn't cover
+    /// `Authorization`.
+    fn preflight(&self, allow_origin: HeaderValue, request: &HeaderMap) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        headers.insert(header::VARY, HeaderValue::from_static("origin"));
+        if let Ok(methods) = HeaderValue::from_str(&self.methods) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        let allowed_headers = match &self.headers {
+            Some(allowed) => HeaderValue::from_str(allowed).ok(),
+            None => request.get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(self.max_age_secs));
+        (StatusCode::NO_CONTENT, headers).into_response()
+    }
+}
+
+fn config() -> Option<&'static CorsConfig> {
+    CONFIG
+        .get_or_init(|| {
+            let config = CorsConfig::from_vars(|name| std::env::var(name).ok());
+            if let Some(config) = &config {
+                tracing::info!(origins = ?config.origins, "CORS enabled");
+            }
+            config
+        })
+        .as_ref()
+}
+
+/// Answer CORS preflights and mark responses to allowed origins as readable
+/// by them. Runs outside access control, which preflights carry no
+/// credentials for, and so that its rejections reach the page too.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let Some(config) = config() else {
+        return next.run(req).await;
+    };
+    let Some(origin) = req.headers().get(header::ORIGIN).cloned() else {
+        return next.run(req).await;
+    };
+    let allow_origin = config.allow_origin(&origin);
+    if req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+        return match allow_origin {
+            Some(allow_origin) => config.preflight(allow_origin, req.headers()),
+            None => StatusCode::FORBIDDEN.into_response(),
+        };
+    }
+    let mut response = next.run(req).await;
+    if let Some(allow_origin) = allow_origin {
+        let headers = response.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSED_HEADERS));
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pairs: &[(&str, &str)]) -> Option<CorsConfig> {
+        CorsConfig::from_vars(crate::api::testing::env(pairs))
+    }
+
+    #[test]
+    fn test_cors_is_off_unless_origins_are_configured() {
+        assert_eq!(config(&[]), None);
+        assert_eq!(config(&[("VEC_CORS_ALLOWED_ORIGINS", " , ")]), None);
+        let any = config(&[("VEC_CORS_ALLOWED_ORIGINS", "*")]).unwrap();
+        assert_eq!(any.allow_origin(&HeaderValue::from_static("https://anywhere.example")).unwrap(), "*");
+    }
+
+    #[test]
+    fn test_only_listed_origins_are_allowed() {
+        let cors = config(&[("VEC_CORS_ALLOWED_ORIGINS", "https://dash.example/, http://localhost:3000")]).unwrap();
+        let dash = HeaderValue::from_static("https://dash.example");
+        assert_eq!(cors.allow_origin(&dash).unwrap(), dash);
+        assert!(cors.allow_origin(&HeaderValue::from_static("http://localhost:3000")).is_some());
+        assert!(cors.allow_origin(&HeaderValue::from_static("https://evil.example")).is_none());
+        assert!(cors.allow_origin(&HeaderValue::from_static("http://localhost:3001")).is_none());
+    }
+
+    #[test]
+    fn test_preflight_lists_what_may_be_sent() {
+        let mut request = HeaderMap::new();
+        request.insert(header::ACCESS_CONTROL_REQUEST_HEADERS, HeaderValue::from_static("authorization, x-custom"));
+        let origin = HeaderValue::from_static("https://dash.example");
+
+        let defaults = config(&[("VEC_CORS_ALLOWED_ORIGINS", "*")]).unwrap();
+        let response = defaults.preflight(origin.clone(), &request);
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS], DEFAULT_METHODS);
+        assert!(response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap().contains("x-api-key"));
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_MAX_AGE], "600");
+
+        let custom = config(&[
+            ("VEC_CORS_ALLOWED_ORIGINS", "*"),
+            ("VEC_CORS_ALLOWED_METHODS", "POST"),
+            ("VEC_CORS_ALLOWED_HEADERS", "*"),
+            ("VEC_CORS_MAX_AGE_SECS", "60"),
+        ])
+        .unwrap();
+        let response = custom.preflight(origin, &request);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS], "POST");
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS], "authorization, x-custom");
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_MAX_AGE], "60");
+    }
+}
@@ -0,0 +1,242 @@
+//! Embeddings as base64-encoded little-endian f32 buffers instead of JSON
+//! number arrays, about a quarter of the size on the wire.
+//!
+//! Wherever PutVectors and QueryVectors take a `float32` array (and for a
+//! bare `queryVector` or `vector`) a base64 string is accepted as well.
+//! GetVectors, ListVectors and QueryVectors return `data.float32` as base64
+//! when `Accept`, or failing that `Content-Type`, carries the
+//! `embedding-encoding=base64` parameter, e.g.
+//! `application/json; embedding-encoding=base64`. Responses larger than
+//! `VEC_MAX_REQUEST_MB` are returned as plain JSON.
+
+use super::request_info::RequestInfo;
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::Engine;
+use serde_json::{json, Value};
+
+const PARAMETER: &str = "embedding-encoding";
+pub const BASE64_CONTENT_TYPE: &str = "application/json; embedding-encoding=base64";
+
+pub fn encode(embedding: &[f32]) -> String {
+    let bytes: Vec<u8> = embedding.iter().flat_map(|x| x.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<f32>, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("not valid base64: {}", e))?;
+    if bytes.len() % 4 != 0 {
+        return Err(format!("{} bytes is not a whole number of float32 values", bytes.len()));
+    }
+    Ok(bytes.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect())
+}
+
+/// Whether `headers` ask for base64 embeddings in the response.
+fn wants_base64(headers: &HeaderMap) -> bool {
+    let parameter = |name: header::HeaderName| {
+        let value = headers.get(name).and_then(|v| v.to_str().ok())?;
+        value.split([';', ',']).find_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            key.trim().eq_ignore_ascii_case(PARAMETER).then(|| value.trim().trim_matches('"').eq_ignore_ascii_case("base64"))
+        })
+    };
+    parameter(header::ACCEPT).or_else(|| parameter(header::CONTENT_TYPE)).unwrap_or(false)
+}
+
+/// Replace a base64 `float32` string in `value` by its array.
+fn decode_in_place(value: &mut Value, field: &str) -> Result<(), String> {
+    if let Some(encoded) = value.as_str() {
+        let embedding = decode(encoded).map_err(|e| format!("{} is {}", field, e))?;
+        *value = json!(embedding);
+    }
+    Ok(())
+}
+
+/// Decode the base64 embeddings of a request body of `operation`.
+fn decode_request(operation: &str, body: &mut Value) -> Result<(), String> {
+    match operation {
+        "PutVectors" => {
+            for (position, vector) in body.get_mut("vectors").and_then(Value::as_array_mut).into_iter().flatten().enumerate() {
+                if let Some(float32) = vector.get_mut("data").and_then(|d| d.get_mut("float32")) {
+                    decode_in_place(float32, &format!("data.float32 of vector {}", position))?;
+                }
+            }
+        }
+        "QueryVectors" => {
+            for field in ["queryVector", "vector"] {
+                let Some(query) = body.get_mut(field) else { continue };
+                if query.is_string() {
+                    decode_in_place(query, field)?;
+                } else if let Some(float32) = query.get_mut("float32") {
+                    decode_in_place(float32, &format!("{}.float32", field))?;
+                } else if let Some(float32) = query.get_mut("data").and_then(|d| d.get_mut("float32")) {
+                    decode_in_place(float32, &format!("{}.data.float32", field))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Encode the `data.float32` of each of `vectors` in a response body.
+fn encode_response(body: &mut Value) {
+    for vector in body.get_mut("vectors").and_then(Value::as_array_mut).into_iter().flatten() {
+        let Some(float32) = vector.get_mut("data").and_then(|d| d.get_mut("float32")) else { continue };
+        let embedding = float32.as_array().and_then(|values| values.iter().map(|v| v.as_f64().map(|f| f as f32)).collect::<Option<Vec<f32>>>());
+        if let Some(embedding) = embedding {
+            *float32 = json!(encode(&embedding));
+        }
+    }
+}
+
+/// Decode base64 embeddings in PutVectors and QueryVectors bodies before the
+/// schema check and handlers see them, and encode returned ones when asked.
+/// Idempotent replays run inside this layer, so a replay is encoded as its
+/// own request asks.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let operation = req
+        .extensions()
+        .get::<RequestInfo>()
+        .filter(|info| req.method() == Method::POST && (path == "/" || path.strip_prefix('/') == Some(info.operation.as_str())))
+        .map(|info| info.operation.clone());
+    let Some(operation) = operation.filter(|op| matches!(op.as_str(), "PutVectors" | "QueryVectors" | "GetVectors" | "ListVectors")) else {
+        return next.run(req).await;
+    };
+    let base64_response = wants_base64(req.headers()) && operation != "PutVectors";
+
+    let req = if matches!(operation.as_str(), "PutVectors" | "QueryVectors") {
+        let (parts, body) = req.into_parts();
+        let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+            Ok(bytes) => bytes,
+            Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e)).into_response(),
+        };
+        // Bodies that aren't JSON are left to the handlers to refuse.
+        let body = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(mut body) => match decode_request(&operation, &mut body) {
+                Ok(()) => Body::from(serde_json::to_vec(&body).unwrap_or_default()),
+                Err(message) => {
+                    let body = json!({"error": message, "code": "ValidationException"});
+                    return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+                }
+            },
+            _ => Body::from(bytes),
+        };
+        Request::from_parts(parts, body)
+    } else {
+        req
+    };
+
+    let response = next.run(req).await;
+    if !base64_response || response.status() != StatusCode::OK {
+        return response;
+    }
+    // Responses too large to buffer go out unencoded, under their JSON content type.
+    if response.body().size_hint().upper().is_none_or(|len| len > super::max_request_bytes() as u64) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read response body: {}", e)).into_response(),
+    };
+    let Ok(mut body) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    encode_response(&mut body);
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(BASE64_CONTENT_TYPE));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(serde_json::to_vec(&body).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::TestApp;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_embeddings_round_trip_through_base64() {
+        let embedding = vec![1.0, -0.5, 3.25, f32::MIN_POSITIVE];
+        let encoded = encode(&embedding);
+        assert_eq!(encoded.len(), 24);
+        assert_eq!(decode(&encoded).unwrap(), embedding);
+        assert_eq!(encode(&[1.0]), "AACAPw==");
+        assert!(decode("AACA").unwrap_err().contains("3 bytes"));
+        assert!(decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_base64_is_negotiated_by_media_type_parameter() {
+        let headers = |pairs: &[(header::HeaderName, &'static str)]| {
+            pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_static(value))).collect::<HeaderMap>()
+        };
+        assert!(!wants_base64(&headers(&[(header::CONTENT_TYPE, "application/json")])));
+        assert!(wants_base64(&headers(&[(header::ACCEPT, BASE64_CONTENT_TYPE)])));
+        assert!(wants_base64(&headers(&[(header::CONTENT_TYPE, "application/json;Embedding-Encoding=\"base64\"")])));
+        assert!(!wants_base64(&headers(&[
+            (header::ACCEPT, "application/json; embedding-encoding=json"),
+            (header::CONTENT_TYPE, BASE64_CONTENT_TYPE),
+        ])));
+    }
+
+    #[test]
+    fn test_request_embeddings_are_decoded() {
+        let mut put = json!({"vectors": [
+            {"key": "a", "data": {"float32": [1.0, 2.0]}},
+            {"key": "b", "data": {"float32": encode(&[3.0, 4.0])}},
+        ]});
+        decode_request("PutVectors", &mut put).unwrap();
+        assert_eq!(put["vectors"][1]["data"]["float32"], json!([3.0, 4.0]));
+        assert_eq!(put["vectors"][0]["data"]["float32"], json!([1.0, 2.0]));
+
+        let mut query = json!({"queryVector": {"float32": encode(&[0.5])}, "vector": encode(&[0.25])});
+        decode_request("QueryVectors", &mut query).unwrap();
+        assert_eq!(query, json!({"queryVector": {"float32": [0.5]}, "vector": [0.25]}));
+
+        let mut bad = json!({"vectors": [{"key": "a", "data": {"float32": "AACA"}}]});
+        assert!(decode_request("PutVectors", &mut bad).unwrap_err().starts_with("data.float32 of vector 0"));
+    }
+
+    #[tokio::test]
+    async fn test_put_and_query_with_base64_embeddings() {
+        let app = TestApp::new();
+        app.create_index("docs", 2, "euclidean", json!({})).await;
+        let put = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "vectors": [
+            {"key": "a", "data": {"float32": encode(&[1.0, 0.0])}},
+            {"key": "b", "data": {"float32": encode(&[0.0, 1.0])}},
+        ]});
+        let (status, response) = app.post("/PutVectors", put).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+
+        let query = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "queryVector": {"float32": encode(&[0.9, 0.1])}, "topK": 1, "returnData": true});
+        let request = Request::post("/QueryVectors")
+            .header(header::CONTENT_TYPE, BASE64_CONTENT_TYPE)
+            .body(Body::from(query.to_string()))
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], BASE64_CONTENT_TYPE);
+        let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["vectors"][0]["key"], "a");
+        assert_eq!(decode(body["vectors"][0]["data"]["float32"].as_str().unwrap()).unwrap(), vec![1.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_bodies_are_refused() {
+        let app = TestApp::new();
+        let embedding = encode(&vec![0.5; super::super::max_request_bytes() / 4]);
+        let put = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "vectors": [{"key": "a", "data": {"float32": embedding}}]});
+        let (status, _) = app.post("/PutVectors", put).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,106 @@
+//! `GET /events`: a websocket over which the node pushes a JSON
+//! [`IndexEvent`] whenever an index gets a new manifest, so downstream caches
+//! know when to invalidate. `?index=a,b` only sends events of those indexes.
+//! A subscriber too slow to keep up is sent `{"type": "Lagged", "missed": n}`
+//! in place of the events it missed, after which it should treat everything
+//! it cached as stale.
+
+use crate::index_events::{self, IndexEvent};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Query;
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Comma-separated indexes to watch; every index when absent.
+    index: Option<String>,
+}
+
+fn watched(query: EventsQuery) -> Option<HashSet<String>> {
+    let indexes: HashSet<String> = query
+        .index?
+        .split(',')
+        .map(|index| index.trim().to_string())
+        .filter(|index| !index.is_empty())
+        .collect();
+    (!indexes.is_empty()).then_some(indexes)
+}
+
+pub async fn events(ws: WebSocketUpgrade, Query(query): Query<EventsQuery>) -> Response {
+    // Subscribe before the upgrade, so nothing published during the
+    // handshake is missed.
+    let events = index_events::subscribe();
+    let indexes = watched(query);
+    ws.on_upgrade(move |socket| forward(socket, events, indexes))
+}
+
+async fn forward(mut socket: WebSocket, mut events: broadcast::Receiver<IndexEvent>, indexes: Option<HashSet<String>>) {
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let message = match event {
+                    Ok(event) if indexes.as_ref().is_none_or(|indexes| indexes.contains(&event.index)) => {
+                        serde_json::to_string(&event).unwrap_or_default()
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => json!({"type": "Lagged", "missed": missed}).to_string(),
+                    Err(RecvError::Closed) => break,
+                };
+                if socket.send(Message::Text(message)).await.is_err() {
+                    break;
+                }
+            }
+            // Pings are answered for us; anything else the client sends is ignored.
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::{serve, ServerConfig};
+    use crate::api::testing::TestApp;
+    use futures::StreamExt;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[test]
+    fn test_index_filter() {
+        let query = |index: Option<&str>| EventsQuery { index: index.map(str::to_string) };
+        assert_eq!(watched(query(None)), None);
+        assert_eq!(watched(query(Some(" , "))), None);
+        assert_eq!(watched(query(Some("docs, logs"))), Some(HashSet::from(["docs".to_string(), "logs".to_string()])));
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_are_told_when_an_index_is_indexed() {
+        let config = ServerConfig { host: "127.0.0.1".to_string(), port: 0, ..Default::default() };
+        let listener = config.bind().await.unwrap();
+        let url = format!("ws://{}/events?index=watched", listener.local_addr().unwrap());
+        let app = TestApp::new();
+        let router = app.router.clone();
+        tokio::spawn(async move { serve(listener, router, &config).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        app.create_index("unwatched", 2, "cosine", serde_json::json!({})).await;
+        app.create_index("watched", 2, "cosine", serde_json::json!({})).await;
+        app.put("unwatched", &[("a", vec![1.0, 0.0], serde_json::json!({}))]).await;
+        app.put("watched", &[("a", vec![1.0, 0.0], serde_json::json!({}))]).await;
+
+        let message = tokio::time::timeout(Duration::from_secs(10), socket.next()).await.unwrap().unwrap().unwrap();
+        let event: Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(event["type"], "Indexed");
+        assert_eq!(event["index"], "watched");
+        assert_eq!((event["shards"].as_u64(), event["totalVectors"].as_u64()), (Some(1), Some(1)));
+        let manifest = app.s3.get_object("indexes/watched/manifest.json").await.unwrap();
+        assert_eq!(event["manifestVersion"], index_events::manifest_version(&manifest));
+    }
+}
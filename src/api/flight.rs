@@ -0,0 +1,252 @@
+//! Arrow Flight endpoint, built with the `flight` feature. `DoGet` tickets are
+//! JSON request bodies naming their operation, e.g.
+//! `{"operation": "QueryVectors", "vectorBucketName": "b", "indexName": "docs", "queryVector": {"float32": [..]}, "topK": 100}`
+//! or `{"operation": "ScrollVectors", "vectorBucketName": "b", "indexName": "docs"}`,
+//! and stream back record batches instead of JSON. Credentials go in the same
+//! `x-api-key` / `authorization` headers as the REST API, as gRPC metadata.
+
+use super::schema::ScrollVectorsRequest;
+use super::{authz, request_info, schema, AppState, S3QueryVectorsRequest};
+use crate::export::{self, ExportRow};
+use arrow::array::{Float32Array, ListArray, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Float32Type, Schema};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo, HandshakeRequest,
+    HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use axum::http::StatusCode;
+use axum::response::Response;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use serde_json::Value;
+use std::sync::Arc;
+use tonic::{Request, Status, Streaming};
+
+const DEFAULT_FLIGHT_ADDR: &str = "0.0.0.0:8815";
+/// Rows per record batch of a ScrollVectors stream.
+const SCROLL_BATCH_ROWS: usize = 1000;
+const SCROLL_FETCH_CONCURRENCY: usize = 32;
+
+/// Serve Flight on `VEC_FLIGHT_ADDR` next to the REST API.
+pub fn spawn(state: AppState) {
+    let addr = std::env::var("VEC_FLIGHT_ADDR").unwrap_or_else(|_| DEFAULT_FLIGHT_ADDR.to_string());
+    tokio::spawn(async move {
+        let addr = match addr.parse() {
+            Ok(addr) => addr,
+            Err(e) => {
+                tracing::error!(addr = %addr, "Invalid VEC_FLIGHT_ADDR: {}", e);
+                return;
+            }
+        };
+        tracing::info!("Flight listening on {addr}");
+        let server = tonic::transport::Server::builder().add_service(FlightServiceServer::new(FlightApi { state }));
+        if let Err(e) = server.serve(addr).await {
+            tracing::error!("Flight server stopped: {}", e);
+        }
+    });
+}
+
+struct FlightApi {
+    state: AppState,
+}
+
+/// Schema of QueryVectors results; `metadata` and `data` are null unless
+/// `returnMetadata` / `returnData` are set, `index_name` unless the query
+/// spans several indexes.
+fn query_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("key", DataType::Utf8, false),
+        Field::new("distance", DataType::Float32, false),
+        Field::new("index_name", DataType::Utf8, true),
+        Field::new("metadata", DataType::Utf8, true),
+        Field::new("data", DataType::List(Arc::new(Field::new("item", DataType::Float32, true))), true),
+    ]))
+}
+
+/// Record batch of the `vectors` of a QueryVectors response body.
+fn query_batch(response: &Value) -> Result<RecordBatch, FlightError> {
+    let hits = response.get("vectors").and_then(|v| v.as_array()).map(Vec::as_slice).unwrap_or_default();
+    let str_field = |hit: &Value, name: &str| hit.get(name).and_then(|v| v.as_str()).map(str::to_string);
+    let batch = RecordBatch::try_new(
+        query_schema(),
+        vec![
+            Arc::new(StringArray::from_iter_values(hits.iter().map(|h| str_field(h, "key").unwrap_or_default()))),
+            Arc::new(Float32Array::from_iter_values(
+                hits.iter().map(|h| h.get("distance").and_then(|d| d.as_f64()).unwrap_or_default() as f32),
+            )),
+            Arc::new(StringArray::from(hits.iter().map(|h| str_field(h, "indexName")).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(
+                hits.iter().map(|h| h.get("metadata").map(Value::to_string)).collect::<Vec<_>>(),
+            )),
+            Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(hits.iter().map(|h| {
+                let values = h.get("data")?.get("float32")?.as_array()?;
+                Some(values.iter().map(|v| v.as_f64().map(|f| f as f32)).collect::<Vec<_>>())
+            }))),
+        ],
+    )?;
+    Ok(batch)
+}
+
+/// gRPC status for an error response of a REST handler.
+async fn status_from(response: Response) -> Status {
+    let code = response.status();
+    let message = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map(|body| String::from_utf8_lossy(&body).into_owned())
+        .unwrap_or_default();
+    match code {
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        code if code.is_client_error() => Status::invalid_argument(message),
+        _ => Status::internal(message),
+    }
+}
+
+impl FlightApi {
+    async fn query(&self, req: S3QueryVectorsRequest) -> Result<BoxStream<'static, Result<FlightData, Status>>, Status> {
+        let response = match super::vectors::execute_query(&req, &self.state).await {
+            Ok(response) => response,
+            Err(response) => return Err(status_from(response).await),
+        };
+        let batch = query_batch(&response).map_err(Status::from)?;
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(query_schema())
+            .build(futures::stream::iter([Ok(batch)]));
+        Ok(stream.map_err(Status::from).boxed())
+    }
+
+    /// Every vector of record of the index, in key order, in the export schema.
+    async fn scroll(&self, req: ScrollVectorsRequest) -> Result<BoxStream<'static, Result<FlightData, Status>>, Status> {
+        let bucket = req.vector_bucket_name;
+        let index = super::aliases::resolve(&self.state.s3, &req.index_name).await;
+        let mut keys = self
+            .state
+            .s3
+            .list_bucket_objects(&bucket, &format!("{}/vectors/", index))
+            .await
+            .map_err(|e| Status::internal(format!("Failed to list vectors: {}", e)))?;
+        keys.sort();
+
+        let s3 = self.state.s3.clone();
+        let chunks: Vec<Vec<String>> = keys.chunks(SCROLL_BATCH_ROWS).map(<[String]>::to_vec).collect();
+        let batches = futures::stream::iter(chunks).then(move |chunk| {
+            let (s3, bucket) = (s3.clone(), bucket.clone());
+            async move {
+                let fetches: Vec<_> = chunk.iter().map(|key| s3.get_bucket_object(&bucket, key)).collect();
+                let documents: Vec<_> = futures::stream::iter(fetches)
+                    .buffered(SCROLL_FETCH_CONCURRENCY)
+                    .collect()
+                    .await;
+                // Vectors deleted since the listing are skipped.
+                let rows: Vec<ExportRow> = documents
+                    .into_iter()
+                    .filter_map(|data| serde_json::from_slice::<Value>(&data.ok()?).ok())
+                    .filter_map(|doc| ExportRow::from_record(&doc))
+                    .collect();
+                export::record_batch(&rows).map_err(|e| FlightError::ExternalError(e.into()))
+            }
+        });
+        let stream = FlightDataEncoderBuilder::new().with_schema(export::schema()).build(batches);
+        Ok(stream.map_err(Status::from).boxed())
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightApi {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+
+    async fn do_get(&self, request: Request<Ticket>) -> Result<tonic::Response<Self::DoGetStream>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let body: Value = serde_json::from_slice(&request.get_ref().ticket)
+            .map_err(|e| Status::invalid_argument(format!("Ticket is not a JSON request: {}", e)))?;
+        let operation = body.get("operation").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let mut request = body.clone();
+        if let Some(fields) = request.as_object_mut() {
+            fields.remove("operation");
+        }
+        schema::check(&operation, request.clone()).map_err(Status::invalid_argument)?;
+        let info = request_info::describe(operation.clone(), &body);
+        authz::authorize(&headers, &info).map_err(|(code, message)| match code {
+            StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+            _ => Status::permission_denied(message),
+        })?;
+
+        let stream = match operation.as_str() {
+            "QueryVectors" => self.query(schema::parse(request).map_err(Status::invalid_argument)?).await?,
+            "ScrollVectors" => self.scroll(schema::parse(request).map_err(Status::invalid_argument)?).await?,
+            _ => return Err(Status::invalid_argument("Ticket operation must be QueryVectors or ScrollVectors")),
+        };
+        Ok(tonic::Response::new(stream))
+    }
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<tonic::Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Credentials are sent as metadata on each call"))
+    }
+
+    async fn list_flights(&self, _request: Request<Criteria>) -> Result<tonic::Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn get_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<tonic::Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info"))
+    }
+
+    async fn poll_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<tonic::Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(&self, _request: Request<FlightDescriptor>) -> Result<tonic::Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema"))
+    }
+
+    async fn do_put(&self, _request: Request<Streaming<FlightData>>) -> Result<tonic::Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("Flight is read-only; use PutVectors"))
+    }
+
+    async fn do_action(&self, _request: Request<Action>) -> Result<tonic::Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(&self, _request: Request<Empty>) -> Result<tonic::Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<tonic::Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use serde_json::json;
+
+    #[test]
+    fn test_query_batch() {
+        let response = json!({"vectors": [
+            {"key": "a", "distance": 0.5, "metadata": {"lang": "en"}, "data": {"float32": [1.0, 2.0]}},
+            {"key": "b", "distance": 0.25},
+        ]});
+        let batch = query_batch(&response).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let metadata = batch.column(3).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(metadata.value(0), "{\"lang\":\"en\"}");
+        assert!(metadata.is_null(1) && batch.column(4).is_null(1));
+        assert_eq!(query_batch(&json!({"vectors": []})).unwrap().num_rows(), 0);
+    }
+}
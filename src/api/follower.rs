@@ -0,0 +1,37 @@
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::{IntoResponse, Response}, Json};
+use serde_json::json;
+use super::authz::{required_role, Role};
+use super::request_info::RequestInfo;
+
+/// Calls a read replica serves: reads, admin stats, and shard searches
+/// forwarded by other replicas.
+fn served_by_follower(operation: &str) -> bool {
+    required_role(operation) == Role::Reader || matches!(operation, "AdminStats" | "InternalShardSearch")
+}
+
+/// On followers (`VEC_ROLE=follower`), reject everything that would write, so
+/// ingestion only ever happens on the leader.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let rejected = crate::replica::is_follower()
+        && req.extensions().get::<RequestInfo>().is_some_and(|info| !served_by_follower(&info.operation));
+    if rejected {
+        let body = json!({
+            "error": "This replica only serves reads; send writes to the leader",
+            "code": "ReadOnlyReplica"
+        });
+        return (StatusCode::FORBIDDEN, Json(body)).into_response();
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follower_operations() {
+        assert!(served_by_follower("QueryVectors") && served_by_follower("InternalShardSearch"));
+        assert!(!served_by_follower("PutVectors") && !served_by_follower("CreateIndex"));
+        assert!(!served_by_follower("RetryQuarantinedSlices"));
+    }
+}
@@ -0,0 +1,194 @@
+use axum::{body::Body, extract::{Request, State}, http::{HeaderValue, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use super::AppState;
+use super::request_info::RequestInfo;
+use crate::integrity::sha256_hex;
+use crate::minio::S3Client;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses replayed from an earlier request with the same key.
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+/// Responses are stored as `idempotency/<sha256 of bucket and key>.json`.
+const IDEMPOTENCY_PREFIX: &str = "idempotency/";
+const MAX_KEY_LEN: usize = 255;
+
+/// Outcome of a PutVectors, kept so a retry with the same key gets the same
+/// answer instead of ingesting the batch again.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StoredResponse {
+    /// Fingerprint of the request; reusing the key for a different one is an error.
+    request_hash: String,
+    expires_at: DateTime<Utc>,
+    status: u16,
+    body: Value,
+}
+
+/// How long keys are remembered, from `VEC_IDEMPOTENCY_TTL_SECS` (default 86400).
+pub fn ttl_from_env() -> Duration {
+    let secs = std::env::var("VEC_IDEMPOTENCY_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(86_400);
+    Duration::seconds(secs.max(1))
+}
+
+/// Keys are scoped to their bucket, so tenants cannot see each other's responses.
+fn record_key(bucket: Option<&str>, key: &str) -> String {
+    format!("{}{}.json", IDEMPOTENCY_PREFIX, sha256_hex(format!("{}/{}", bucket.unwrap_or_default(), key).as_bytes()))
+}
+
+/// Hash of the request body without its `idempotencyKey`, so the header and
+/// body forms of the same retry match.
+fn request_hash(body: &Value) -> String {
+    let mut body = body.clone();
+    if let Some(fields) = body.as_object_mut() {
+        fields.remove("idempotencyKey");
+    }
+    sha256_hex(body.to_string().as_bytes())
+}
+
+/// Record objects of keys whose first request is still running on this replica.
+fn in_flight() -> &'static Mutex<HashSet<String>> {
+    static IN_FLIGHT: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    IN_FLIGHT.get_or_init(Default::default)
+}
+
+struct InFlight(String);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        in_flight().lock().unwrap().remove(&self.0);
+    }
+}
+
+async fn load(s3: &S3Client, object: &str) -> Option<StoredResponse> {
+    let data = s3.get_object(object).await.ok()?;
+    let stored: StoredResponse = serde_json::from_slice(&data).ok()?;
+    (stored.expires_at > Utc::now()).then_some(stored)
+}
+
+fn conflict(message: &str) -> Response {
+    let body = json!({"error": message, "code": "ConflictException"});
+    (StatusCode::CONFLICT, Json(body)).into_response()
+}
+
+/// Make PutVectors calls carrying an `Idempotency-Key` header or an
+/// `idempotencyKey` field safe to retry: the first successful response is
+/// stored and returned again for repeats within the TTL, without ingesting.
+pub async fn middleware(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(bucket) = req.extensions().get::<RequestInfo>()
+        .filter(|info| info.operation == "PutVectors")
+        .map(|info| info.bucket.clone())
+    else {
+        return next.run(req).await;
+    };
+    let header_key = req.headers().get(IDEMPOTENCY_KEY_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let (parts, body) = req.into_parts();
+    let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e)).into_response(),
+    };
+    let json: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    let key = header_key.or_else(|| json.get("idempotencyKey").and_then(|v| v.as_str()).map(str::to_string));
+    let req = Request::from_parts(parts, Body::from(bytes));
+    let Some(key) = key else {
+        return next.run(req).await;
+    };
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        let body = json!({"error": format!("Idempotency key must be 1 to {} characters", MAX_KEY_LEN), "code": "ValidationException"});
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+
+    let object = record_key(bucket.as_deref(), &key);
+    let hash = request_hash(&json);
+    if let Some(stored) = load(&state.s3, &object).await {
+        if stored.request_hash != hash {
+            return conflict("Idempotency key was already used for a different request");
+        }
+        let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+        let mut response = (status, Json(stored.body)).into_response();
+        response.headers_mut().insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+        return response;
+    }
+    if !in_flight().lock().unwrap().insert(object.clone()) {
+        return conflict("A request with this idempotency key is still in progress");
+    }
+    let _in_flight = InFlight(object.clone());
+
+    let response = next.run(req).await;
+    if !response.status().is_success() {
+        // Failures are not remembered, so the retry runs again.
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read response: {}", e)).into_response(),
+    };
+    if let Ok(body) = serde_json::from_slice::<Value>(&bytes) {
+        let stored = StoredResponse {
+            request_hash: hash,
+            expires_at: Utc::now() + ttl_from_env(),
+            status: parts.status.as_u16(),
+            body,
+        };
+        let saved = match serde_json::to_vec(&stored) {
+            Ok(data) => state.s3.put_object(&object, data.into()).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            tracing::warn!(error = %e, "Failed to store idempotent response");
+        }
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Delete stored responses past their TTL, returning how many were removed.
+pub async fn sweep_expired(s3: &S3Client) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let mut swept = 0;
+    for object in s3.list_objects(IDEMPOTENCY_PREFIX).await? {
+        let Ok(data) = s3.get_object(&object).await else { continue };
+        let expired = serde_json::from_slice::<StoredResponse>(&data).ok().is_none_or(|stored| stored.expires_at <= now);
+        if expired {
+            s3.delete_object(&object).await?;
+            swept += 1;
+        }
+    }
+    Ok(swept)
+}
+
+/// Sweep expired responses in the background, hourly.
+pub fn spawn_sweeper(s3: S3Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match sweep_expired(&s3).await {
+                Ok(0) => {}
+                Ok(swept) => tracing::info!(swept, "Swept expired idempotency keys"),
+                Err(e) => tracing::warn!(error = %e, "Failed to sweep idempotency keys"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_hash_ignores_key_field() {
+        let body = json!({"vectorBucketName": "b", "indexName": "i", "vectors": [{"key": "a"}]});
+        let mut with_key = body.clone();
+        with_key["idempotencyKey"] = json!("retry-1");
+        assert_eq!(request_hash(&body), request_hash(&with_key));
+        assert_ne!(request_hash(&body), request_hash(&json!({"vectorBucketName": "b", "vectors": []})));
+        assert_ne!(record_key(Some("b1"), "k"), record_key(Some("b2"), "k"));
+    }
+}
@@ -1,33 +1,223 @@
 use axum::{response::{IntoResponse, Response}, Json, http::StatusCode};
 use serde_json::{json, Value};
-use super::{AppState, S3CreateIndexRequest};
+use super::{AppState, S3CreateIndexRequest, S3ListIndexesRequest};
+use super::schema::{CopyIndexRequest, DeleteIndexRequest, IndexRequest, RestoreIndexRequest, ShadowBuildRequest, Typed};
+use base64::Engine;
 use crate::model::*;
 use anyhow::Context;
 
-/// CreateIndex - Create a new vector index
-pub async fn create(bucket: String, body: Value, state: AppState) -> Response {
-    let req: S3CreateIndexRequest = match serde_json::from_value(body) {
-        Ok(req) => req,
-        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response(),
-    };
-    
+/// Largest vector dimension accepted by CreateIndex, matching S3 Vectors.
+const MAX_DIMENSION: u32 = 4096;
+const SUPPORTED_METRICS: &[&str] = &["cosine", "euclidean", "dotproduct", "manhattan", "chebyshev"];
+const SUPPORTED_ALGORITHMS: &[&str] = &["auto", "flat", "ivfpq", "hnsw_flat", "hybrid"];
+
+/// Names are 3-63 characters of lowercase letters, digits, hyphens and dots,
+/// starting and ending with a letter or digit (S3 Vectors naming rules).
+fn validate_index_name(name: &str) -> Result<(), String> {
+    let valid_char = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.';
+    let alnum = |c: Option<char>| c.is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    if !(3..=63).contains(&name.len()) {
+        return Err(format!("Index name must be between 3 and 63 characters, got {}", name.len()));
+    }
+    if !name.chars().all(valid_char) || !alnum(name.chars().next()) || !alnum(name.chars().last()) {
+        return Err(format!(
+            "Invalid index name '{}': use lowercase letters, numbers, hyphens and dots, starting and ending with a letter or number",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn validate_create_index(req: &S3CreateIndexRequest) -> Result<(), String> {
+    validate_index_name(&req.index_name)?;
+    if !(1..=MAX_DIMENSION).contains(&req.dimension) {
+        return Err(format!("Dimension must be between 1 and {}, got {}", MAX_DIMENSION, req.dimension));
+    }
+    if !SUPPORTED_METRICS.contains(&req.distance_metric.to_lowercase().as_str()) {
+        return Err(format!(
+            "Unsupported distance metric '{}', expected one of {:?}",
+            req.distance_metric, SUPPORTED_METRICS
+        ));
+    }
+    if let Some(algorithm) = &req.algorithm {
+        if !SUPPORTED_ALGORITHMS.contains(&algorithm.to_lowercase().as_str()) {
+            return Err(format!("Unsupported algorithm '{}', expected one of {:?}", algorithm, SUPPORTED_ALGORITHMS));
+        }
+        // Hybrid indexes move to IVF-PQ once they grow; auto ones never pick it
+        // for metrics it can't index.
+        let ivfpq_free = ["hnsw_flat", "flat", "auto"].contains(&algorithm.to_lowercase().as_str());
+        if !ivfpq_free && !crate::faiss_utils::ivfpq_supports(&req.distance_metric) {
+            return Err(format!(
+                "Distance metric '{}' needs algorithm hnsw_flat, flat or auto; IVF-PQ only supports cosine, dotproduct and euclidean",
+                req.distance_metric
+            ));
+        }
+    }
+    if !req.data_type.eq_ignore_ascii_case("float32") {
+        return Err(format!("Unsupported data type '{}', only float32 is supported", req.data_type));
+    }
+    if let Some(partitioning) = &req.partitioning {
+        // Partition names append a date to the parent's name.
+        if req.index_name.len() + crate::partitions::PARTITION_SUFFIX_LEN > 63 {
+            return Err(format!(
+                "Partitioned index names can be at most {} characters",
+                63 - crate::partitions::PARTITION_SUFFIX_LEN
+            ));
+        }
+        if partitioning.timestamp_key.is_empty() {
+            return Err("partitioning.timestampKey must name a metadata key".to_string());
+        }
+    }
+    if let Some(dedup) = &req.dedup {
+        dedup.validate()?;
+        // Duplicates are found by comparing raw vectors.
+        if req.store_raw_vectors == Some(false) {
+            return Err("dedup needs storeRawVectors".to_string());
+        }
+    }
+    if let Some(versioning) = &req.versioning {
+        versioning.validate()?;
+    }
+    if let Some(reduction) = &req.reduction {
+        reduction.validate(req.dimension)?;
+    }
+    if let Some(truncation) = &req.truncation {
+        truncation.validate(req.dimension, MAX_DIMENSION)?;
+    }
+    if req.flat_threshold.is_some_and(|threshold| threshold > crate::tuning::MAX_FLAT_THRESHOLD) {
+        return Err(format!("flatThreshold can be at most {}", crate::tuning::MAX_FLAT_THRESHOLD));
+    }
+    let settings = ivfpq_settings(req);
+    if !settings.is_empty() {
+        if matches!(create_algorithm(req).as_deref(), Some("flat" | "hnsw_flat")) {
+            return Err("nlist, m and nbits only apply to IVF-PQ indexes".to_string());
+        }
+        // Faiss indexes the projections when the index reduces dimensions.
+        settings.validate(req.reduction.map_or(req.dimension, |reduction| reduction.dimension))?;
+    }
+    match (req.default_nprobe, req.nlist) {
+        (Some(0), _) => return Err("defaultNprobe must be at least 1".to_string()),
+        (Some(nprobe), Some(nlist)) if nprobe > nlist => {
+            return Err(format!("defaultNprobe {} exceeds nlist {}", nprobe, nlist));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The IVF-PQ parameters a CreateIndex request fixes.
+fn ivfpq_settings(req: &S3CreateIndexRequest) -> crate::tuning::IvfpqSettings {
+    crate::tuning::IvfpqSettings { nlist: req.nlist, m: req.m, nbits: req.nbits }
+}
+
+/// The requested algorithm, or HNSW for metrics IVF-PQ can't index.
+fn create_algorithm(req: &S3CreateIndexRequest) -> Option<String> {
+    match &req.algorithm {
+        Some(algorithm) => Some(algorithm.to_lowercase()),
+        None if !crate::faiss_utils::ivfpq_supports(&req.distance_metric) => Some("hnsw_flat".to_string()),
+        None => None,
+    }
+}
+
+/// Parameters that define an index; a repeated CreateIndex with the same ones is a no-op.
+pub(crate) fn same_index_parameters(existing: &CreateIndex, requested: &CreateIndex) -> bool {
+    let mut existing_keys = existing.non_filterable_metadata_keys.clone();
+    let mut requested_keys = requested.non_filterable_metadata_keys.clone();
+    existing_keys.sort();
+    requested_keys.sort();
+    existing.dim == requested.dim
+        && existing.metric == requested.metric
+        && existing_keys == requested_keys
+        && existing.store_raw_vectors == requested.store_raw_vectors
+        && existing.partitioning == requested.partitioning
+        && existing.dedup == requested.dedup
+        && existing.versioning == requested.versioning
+        && existing.algorithm == requested.algorithm
+        && existing.reduction == requested.reduction
+        && existing.truncation == requested.truncation
+        && existing.flat_threshold == requested.flat_threshold
+        && existing.indexing_priority == requested.indexing_priority
+        && existing.ivfpq == requested.ivfpq
+        && existing.default_nprobe == requested.default_nprobe
+        && existing.vector_bucket_name.as_ref().is_none_or(|b| Some(b) == requested.vector_bucket_name.as_ref())
+}
+
+/// The validated `config.json` of a CreateIndex request.
+pub(crate) fn index_config(req: &S3CreateIndexRequest) -> Result<CreateIndex, String> {
+    validate_create_index(req)?;
     let non_filterable_keys = req.metadata_configuration
         .as_ref()
         .map(|config| config.non_filterable_metadata_keys.clone())
         .unwrap_or_default();
     
-    let create_index_req = CreateIndex {
+    // Placeholders for what isn't configured; builds tune those from the
+    // data, see `crate::tuning`.
+    let (nlist, m, nbits) = crate::tuning::defaults(req.dimension as usize);
+    let settings = ivfpq_settings(req);
+    Ok(CreateIndex {
+        schema_version: crate::schema::CONFIG_SCHEMA_VERSION,
         name: req.index_name.clone(),
         dim: req.dimension,
         metric: req.distance_metric.to_lowercase(),
-        nlist: 16,
-        m: 8,
-        nbits: 8,
-        default_nprobe: Some(8),
-        non_filterable_metadata_keys: non_filterable_keys.clone(),
+        nlist: settings.nlist.unwrap_or(nlist as u32),
+        m: settings.m.unwrap_or(m as u32),
+        nbits: settings.nbits.unwrap_or(nbits as u32),
+        default_nprobe: Some(req.default_nprobe.unwrap_or(crate::faiss_utils::DEFAULT_NPROBE as u32)),
+        ivfpq: Some(settings).filter(|settings| !settings.is_empty()),
+        non_filterable_metadata_keys: non_filterable_keys,
+        vector_bucket_name: Some(req.vector_bucket_name.clone()),
+        store_raw_vectors: req.store_raw_vectors.unwrap_or(true),
+        partitioning: req.partitioning.clone(),
+        dedup: req.dedup,
+        versioning: req.versioning,
+        algorithm: create_algorithm(req),
+        reduction: req.reduction,
+        truncation: req.truncation,
+        flat_threshold: req.flat_threshold,
+        hnsw_threshold: None,
+        indexing_priority: req.indexing_priority,
+    })
+}
+
+/// CreateIndex - Create a new vector index
+pub async fn create(req: S3CreateIndexRequest, state: AppState) -> Response {
+    let create_index_req = match index_config(&req) {
+        Ok(config) => config,
+        Err(message) => {
+            let body = json!({"error": message, "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
     };
     
     let config_key = format!("indexes/{}/config.json", create_index_req.name);
+    let existing = state.s3.get_object(&config_key).await;
+    if let Some(max) = super::quotas::config().and_then(|q| q.for_bucket(&req.vector_bucket_name).max_indexes_per_bucket) {
+        if existing.is_err() {
+            match count_bucket_indexes(&state, &req.vector_bucket_name).await {
+                Ok(count) if count >= max => {
+                    return super::quotas::quota_exceeded(format!(
+                        "Bucket {} already has {} indexes, its limit", req.vector_bucket_name, count
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to count indexes: {}", e)).into_response(),
+            }
+        }
+    }
+    if let Ok(data) = existing {
+        match CreateIndex::from_slice(&data) {
+            Ok(existing) if same_index_parameters(&existing, &create_index_req) => {
+                tracing::info!("CreateIndex for existing index {} with identical parameters", create_index_req.name);
+            }
+            _ => {
+                let body = json!({
+                    "error": format!("Index {} already exists with different parameters", create_index_req.name),
+                    "code": "ConflictException"
+                });
+                return (StatusCode::CONFLICT, Json(body)).into_response();
+            }
+        }
+    }
     let config_data = match serde_json::to_vec(&create_index_req) {
         Ok(data) => data,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Serialization error: {}", e)).into_response(),
@@ -38,7 +228,7 @@ pub async fn create(bucket: String, body: Value, state: AppState) -> Response {
     }
     
     // AWS S3 Vectors CreateIndex returns index object per OpenAPI spec
-    let body = json!({
+    let mut body = json!({
         "index": {
             "vectorBucketName": req.vector_bucket_name,
             "indexName": req.index_name,
@@ -48,51 +238,157 @@ pub async fn create(bucket: String, body: Value, state: AppState) -> Response {
             "dimension": req.dimension,
             "distanceMetric": req.distance_metric.to_lowercase(),
             "metadataConfiguration": {
-                "nonFilterableMetadataKeys": non_filterable_keys
-            }
+                "nonFilterableMetadataKeys": create_index_req.non_filterable_metadata_keys
+            },
+            "storeRawVectors": create_index_req.store_raw_vectors
         }
     });
+    if let Some(partitioning) = &create_index_req.partitioning {
+        body["index"]["partitioning"] = json!(partitioning);
+    }
+    if let Some(dedup) = &create_index_req.dedup {
+        body["index"]["dedup"] = json!(dedup);
+    }
+    if let Some(versioning) = &create_index_req.versioning {
+        body["index"]["versioning"] = json!(versioning);
+    }
+    if let Some(algorithm) = &create_index_req.algorithm {
+        body["index"]["algorithm"] = json!(algorithm);
+    }
+    if let Some(reduction) = &create_index_req.reduction {
+        body["index"]["reduction"] = json!(reduction);
+    }
+    if let Some(truncation) = &create_index_req.truncation {
+        body["index"]["truncation"] = json!(truncation);
+    }
+    if let Some(flat_threshold) = create_index_req.flat_threshold {
+        body["index"]["flatThreshold"] = json!(flat_threshold);
+    }
+    if let Some(priority) = create_index_req.indexing_priority {
+        body["index"]["indexingPriority"] = json!(priority);
+    }
+    for (key, value) in [("nlist", req.nlist), ("m", req.m), ("nbits", req.nbits), ("defaultNprobe", req.default_nprobe)] {
+        if let Some(value) = value {
+            body["index"][key] = json!(value);
+        }
+    }
     (StatusCode::OK, Json(body)).into_response()
 }
 
-/// ListIndexes - List all indexes in a bucket
-pub async fn list(bucket: String, state: AppState) -> Response {
+/// Default and maximum page size for ListIndexes, per the S3 Vectors spec.
+const MAX_LIST_INDEXES_RESULTS: usize = 500;
+
+/// Pagination tokens are the last index (or bucket) name of the previous page,
+/// encoded so clients treat them as opaque.
+pub(super) fn encode_token(index_name: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(index_name)
+}
+
+pub(super) fn decode_token(token: &str) -> Option<String> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(token).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Index names from `indexes/<name>/config.json` keys, sorted, filtered by prefix
+/// and starting after the pagination token.
+pub(super) fn candidate_index_names(keys: Vec<String>, prefix: Option<&str>, after: Option<&str>) -> Vec<String> {
+    let mut names: Vec<String> = keys
+        .iter()
+        .filter_map(|key| key.strip_prefix("indexes/").and_then(|s| s.strip_suffix("/config.json")))
+        .filter(|name| !name.contains('/'))
+        .filter(|name| prefix.is_none_or(|p| name.starts_with(p)))
+        .filter(|name| after.is_none_or(|a| *name > a))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    names
+}
+
+/// Indexes created before configs recorded their bucket belong to the service's
+/// default bucket.
+fn index_in_bucket(config: &CreateIndex, bucket: &str) -> bool {
+    match &config.vector_bucket_name {
+        Some(owner) => owner == bucket,
+        None => bucket == std::env::var("VEC_BUCKET").unwrap_or_else(|_| "vectors".to_string()),
+    }
+}
+
+async fn count_bucket_indexes(state: &AppState, bucket: &str) -> anyhow::Result<u64> {
+    let mut count = 0;
+    for name in candidate_index_names(state.s3.list_objects("indexes/").await?, None, None) {
+        let Ok(data) = state.s3.get_object(&format!("indexes/{}/config.json", name)).await else { continue };
+        if CreateIndex::from_slice(&data).is_ok_and(|config| index_in_bucket(&config, bucket)) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// ListIndexes - List the indexes in a bucket, with prefix filtering and pagination
+pub async fn list(req: S3ListIndexesRequest, state: AppState) -> Response {
+    let bucket = req.vector_bucket_name;
+    let max_results = match req.max_results {
+        None => MAX_LIST_INDEXES_RESULTS,
+        Some(n) if (1..=MAX_LIST_INDEXES_RESULTS as u32).contains(&n) => n as usize,
+        Some(n) => {
+            let body = json!({"error": format!("maxResults must be between 1 and {}, got {}", MAX_LIST_INDEXES_RESULTS, n)});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    let after = match req.next_token.as_deref().map(decode_token) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid nextToken").into_response(),
+        Some(Some(name)) => Some(name),
+        None => None,
+    };
+
     match state.s3.list_objects("indexes/").await {
         Ok(objects) => {
             let mut indexes = Vec::new();
+            let mut next_token = None;
             
-            for object_key in objects {
-                if object_key.ends_with("/config.json") {
-                    if let Some(index_name) = object_key.strip_prefix("indexes/").and_then(|s| s.strip_suffix("/config.json")) {
-                        // Load the index configuration to get details
-                        match state.s3.get_object(&object_key).await {
-                            Ok(data) => {
-                                if let Ok(config) = serde_json::from_slice::<CreateIndex>(&data) {
-                                    let index_summary = json!({
-                                        "vectorBucketName": bucket,
-                                        "indexName": index_name,
-                                        "indexArn": format!("arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/{}/index/{}", bucket, index_name),
-                                        "creationTime": "2025-07-01T13:00:00Z",
-                                        "dataType": "float32",
-                                        "dimension": config.dim,
-                                        "distanceMetric": config.metric.to_lowercase(),
-                                        "metadataConfiguration": {
-                                            "nonFilterableMetadataKeys": config.non_filterable_metadata_keys
-                                        }
-                                    });
-                                    indexes.push(index_summary);
-                                }
-                            }
-                            Err(e) => {
-                                tracing::warn!("Failed to load config for index {}: {}", index_name, e);
-                            }
-                        }
+            for index_name in candidate_index_names(objects, req.prefix.as_deref(), after.as_deref()) {
+                let object_key = format!("indexes/{}/config.json", index_name);
+                // Load the index configuration to get details
+                let config = match state.s3.get_object(&object_key).await {
+                    Ok(data) => match CreateIndex::from_slice(&data) {
+                        Ok(config) => config,
+                        Err(_) => continue,
+                    },
+                    Err(e) => {
+                        tracing::warn!("Failed to load config for index {}: {}", index_name, e);
+                        continue;
                     }
+                };
+                if !index_in_bucket(&config, &bucket) {
+                    continue;
+                }
+                if indexes.len() == max_results {
+                    // Another matching index exists, so there is a next page.
+                    next_token = indexes.last()
+                        .and_then(|i: &Value| i["indexName"].as_str())
+                        .map(encode_token);
+                    break;
                 }
+                indexes.push(json!({
+                    "vectorBucketName": bucket,
+                    "indexName": index_name,
+                    "indexArn": format!("arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/{}/index/{}", bucket, index_name),
+                    "creationTime": "2025-07-01T13:00:00Z",
+                    "dataType": "float32",
+                    "dimension": config.dim,
+                    "distanceMetric": config.metric.to_lowercase(),
+                    "metadataConfiguration": {
+                        "nonFilterableMetadataKeys": config.non_filterable_metadata_keys
+                    },
+                    "storeRawVectors": config.store_raw_vectors
+                }));
             }
             
             // AWS S3 Vectors ListIndexes format per OpenAPI spec
-            let body = json!({"indexes": indexes});
+            let mut body = json!({"indexes": indexes});
+            if let Some(token) = next_token {
+                body["nextToken"] = json!(token);
+            }
             (StatusCode::OK, Json(body)).into_response()
         }
         Err(e) => {
@@ -103,15 +399,13 @@ pub async fn list(bucket: String, state: AppState) -> Response {
 }
 
 /// GetIndex - Get information about a specific index
-pub async fn get(bucket: String, body: Value, state: AppState) -> Response {
-    let index_name = body.get("indexName")
-        .and_then(|v| v.as_str())
-        .unwrap_or("default");
+pub async fn get(req: IndexRequest, state: AppState) -> Response {
+    let (bucket, index_name) = (req.vector_bucket_name, req.index_name.as_str());
     
     let config_key = format!("indexes/{}/config.json", index_name);
     match state.s3.get_object(&config_key).await {
         Ok(data) => {
-            match serde_json::from_slice::<CreateIndex>(&data) {
+            match CreateIndex::from_slice(&data) {
                 Ok(config) => {
                     let vector_prefix = format!("{}/vectors/", index_name);
                     let vector_count = state.s3.list_objects(&vector_prefix).await
@@ -119,7 +413,7 @@ pub async fn get(bucket: String, body: Value, state: AppState) -> Response {
                         .unwrap_or(0);
                     
                     // AWS S3 Vectors GetIndex format per OpenAPI spec
-                    let body = json!({
+                    let mut body = json!({
                         "index": {
                             "vectorBucketName": bucket,
                             "indexName": index_name,
@@ -130,9 +424,25 @@ pub async fn get(bucket: String, body: Value, state: AppState) -> Response {
                             "distanceMetric": config.metric.to_lowercase(),
                             "metadataConfiguration": {
                                 "nonFilterableMetadataKeys": config.non_filterable_metadata_keys
-                            }
+                            },
+                            "storeRawVectors": config.store_raw_vectors
                         }
                     });
+                    if let Some(partitioning) = &config.partitioning {
+                        let partitions = crate::partitions::list(&state.s3, index_name, partitioning).await
+                            .unwrap_or_default();
+                        body["index"]["partitioning"] = json!(partitioning);
+                        body["index"]["partitions"] = json!(partitions);
+                    }
+                    if let Some(dedup) = &config.dedup {
+                        body["index"]["dedup"] = json!(dedup);
+                    }
+                    if let Some(versioning) = &config.versioning {
+                        body["index"]["versioning"] = json!(versioning);
+                    }
+                    if let Some(priority) = config.indexing_priority {
+                        body["index"]["indexingPriority"] = json!(priority);
+                    }
                     (StatusCode::OK, Json(body)).into_response()
                 }
                 Err(e) => {
@@ -148,29 +458,73 @@ pub async fn get(bucket: String, body: Value, state: AppState) -> Response {
     }
 }
 
+/// GetIndexStats - Stored vectors, shards and pending slices of an index, with
+/// the ingest throughput this replica has seen for it
+pub async fn stats(req: IndexRequest, state: AppState) -> Response {
+    let (bucket, index_name) = (req.vector_bucket_name, req.index_name.as_str());
+    
+    if state.s3.get_object(&format!("indexes/{}/config.json", index_name)).await.is_err() {
+        let body = json!({"error": format!("Index {} not found", index_name), "code": "NotFoundException"});
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    let manifest: Value = state.s3.get_object(&format!("indexes/{}/manifest.json", index_name)).await
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or(Value::Null);
+    let pending_slices = state.s3.list_objects(&format!("staged/{}/", index_name)).await
+        .map(|keys| keys.len())
+        .unwrap_or(0);
+    
+    let body = json!({
+        "vectorBucketName": bucket,
+        "indexName": index_name,
+        "totalVectors": manifest.get("total_vectors").and_then(|t| t.as_u64()).unwrap_or(0),
+        "shardCount": manifest.get("shards").and_then(|s| s.as_array()).map_or(0, |s| s.len()),
+        "pendingSlices": pending_slices,
+        "ingest": crate::metrics::get_metrics_collector().ingest_stats(index_name),
+    });
+    (StatusCode::OK, Json(body)).into_response()
+}
+
 /// DeleteIndex - Delete an index and all its vectors
-pub async fn delete(bucket: String, body: Value, state: AppState) -> Response {
-    let index_name = body.get("indexName")
-        .and_then(|v| v.as_str())
-        .unwrap_or("default");
-    
-    // Delete all objects associated with this index
-    let index_prefix = format!("{}/", index_name);
-    match state.s3.list_objects(&index_prefix).await {
-        Ok(objects) => {
-            for object_key in objects {
-                let _ = state.s3.delete_object(&object_key).await;
+pub async fn delete(req: DeleteIndexRequest, state: AppState) -> Response {
+    let (bucket, index_name) = (req.vector_bucket_name, req.index_name.as_str());
+    let force = req.force.unwrap_or(false);
+    let quotas = super::quotas::config().is_some();
+    
+    if let Some(retention) = crate::trash::retention_from_env().filter(|_| !force) {
+        // The ledger entry goes into the trash with the index, for RestoreIndex.
+        let usage = if quotas {
+            crate::usage::load(&state.s3, &bucket).await.ok().and_then(|mut usage| usage.indexes.remove(index_name))
+        } else {
+            None
+        };
+        return match crate::trash::soft_delete(&state.s3, index_name, &bucket, retention, usage).await {
+            Ok(tombstone) => {
+                if quotas {
+                    release_usage(&state, &bucket, index_name).await;
+                }
+                let body = json!({
+                    "bucket": bucket,
+                    "index": index_name,
+                    "deleted": true,
+                    "status": "success",
+                    "restorableUntil": tombstone.expires_at.to_rfc3339()
+                });
+                (StatusCode::OK, Json(body)).into_response()
             }
-        }
-        Err(e) => {
-            tracing::warn!("Failed to list objects for index {}: {}", index_name, e);
-        }
+            Err(e) => {
+                let body = json!({"error": format!("Failed to delete index: {}", e)});
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+            }
+        };
     }
     
-    // Delete the index configuration
-    let config_key = format!("indexes/{}/config.json", index_name);
-    match state.s3.delete_object(&config_key).await {
+    match crate::trash::delete_now(&state.s3, index_name, &bucket).await {
         Ok(_) => {
+            if quotas {
+                release_usage(&state, &bucket, index_name).await;
+            }
             let body = json!({"bucket": bucket, "index": index_name, "deleted": true, "status": "success"});
             (StatusCode::OK, Json(body)).into_response()
         }
@@ -181,61 +535,291 @@ pub async fn delete(bucket: String, body: Value, state: AppState) -> Response {
     }
 }
 
+/// Drop a deleted index from its bucket's usage ledger.
+async fn release_usage(state: &AppState, bucket: &str, index_name: &str) {
+    if let Err(e) = crate::usage::remove_index(&state.s3, bucket, index_name).await {
+        tracing::warn!(index = %index_name, error = %e, "Failed to remove index from usage ledger");
+    }
+}
+
+/// RestoreIndex - Bring back a soft-deleted index within its retention window
+pub async fn restore(req: RestoreIndexRequest, state: AppState) -> Response {
+    let (bucket, index_name, deleted_at) = (req.vector_bucket_name, req.index_name.as_str(), req.deleted_at);
+    
+    if state.s3.get_object(&format!("indexes/{}/config.json", index_name)).await.is_ok() {
+        let body = json!({
+            "error": format!("Index {} already exists; delete or rename it before restoring", index_name),
+            "code": "ConflictException"
+        });
+        return (StatusCode::CONFLICT, Json(body)).into_response();
+    }
+    
+    // Restores copy every object back, so they run as a job (poll with GetJob).
+    // They are not cancellable: stopping halfway would leave a partial index.
+    let s3 = state.s3.clone();
+    let index = index_name.to_string();
+    let job = crate::jobs::spawn(&state.s3, "RestoreIndex", move |_cancel| async move {
+        let tombstone = crate::trash::restore(&s3, &index, deleted_at).await?;
+        if let (Some(usage), Some(vector_bucket)) = (tombstone.usage.clone(), tombstone.vector_bucket.as_deref()) {
+            if let Err(e) = crate::usage::restore_index(&s3, vector_bucket, &index, usage).await {
+                tracing::warn!(index = %index, error = %e, "Failed to restore index usage");
+            }
+        }
+        Ok(json!({ "index": index, "restored": true, "deletedAt": tombstone.deleted_at.to_rfc3339() }))
+    }).await;
+    job_accepted(bucket, job)
+}
+
+/// 202 response for operations handed to the jobs subsystem.
+fn job_accepted(bucket: String, job: anyhow::Result<crate::jobs::Job>) -> Response {
+    match job {
+        Ok(job) => {
+            let body = json!({ "bucket": bucket, "jobId": job.job_id, "status": job.status });
+            (StatusCode::ACCEPTED, Json(body)).into_response()
+        }
+        Err(e) => {
+            let body = json!({"error": format!("Failed to start job: {}", e)});
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+/// CopyIndex - Duplicate an index (config, manifest, shards, vectors) under a new name
+pub async fn copy(req: CopyIndexRequest, state: AppState) -> Response {
+    let CopyIndexRequest { vector_bucket_name: bucket, source_index_name: source, target_index_name: target } = req;
+    
+    if state.s3.get_object(&format!("indexes/{}/config.json", source)).await.is_err() {
+        let body = json!({"error": format!("Index not found: {}", source)});
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    if state.s3.get_object(&format!("indexes/{}/config.json", target)).await.is_ok() {
+        let body = json!({"error": format!("Index {} already exists", target), "code": "ConflictException"});
+        return (StatusCode::CONFLICT, Json(body)).into_response();
+    }
+    
+    let s3 = state.s3.clone();
+    let vector_bucket = bucket.clone();
+    let job = crate::jobs::spawn(&state.s3, "CopyIndex", move |cancel| async move {
+        let copied = crate::index_copy::copy_index(&s3, &source, &target, &vector_bucket, &cancel).await?;
+        Ok(json!({ "sourceIndexName": source, "targetIndexName": target, "objectsCopied": copied }))
+    }).await;
+    job_accepted(bucket, job)
+}
+
+/// ExportIndex - Write every vector of an index as parquet files under `exports/<index>/<ts>/`
+pub async fn export(req: IndexRequest, state: AppState) -> Response {
+    let bucket = req.vector_bucket_name;
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    
+    if state.s3.get_object(&format!("indexes/{}/config.json", index_name)).await.is_err() {
+        let body = json!({"error": format!("Index not found: {}", index_name)});
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    
+    let s3 = state.s3.clone();
+    let vector_bucket = bucket.clone();
+    let job = crate::jobs::spawn(&state.s3, "ExportIndex", move |cancel| async move {
+        let report = crate::export::export_index(&s3, &vector_bucket, &index_name, &cancel).await?;
+        Ok(serde_json::to_value(report)?)
+    }).await;
+    job_accepted(bucket, job)
+}
+
+/// ShadowBuild - Build an index's vectors again with other parameters and compare
+/// recall and latency against the parameters it is served with
+pub async fn shadow_build(req: ShadowBuildRequest, state: AppState) -> Response {
+    let bucket = req.vector_bucket_name;
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    
+    let config = match state.s3.get_object(&format!("indexes/{}/config.json", index_name)).await {
+        Ok(data) => crate::model::CreateIndex::from_slice(&data),
+        Err(_) => {
+            let body = json!({"error": format!("Index not found: {}", index_name)});
+            return (StatusCode::NOT_FOUND, Json(body)).into_response();
+        }
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            let body = json!({"error": format!("Failed to parse index config: {}", e)});
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response();
+        }
+    };
+    let parameters = req.parameters.unwrap_or_default();
+    let parameters = parameters.validate(&config.metric).map(|_| parameters);
+    let defaults = crate::shadow::BenchmarkOptions::default();
+    let options = crate::shadow::BenchmarkOptions {
+        queries: req.queries.unwrap_or(defaults.queries),
+        top_k: req.top_k.unwrap_or(defaults.top_k),
+        max_vectors: req.max_vectors.unwrap_or(defaults.max_vectors),
+    };
+    let options = options.validate().map(|_| options);
+    let (parameters, options) = match (parameters, options) {
+        (Ok(parameters), Ok(options)) => (parameters, options),
+        (Err(e), _) | (_, Err(e)) => {
+            let body = json!({"error": e, "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    
+    // Both builds stay inside the job; serving never sees them.
+    let s3 = state.s3.clone();
+    let vector_bucket = bucket.clone();
+    let job = crate::jobs::spawn(&state.s3, "ShadowBuild", move |cancel| async move {
+        let report = crate::shadow::shadow_build(&s3, &vector_bucket, &index_name, parameters, options, &cancel).await?;
+        Ok(serde_json::to_value(report)?)
+    }).await;
+    job_accepted(bucket, job)
+}
+
 // Direct handlers for S3 API routes
 use axum::extract::State;
 
 pub async fn list_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3ListIndexesRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    list(bucket, state).await
+    list(req, state).await
 }
 
 pub async fn get_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<IndexRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    get(bucket, payload, state).await
+    get(req, state).await
+}
+
+pub async fn stats_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<IndexRequest>,
+) -> impl IntoResponse {
+    stats(req, state).await
 }
 
 pub async fn delete_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<DeleteIndexRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    delete(bucket, payload, state).await
+    delete(req, state).await
 }
 
 pub async fn create_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3CreateIndexRequest>,
+) -> impl IntoResponse {
+    create(req, state).await
+}
+
+pub async fn restore_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<RestoreIndexRequest>,
+) -> impl IntoResponse {
+    restore(req, state).await
+}
+
+pub async fn copy_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<CopyIndexRequest>,
+) -> impl IntoResponse {
+    copy(req, state).await
+}
+
+pub async fn export_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<IndexRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    create(bucket, payload, state).await
+    export(req, state).await
+}
+
+pub async fn shadow_build_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<ShadowBuildRequest>,
+) -> impl IntoResponse {
+    shadow_build(req, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_index_name() {
+        assert!(validate_index_name("my-index.v2").is_ok());
+        assert!(validate_index_name("ab").is_err());
+        assert!(validate_index_name("My-Index").is_err());
+        assert!(validate_index_name("-index").is_err());
+        assert!(validate_index_name("index-").is_err());
+        assert!(validate_index_name("idx/evil").is_err());
+    }
+
+    #[test]
+    fn test_metric_algorithm_combinations() {
+        let request = |metric: &str, algorithm: Option<&str>| -> S3CreateIndexRequest {
+            serde_json::from_value(json!({
+                "vectorBucketName": "b",
+                "indexName": "idx",
+                "dataType": "float32",
+                "dimension": 4,
+                "distanceMetric": metric,
+                "algorithm": algorithm,
+            }))
+            .unwrap()
+        };
+        assert!(validate_create_index(&request("MANHATTAN", None)).is_ok());
+        assert_eq!(create_algorithm(&request("manhattan", None)).as_deref(), Some("hnsw_flat"));
+        assert_eq!(create_algorithm(&request("cosine", None)), None);
+        assert!(validate_create_index(&request("chebyshev", Some("hnsw_flat"))).is_ok());
+        assert!(validate_create_index(&request("chebyshev", Some("ivfpq"))).unwrap_err().contains("hnsw_flat"));
+        assert!(validate_create_index(&request("manhattan", Some("hybrid"))).is_err());
+        assert!(validate_create_index(&request("manhattan", Some("flat"))).is_ok());
+        assert!(validate_create_index(&request("dotproduct", Some("ivfpq"))).is_ok());
+        assert!(validate_create_index(&request("cosine", Some("lsh"))).is_err());
+
+        let mut large = request("cosine", None);
+        large.flat_threshold = Some(crate::tuning::MAX_FLAT_THRESHOLD + 1);
+        assert!(validate_create_index(&large).is_err());
+    }
+
+    #[test]
+    fn test_ivfpq_parameters_are_validated_and_kept() {
+        let request = |extra: Value| -> S3CreateIndexRequest {
+            let mut body = json!({
+                "vectorBucketName": "b",
+                "indexName": "idx",
+                "dataType": "float32",
+                "dimension": 64,
+                "distanceMetric": "cosine",
+            });
+            body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(body).unwrap()
+        };
+        let configured = request(json!({"nlist": 128, "m": 16, "defaultNprobe": 12}));
+        assert!(validate_create_index(&configured).is_ok());
+        assert_eq!(ivfpq_settings(&configured), crate::tuning::IvfpqSettings { nlist: Some(128), m: Some(16), nbits: None });
+        assert!(ivfpq_settings(&request(json!({}))).is_empty());
+
+        assert!(validate_create_index(&request(json!({"m": 10}))).unwrap_err().contains("divide"));
+        // m divides the reduced dimension, not the original one.
+        let reduced = json!({"m": 8, "reduction": {"method": "random", "dimension": 24}});
+        assert!(validate_create_index(&request(reduced)).is_ok());
+        assert!(validate_create_index(&request(json!({"nlist": 8, "defaultNprobe": 16}))).is_err());
+        assert!(validate_create_index(&request(json!({"defaultNprobe": 0}))).is_err());
+        assert!(validate_create_index(&request(json!({"nlist": 8, "algorithm": "hnsw_flat"}))).is_err());
+        assert!(validate_create_index(&request(json!({"nbits": 8, "distanceMetric": "manhattan"}))).is_err());
+    }
+
+    #[test]
+    fn test_candidate_index_names_prefix_and_token() {
+        let keys = ["b", "a", "ab", "c"]
+            .iter()
+            .map(|n| format!("indexes/{}/config.json", n))
+            .chain(["indexes/a/manifest.json".to_string()])
+            .collect();
+        assert_eq!(candidate_index_names(keys, Some("a"), None), vec!["a", "ab"]);
+
+        let keys = ["b", "a", "c"].iter().map(|n| format!("indexes/{}/config.json", n)).collect();
+        let token = encode_token("a");
+        let after = decode_token(&token);
+        assert_eq!(candidate_index_names(keys, None, after.as_deref()), vec!["b", "c"]);
+    }
 }
@@ -0,0 +1,179 @@
+use axum::{response::{IntoResponse, Response}, Json, http::StatusCode};
+use axum::extract::Path;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use crate::jobs::{Job, JobEvent, JobStatus};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use super::AppState;
+use super::schema::{JobRequest, ListJobsRequest, Typed};
+
+/// GetJob - Status and, once finished, result or error of a job
+pub async fn get(req: JobRequest, state: AppState) -> Response {
+    match crate::jobs::get(&state.s3, &req.job_id).await {
+        Ok(job) => (StatusCode::OK, Json(json!({ "job": job }))).into_response(),
+        Err(e) => {
+            let body = json!({"error": e.to_string()});
+            (StatusCode::NOT_FOUND, Json(body)).into_response()
+        }
+    }
+}
+
+/// ListJobs - List jobs, newest first, optionally only those with `status`
+pub async fn list(req: ListJobsRequest, state: AppState) -> Response {
+    match crate::jobs::list(&state.s3).await {
+        Ok(mut jobs) => {
+            if let Some(status) = req.status {
+                jobs.retain(|job| format!("{:?}", job.status) == status);
+            }
+            (StatusCode::OK, Json(json!({ "jobs": jobs }))).into_response()
+        }
+        Err(e) => {
+            let body = json!({"error": format!("Failed to list jobs: {}", e)});
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+/// CancelJob - Request cancellation of a running job
+pub async fn cancel(req: JobRequest, state: AppState) -> Response {
+    let job_id = req.job_id.as_str();
+    if crate::jobs::get(&state.s3, job_id).await.is_err() {
+        let body = json!({"error": format!("Job not found: {}", job_id)});
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    match crate::jobs::cancel(&state.s3, job_id).await {
+        Ok(job) => (StatusCode::OK, Json(json!({ "job": job }))).into_response(),
+        Err(e) => {
+            let body = json!({"error": e.to_string(), "code": "ConflictException"});
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+    }
+}
+
+/// How often a job running on another replica is checked on.
+const REMOTE_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Where an event stream is at: events to send before waiting for more, and
+/// what to wait on.
+struct EventStream {
+    s3: crate::minio::S3Client,
+    job_id: String,
+    queued: std::collections::VecDeque<Event>,
+    /// Events of a job running here; `None` polls the record instead.
+    events: Option<tokio::sync::broadcast::Receiver<JobEvent>>,
+    last_status: Option<JobStatus>,
+    /// Progress already sent, so the record's copy of it isn't sent again.
+    last_progress: Option<Value>,
+    finished: bool,
+}
+
+fn event(name: &str, data: &impl serde::Serialize) -> Event {
+    Event::default().event(name).json_data(data).unwrap_or_else(|_| Event::default().event(name))
+}
+
+impl EventStream {
+    /// Queue what a record says: its status when it changed, and the job
+    /// itself once it has finished.
+    fn record(&mut self, job: &Job) {
+        if job.status.is_finished() {
+            if job.progress.is_some() && job.progress != self.last_progress {
+                self.progress(job.progress.clone().unwrap_or_default());
+            }
+            self.queued.push_back(event("finished", &json!({ "job": job })));
+            self.finished = true;
+        } else if self.last_status != Some(job.status) {
+            self.queued.push_back(event("status", &json!({ "status": job.status })));
+        }
+        self.last_status = Some(job.status);
+    }
+
+    fn progress(&mut self, progress: Value) {
+        self.queued.push_back(event("progress", &progress));
+        self.last_progress = Some(progress);
+    }
+
+    async fn next(mut self) -> Option<(Event, Self)> {
+        loop {
+            if let Some(event) = self.queued.pop_front() {
+                return Some((event, self));
+            }
+            if self.finished {
+                return None;
+            }
+            if let Some(events) = &mut self.events {
+                match events.recv().await {
+                    Ok(JobEvent::Progress(progress)) => {
+                        self.progress(progress);
+                        continue;
+                    }
+                    Ok(JobEvent::Partial(result)) => return Some((event("partial", &result), self)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    // The outcome is recorded before the job lets go.
+                    Err(RecvError::Closed) => self.events = None,
+                }
+            } else {
+                tokio::time::sleep(REMOTE_POLL).await;
+            }
+            match crate::jobs::get(&self.s3, &self.job_id).await {
+                Ok(job) => self.record(&job),
+                Err(e) => {
+                    self.queued.push_back(event("error", &json!({ "error": e.to_string() })));
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+/// GET /jobs/:job_id/events - Server-sent events of a job: `progress` as it
+/// runs (e.g. slices read and shards built), `partial` results, `status`
+/// changes seen on other replicas, and `finished` with the job record, after
+/// which the stream ends. Works for jobs running on any replica; only those
+/// running on this one stream progress as it happens.
+pub async fn events(State(state): State<AppState>, Path(job_id): Path<String>) -> Response {
+    let job = match crate::jobs::get(&state.s3, &job_id).await {
+        Ok(job) => job,
+        Err(e) => return (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response(),
+    };
+    let (latest, events) = crate::jobs::subscribe(&job_id).unzip();
+    let mut stream = EventStream {
+        s3: state.s3,
+        job_id,
+        queued: Default::default(),
+        events,
+        last_status: None,
+        last_progress: None,
+        finished: false,
+    };
+    stream.record(&job);
+    if let Some(progress) = latest.flatten().filter(|_| !stream.finished) {
+        stream.progress(progress);
+    }
+    let stream = futures::stream::unfold(stream, EventStream::next).map(Ok::<_, std::convert::Infallible>);
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+// Direct handlers for S3 API routes
+use axum::extract::State;
+
+pub async fn get_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<JobRequest>,
+) -> impl IntoResponse {
+    get(req, state).await
+}
+
+pub async fn list_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<ListJobsRequest>,
+) -> impl IntoResponse {
+    list(req, state).await
+}
+
+pub async fn cancel_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<JobRequest>,
+) -> impl IntoResponse {
+    cancel(req, state).await
+}
@@ -1,33 +1,49 @@
-use axum::{Router, routing::{post, get, put, delete}, extract::{State, Path, Query}, Json, serve, response::{IntoResponse, Response}, http::StatusCode};
+use axum::{Router, routing::{post, get, put}, extract::{State, Path, Query}, Json, response::{IntoResponse, Response}, http::StatusCode};
 use crate::{model::*, ingest::Ingestor, minio::S3Client};
 use std::sync::Arc;
-use tokio::net::TcpListener;
 use serde::{Deserialize};
 use anyhow::Context;
 use serde_json::json;
 
 mod buckets;
 mod vectors;
-mod indices;
+pub(crate) mod indices;
+pub(crate) mod aliases;
+mod jobs;
+mod admin;
+mod audit;
+mod authz;
+mod cors;
+mod embedding_codec;
+mod events;
+#[cfg(feature = "flight")]
+mod flight;
+mod follower;
+mod idempotency;
+mod query_timeout;
+mod quotas;
+mod request_info;
+mod schema;
+pub mod server;
+mod transcoding;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use authz::{cluster_secret, CLUSTER_SECRET_HEADER};
 
 // Standard S3 API handlers for boto3 compatibility
 
 /// Handle GET / - List all buckets (S3 ListBuckets operation)
 async fn s3_list_buckets(State(state): State<AppState>) -> impl IntoResponse {
-    buckets::list(state).await
+    buckets::list(Default::default(), state).await
 }
 
 /// Handle PUT / - Create bucket from root with bucket name in request body
 async fn s3_create_bucket_root(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    schema::Typed(req): schema::Typed<schema::BucketRequest>,
 ) -> impl IntoResponse {
-    let bucket_name = payload.get("Bucket")
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    buckets::create(bucket_name, state).await
+    buckets::create(req.vector_bucket_name, state).await
 }
 
 /// Handle PUT /:bucket - Create bucket (S3 CreateBucket operation)
@@ -85,8 +101,12 @@ struct S3VectorBucketQuery {
     query_vectors: Option<String>,
 }
 
+// Request types of the S3 Vectors operations. They refuse fields they don't
+// know, so a misspelt one is an error rather than a silent default; the
+// schema layer checks every request against them (see `schema::check`).
+
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct S3CreateIndexRequest {
     pub vector_bucket_name: String,
     pub index_name: String,
@@ -95,31 +115,99 @@ pub struct S3CreateIndexRequest {
     pub distance_metric: String,
     #[serde(default)]
     pub metadata_configuration: Option<MetadataConfiguration>,
+    /// Defaults to storing raw vectors; `false` trades returnData and exact
+    /// reranking for half the shard storage.
+    #[serde(default)]
+    pub store_raw_vectors: Option<bool>,
+    /// Make this a parent index over per-day or per-week child indexes.
+    #[serde(default)]
+    pub partitioning: Option<crate::partitions::Partitioning>,
+    /// Reject or flag vectors nearly identical to one already indexed.
+    #[serde(default)]
+    pub dedup: Option<crate::dedup::Dedup>,
+    /// Keep a history of each key's writes, readable through GetVectors.
+    #[serde(default)]
+    pub versioning: Option<crate::versions::Versioning>,
+    /// Shard index structure: `auto` (the default, chosen per build from the
+    /// data), `flat`, `ivfpq`, `hnsw_flat`, or `hybrid` (HNSW while the index
+    /// is small, IVF-PQ past its threshold).
+    #[serde(default)]
+    pub algorithm: Option<String>,
+    /// Vectors up to which shards are built flat (exact) whatever the algorithm
+    /// but `hnsw_flat`; 0 turns flat shards off.
+    #[serde(default)]
+    pub flat_threshold: Option<usize>,
+    /// IVF-PQ inverted lists, PQ subquantizers and bits per code; each one
+    /// left unset is tuned per build from the data.
+    #[serde(default)]
+    pub nlist: Option<u32>,
+    #[serde(default)]
+    pub m: Option<u32>,
+    #[serde(default)]
+    pub nbits: Option<u32>,
+    /// nprobe for queries against the index; `DEFAULT_NPROBE` when unset.
+    #[serde(default)]
+    pub default_nprobe: Option<u32>,
+    /// Index lower-dimensional projections of the embeddings.
+    #[serde(default)]
+    pub reduction: Option<crate::reduction::Reduction>,
+    /// Accept longer Matryoshka embeddings and store them cut to `dimension`.
+    #[serde(default)]
+    pub truncation: Option<crate::truncation::Truncation>,
+    /// Build this index's pending slices before those of lower-priority
+    /// indexes (default 0; negative to yield to the rest).
+    #[serde(default)]
+    pub indexing_priority: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct MetadataConfiguration {
     #[serde(default)]
     pub non_filterable_metadata_keys: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct S3ListIndexesRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub max_results: Option<u32>,
+    #[serde(default)]
+    pub next_token: Option<String>,
+}
+
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct S3PutVectorsRequest {
     #[serde(rename = "indexName")]
     pub index_name: Option<String>,
-    #[serde(rename = "vectorBucketName")]
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
     pub vector_bucket_name: Option<String>,
     #[serde(rename = "indexArn")]
     pub index_arn: Option<String>,
     pub vectors: Vec<serde_json::Value>,
+    /// Applies to every vector without its own `condition`.
+    #[serde(default)]
+    pub condition: Option<crate::ingest::PutCondition>,
+    /// Write the vectors that pass validation and report the rest, instead
+    /// of rejecting the whole batch.
+    #[serde(rename = "allowPartial", default)]
+    pub allow_partial: bool,
+    /// Read by the idempotency layer, in place of the `Idempotency-Key` header.
+    #[serde(rename = "idempotencyKey", default)]
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct S3GetVectorsRequest {
     #[serde(rename = "indexName")]
     pub index_name: Option<String>,
-    #[serde(rename = "vectorBucketName")]
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
     pub vector_bucket_name: Option<String>,
     #[serde(rename = "indexArn")]
     pub index_arn: Option<String>,
@@ -128,13 +216,20 @@ pub struct S3GetVectorsRequest {
     pub return_data: bool,
     #[serde(rename = "returnMetadata", default)]
     pub return_metadata: bool,
+    /// Version to read per key, on versioned indexes; other keys read the latest.
+    #[serde(default)]
+    pub versions: std::collections::HashMap<String, i64>,
+    /// Add the versions kept for each key as `versionHistory`.
+    #[serde(rename = "returnVersionHistory", default)]
+    pub return_version_history: bool,
 }
 
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct S3DeleteVectorsRequest {
     #[serde(rename = "indexName")]
     pub index_name: Option<String>,
-    #[serde(rename = "vectorBucketName")]
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
     pub vector_bucket_name: Option<String>,
     #[serde(rename = "indexArn")]
     pub index_arn: Option<String>,
@@ -142,36 +237,58 @@ pub struct S3DeleteVectorsRequest {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct S3ListVectorsRequest {
-    #[serde(rename = "indexName")]
-    pub index_name: Option<String>,
-    #[serde(rename = "vectorBucketName")]
-    pub vector_bucket_name: Option<String>,
-    #[serde(rename = "indexArn")]
-    pub index_arn: Option<String>,
-    #[serde(rename = "maxResults")]
+    pub index_name: String,
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
     pub max_results: Option<u32>,
-    #[serde(rename = "nextToken")]
     pub next_token: Option<String>,
+    #[serde(default)]
+    pub return_data: bool,
+    #[serde(default)]
+    pub return_metadata: bool,
+    /// Parallel listing segments of the S3 Vectors API; one segment here.
+    pub segment_count: Option<u32>,
+    pub segment_index: Option<u32>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct S3QueryVectorsRequest {
-    #[serde(rename = "indexName")]
+    /// One of `indexName` and `indexNames` is required.
     pub index_name: Option<String>,
-    #[serde(rename = "vectorBucketName")]
-    pub vector_bucket_name: Option<String>,
-    #[serde(rename = "indexArn")]
-    pub index_arn: Option<String>,
-    pub vector: Vec<f64>,
-    #[serde(rename = "topK")]
+    pub index_names: Option<Vec<String>>,
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    /// `queryVector` (or `vector`): an array, `{"float32": [..]}` or
+    /// `{"data": {"float32": [..]}}`.
+    pub query_vector: Option<serde_json::Value>,
+    pub vector: Option<serde_json::Value>,
     pub top_k: Option<u32>,
-    #[serde(rename = "returnData", default)]
+    pub next_token: Option<String>,
+    pub offset: Option<u64>,
+    pub result_window: Option<u64>,
+    #[serde(default)]
     pub return_data: bool,
-    #[serde(rename = "returnMetadata", default)]
+    #[serde(default)]
     pub return_metadata: bool,
-    #[serde(rename = "metadataFilter")]
+    /// Distances are always returned; accepted for S3 Vectors clients.
+    #[serde(default)]
+    pub return_distance: bool,
+    /// `metadataFilter`, or `filter` as the S3 Vectors API names it.
     pub metadata_filter: Option<serde_json::Value>,
+    pub filter: Option<serde_json::Value>,
+    pub fusion: Option<crate::fusion::Fusion>,
+    pub query_text: Option<String>,
+    pub vector_weight: Option<f64>,
+    #[serde(default)]
+    pub explain: bool,
+    #[serde(default)]
+    pub exact_rerank: bool,
+    /// Skip shards that fail to load or search instead of failing the query.
+    #[serde(default)]
+    pub allow_partial_results: bool,
 }
 
 #[derive(Clone)]
@@ -180,6 +297,14 @@ pub struct AppState {
     pub ingest: Arc<Ingestor>,
 }
 
+impl AppState {
+    /// State over `s3`, staging PutVectors slices for `bucket`.
+    pub fn new(s3: S3Client, bucket: String) -> Self {
+        let ingest = Arc::new(Ingestor::new(s3.clone(), bucket));
+        AppState { s3, ingest }
+    }
+}
+
 // Handler for S3-style path-based operations (e.g., GET /:bucket_name?operation=value)
 async fn s3_vectors_handler(
     Path(operation): Path<String>,
@@ -198,75 +323,9 @@ async fn s3_vectors_handler(
         }
     };
 
-    match operation.as_str() {
-        "CreateVectorBucket" => {
-            buckets::create(operation.clone(), state).await
-        }
-        "ListVectorBuckets" => {
-            buckets::list(state).await
-        }
-        "GetVectorBucket" => {
-            buckets::get(operation.clone(), state).await
-        }
-        "DeleteVectorBucket" => {
-            buckets::delete(operation.clone(), state).await
-        }
-        "CreateIndex" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            indices::create(bucket_name.to_string(), body, state).await
-        }
-        "ListIndexes" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            indices::list(bucket_name.to_string(), state).await
-        }
-        "GetIndex" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            indices::get(bucket_name.to_string(), body, state).await
-        }
-        "DeleteIndex" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            indices::delete(bucket_name.to_string(), body, state).await
-        }
-        "PutVectors" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            vectors::put(bucket_name.to_string(), body, state).await
-        }
-        "ListVectors" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            vectors::list(bucket_name.to_string(), body, state).await
-        }
-        "GetVectors" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            vectors::get(bucket_name.to_string(), body, state).await
-        }
-        "DeleteVectors" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            vectors::delete(bucket_name.to_string(), body, state).await
-        }
-        "QueryVectors" => {
-            let bucket_name = body.get("vectorBucketName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default-bucket");
-            vectors::query(bucket_name.to_string(), body, state).await
-        },
-        // Fallback: legacy operations not supported
-        _ => {
+    match dispatch(&operation, body, state).await {
+        Some(response) => response,
+        None => {
             tracing::warn!("Unknown S3 vectors operation - path: {}", operation);
             (StatusCode::BAD_REQUEST, format!("Invalid S3 vectors operation: {}", operation)).into_response()
         }
@@ -280,104 +339,86 @@ async fn s3_rpc_handler(
 ) -> Response {
     tracing::info!("S3 RPC handler - body: {}", body);
     
-    let body = if body.is_empty() {
-        serde_json::json!({})
-    } else {
-        match serde_json::from_str::<serde_json::Value>(&body) {
-            Ok(json) => json,
-            Err(e) => {
-                tracing::error!("Failed to parse RPC body: {}", e);
-                return (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)).into_response();
-            }
+    let (operation, body) = match parse_rpc_body(&body) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            tracing::warn!("Rejected RPC request: {}", message);
+            return (StatusCode::BAD_REQUEST, message).into_response();
         }
     };
     
-    // Check for operation in the body
-    if let Some(operation) = body.get("operation").and_then(|v| v.as_str()) {
-        match operation {
-            "CreateVectorBucket" => {
-                let bucket_name = body.get("bucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                buckets::create(bucket_name.to_string(), state).await
-            }
-            "ListVectorBuckets" => {
-                buckets::list(state).await
-            }
-            "GetVectorBucket" => {
-                let bucket_name = body.get("bucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                buckets::get(bucket_name.to_string(), state).await
-            }
-            "DeleteVectorBucket" => {
-                let bucket_name = body.get("bucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                buckets::delete(bucket_name.to_string(), state).await
-            }
-            "CreateIndex" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                indices::create(bucket_name.to_string(), body, state).await
-            }
-            "ListIndexes" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                indices::list(bucket_name.to_string(), state).await
-            }
-            "GetIndex" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                indices::get(bucket_name.to_string(), body, state).await
-            }
-            "DeleteIndex" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                indices::delete(bucket_name.to_string(), body, state).await
-            }
-            "PutVectors" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                vectors::put(bucket_name.to_string(), body, state).await
-            }
-            "ListVectors" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                vectors::list(bucket_name.to_string(), body, state).await
-            }
-            "GetVectors" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                vectors::get(bucket_name.to_string(), body, state).await
-            }
-            "DeleteVectors" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                vectors::delete(bucket_name.to_string(), body, state).await
-            }
-            "QueryVectors" => {
-                let bucket_name = body.get("vectorBucketName")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("default-bucket");
-                vectors::query(bucket_name.to_string(), body, state).await
-            }
-            _ => {
-                tracing::warn!("Unknown RPC operation: {}", operation);
-                (StatusCode::BAD_REQUEST, format!("Unknown operation: {}", operation)).into_response()
-            }
+    match dispatch(&operation, body, state).await {
+        Some(response) => response,
+        None => {
+            tracing::warn!("Unknown RPC operation: {}", operation);
+            (StatusCode::BAD_REQUEST, format!("Unknown operation: {}", operation)).into_response()
         }
+    }
+}
+
+/// Run the handler of `operation` on `body` read as the operation's request
+/// type; `None` for operations without a handler.
+async fn dispatch(operation: &str, body: serde_json::Value, state: AppState) -> Option<Response> {
+    let response = match operation {
+        "CreateVectorBucket" => with_request(body, state, |req: schema::BucketRequest, state| buckets::create(req.vector_bucket_name, state)).await,
+        "ListVectorBuckets" => with_request(body, state, buckets::list).await,
+        "GetVectorBucket" => with_request(body, state, |req: schema::BucketRequest, state| buckets::get(req.vector_bucket_name, state)).await,
+        "DeleteVectorBucket" => with_request(body, state, |req: schema::BucketRequest, state| buckets::delete(req.vector_bucket_name, state)).await,
+        "CreateIndex" => with_request(body, state, indices::create).await,
+        "ListIndexes" => with_request(body, state, indices::list).await,
+        "GetIndex" => with_request(body, state, indices::get).await,
+        "GetIndexStats" => with_request(body, state, indices::stats).await,
+        "DeleteIndex" => with_request(body, state, indices::delete).await,
+        "RestoreIndex" => with_request(body, state, indices::restore).await,
+        "CopyIndex" => with_request(body, state, indices::copy).await,
+        "ExportIndex" => with_request(body, state, indices::export).await,
+        "ShadowBuild" => with_request(body, state, indices::shadow_build).await,
+        "GetJob" => with_request(body, state, jobs::get).await,
+        "ListJobs" => with_request(body, state, jobs::list).await,
+        "CancelJob" => with_request(body, state, jobs::cancel).await,
+        "PutVectors" => with_request(body, state, vectors::put).await,
+        "ListVectors" => with_request(body, state, vectors::list).await,
+        "ScrollVectors" => with_request(body, state, vectors::scroll).await,
+        "FilterVectors" => with_request(body, state, vectors::filter).await,
+        "CountVectors" => with_request(body, state, vectors::count).await,
+        "GetVectors" => with_request(body, state, vectors::get).await,
+        "DeleteVectors" => with_request(body, state, vectors::delete).await,
+        "QueryVectors" => with_request(body, state, vectors::query).await,
+        "UpdateAlias" => with_request(body, state, aliases::update).await,
+        "GetAlias" => with_request(body, state, aliases::get).await,
+        "ListAliases" => with_request(body, state, aliases::list).await,
+        "DeleteAlias" => with_request(body, state, aliases::delete).await,
+        _ => return None,
+    };
+    Some(response)
+}
+
+/// `handler`'s response to `body` read as its request type, or the 400
+/// refusing a body that doesn't fit.
+async fn with_request<T, F, Fut>(body: serde_json::Value, state: AppState, handler: F) -> Response
+where
+    T: serde::de::DeserializeOwned + schema::Checked,
+    F: FnOnce(T, AppState) -> Fut,
+    Fut: std::future::Future<Output = Response>,
+{
+    match schema::parse(body) {
+        Ok(req) => handler(req, state).await,
+        Err(e) => schema::invalid(format!("Invalid request: {}", e)),
+    }
+}
+
+/// The `operation` of an RPC request body and the rest of the body, as the
+/// operation's handler takes it; an empty body is an empty object. Errors are
+/// the 400 response's message.
+pub fn parse_rpc_body(body: &str) -> Result<(String, serde_json::Value), String> {
+    let mut body = if body.is_empty() {
+        serde_json::json!({})
     } else {
-        tracing::warn!("Missing operation in RPC request");
-        (StatusCode::BAD_REQUEST, "Missing operation field".to_string()).into_response()
+        serde_json::from_str::<serde_json::Value>(body).map_err(|e| format!("Invalid JSON: {}", e))?
+    };
+    match body.as_object_mut().and_then(|fields| fields.remove("operation")) {
+        Some(serde_json::Value::String(operation)) => Ok((operation, body)),
+        _ => Err("Missing operation field".to_string()),
     }
 }
 
@@ -397,7 +438,7 @@ async fn create_index(
         "dataType": "FLOAT32"
     });
     
-    indices::create("vectors".to_string(), s3_body, state).await
+    with_request(s3_body, state, indices::create).await
 }
 
 async fn put_vectors(
@@ -410,10 +451,10 @@ async fn put_vectors(
     let s3_body = json!({
         "vectorBucketName": "vectors",
         "indexName": body.index,
-        "vectors": body.vectors
+        "vectors": body.vectors.iter().map(crate::ingest::record_document).collect::<Vec<_>>()
     });
     
-    vectors::put("vectors".to_string(), s3_body, state).await
+    with_request(s3_body, state, vectors::put).await
 }
 
 async fn query(
@@ -433,7 +474,18 @@ async fn query(
         "metadataFilter": body.filter
     });
     
-    vectors::query("vectors".to_string(), s3_body, state).await
+    with_request(s3_body, state, vectors::query).await
+}
+
+// POST /internal/shards/search - Search one shard owned by this replica
+async fn internal_search_shard(
+    State(state): State<AppState>,
+    Json(req): Json<crate::query::ShardSearchRequest>,
+) -> Response {
+    match crate::query::search_local_shard(state.s3, req).await {
+        Ok(results) => Json(results).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
 }
 
 // GET /health - Health check
@@ -441,19 +493,67 @@ async fn health() -> impl IntoResponse {
     Json(serde_json::json!({"status": "healthy"})).into_response()
 }
 
+/// Prometheus scrape endpoint
+async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::get_metrics_collector().prometheus_text(),
+    )
+}
+
+/// Largest request body accepted, from `VEC_MAX_REQUEST_MB` (default 2, axum's
+/// own default); bulk PutVectors loads need more.
+fn max_request_bytes() -> usize {
+    let mb = std::env::var("VEC_MAX_REQUEST_MB").ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(2);
+    mb.max(1) * 1024 * 1024
+}
+
 pub async fn run() -> anyhow::Result<()> {
     let bucket = std::env::var("VEC_BUCKET").unwrap_or_else(|_| "vectors".to_string());
     let s3 = S3Client::from_env().await?;
-    let ingest = Arc::new(Ingestor::new(s3.clone(), bucket));
+    crate::warmup::spawn(s3.clone());
+    crate::cluster::start(s3.clone());
+    crate::metrics::spawn_exporter(s3.clone());
+    crate::query_snapshots::spawn_sweeper(s3.clone());
+    // Write-side housekeeping stays with the leader.
+    if !crate::replica::is_follower() {
+        idempotency::spawn_sweeper(s3.clone());
+        crate::jobs::spawn_reaper(s3.clone());
+        crate::changelog::spawn_sweeper(s3.clone());
+    }
+    crate::replica::spawn_poller(s3.clone());
 
-    let state = AppState {
-        s3,
-        ingest,
-    };
+    authz::init()?;
+    quotas::init()?;
+
+    let state = AppState::new(s3, bucket);
+    #[cfg(feature = "flight")]
+    flight::spawn(state.clone());
+
+    let config = server::ServerConfig::from_env();
+    let listener = config.bind().await?;
+    tracing::info!(max_connections = ?config.max_connections, "API listening on {}", listener.local_addr()?);
+    server::serve(listener, build_router(state), &config).await?;
+    Ok(())
+}
 
-    let app = Router::new()
+/// Every route with its middleware, over `state`: the S3 Vectors operations
+/// (RPC-style on `/` and as `/{operation}`), the S3 bucket routes, the
+/// original `/indexes`, `/vectors` and `/query` endpoints and the admin API.
+/// Background tasks are started by [`run`], so tests and services embedding
+/// the API can serve this in-process.
+pub fn build_router(state: AppState) -> Router {
+    let mut app = Router::new()
         // Health check
         .route("/health", get(health))
+        .route("/metrics", get(prometheus_metrics))
+        // Operator stats, also the data source of the /ui dashboard
+        .route("/admin/stats/indexes", get(admin::indexes))
+        .route("/admin/stats/queries", get(admin::queries))
+        .route("/admin/stats/metrics", get(admin::metrics))
+        // Slices the indexer quarantined, and releasing them back to it
+        .route("/admin/quarantine", get(admin::quarantined))
+        .route("/admin/quarantine/retry", post(admin::retry_quarantined))
         // Standard S3 API endpoints for boto3 compatibility
         .route("/", get(s3_list_buckets))
         .route("/:bucket", put(s3_create_bucket).get(s3_get_bucket).delete(s3_delete_bucket))
@@ -461,6 +561,8 @@ pub async fn run() -> anyhow::Result<()> {
         .route("/indexes", post(create_index))
         .route("/vectors", post(put_vectors))
         .route("/query", post(query))
+        // Shard searches forwarded by other replicas in cluster mode
+        .route("/internal/shards/search", post(internal_search_shard))
         // S3 Vectors API compatibility endpoints - using the actual paths boto3 calls
         .route("/CreateVectorBucket", post(buckets::create_direct))
         .route("/ListVectorBuckets", post(buckets::list_direct))
@@ -469,22 +571,57 @@ pub async fn run() -> anyhow::Result<()> {
         .route("/CreateIndex", post(indices::create_direct))
         .route("/ListIndexes", post(indices::list_direct))
         .route("/GetIndex", post(indices::get_direct))
+        .route("/GetIndexStats", post(indices::stats_direct))
         .route("/DeleteIndex", post(indices::delete_direct))
+        .route("/RestoreIndex", post(indices::restore_direct))
+        .route("/CopyIndex", post(indices::copy_direct))
+        .route("/ExportIndex", post(indices::export_direct))
+        .route("/ShadowBuild", post(indices::shadow_build_direct))
         .route("/PutVectors", post(vectors::put_direct))
         .route("/ListVectors", post(vectors::list_direct))
         .route("/GetVectors", post(vectors::get_direct))
+        .route("/ScrollVectors", post(vectors::scroll_direct))
+        .route("/FilterVectors", post(vectors::filter_direct))
+        .route("/CountVectors", post(vectors::count_direct))
         .route("/DeleteVectors", post(vectors::delete_direct))
         .route("/QueryVectors", post(vectors::query_direct))
+        .route("/UpdateAlias", post(aliases::update_direct))
+        .route("/GetAlias", post(aliases::get_direct))
+        .route("/ListAliases", post(aliases::list_direct))
+        .route("/DeleteAlias", post(aliases::delete_direct))
+        .route("/GetJob", post(jobs::get_direct))
+        .route("/ListJobs", post(jobs::list_direct))
+        .route("/CancelJob", post(jobs::cancel_direct))
+        // Progress of a job as server-sent events
+        .route("/jobs/:job_id/events", get(jobs::events))
+        // Websocket of index manifest changes
+        .route("/events", get(events::events))
         // RPC and fallback handlers
         .route("/", post(s3_rpc_handler))
-        .route("/:bucket", post(s3_vectors_handler)) // For path-based ops
-        .with_state(state);
-
-    let addr = "0.0.0.0:8081";
-    let listener = TcpListener::bind(addr).await?;
-    tracing::info!("API listening on {addr}");
-    serve(listener, app).await?;
-    Ok(())
+        .route("/:bucket", post(s3_vectors_handler)); // For path-based ops
+    if admin::ui_enabled() {
+        app = app
+            .route("/ui", get(admin::ui_index))
+            .route("/ui/app.js", get(admin::ui_app_js));
+    }
+    app
+        // Layers run bottom-up: CORS, request id, MessagePack/CBOR transcoding, request
+        // info, audit, access control, the read-replica check, quotas, base64
+        // embeddings, the request schema, query timeouts, then idempotent replays
+        .layer(axum::middleware::from_fn_with_state(state.clone(), idempotency::middleware))
+        .layer(axum::middleware::from_fn(query_timeout::middleware))
+        .layer(axum::middleware::from_fn(schema::middleware))
+        .layer(axum::middleware::from_fn(embedding_codec::middleware))
+        .layer(axum::middleware::from_fn(quotas::middleware))
+        .layer(axum::middleware::from_fn(follower::middleware))
+        .layer(axum::middleware::from_fn(authz::middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), audit::middleware))
+        .layer(axum::middleware::from_fn(request_info::middleware))
+        .layer(axum::middleware::from_fn(transcoding::middleware))
+        .layer(axum::middleware::from_fn(crate::request_id::middleware))
+        .layer(axum::middleware::from_fn(cors::middleware))
+        .layer(axum::extract::DefaultBodyLimit::max(max_request_bytes()))
+        .with_state(state)
 }
 
 // Helper functions for metadata validation
@@ -495,19 +632,48 @@ async fn load_index_configuration(s3: &S3Client, index_name: &str) -> anyhow::Re
     let data = s3.get_object(&config_key).await
         .context("Failed to load index configuration")?;
     
-    let create_index: CreateIndex = serde_json::from_slice(&data)
+    let create_index = CreateIndex::from_slice(&data)
         .context("Failed to parse index configuration")?;
     
     Ok(IndexConfiguration {
+        dimension: create_index.dim as usize,
+        truncation: create_index.truncation,
         non_filterable_metadata_keys: create_index.non_filterable_metadata_keys,
     })
 }
 
 #[derive(Debug, Clone)]
 struct IndexConfiguration {
+    dimension: usize,
+    truncation: Option<crate::truncation::Truncation>,
     non_filterable_metadata_keys: Vec<String>,
 }
 
+/// Why PutVectors can't take `vector`, checked against the index's `config`
+/// when it has one: a key, numeric `data.float32` of the index's dimension
+/// (or the source dimension it truncates) and metadata within the size limits.
+fn validate_put_vector(vector: &serde_json::Value, config: Option<&IndexConfiguration>) -> Result<(), String> {
+    if vector.get("key").and_then(|k| k.as_str()).is_none_or(|k| k.is_empty()) {
+        return Err("Missing key".to_string());
+    }
+    let Some(data) = vector.get("data").and_then(|d| d.get("float32")).and_then(|f| f.as_array()) else {
+        return Err("Missing data.float32".to_string());
+    };
+    if !data.iter().all(serde_json::Value::is_number) {
+        return Err("data.float32 must only hold numbers".to_string());
+    }
+    let Some(config) = config else { return Ok(()) };
+    match config.truncation {
+        Some(truncation) if data.len() == truncation.source_dimension as usize => {}
+        _ if data.len() != config.dimension => {
+            return Err(format!("Vector has {} dimensions, expected {}", data.len(), config.dimension));
+        }
+        _ => {}
+    }
+    validate_vector_metadata(vector.get("metadata").unwrap_or(&serde_json::Value::Null), config)
+        .map_err(|e| e.to_string())
+}
+
 fn validate_vector_metadata(metadata: &serde_json::Value, config: &IndexConfiguration) -> anyhow::Result<()> {
     if let serde_json::Value::Object(map) = metadata {
         let mut filterable_size = 0;
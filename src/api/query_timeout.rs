@@ -0,0 +1,127 @@
+//! How long QueryVectors may take: `VEC_QUERY_TIMEOUT_MS` for every query,
+//! or the `x-query-timeout-ms` header for one. Shard searches still running
+//! at the timeout are cancelled, and the query fails with 504 `QueryTimeout`,
+//! or with `VEC_QUERY_PARTIAL_RESULTS=true` returns what the shards searched
+//! so far found, flagged `timedOut`.
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const TIMEOUT_HEADER: &str = "x-query-timeout-ms";
+
+tokio::task_local! {
+    static REQUEST_TIMEOUT: Duration;
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct QueryTimeouts {
+    /// `None` (the default, or `0`) lets queries run as long as they take.
+    timeout: Option<Duration>,
+    partial_results: bool,
+}
+
+impl QueryTimeouts {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        QueryTimeouts {
+            timeout: var("VEC_QUERY_TIMEOUT_MS")
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            partial_results: var("VEC_QUERY_PARTIAL_RESULTS").is_some_and(|v| v == "true" || v == "1"),
+        }
+    }
+}
+
+fn config() -> &'static QueryTimeouts {
+    static CONFIG: OnceLock<QueryTimeouts> = OnceLock::new();
+    CONFIG.get_or_init(|| QueryTimeouts::from_vars(|name| std::env::var(name).ok()))
+}
+
+/// Time the query being served may take: its header's, else the configured
+/// default.
+pub(super) fn timeout() -> Option<Duration> {
+    REQUEST_TIMEOUT.try_with(|timeout| *timeout).ok().or(config().timeout)
+}
+
+/// Whether a query that times out returns partial results.
+pub(super) fn partial_results() -> bool {
+    config().partial_results
+}
+
+/// Timeout a request asks for in `x-query-timeout-ms`; `Err` when the header
+/// isn't a positive number of milliseconds.
+fn requested(req: &Request) -> Result<Option<Duration>, String> {
+    let Some(value) = req.headers().get(TIMEOUT_HEADER) else { return Ok(None) };
+    match value.to_str().ok().and_then(|v| v.trim().parse::<u64>().ok()) {
+        Some(ms) if ms > 0 => Ok(Some(Duration::from_millis(ms))),
+        _ => Err(format!("{} must be a positive number of milliseconds", TIMEOUT_HEADER)),
+    }
+}
+
+/// Make the `x-query-timeout-ms` of a request the timeout of the queries it
+/// runs.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    match requested(&req) {
+        Ok(Some(timeout)) => REQUEST_TIMEOUT.scope(timeout, next.run(req)).await,
+        Ok(None) => next.run(req).await,
+        Err(message) => {
+            let body = json!({"error": message, "code": "ValidationException"});
+            (StatusCode::BAD_REQUEST, Json(body)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::TestApp;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_timeouts_come_from_env() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            QueryTimeouts::from_vars(crate::api::testing::env(pairs))
+        };
+        assert_eq!(vars(&[]), QueryTimeouts::default());
+        assert_eq!(vars(&[("VEC_QUERY_TIMEOUT_MS", "0")]), QueryTimeouts::default());
+        let config = vars(&[("VEC_QUERY_TIMEOUT_MS", "2500"), ("VEC_QUERY_PARTIAL_RESULTS", "true")]);
+        assert_eq!(config, QueryTimeouts { timeout: Some(Duration::from_millis(2500)), partial_results: true });
+    }
+
+    #[tokio::test]
+    async fn test_timeout_header() {
+        let request = |value: &str| Request::post("/QueryVectors").header(TIMEOUT_HEADER, value).body(Body::empty()).unwrap();
+        assert_eq!(requested(&request("150")), Ok(Some(Duration::from_millis(150))));
+        assert!(requested(&request("0")).is_err());
+        assert!(requested(&request("soon")).is_err());
+        assert_eq!(REQUEST_TIMEOUT.scope(Duration::from_secs(3), async { timeout() }).await, Some(Duration::from_secs(3)));
+
+        let app = TestApp::new();
+        app.create_index("docs", 2, "cosine", json!({})).await;
+        app.put("docs", &[("a", vec![1.0, 0.0], json!({}))]).await;
+        let query = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "queryVector": [1.0, 0.0], "topK": 1});
+        let request = |value: &str| {
+            Request::post("/QueryVectors")
+                .header("content-type", "application/json")
+                .header(TIMEOUT_HEADER, value)
+                .body(Body::from(query.to_string()))
+                .unwrap()
+        };
+        let response = app.router.clone().oneshot(request("soon")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app.router.clone().oneshot(request("30000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["vectors"][0]["key"], "a");
+        assert!(body.get("timedOut").is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::{IntoResponse, Response}, Json};
+use anyhow::Context;
+use serde::Deserialize;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use super::request_info::RequestInfo;
+use crate::{minio::S3Client, usage::{self, IndexUsage}};
+
+/// Limits for a bucket; unset fields are unlimited.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Quotas {
+    pub max_vectors_per_index: Option<u64>,
+    pub max_indexes_per_bucket: Option<u64>,
+    pub max_storage_bytes: Option<u64>,
+    /// Requests per second against the bucket, enforced per replica.
+    pub max_qps: Option<f64>,
+}
+
+impl Quotas {
+    /// `self` with unset fields taken from `fallback`.
+    fn or(self, fallback: Quotas) -> Quotas {
+        Quotas {
+            max_vectors_per_index: self.max_vectors_per_index.or(fallback.max_vectors_per_index),
+            max_indexes_per_bucket: self.max_indexes_per_bucket.or(fallback.max_indexes_per_bucket),
+            max_storage_bytes: self.max_storage_bytes.or(fallback.max_storage_bytes),
+            max_qps: self.max_qps.or(fallback.max_qps),
+        }
+    }
+}
+
+/// `VEC_QUOTA_FILE` contents: defaults for every bucket, per-bucket overrides,
+/// and per-index vector limits keyed `<bucket>/<index>`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub default: Quotas,
+    #[serde(default)]
+    pub buckets: HashMap<String, Quotas>,
+    #[serde(default)]
+    pub indexes: HashMap<String, u64>,
+}
+
+impl QuotaConfig {
+    pub fn for_bucket(&self, bucket: &str) -> Quotas {
+        self.buckets.get(bucket).copied().unwrap_or_default().or(self.default)
+    }
+
+    pub fn max_vectors(&self, bucket: &str, index: &str) -> Option<u64> {
+        self.indexes.get(&format!("{}/{}", bucket, index)).copied()
+            .or(self.for_bucket(bucket).max_vectors_per_index)
+    }
+}
+
+static CONFIG: OnceLock<Option<QuotaConfig>> = OnceLock::new();
+
+/// Load quotas from `VEC_QUOTA_FILE`; without it nothing is limited.
+pub fn init() -> anyhow::Result<()> {
+    let config = match std::env::var("VEC_QUOTA_FILE") {
+        Ok(path) => {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read quota file {}", path))?;
+            let config: QuotaConfig = serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse quota file {}", path))?;
+            tracing::info!(path = %path, buckets = config.buckets.len(), "Loaded quotas");
+            Some(config)
+        }
+        Err(_) => None,
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+pub fn config() -> Option<&'static QuotaConfig> {
+    CONFIG.get().and_then(Option::as_ref)
+}
+
+/// 402 is what S3 Vectors returns for ServiceQuotaExceededException.
+pub fn quota_exceeded(message: String) -> Response {
+    let body = json!({"error": message, "code": "ServiceQuotaExceededException"});
+    (StatusCode::PAYMENT_REQUIRED, Json(body)).into_response()
+}
+
+/// Token bucket per vector bucket, refilled at `max_qps` with a burst of one second.
+struct RateLimiter {
+    buckets: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    fn try_acquire(&self, bucket: &str, max_qps: f64) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let (tokens, last) = buckets.entry(bucket.to_string()).or_insert((max_qps, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * max_qps).min(max_qps.max(1.0));
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn rate_limiter() -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| RateLimiter { buckets: Mutex::new(HashMap::new()) })
+}
+
+/// Throttle calls over their bucket's `maxQps` with 429 ThrottlingException.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let limit = req.extensions().get::<RequestInfo>()
+        .and_then(|info| info.bucket.as_deref())
+        .and_then(|bucket| Some((bucket, config()?.for_bucket(bucket).max_qps?)));
+    if let Some((bucket, max_qps)) = limit {
+        if !rate_limiter().try_acquire(bucket, max_qps) {
+            let body = json!({"error": format!("Request rate for bucket {} exceeds {} per second", bucket, max_qps), "code": "ThrottlingException"});
+            return (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+/// What a PutVectors adds to the usage ledger once its records are written.
+pub struct PutUsage {
+    base: IndexUsage,
+    vectors: u64,
+    bytes: u64,
+}
+
+impl PutUsage {
+    pub async fn record(self, s3: &S3Client, bucket: &str, index: &str) -> anyhow::Result<()> {
+        usage::add_vectors(s3, bucket, index, self.base, self.vectors, self.bytes).await
+    }
+}
+
+/// Keys among `records` without a vector-of-record object yet; overwrites do
+/// not count against the vector limit.
+async fn new_keys(s3: &S3Client, bucket: &str, index: &str, records: &[Value]) -> u64 {
+    let objects: Vec<String> = records.iter()
+        .filter_map(|r| r.get("key").and_then(|k| k.as_str()))
+        .map(|key| crate::ingest::record_object_key(index, key))
+        .collect();
+    futures::stream::iter(objects)
+        .map(|object| {
+            let (s3, bucket) = (s3.clone(), bucket.to_string());
+            async move { s3.get_bucket_object(&bucket, &object).await.is_err() }
+        })
+        .buffer_unordered(16)
+        .filter(|is_new| futures::future::ready(*is_new))
+        .count()
+        .await as u64
+}
+
+/// Reject a PutVectors that would take `index` past its vector limit or the
+/// bucket past its storage limit. Returns the usage to record once the write
+/// succeeds, or `None` when no limit applies and usage is not tracked.
+pub async fn check_put(s3: &S3Client, bucket: &str, index: &str, records: &[Value]) -> Result<Option<PutUsage>, Response> {
+    let Some(config) = config() else { return Ok(None) };
+    let max_vectors = config.max_vectors(bucket, index);
+    let max_bytes = config.for_bucket(bucket).max_storage_bytes;
+    if max_vectors.is_none() && max_bytes.is_none() {
+        return Ok(None);
+    }
+    let internal = |e: anyhow::Error| {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read usage: {}", e)).into_response()
+    };
+    let base = usage::index_usage(s3, bucket, index).await.map_err(internal)?;
+    let vectors = new_keys(s3, bucket, index, records).await;
+    let bytes = records.iter().map(|r| serde_json::to_vec(r).map_or(0, |b| b.len() as u64)).sum();
+    if let Some(max) = max_vectors.filter(|max| base.vectors + vectors > *max) {
+        return Err(quota_exceeded(format!(
+            "Index {} holds {} vectors; adding {} exceeds its limit of {}",
+            index, base.vectors, vectors, max
+        )));
+    }
+    if let Some(max) = max_bytes {
+        let stored = usage::load(s3, bucket).await.map_err(internal)?.total_bytes();
+        if stored + bytes > max {
+            return Err(quota_exceeded(format!(
+                "Bucket {} stores {} bytes; adding {} exceeds its limit of {}",
+                bucket, stored, bytes, max
+            )));
+        }
+    }
+    Ok(Some(PutUsage { base, vectors, bytes }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_overrides() {
+        let config: QuotaConfig = serde_json::from_value(json!({
+            "default": {"maxVectorsPerIndex": 1000, "maxQps": 50.0},
+            "buckets": {"big": {"maxVectorsPerIndex": 100000}},
+            "indexes": {"big/huge": 5000000}
+        }))
+        .unwrap();
+        assert_eq!(config.max_vectors("small", "a"), Some(1000));
+        assert_eq!(config.max_vectors("big", "a"), Some(100000));
+        assert_eq!(config.max_vectors("big", "huge"), Some(5000000));
+        assert_eq!(config.for_bucket("big").max_qps, Some(50.0));
+        assert_eq!(config.for_bucket("big").max_storage_bytes, None);
+
+        let limiter = RateLimiter { buckets: Mutex::new(HashMap::new()) };
+        assert!(limiter.try_acquire("b", 2.0));
+        assert!(limiter.try_acquire("b", 2.0));
+        assert!(!limiter.try_acquire("b", 2.0));
+    }
+}
@@ -0,0 +1,135 @@
+use axum::{body::Body, extract::Request, http::{Method, StatusCode}, middleware::Next, response::{IntoResponse, Response}};
+use serde_json::Value;
+
+/// The API operation a request performs, with the bucket and index it targets.
+/// Resolved once per request and stored in its extensions for the audit and
+/// access-control layers; `None` for unauthenticated endpoints such as `/health`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestInfo {
+    pub operation: String,
+    pub bucket: Option<String>,
+    pub index: Option<String>,
+    /// Every index of a multi-index QueryVectors (`indexNames`).
+    pub indexes: Vec<String>,
+    /// Vectors written or keys deleted, for PutVectors/DeleteVectors.
+    pub item_count: Option<usize>,
+}
+
+/// What method and path alone say about the operation.
+enum PathOperation {
+    Public,
+    Named(String),
+    /// S3-style `/:bucket` calls, where the segment is the bucket name.
+    Bucket(&'static str, String),
+    /// The `/` RPC endpoint names the operation in the body.
+    FromBody,
+}
+
+fn path_operation(method: &Method, path: &str) -> PathOperation {
+    match path {
+        "/health" | "/metrics" => PathOperation::Public,
+        "/" if method == Method::GET => PathOperation::Named("ListVectorBuckets".to_string()),
+        "/" => PathOperation::FromBody,
+        "/indexes" => PathOperation::Named("CreateIndex".to_string()),
+        "/vectors" => PathOperation::Named("PutVectors".to_string()),
+        "/query" => PathOperation::Named("QueryVectors".to_string()),
+        "/internal/shards/search" => PathOperation::Named("InternalShardSearch".to_string()),
+        "/admin/quarantine/retry" => PathOperation::Named("RetryQuarantinedSlices".to_string()),
+        "/events" => PathOperation::Named("SubscribeIndexEvents".to_string()),
+        // The event stream of a job is read like GetJob.
+        p if p.starts_with("/jobs/") && p.ends_with("/events") => PathOperation::Named("GetJob".to_string()),
+        p if p.starts_with("/admin/") || p == "/ui" || p.starts_with("/ui/") => {
+            PathOperation::Named("AdminStats".to_string())
+        }
+        p => {
+            let segment = p.trim_start_matches('/').to_string();
+            match *method {
+                Method::PUT => PathOperation::Bucket("CreateVectorBucket", segment),
+                Method::DELETE => PathOperation::Bucket("DeleteVectorBucket", segment),
+                Method::GET => PathOperation::Bucket("GetVectorBucket", segment),
+                _ => PathOperation::Named(segment),
+            }
+        }
+    }
+}
+
+pub(super) fn describe(operation: String, body: &Value) -> RequestInfo {
+    let str_field = |body: &Value, names: &[&str]| {
+        names.iter().find_map(|n| body.get(*n).and_then(|v| v.as_str())).map(str::to_string)
+    };
+    // Forwarded shard searches carry the query one level down.
+    let target = body.get("query").filter(|_| operation == "InternalShardSearch").unwrap_or(body);
+    let item_count = match operation.as_str() {
+        "PutVectors" => body.get("vectors").and_then(|v| v.as_array()).map(Vec::len),
+        "DeleteVectors" => body.get("keys").and_then(|v| v.as_array()).map(Vec::len),
+        _ => None,
+    };
+    RequestInfo {
+        bucket: str_field(target, &["vectorBucketName", "Bucket", "bucket", "bucketName"]),
+        index: str_field(target, &["indexName", "index", "sourceIndexName", "aliasName", "name"]),
+        indexes: target.get("indexNames")
+            .and_then(|v| v.as_array())
+            .map(|names| names.iter().filter_map(|n| n.as_str()).map(str::to_string).collect())
+            .unwrap_or_default(),
+        item_count,
+        operation,
+    }
+}
+
+/// Resolve [`RequestInfo`] for every request. POST bodies are buffered to read
+/// it, then passed on unchanged.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let op = path_operation(req.method(), req.uri().path());
+    let (mut parts, body) = req.into_parts();
+    let (info, body) = match op {
+        PathOperation::Public => (None, body),
+        PathOperation::Bucket(operation, bucket) => {
+            let info = RequestInfo { operation: operation.to_string(), bucket: Some(bucket), ..Default::default() };
+            (Some(info), body)
+        }
+        op if parts.method == Method::POST => {
+            let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+                Ok(bytes) => bytes,
+                Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e)).into_response(),
+            };
+            let json: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            let operation = match op {
+                PathOperation::Named(name) => name,
+                _ => json.get("operation").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string(),
+            };
+            (Some(describe(operation, &json)), Body::from(bytes))
+        }
+        PathOperation::Named(operation) => (Some(RequestInfo { operation, ..Default::default() }), body),
+        PathOperation::FromBody => (Some(RequestInfo { operation: "Unknown".to_string(), ..Default::default() }), body),
+    };
+    if let Some(info) = info {
+        parts.extensions.insert(info);
+    }
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_describe_request() {
+        assert!(matches!(path_operation(&Method::GET, "/health"), PathOperation::Public));
+        assert!(matches!(path_operation(&Method::POST, "/DeleteVectors"), PathOperation::Named(op) if op == "DeleteVectors"));
+        assert!(matches!(path_operation(&Method::DELETE, "/docs"), PathOperation::Bucket("DeleteVectorBucket", b) if b == "docs"));
+        assert!(matches!(path_operation(&Method::GET, "/admin/quarantine"), PathOperation::Named(op) if op == "AdminStats"));
+        assert!(matches!(path_operation(&Method::POST, "/admin/quarantine/retry"), PathOperation::Named(op) if op == "RetryQuarantinedSlices"));
+        assert!(matches!(path_operation(&Method::GET, "/jobs/42/events"), PathOperation::Named(op) if op == "GetJob"));
+        assert!(matches!(path_operation(&Method::GET, "/events"), PathOperation::Named(op) if op == "SubscribeIndexEvents"));
+
+        let info = describe("DeleteVectors".to_string(), &json!({"vectorBucketName": "b", "indexName": "i", "keys": ["a", "b", "c"]}));
+        assert_eq!((info.bucket.as_deref(), info.index.as_deref(), info.item_count), (Some("b"), Some("i"), Some(3)));
+
+        let info = describe("InternalShardSearch".to_string(), &json!({"shard_id": "s", "query": {"index": "docs"}}));
+        assert_eq!(info.index.as_deref(), Some("docs"));
+
+        let info = describe("QueryVectors".to_string(), &json!({"vectorBucketName": "b", "indexNames": ["logs-01", "logs-02"]}));
+        assert_eq!((info.index, info.indexes), (None, vec!["logs-01".to_string(), "logs-02".to_string()]));
+    }
+}
@@ -0,0 +1,368 @@
+//! Request schemas: every S3 Vectors request body is read as its operation's
+//! request type, which is what the operation's handler takes. The types
+//! refuse fields they don't know and require the ones the operation can't do
+//! without, so a misspelt `vectorbucketname` is a ValidationException rather
+//! than a write to some default bucket.
+
+use axum::{body::Body, extract::{FromRequest, Request}, http::{Method, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use super::request_info::RequestInfo;
+use super::{
+    S3CreateIndexRequest, S3DeleteVectorsRequest, S3GetVectorsRequest, S3ListIndexesRequest, S3ListVectorsRequest,
+    S3PutVectorsRequest, S3QueryVectorsRequest,
+};
+
+/// Checks a request makes beyond its fields' presence and types.
+pub trait Checked {
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Check `body` (without the RPC `operation` field) against the request type
+/// of `operation`; the error is the message of the 400 response. Operations
+/// without a schema pass.
+pub fn check(operation: &str, body: Value) -> Result<(), String> {
+    let checked = match operation {
+        "CreateVectorBucket" | "GetVectorBucket" | "DeleteVectorBucket" => strict::<BucketRequest>(body),
+        "ListVectorBuckets" => strict::<ListVectorBucketsRequest>(body),
+        "CreateIndex" => strict::<S3CreateIndexRequest>(body),
+        "ListIndexes" => strict::<S3ListIndexesRequest>(body),
+        "GetIndex" | "GetIndexStats" | "ExportIndex" => strict::<IndexRequest>(body),
+        "DeleteIndex" => strict::<DeleteIndexRequest>(body),
+        "RestoreIndex" => strict::<RestoreIndexRequest>(body),
+        "CopyIndex" => strict::<CopyIndexRequest>(body),
+        "ShadowBuild" => strict::<ShadowBuildRequest>(body),
+        "PutVectors" => strict::<S3PutVectorsRequest>(body),
+        "GetVectors" => strict::<S3GetVectorsRequest>(body),
+        "DeleteVectors" => strict::<S3DeleteVectorsRequest>(body),
+        "ListVectors" => strict::<S3ListVectorsRequest>(body),
+        "ScrollVectors" => strict::<ScrollVectorsRequest>(body),
+        "FilterVectors" => strict::<FilterVectorsRequest>(body),
+        "CountVectors" => strict::<CountVectorsRequest>(body),
+        "QueryVectors" => strict::<S3QueryVectorsRequest>(body),
+        "UpdateAlias" => strict::<UpdateAliasRequest>(body),
+        "GetAlias" | "DeleteAlias" => strict::<AliasRequest>(body),
+        "ListAliases" => strict::<ListAliasesRequest>(body),
+        "GetJob" | "CancelJob" => strict::<JobRequest>(body),
+        "ListJobs" => strict::<ListJobsRequest>(body),
+        _ => return Ok(()),
+    };
+    checked.map_err(|e| format!("Invalid {} request: {}", operation, e))
+}
+
+fn strict<T: DeserializeOwned + Checked>(body: Value) -> Result<(), String> {
+    parse::<T>(body).map(drop)
+}
+
+/// `body` as the request type `T`, once it passes the type's checks.
+pub fn parse<T: DeserializeOwned + Checked>(body: Value) -> Result<T, String> {
+    let request: T = serde_json::from_value(body).map_err(|e| e.to_string())?;
+    request.check()?;
+    Ok(request)
+}
+
+/// The 400 response refusing a request.
+pub fn invalid(message: String) -> Response {
+    let body = json!({"error": message, "code": "ValidationException"});
+    (StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
+/// Extractor of a JSON body as the request type `T`; bodies that don't fit
+/// are refused with [`invalid`].
+pub struct Typed<T>(pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for Typed<T>
+where
+    T: DeserializeOwned + Checked,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+        let Json(body) = Json::<Value>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        parse(body).map(Typed).map_err(|e| invalid(format!("Invalid request: {}", e)))
+    }
+}
+
+/// Refuse S3 Vectors requests, on `/` or their own path, whose body doesn't
+/// fit the operation's schema. Bodies that aren't JSON are left to the
+/// handlers, as are the legacy `/indexes`, `/vectors` and `/query` routes.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let rpc = path == "/";
+    let Some(operation) = req
+        .extensions()
+        .get::<RequestInfo>()
+        .filter(|info| req.method() == Method::POST && (rpc || path.strip_prefix('/') == Some(info.operation.as_str())))
+        .map(|info| info.operation.clone())
+    else {
+        return next.run(req).await;
+    };
+    let (parts, body) = req.into_parts();
+    let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e)).into_response(),
+    };
+    if let Ok(mut body) = serde_json::from_slice::<Value>(&bytes) {
+        if rpc {
+            if let Some(fields) = body.as_object_mut() {
+                fields.remove("operation");
+            }
+        }
+        if let Err(message) = check(&operation, body) {
+            return invalid(message);
+        }
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// Bucket and index a put, get or delete names, by bucket and name or by ARN.
+pub fn bucket_and_index_or_arn(
+    bucket: &Option<String>,
+    index: &Option<String>,
+    arn: &Option<String>,
+) -> Result<(String, String), String> {
+    match (bucket, index, arn) {
+        (_, _, Some(arn)) => {
+            // arn:aws:s3vectors:<region>:<account>:vector-bucket/<bucket>/index/<index>
+            let parts: Vec<&str> = arn.split('/').collect();
+            match parts[..] {
+                [_, .., bucket, _, index] => Ok((bucket.to_string(), index.to_string())),
+                _ => Err(format!("indexArn must end in vector-bucket/<bucket>/index/<index>, got {}", arn)),
+            }
+        }
+        (Some(bucket), Some(index), None) => Ok((bucket.clone(), index.clone())),
+        _ => Err("vectorBucketName and indexName, or indexArn, are required".to_string()),
+    }
+}
+
+impl Checked for S3CreateIndexRequest {}
+impl Checked for S3ListIndexesRequest {}
+impl Checked for S3ListVectorsRequest {}
+
+impl Checked for S3PutVectorsRequest {
+    fn check(&self) -> Result<(), String> {
+        bucket_and_index_or_arn(&self.vector_bucket_name, &self.index_name, &self.index_arn).map(drop)
+    }
+}
+
+impl Checked for S3GetVectorsRequest {
+    fn check(&self) -> Result<(), String> {
+        bucket_and_index_or_arn(&self.vector_bucket_name, &self.index_name, &self.index_arn).map(drop)
+    }
+}
+
+impl Checked for S3DeleteVectorsRequest {
+    fn check(&self) -> Result<(), String> {
+        bucket_and_index_or_arn(&self.vector_bucket_name, &self.index_name, &self.index_arn).map(drop)
+    }
+}
+
+impl Checked for S3QueryVectorsRequest {
+    fn check(&self) -> Result<(), String> {
+        if self.index_name.is_none() && self.index_names.is_none() {
+            return Err("indexName or indexNames is required".to_string());
+        }
+        if self.query_vector.is_none() && self.vector.is_none() && self.next_token.is_none() {
+            return Err("queryVector is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BucketRequest {
+    #[serde(alias = "Bucket", alias = "bucket", alias = "BucketName", alias = "bucketName")]
+    pub vector_bucket_name: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListVectorBucketsRequest {
+    pub prefix: Option<String>,
+    pub max_results: Option<u32>,
+    pub next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IndexRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub index_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DeleteIndexRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub index_name: String,
+    pub force: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RestoreIndexRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub index_name: String,
+    pub deleted_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CopyIndexRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub source_index_name: String,
+    pub target_index_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ShadowBuildRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub index_name: String,
+    pub parameters: Option<crate::shadow::ShadowParameters>,
+    pub queries: Option<usize>,
+    pub top_k: Option<usize>,
+    pub max_vectors: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScrollVectorsRequest {
+    #[serde(alias = "Bucket", alias = "bucket")]
+    pub vector_bucket_name: String,
+    pub index_name: String,
+    pub max_results: Option<u64>,
+    pub next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FilterVectorsRequest {
+    /// Required, though shards and their metadata aren't kept per bucket.
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: String,
+    pub index_name: String,
+    pub filter: Option<Value>,
+    pub metadata_filter: Option<Value>,
+    #[serde(default)]
+    pub return_metadata: bool,
+    pub max_results: Option<u64>,
+    pub next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CountVectorsRequest {
+    /// Required, though shards and their metadata aren't kept per bucket.
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: String,
+    pub index_name: String,
+    pub filter: Option<Value>,
+    pub metadata_filter: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UpdateAliasRequest {
+    /// Required, though aliases aren't kept per bucket.
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: String,
+    pub alias_name: String,
+    pub index_name: String,
+    /// When set, the update only happens if the alias currently points here.
+    pub expected_index_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AliasRequest {
+    /// Required, though aliases aren't kept per bucket.
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: String,
+    pub alias_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListAliasesRequest {
+    /// Required, though aliases aren't kept per bucket.
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: String,
+}
+
+/// Jobs aren't kept per bucket; naming one is allowed, not required.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct JobRequest {
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: Option<String>,
+    pub job_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListJobsRequest {
+    #[serde(rename = "vectorBucketName", alias = "Bucket", alias = "bucket")]
+    pub _vector_bucket_name: Option<String>,
+    pub status: Option<String>,
+}
+
+impl Checked for BucketRequest {}
+impl Checked for ListVectorBucketsRequest {}
+impl Checked for IndexRequest {}
+impl Checked for DeleteIndexRequest {}
+impl Checked for RestoreIndexRequest {}
+impl Checked for CopyIndexRequest {}
+impl Checked for ShadowBuildRequest {}
+impl Checked for ScrollVectorsRequest {}
+impl Checked for FilterVectorsRequest {}
+impl Checked for CountVectorsRequest {}
+impl Checked for UpdateAliasRequest {}
+impl Checked for AliasRequest {}
+impl Checked for ListAliasesRequest {}
+impl Checked for JobRequest {}
+impl Checked for ListJobsRequest {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_and_missing_fields_are_refused() {
+        let typo = check("PutVectors", json!({"vectorbucketname": "b", "indexName": "docs", "vectors": []}));
+        assert!(typo.unwrap_err().contains("unknown field `vectorbucketname`"));
+        let missing = check("GetIndex", json!({"indexName": "docs"}));
+        assert!(missing.unwrap_err().contains("missing field `vectorBucketName`"));
+        let no_bucket = check("DeleteVectors", json!({"indexName": "docs", "keys": ["a"]}));
+        assert!(no_bucket.unwrap_err().contains("indexArn"));
+        assert!(check("QueryVectors", json!({"vectorBucketName": "b", "vector": [1.0]})).is_err());
+        assert!(check("CreateIndex", json!({
+            "vectorBucketName": "b", "indexName": "docs", "dataType": "float32", "dimension": 4,
+            "distanceMetric": "cosine", "storeRawVector": false,
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_well_formed_requests_pass() {
+        let arn = "arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/b/index/docs";
+        assert!(check("PutVectors", json!({"indexArn": arn, "vectors": [], "idempotencyKey": "k"})).is_ok());
+        assert!(check("GetVectors", json!({"Bucket": "b", "indexName": "docs", "keys": ["a"]})).is_ok());
+        assert!(check("QueryVectors", json!({"vectorBucketName": "b", "indexNames": ["a", "b"], "queryVector": {"float32": [1.0]}, "filter": {"genre": "news"}})).is_ok());
+        assert!(check("QueryVectors", json!({"vectorBucketName": "b", "indexName": "a", "vector": [1.0], "allowPartialResults": true})).is_ok());
+        assert!(check("CreateVectorBucket", json!({"bucketName": "b"})).is_ok());
+        assert!(check("ListJobs", json!({})).is_ok());
+        assert!(check("Reindex", json!({"anything": true})).is_ok());
+    }
+}
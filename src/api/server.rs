@@ -0,0 +1,151 @@
+//! The HTTP listener behind `genai-vectors api`: where it binds, how many
+//! connections it holds open and how it keeps them alive. Serves the router
+//! with hyper directly, since axum's `serve` exposes none of these.
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    /// `SERVER_HOST`, default `0.0.0.0`.
+    pub host: String,
+    /// `SERVER_PORT`, default 8080.
+    pub port: u16,
+    /// `VEC_MAX_CONNECTIONS`: connections served at once; further clients
+    /// wait in the listen backlog. `None` (the default, or `0`) is unlimited.
+    pub max_connections: Option<usize>,
+    /// `VEC_KEEP_ALIVE`: reuse HTTP/1 connections across requests (default
+    /// true); `false` closes each after its response.
+    pub keep_alive: bool,
+    /// `VEC_KEEP_ALIVE_INTERVAL_SECS`: how often HTTP/2 connections are
+    /// pinged, closing those whose ping goes unanswered for 20 seconds.
+    /// `None` (the default, or `0`) never pings.
+    pub keep_alive_interval: Option<Duration>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            max_connections: None,
+            keep_alive: true,
+            keep_alive_interval: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Settings from `var`; values that don't parse keep their default.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let default = ServerConfig::default();
+        let number = |name: &str| var(name).and_then(|v| v.trim().parse::<u64>().ok()).filter(|&n| n > 0);
+        ServerConfig {
+            host: var("SERVER_HOST").filter(|host| !host.is_empty()).unwrap_or(default.host),
+            port: var("SERVER_PORT").and_then(|v| v.trim().parse().ok()).unwrap_or(default.port),
+            max_connections: number("VEC_MAX_CONNECTIONS").map(|n| n as usize),
+            keep_alive: var("VEC_KEEP_ALIVE").and_then(|v| v.trim().parse().ok()).unwrap_or(default.keep_alive),
+            keep_alive_interval: number("VEC_KEEP_ALIVE_INTERVAL_SECS").map(Duration::from_secs),
+        }
+    }
+
+    pub async fn bind(&self) -> anyhow::Result<TcpListener> {
+        TcpListener::bind((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to bind {}:{}: {}", self.host, self.port, e))
+    }
+}
+
+/// Serve `router` on `listener` until accepting fails for good, each
+/// connection on its own task.
+pub async fn serve(listener: TcpListener, router: Router, config: &ServerConfig) -> anyhow::Result<()> {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(config.keep_alive);
+    builder.http2().timer(TokioTimer::new()).keep_alive_interval(config.keep_alive_interval);
+    let slots = config.max_connections.map(|n| Arc::new(Semaphore::new(n)));
+
+    loop {
+        // Wait for a free slot before accepting, so clients over the limit
+        // queue in the kernel instead of holding a connection we won't read.
+        let permit = match &slots {
+            Some(slots) => Some(slots.clone().acquire_owned().await?),
+            None => None,
+        };
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                // Usually out of file descriptors; back off instead of spinning.
+                tracing::warn!(error = %e, "Failed to accept connection");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let builder = builder.clone();
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            if let Err(e) = builder.serve_connection_with_upgrades(TokioIo::new(stream), service).await {
+                tracing::debug!(error = %e, "Connection closed with error");
+            }
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_settings_come_from_env_with_defaults() {
+        let vars = |pairs: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            ServerConfig::from_vars(move |name| vars.get(name).cloned())
+        };
+        assert_eq!(vars(&[]), ServerConfig::default());
+        assert_eq!(vars(&[("SERVER_PORT", "http"), ("VEC_MAX_CONNECTIONS", "0")]), ServerConfig::default());
+
+        let config = vars(&[
+            ("SERVER_HOST", "127.0.0.1"),
+            ("SERVER_PORT", "9090"),
+            ("VEC_MAX_CONNECTIONS", "256"),
+            ("VEC_KEEP_ALIVE", "false"),
+            ("VEC_KEEP_ALIVE_INTERVAL_SECS", "15"),
+        ]);
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.max_connections, Some(256));
+        assert!(!config.keep_alive);
+        assert_eq!(config.keep_alive_interval, Some(Duration::from_secs(15)));
+    }
+
+    #[tokio::test]
+    async fn test_connections_over_the_limit_wait_for_a_slot() {
+        let config = ServerConfig { host: "127.0.0.1".to_string(), port: 0, max_connections: Some(1), ..Default::default() };
+        let listener = config.bind().await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        let app = super::super::testing::TestApp::new();
+        tokio::spawn(async move { serve(listener, app.router, &config).await });
+
+        // Each client keeps its connection open, so the second is only
+        // served once the first is dropped.
+        let first = reqwest::Client::new();
+        assert!(first.get(&url).send().await.unwrap().status().is_success());
+        let second = reqwest::Client::new();
+        let waiting = tokio::time::timeout(Duration::from_millis(300), second.get(&url).send()).await;
+        assert!(waiting.is_err(), "second connection was served while the first was open");
+        drop(first);
+        let served = tokio::time::timeout(Duration::from_secs(5), second.get(&url).send()).await;
+        assert!(served.unwrap().unwrap().status().is_success());
+    }
+}
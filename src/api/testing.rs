@@ -0,0 +1,207 @@
+//! In-process API for tests: the full router and middleware over an
+//! in-memory store, called without a listener. PutVectors stages a slice per
+//! call and indexes it before returning, so tests can query what they put.
+
+use super::{build_router, AppState};
+use crate::ingest::Ingestor;
+use crate::minio::S3Client;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+pub(crate) struct TestApp {
+    pub router: Router,
+    /// The store behind the app, for asserting on what was written.
+    pub s3: S3Client,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let s3 = S3Client::in_memory("vectors");
+        let ingest = Arc::new(Ingestor::new(s3.clone(), "vectors".to_string()).with_slice_row_limit(1));
+        TestApp { router: build_router(AppState { s3: s3.clone(), ingest }), s3 }
+    }
+
+    /// POST `body` to `path`; returns the status and the JSON response body
+    /// (`Value::Null` when it is empty or not JSON).
+    pub async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::post(path)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// CreateIndex `index` in the default bucket with `options` merged into
+    /// the request; panics unless it succeeds.
+    pub async fn create_index(&self, index: &str, dimension: u32, metric: &str, options: Value) -> Value {
+        let mut body = json!({
+            "vectorBucketName": "default-bucket",
+            "indexName": index,
+            "dataType": "float32",
+            "dimension": dimension,
+            "distanceMetric": metric,
+        });
+        for (key, value) in options.as_object().into_iter().flatten() {
+            body[key] = value.clone();
+        }
+        let (status, response) = self.post("/CreateIndex", body).await;
+        assert_eq!(status, StatusCode::OK, "CreateIndex {}: {}", index, response);
+        response
+    }
+
+    /// PutVectors `(key, embedding, metadata)` into `index`; panics unless it
+    /// succeeds.
+    pub async fn put(&self, index: &str, vectors: &[(&str, Vec<f32>, Value)]) -> Value {
+        let vectors: Vec<Value> = vectors
+            .iter()
+            .map(|(key, embedding, metadata)| json!({"key": key, "data": {"float32": embedding}, "metadata": metadata}))
+            .collect();
+        let body = json!({"vectorBucketName": "default-bucket", "indexName": index, "vectors": vectors});
+        let (status, response) = self.post("/PutVectors", body).await;
+        assert_eq!(status, StatusCode::OK, "PutVectors {}: {}", index, response);
+        response
+    }
+
+    /// QueryVectors `index` with metadata returned; `request` adds to or
+    /// overrides the body. Returns the keys of the hits, best first.
+    pub async fn query(&self, index: &str, vector: &[f32], top_k: u32, request: Value) -> Vec<String> {
+        let mut body = json!({
+            "vectorBucketName": "default-bucket",
+            "indexName": index,
+            "vector": vector,
+            "topK": top_k,
+            "returnMetadata": true,
+        });
+        for (key, value) in request.as_object().into_iter().flatten() {
+            body[key] = value.clone();
+        }
+        let (status, response) = self.post("/QueryVectors", body).await;
+        assert_eq!(status, StatusCode::OK, "QueryVectors {}: {}", index, response);
+        response["vectors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|hit| hit["key"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+/// Variable lookup over `pairs` in place of the environment, for the
+/// `from_vars` constructors of config types.
+pub(crate) fn env<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    move |name| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_put_index_and_query_without_minio() {
+        let app = TestApp::new();
+        app.create_index("docs", 4, "cosine", json!({})).await;
+        app.put(
+            "docs",
+            &[
+                ("north", vec![0.0, 1.0, 0.0, 0.0], json!({"genre": "news"})),
+                ("east", vec![1.0, 0.0, 0.0, 0.0], json!({"genre": "sport"})),
+                ("up", vec![0.0, 0.0, 1.0, 0.0], json!({"genre": "news"})),
+            ],
+        )
+        .await;
+        assert!(app.s3.get_object("indexes/docs/manifest.json").await.is_ok());
+
+        let hits = app.query("docs", &[0.9, 0.1, 0.0, 0.0], 2, json!({})).await;
+        assert_eq!(hits, vec!["east", "north"]);
+        let filtered = app
+            .query("docs", &[0.9, 0.1, 0.0, 0.0], 2, json!({"metadataFilter": {"genre": "news"}}))
+            .await;
+        assert_eq!(filtered, vec!["north", "up"]);
+
+        let (status, _) = app
+            .post("/DeleteVectors", json!({"vectorBucketName": "default-bucket", "indexName": "docs", "keys": ["east"]}))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(app.query("docs", &[0.9, 0.1, 0.0, 0.0], 1, json!({})).await, vec!["north"]);
+    }
+
+    #[tokio::test]
+    async fn test_put_vectors_reports_rejected_vectors() {
+        let app = TestApp::new();
+        app.create_index("docs", 4, "cosine", json!({})).await;
+        let vectors = json!([
+            {"key": "good", "data": {"float32": [1.0, 0.0, 0.0, 0.0]}},
+            {"key": "short", "data": {"float32": [1.0, 0.0]}},
+            {"data": {"float32": [0.0, 1.0, 0.0, 0.0]}},
+            {"key": "heavy", "data": {"float32": [0.0, 0.0, 1.0, 0.0]}, "metadata": {"blob": "x".repeat(4096)}},
+        ]);
+        let body = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "vectors": vectors});
+
+        let (status, response) = app.post("/PutVectors", body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let rejected = response["rejected"].as_array().unwrap();
+        assert_eq!(rejected.iter().map(|r| r["position"].as_u64().unwrap()).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(rejected[0]["reason"].as_str().unwrap().contains("2 dimensions, expected 4"));
+        assert_eq!(rejected[1]["reason"], "Missing key");
+        assert!(app.s3.get_object("indexes/docs/manifest.json").await.is_err());
+
+        let mut partial = body;
+        partial["allowPartial"] = json!(true);
+        let (status, response) = app.post("/PutVectors", partial).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        assert_eq!(response["rejected"].as_array().unwrap().len(), 3);
+        assert_eq!(app.query("docs", &[1.0, 0.0, 0.0, 0.0], 5, json!({})).await, vec!["good"]);
+    }
+
+    #[tokio::test]
+    async fn test_job_events_stream_until_the_job_finishes() {
+        let app = TestApp::new();
+        app.create_index("docs", 2, "cosine", json!({})).await;
+        app.put("docs", &[("a", vec![1.0, 0.0], json!({}))]).await;
+        let (status, response) = app
+            .post("/CopyIndex", json!({"vectorBucketName": "default-bucket", "sourceIndexName": "docs", "targetIndexName": "copy"}))
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", response);
+
+        let request = Request::get(format!("/jobs/{}/events", response["jobId"].as_str().unwrap())).body(Body::empty()).unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let body = tokio::time::timeout(std::time::Duration::from_secs(30), axum::body::to_bytes(response.into_body(), usize::MAX))
+            .await
+            .expect("event stream did not end")
+            .unwrap();
+        let events = String::from_utf8(body.to_vec()).unwrap();
+        assert!(events.contains("event: progress\n") && events.contains("\"objectsCopied\""), "{}", events);
+        assert!(events.trim_end().rsplit("\n\n").next().unwrap().starts_with("event: finished"), "{}", events);
+        assert!(events.contains("\"status\":\"Succeeded\""), "{}", events);
+
+        let request = Request::get("/jobs/missing/events").body(Body::empty()).unwrap();
+        assert_eq!(app.router.clone().oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_rpc_bodies_are_rejected() {
+        let app = TestApp::new();
+        let request = |body: &'static str| Request::post("/").body(Body::from(body)).unwrap();
+        for body in ["{\"operation\": ", "[]", "{\"operation\": 7}", "{}"] {
+            let response = app.router.clone().oneshot(request(body)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", body);
+        }
+        let (status, _) = app.post("/", json!({"operation": "Reindex"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let typo = json!({"operation": "CreateIndex", "vectorbucketname": "other", "indexName": "docs", "dataType": "float32", "dimension": 4, "distanceMetric": "cosine"});
+        let (status, response) = app.post("/", typo).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response["error"].as_str().unwrap().contains("vectorbucketname"), "{}", response);
+        assert!(app.s3.get_object("indexes/docs/config.json").await.is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! MessagePack and CBOR bodies for callers that find JSON too slow to
+//! produce or parse. Requests are transcoded to JSON on the way in, so every
+//! layer and handler behind this one only ever sees JSON; JSON responses are
+//! transcoded back to what `Accept` asks for, or else to the request's own
+//! format. Responses larger than `VEC_MAX_REQUEST_MB` stay JSON.
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    /// Format of a media type, ignoring its parameters.
+    fn of_media_type(media_type: &str) -> Option<Format> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            "application/cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::MessagePack => "MessagePack",
+            Format::Cbor => "CBOR",
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> Result<Value, String> {
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::Cbor => ciborium::from_reader(bytes).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn encode(self, value: &Value) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Format of the request body; anything but MessagePack and CBOR is left
+/// as it is.
+fn request_format(headers: &HeaderMap) -> Format {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Format::of_media_type)
+        .unwrap_or(Format::Json)
+}
+
+/// First format `Accept` lists that we can write, if any.
+fn accepted_format(headers: &HeaderMap) -> Option<Format> {
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok())?;
+    accept.split(',').find_map(Format::of_media_type)
+}
+
+/// `format` as the response body, with a ValidationException in it.
+fn error(format: Format, status: StatusCode, message: String) -> Response {
+    let body = json!({"error": message, "code": "ValidationException"});
+    let bytes = format.encode(&body).unwrap_or_default();
+    (status, [(header::CONTENT_TYPE, format.content_type())], bytes).into_response()
+}
+
+/// Transcode MessagePack and CBOR requests to JSON and JSON responses to the
+/// format the caller accepts.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let request_format = request_format(req.headers());
+    let response_format = accepted_format(req.headers()).unwrap_or(request_format);
+    if request_format == Format::Json && response_format == Format::Json {
+        return next.run(req).await;
+    }
+
+    let req = if request_format == Format::Json {
+        req
+    } else {
+        let (mut parts, body) = req.into_parts();
+        let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+            Ok(bytes) => bytes,
+            Err(e) => return error(response_format, StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e)),
+        };
+        let json = match request_format.decode(&bytes).and_then(|value| Format::Json.encode(&value)) {
+            Ok(json) => json,
+            Err(e) => {
+                let message = format!("Request body is not valid {}: {}", request_format.name(), e);
+                return error(response_format, StatusCode::BAD_REQUEST, message);
+            }
+        };
+        // Keep the media type's parameters, such as embedding-encoding.
+        let content_type = parts.headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        let parameters = content_type.split_once(';').map(|(_, parameters)| format!(";{}", parameters)).unwrap_or_default();
+        if let Ok(content_type) = HeaderValue::from_str(&format!("application/json{}", parameters)) {
+            parts.headers.insert(header::CONTENT_TYPE, content_type);
+        }
+        parts.headers.remove(header::CONTENT_LENGTH);
+        Request::from_parts(parts, Body::from(json))
+    };
+
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Format::of_media_type)
+        == Some(Format::Json);
+    if response_format == Format::Json || !is_json {
+        return response;
+    }
+    // Responses too large to buffer go out as JSON, like ones that fail to transcode.
+    if response.body().size_hint().upper().is_none_or(|len| len > super::max_request_bytes() as u64) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, super::max_request_bytes()).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read response body: {}", e)).into_response(),
+    };
+    let encoded = match Format::Json.decode(&bytes).and_then(|value| response_format.encode(&value)) {
+        Ok(encoded) => encoded,
+        Err(e) => {
+            tracing::warn!(error = %e, format = response_format.name(), "Failed to transcode response, returning JSON");
+            return Response::from_parts(parts, Body::from(bytes));
+        }
+    };
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(response_format.content_type()));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::TestApp;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_formats_are_negotiated_by_media_type() {
+        let headers = |pairs: &[(header::HeaderName, &'static str)]| {
+            pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_static(value))).collect::<HeaderMap>()
+        };
+        assert_eq!(request_format(&headers(&[])), Format::Json);
+        assert_eq!(request_format(&headers(&[(header::CONTENT_TYPE, "text/plain")])), Format::Json);
+        assert_eq!(request_format(&headers(&[(header::CONTENT_TYPE, "application/x-msgpack")])), Format::MessagePack);
+        assert_eq!(request_format(&headers(&[(header::CONTENT_TYPE, "Application/CBOR; embedding-encoding=base64")])), Format::Cbor);
+        assert_eq!(accepted_format(&headers(&[(header::ACCEPT, "*/*")])), None);
+        assert_eq!(accepted_format(&headers(&[(header::ACCEPT, "text/html, application/cbor, application/json")])), Some(Format::Cbor));
+    }
+
+    #[test]
+    fn test_bodies_survive_transcoding() {
+        let value = json!({"indexName": "docs", "topK": 3, "queryVector": {"float32": [0.5, -1.25]}, "returnMetadata": true, "filter": null});
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let encoded = format.encode(&value).unwrap();
+            assert_eq!(format.decode(&encoded).unwrap(), value, "{}", format.name());
+        }
+        assert!(Format::MessagePack.decode(b"\xc1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_requests_get_msgpack_responses() {
+        let app = TestApp::new();
+        let body = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "dataType": "float32", "dimension": 2, "distanceMetric": "cosine"});
+        let request = Request::post("/CreateIndex")
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .body(Body::from(Format::MessagePack.encode(&body).unwrap()))
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/msgpack");
+
+        let request = Request::post("/GetIndex")
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .header(header::ACCEPT, "application/cbor")
+            .body(Body::from(Format::MessagePack.encode(&json!({"vectorBucketName": "default-bucket", "indexName": "docs"})).unwrap()))
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/cbor");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(Format::Cbor.decode(&bytes).unwrap()["index"]["dimension"], 2);
+
+        let request = Request::post("/CreateIndex")
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .body(Body::from(&b"\xc1"[..]))
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(Format::MessagePack.decode(&bytes).unwrap()["error"].as_str().unwrap().contains("MessagePack"));
+    }
+}
@@ -0,0 +1,92 @@
+// Polls the /admin/stats endpoints and renders them; no build step or dependencies.
+const REFRESH_MS = 10000;
+
+function cell(text, numeric) {
+  const td = document.createElement("td");
+  td.textContent = text === undefined || text === null ? "-" : String(text);
+  if (numeric) td.className = "num";
+  return td;
+}
+
+function row(cells) {
+  const tr = document.createElement("tr");
+  cells.forEach((c) => tr.appendChild(c));
+  return tr;
+}
+
+function fixed(value) {
+  return typeof value === "number" ? value.toFixed(2) : value;
+}
+
+function shardLayout(shards) {
+  const td = document.createElement("td");
+  if (!shards.length) {
+    td.textContent = "0";
+    return td;
+  }
+  const details = document.createElement("details");
+  const summary = document.createElement("summary");
+  summary.textContent = shards.length;
+  details.appendChild(summary);
+  const list = document.createElement("ul");
+  shards.forEach((s) => {
+    const li = document.createElement("li");
+    li.textContent = `${s.shardId}: ${s.vectorCount} vectors, ${s.algorithm || "ivf"}, ${s.createdAt}`;
+    list.appendChild(li);
+  });
+  details.appendChild(list);
+  td.appendChild(details);
+  return td;
+}
+
+async function getJson(path) {
+  const response = await fetch(path);
+  if (!response.ok) throw new Error(`${path}: HTTP ${response.status}`);
+  return response.json();
+}
+
+async function refresh() {
+  try {
+    const [layout, queries, metrics] = await Promise.all([
+      getJson("/admin/stats/indexes"),
+      getJson("/admin/stats/queries"),
+      getJson("/admin/stats/metrics"),
+    ]);
+
+    document.getElementById("buckets").replaceChildren(
+      ...layout.buckets.map((b) => {
+        const li = document.createElement("li");
+        li.textContent = b;
+        return li;
+      })
+    );
+    document.getElementById("indexes").replaceChildren(
+      ...layout.indexes.map((i) =>
+        row([
+          cell(i.indexName), cell(i.vectorBucketName), cell(i.dimension, true), cell(i.metric),
+          cell(i.totalVectors, true), shardLayout(i.shards), cell(i.pendingSlices, true),
+        ])
+      )
+    );
+    document.getElementById("queries").replaceChildren(
+      ...queries.queries.map((q) =>
+        row([cell(q.timestamp), cell(q.index_name), cell(q.topk, true), cell(q.results, true), cell(fixed(q.took_ms), true)])
+      )
+    );
+    document.getElementById("metrics").replaceChildren(
+      ...Object.entries(metrics.metrics).map(([name, m]) =>
+        row([
+          cell(name), cell(m.count, true), cell(fixed(m.sum / m.count), true),
+          cell(fixed(m.min), true), cell(fixed(m.max), true), cell(fixed(m.last), true),
+        ])
+      )
+    );
+    document.getElementById("error").textContent = "";
+    document.getElementById("updated").textContent = `Updated ${new Date().toLocaleTimeString()}`;
+  } catch (e) {
+    document.getElementById("error").textContent = e.message;
+  }
+}
+
+refresh();
+setInterval(refresh, REFRESH_MS);
@@ -1,144 +1,267 @@
 use axum::{response::{IntoResponse, Response}, Json, http::StatusCode};
 use serde_json::{json, Value};
 use super::{AppState, S3PutVectorsRequest, S3ListVectorsRequest, S3GetVectorsRequest, S3DeleteVectorsRequest, S3QueryVectorsRequest};
+use super::schema::{CountVectorsRequest, FilterVectorsRequest, ScrollVectorsRequest, Typed};
 use crate::model::*;
-
-// Helper function to extract bucket and index names from request
-fn extract_bucket_and_index(
-    bucket_name: Option<String>, 
-    index_name: Option<String>, 
-    index_arn: Option<String>
-) -> (String, String) {
-    if let Some(arn) = index_arn {
-        // Parse ARN: arn:aws:s3vectors:us-east-1:123456789012:vector-bucket/bucket-name/index/index-name
-        let parts: Vec<&str> = arn.split('/').collect();
-        if parts.len() >= 4 {
-            let bucket = parts[parts.len() - 3].to_string();
-            let index = parts[parts.len() - 1].to_string();
-            return (bucket, index);
-        }
-    }
-    
-    let bucket = bucket_name.unwrap_or_else(|| "default-bucket".to_string());
-    let index = index_name.unwrap_or_else(|| "default-index".to_string());
-    (bucket, index)
-}
+use futures::StreamExt;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
 
 /// PutVectors - Add vectors to an index
-pub async fn put(_bucket: String, body: Value, state: AppState) -> Response {
-    // Parse S3 put-vectors request
-    let req: S3PutVectorsRequest = match serde_json::from_value(body.clone()) {
-        Ok(req) => req,
-        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response(),
+pub async fn put(req: S3PutVectorsRequest, state: AppState) -> Response {
+    let (bucket_name, index_name) = match super::schema::bucket_and_index_or_arn(&req.vector_bucket_name, &req.index_name, &req.index_arn) {
+        Ok(names) => names,
+        Err(message) => return super::schema::invalid(message),
     };
     
-    let (_bucket_name, index_name) = extract_bucket_and_index(
-        req.vector_bucket_name.clone(),
-        req.index_name.clone(),
-        req.index_arn.clone()
-    );
-    
-    // Convert to internal format and ingest
-    let vectors: Vec<VectorRecord> = req.vectors.into_iter().filter_map(|v| {
-        let id = v.get("key").and_then(|k| k.as_str())?;
-        let data = v.get("data").and_then(|d| d.get("float32")).and_then(|f| f.as_array())?;
-        let embedding: Vec<f32> = data.iter().filter_map(|x| x.as_f64().map(|f| f as f32)).collect();
-        let metadata = v.get("metadata").cloned().unwrap_or(json!({}));
-        
-        Some(VectorRecord {
-            id: id.to_string(),
-            embedding,
-            meta: metadata,
-            created_at: chrono::Utc::now(),
+    let bucket_for_ingest = bucket_name.as_str();
+
+    // Vectors failing validation are reported with the reason; unless the
+    // request allows a partial write, any of them fails the whole batch
+    // before anything is written.
+    let index_config = super::load_index_configuration(&state.s3, &index_name).await.ok();
+    let mut rejected = Vec::new();
+    let valid: Vec<&Value> = req
+        .vectors
+        .iter()
+        .enumerate()
+        .filter_map(|(position, v)| match super::validate_put_vector(v, index_config.as_ref()) {
+            Ok(()) => Some(v),
+            Err(reason) => {
+                rejected.push(json!({"position": position, "key": v.get("key"), "reason": reason}));
+                None
+            }
         })
-    }).collect();
+        .collect();
+    if !rejected.is_empty() && !req.allow_partial {
+        let body = json!({
+            "error": format!("{} of {} vectors failed validation; nothing was written", rejected.len(), req.vectors.len()),
+            "code": "ValidationException",
+            "rejected": rejected,
+        });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
 
-    let bucket_for_ingest = req.vector_bucket_name.as_deref().unwrap_or("default-bucket");
-    if let Err(e) = state.ingest.append(vectors, &index_name).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Ingestion failed: {}", e)).into_response();
-    }
-
-    // Store individual vector JSON objects for listing/getting
-    if let Some(vectors_json) = body.get("vectors").and_then(|v| v.as_array()) {
-        for vec_json in vectors_json {
-            if let Some(key) = vec_json.get("key").and_then(|v| v.as_str()) {
-                let object_key = format!("{}/vectors/{}.json", index_name, key);
-                if let Ok(data_bytes) = serde_json::to_vec(vec_json) {
-                    let _ = state.s3.client
-                        .put_object()
-                        .bucket(bucket_for_ingest)
-                        .key(&object_key)
-                        .body(aws_sdk_s3::primitives::ByteStream::from(data_bytes))
-                        .send()
-                        .await;
+    let default_condition = req.condition.unwrap_or_default();
+    let conditional = req.condition.is_some() || req.vectors.iter().any(|v| v.get("condition").is_some());
+    let _records_guard = if conditional { Some(state.ingest.lock_records().await) } else { None };
+    
+    // Partitioned parents hold no vectors; each one goes to its time partition.
+    let targets: Vec<(String, Vec<&Value>)> = match crate::partitions::load(&state.s3, &index_name).await {
+        Some((parent, partitioning)) => {
+            let now = chrono::Utc::now();
+            let mut targets: Vec<(String, Vec<&Value>)> = Vec::new();
+            for v in valid {
+                let partition = partitioning.partition_for(&index_name, v.get("metadata").unwrap_or(&Value::Null), now);
+                match targets.iter_mut().find(|(name, _)| *name == partition) {
+                    Some((_, batch)) => batch.push(v),
+                    None => targets.push((partition, vec![v])),
+                }
+            }
+            for (partition, _) in &targets {
+                if let Err(e) = crate::partitions::ensure_partition(&state.s3, &parent, partition).await {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response();
                 }
             }
+            targets
+        }
+        None => vec![(index_name.clone(), valid)],
+    };
+    
+    // Partitions carry their parent's dedup settings.
+    let dedup = crate::dedup::load(&state.s3, &index_name).await;
+    let versioning = crate::versions::load(&state.s3, &index_name).await;
+    let truncation = crate::truncation::load(&state.s3, &index_name).await;
+    let mut duplicates = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut written = false;
+    let mut changed = Vec::new();
+    for (index_name, batch) in targets {
+        // Convert to internal format, dropping vectors whose condition fails
+        let mut vectors: Vec<VectorRecord> = Vec::new();
+        for v in batch {
+            let Some(id) = v.get("key").and_then(|k| k.as_str()) else { continue };
+            let Some(data) = v.get("data").and_then(|d| d.get("float32")).and_then(|f| f.as_array()) else { continue };
+            let condition = match v.get("condition").map(|c| serde_json::from_value(c.clone())) {
+                Some(Ok(condition)) => condition,
+                Some(Err(e)) => return (StatusCode::BAD_REQUEST, format!("Invalid condition for {}: {}", id, e)).into_response(),
+                None => default_condition,
+            };
+            if let Some(conflict) = state.ingest.check_condition(bucket_for_ingest, &index_name, id, condition).await {
+                conflicts.push(conflict);
+                continue;
+            }
+            let mut embedding: Vec<f32> = data.iter().filter_map(|x| x.as_f64().map(|f| f as f32)).collect();
+            if let Some((dim, truncation)) = truncation {
+                embedding = match truncation.apply(dim, embedding) {
+                    Ok(embedding) => embedding,
+                    Err(message) => {
+                        let body = json!({"error": format!("{}: {}", id, message), "code": "ValidationException"});
+                        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+                    }
+                };
+            }
+            if let Some(dedup) = dedup {
+                let earlier = vectors.iter().filter(|r| r.id != id).map(|r| (r.id.as_str(), r.embedding.as_slice()));
+                let found = match dedup.closest(&embedding, earlier) {
+                    Some(found) => Some(found),
+                    None => dedup.find_in_index(&state.s3, &index_name, id, &embedding).await.unwrap_or_else(|e| {
+                        tracing::warn!(index = %index_name, error = %e, "Duplicate check failed, accepting vector");
+                        None
+                    }),
+                };
+                if let Some((duplicate_of, similarity)) = found {
+                    duplicates.push(crate::dedup::Duplicate { key: id.to_string(), duplicate_of, similarity });
+                    if dedup.mode == crate::dedup::DedupMode::Reject {
+                        continue;
+                    }
+                }
+            }
+            let metadata = v.get("metadata").cloned().unwrap_or(json!({}));
+            
+            vectors.push(VectorRecord {
+                id: id.to_string(),
+                embedding,
+                meta: metadata,
+                created_at: chrono::Utc::now(),
+                text: v.get("text").and_then(|t| t.as_str()).map(str::to_string),
+            });
+        }
+        if vectors.is_empty() {
+            continue;
+        }
+
+        let records: Vec<Value> = vectors.iter().map(crate::ingest::record_document).collect();
+        let put_usage = match super::quotas::check_put(&state.s3, bucket_for_ingest, &index_name, &records).await {
+            Ok(put_usage) => put_usage,
+            Err(response) => return response,
+        };
+        let ingested = if direct_build_threshold().is_some_and(|threshold| vectors.len() >= threshold) {
+            crate::metrics::get_metrics_collector().record_ingest(
+                &index_name,
+                vectors.len() as u64,
+                vectors.iter().map(|v| v.embedding.len() as u64 * 4).sum(),
+            );
+            crate::indexer::build_shards(&state.s3, &index_name, vectors).await.map(|_| ())
+        } else {
+            state.ingest.append(vectors, &index_name).await
+        };
+        if let Err(e) = ingested {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Ingestion failed: {}", e)).into_response();
+        }
+        written = true;
+
+        // Store individual vector JSON objects, the vector of record for listing/getting
+        if let Err(e) = state.ingest.write_records(bucket_for_ingest, &index_name, &records).await {
+            tracing::warn!("Failed to write vector records for index {}: {}", index_name, e);
+        }
+        if let Some(versioning) = versioning {
+            if let Err(e) = versioning.record(&state.s3, bucket_for_ingest, &index_name, &records).await {
+                tracing::warn!(index = %index_name, error = %e, "Failed to record vector versions");
+            }
+        }
+        changed.extend(records);
+        if let Some(put_usage) = put_usage {
+            if let Err(e) = put_usage.record(&state.s3, bucket_for_ingest, &index_name).await {
+                tracing::warn!(index = %index_name, error = %e, "Failed to update usage ledger");
+            }
+        }
+    }
+    if !changed.is_empty() {
+        let mutation = crate::changelog::Mutation::Put { vectors: changed };
+        if let Err(e) = crate::changelog::append(&state.s3, bucket_for_ingest, &index_name, mutation).await {
+            tracing::warn!(index = %index_name, error = %e, "Failed to log PutVectors change");
         }
     }
+    if !written {
+        let mut body = json!({ "conflicts": conflicts });
+        if !duplicates.is_empty() {
+            body["duplicates"] = json!(duplicates);
+        }
+        if !rejected.is_empty() {
+            body["rejected"] = json!(rejected);
+        }
+        return (StatusCode::OK, Json(body)).into_response();
+    }
+    
+    drop(_records_guard);
     
     // Trigger indexing
-    let _ = crate::indexer::run_once().await;
+    let _ = crate::indexer::run(&state.s3).await;
     
-    // AWS S3 Vectors PutVectors returns empty response per OpenAPI spec
-    let body = json!({});
+    // AWS S3 Vectors PutVectors returns empty response per OpenAPI spec;
+    // conditional writes add the keys that were not written, dedup the
+    // vectors found to duplicate others, partial writes those rejected.
+    let mut body = if conflicts.is_empty() { json!({}) } else { json!({ "conflicts": conflicts }) };
+    if !duplicates.is_empty() {
+        body["duplicates"] = json!(duplicates);
+    }
+    if !rejected.is_empty() {
+        body["rejected"] = json!(rejected);
+    }
     (StatusCode::OK, Json(body)).into_response()
 }
 
+/// Batches of at least `VEC_DIRECT_BUILD_THRESHOLD` vectors (default 100000, 0
+/// disables) are built into shards within the PutVectors call instead of going
+/// through the WAL and a staged slice.
+fn direct_build_threshold() -> Option<usize> {
+    let threshold = std::env::var("VEC_DIRECT_BUILD_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(100_000);
+    (threshold > 0).then_some(threshold)
+}
+
 /// ListVectors - List vectors in an index
-pub async fn list(_bucket: String, body: Value, state: AppState) -> Response {
-    // Get parameters from body directly
-    let index_name = body.get("indexName")
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-index");
-    
-    let bucket_name = body.get("vectorBucketName")
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket");
-    
-    let return_data = body.get("returnData").and_then(|v| v.as_bool()).unwrap_or(false);
-    let return_metadata = body.get("returnMetadata").and_then(|v| v.as_bool()).unwrap_or(false);
+pub async fn list(req: S3ListVectorsRequest, state: AppState) -> Response {
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    let bucket_name = req.vector_bucket_name.as_str();
+    let (return_data, return_metadata) = (req.return_data, req.return_metadata);
     
+    let (targets, partitioned) = match vector_indexes(&state.s3, &index_name).await {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
     let mut vectors = Vec::new();
-    let prefix = format!("{}/vectors/", index_name);
-    
-    // List vectors from S3 storage  
-    match state.s3.client
-        .list_objects_v2()
-        .bucket(bucket_name)
-        .prefix(&prefix)
-        .send()
-        .await 
-    {
-        Ok(output) => {
-            for object in output.contents() {
-                if let Some(key) = object.key() {
+    for target in &targets {
+        let prefix = format!("{}/vectors/", target);
+        
+        // List vectors from S3 storage  
+        match state.s3.list_bucket_objects(bucket_name, &prefix).await {
+            Ok(keys) => {
+                for key in &keys {
                     if let Some(vector_id) = key.strip_prefix(&prefix).and_then(|s| s.strip_suffix(".json")) {
                         // Try to load the vector data
-                        if let Ok(obj_output) = state.s3.client.get_object().bucket(bucket_name).key(key).send().await {
-                            if let Ok(data) = obj_output.body.collect().await {
-                                if let Ok(json_val) = serde_json::from_slice::<Value>(&data.into_bytes()) {
-                                    let mut vector_entry = json!({
-                                        "key": vector_id
-                                    });
-                                    
-                                    if return_data {
-                                        vector_entry["data"] = json_val.get("data").unwrap_or(&json!({})).clone();
-                                    }
-                                    
-                                    if return_metadata {
-                                        vector_entry["metadata"] = json_val.get("metadata").unwrap_or(&json!({})).clone();
-                                    }
-                                    
-                                    vectors.push(vector_entry);
+                        if let Ok(data) = state.s3.get_bucket_object(bucket_name, key).await {
+                            if let Ok(json_val) = serde_json::from_slice::<Value>(&data) {
+                                let mut vector_entry = json!({
+                                    "key": vector_id
+                                });
+                                if partitioned {
+                                    vector_entry["indexName"] = json!(target);
+                                }
+                                
+                                if return_data {
+                                    vector_entry["data"] = json_val.get("data").unwrap_or(&json!({})).clone();
                                 }
+                                
+                                if return_metadata {
+                                    vector_entry["metadata"] = json_val.get("metadata").unwrap_or(&json!({})).clone();
+                                }
+                                if let Some(version) = json_val.get("version") {
+                                    vector_entry["version"] = version.clone();
+                                }
+                                
+                                vectors.push(vector_entry);
                             }
                         }
                     }
                 }
             }
-        }
-        Err(e) => {
-            tracing::error!("Failed to list vectors: {}", e);
+            Err(e) => {
+                tracing::error!("Failed to list vectors: {}", e);
+            }
         }
     }
     
@@ -149,52 +272,330 @@ pub async fn list(_bucket: String, body: Value, state: AppState) -> Response {
     (StatusCode::OK, Json(body)).into_response()
 }
 
+/// Indexes holding the vectors of `index`: the partitions of a partitioned
+/// parent, oldest first, or the index itself. The flag is set for parents.
+async fn vector_indexes(s3: &crate::minio::S3Client, index: &str) -> Result<(Vec<String>, bool), Response> {
+    match crate::partitions::load(s3, index).await {
+        Some((_, partitioning)) => match crate::partitions::list(s3, index, &partitioning).await {
+            Ok(partitions) => Ok((partitions, true)),
+            Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list partitions of {}: {}", index, e)).into_response()),
+        },
+        None => Ok((vec![index.to_string()], false)),
+    }
+}
+
+/// Default and maximum page size for ScrollVectors.
+const DEFAULT_SCROLL_BATCH: usize = 500;
+const MAX_SCROLL_BATCH: usize = 1000;
+/// Record objects fetched at once while filling a scroll page.
+const SCROLL_FETCH_CONCURRENCY: usize = 32;
+
+/// Scroll position: pages walk record keys in order, so resuming after the last
+/// key returned neither repeats nor skips vectors that existed throughout.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct ScrollCursor {
+    index: String,
+    after: String,
+}
+
+/// Position in a stored QueryVectors snapshot (see [`crate::query_snapshots`]).
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct QueryCursor {
+    snapshot: String,
+    offset: usize,
+}
+
+fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode_cursor<T: serde::de::DeserializeOwned>(token: &str) -> Option<T> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(token).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// ScrollVectors - Page through every vector of an index with its data and
+/// metadata, for exports and offline evaluation
+pub async fn scroll(req: ScrollVectorsRequest, state: AppState) -> Response {
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    let bucket_name = req.vector_bucket_name.as_str();
+    
+    let batch_size = match req.max_results {
+        None => DEFAULT_SCROLL_BATCH,
+        Some(n) if (1..=MAX_SCROLL_BATCH as u64).contains(&n) => n as usize,
+        Some(_) => {
+            let body = json!({"error": format!("maxResults must be between 1 and {}", MAX_SCROLL_BATCH), "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    let (targets, partitioned) = match vector_indexes(&state.s3, &index_name).await {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
+    let after = match req.next_token.as_deref() {
+        None => None,
+        Some(token) => match decode_cursor::<ScrollCursor>(token) {
+            Some(cursor) if targets.contains(&cursor.index) => Some(cursor),
+            _ => {
+                let body = json!({"error": "Invalid nextToken for this index", "code": "ValidationException"});
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+        },
+    };
+    
+    // Pages follow the listing's order of record objects, index by index for
+    // partitioned parents, starting after the cursor's, so each lists only
+    // the page it returns.
+    let mut keys: Vec<(&String, String)> = Vec::new();
+    let remaining = targets.iter().skip_while(|t| after.as_ref().is_some_and(|c| c.index != **t));
+    for target in remaining {
+        let prefix = format!("{}/vectors/", target);
+        let start_after = after.as_ref()
+            .filter(|c| c.index == *target)
+            .map(|c| crate::ingest::record_object_key(target, &c.after));
+        let limit = batch_size + 1 - keys.len();
+        match state.s3.list_bucket_objects_after(bucket_name, &prefix, start_after.as_deref(), limit).await {
+            Ok(listed) => keys.extend(listed.iter()
+                .filter_map(|key| key.strip_prefix(&prefix).and_then(|k| k.strip_suffix(".json")))
+                .map(|key| (target, key.to_string()))),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list vectors: {}", e)).into_response(),
+        }
+        if keys.len() > batch_size {
+            break;
+        }
+    }
+    let more = keys.len() > batch_size;
+    keys.truncate(batch_size);
+    
+    let fetches: Vec<_> = keys.iter().map(|(target, key)| {
+        let object_key = crate::ingest::record_object_key(target, key);
+        let (s3, bucket_name) = (state.s3.clone(), bucket_name.to_string());
+        async move { s3.get_bucket_object(&bucket_name, &object_key).await }
+    }).collect();
+    let documents: Vec<_> = futures::stream::iter(fetches)
+        .buffered(SCROLL_FETCH_CONCURRENCY)
+        .collect()
+        .await;
+    // Vectors deleted since the listing are skipped.
+    let vectors: Vec<Value> = keys.iter().zip(documents)
+        .filter_map(|((target, key), data)| {
+            let doc = serde_json::from_slice::<Value>(&data.ok()?).ok()?;
+            let mut entry = json!({
+                "key": key,
+                "data": doc.get("data").cloned().unwrap_or_else(|| json!({})),
+                "metadata": doc.get("metadata").cloned().unwrap_or_else(|| json!({}))
+            });
+            if partitioned {
+                entry["indexName"] = json!(target);
+            }
+            if let Some(version) = doc.get("version") {
+                entry["version"] = version.clone();
+            }
+            Some(entry)
+        })
+        .collect();
+    
+    let mut body = json!({"vectors": vectors});
+    if let Some((target, last)) = keys.last().filter(|_| more) {
+        body["nextToken"] = json!(encode_cursor(&ScrollCursor { index: target.to_string(), after: last.clone() }));
+    }
+    (StatusCode::OK, Json(body)).into_response()
+}
+
+/// FilterVectors - Keys, and optionally metadata, of the vectors matching a
+/// metadata filter, without a query vector. Pages walk keys in order (index by
+/// index for partitioned parents) with the same cursor as ScrollVectors.
+pub async fn filter(req: FilterVectorsRequest, state: AppState) -> Response {
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    let return_metadata = req.return_metadata;
+    
+    let filter = match req.filter.as_ref().or(req.metadata_filter.as_ref()) {
+        Some(filter) => match crate::metadata_filter::MetadataFilter::try_from(filter.clone()) {
+            Ok(_) => filter,
+            Err(e) => {
+                let body = json!({"error": format!("Invalid filter: {}", e), "code": "ValidationException"});
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+        },
+        None => {
+            let body = json!({"error": "A valid filter is required", "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    let batch_size = match req.max_results {
+        None => DEFAULT_SCROLL_BATCH,
+        Some(n) if (1..=MAX_SCROLL_BATCH as u64).contains(&n) => n as usize,
+        Some(_) => {
+            let body = json!({"error": format!("maxResults must be between 1 and {}", MAX_SCROLL_BATCH), "code": "ValidationException"});
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    
+    let (targets, partitioned) = match crate::partitions::load(&state.s3, &index_name).await {
+        Some((_, partitioning)) => match crate::partitions::list(&state.s3, &index_name, &partitioning).await {
+            Ok(partitions) => (partitioning.prune(&index_name, partitions, Some(filter)), true),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list partitions of {}: {}", index_name, e)).into_response(),
+        },
+        None => (vec![index_name.clone()], false),
+    };
+    let after = match req.next_token.as_deref() {
+        None => None,
+        Some(token) => match decode_cursor::<ScrollCursor>(token) {
+            Some(cursor) if targets.contains(&cursor.index) => Some(cursor),
+            _ => {
+                let body = json!({"error": "Invalid nextToken for this index", "code": "ValidationException"});
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+        },
+    };
+    
+    let mut vectors: Vec<(&String, String, Value)> = Vec::new();
+    let mut next = None;
+    let remaining = targets.iter().skip_while(|t| after.as_ref().is_some_and(|c| c.index != **t));
+    'targets: for target in remaining {
+        let matches = match crate::query::filter_vectors(&state.s3, target, filter).await {
+            Ok(matches) => matches,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Filter failed: {}", e)).into_response(),
+        };
+        let resume = after.as_ref().filter(|c| c.index == *target).map(|c| c.after.as_str());
+        for (key, metadata) in matches.into_iter().filter(|(key, _)| resume.is_none_or(|after| key.as_str() > after)) {
+            if vectors.len() == batch_size {
+                let (index, key, _) = &vectors[batch_size - 1];
+                next = Some(ScrollCursor { index: index.to_string(), after: key.clone() });
+                break 'targets;
+            }
+            vectors.push((target, key, metadata));
+        }
+    }
+    
+    let vectors: Vec<Value> = vectors.into_iter().map(|(index, key, metadata)| {
+        let mut entry = json!({"key": key});
+        if partitioned {
+            entry["indexName"] = json!(index);
+        }
+        if return_metadata {
+            entry["metadata"] = metadata;
+        }
+        entry
+    }).collect();
+    let mut body = json!({"vectors": vectors});
+    if let Some(next) = next {
+        body["nextToken"] = json!(encode_cursor(&next));
+    }
+    (StatusCode::OK, Json(body)).into_response()
+}
+
+/// CountVectors - Exact number of live vectors in an index, optionally only
+/// those matching `filter`; partitioned parents sum their partitions
+pub async fn count(req: CountVectorsRequest, state: AppState) -> Response {
+    let index_name = super::aliases::resolve(&state.s3, &req.index_name).await;
+    let filter = req.filter.as_ref().or(req.metadata_filter.as_ref());
+    if let Some(Err(e)) = filter.map(|f| crate::metadata_filter::MetadataFilter::try_from(f.clone())) {
+        let body = json!({"error": format!("Invalid filter: {}", e), "code": "ValidationException"});
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+    
+    let targets = match crate::partitions::load(&state.s3, &index_name).await {
+        Some((_, partitioning)) => match crate::partitions::list(&state.s3, &index_name, &partitioning).await {
+            Ok(partitions) => partitioning.prune(&index_name, partitions, filter),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list partitions of {}: {}", index_name, e)).into_response(),
+        },
+        None => vec![index_name],
+    };
+    let mut count = 0;
+    for target in &targets {
+        match crate::query::count_vectors(&state.s3, target, filter).await {
+            Ok(n) => count += n,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Count failed: {}", e)).into_response(),
+        }
+    }
+    (StatusCode::OK, Json(json!({"count": count}))).into_response()
+}
+
 /// GetVectors - Retrieve specific vectors by ID
-pub async fn get(_bucket: String, body: Value, state: AppState) -> Response {
-    let req: S3GetVectorsRequest = match serde_json::from_value(body) {
-        Ok(r) => r,
-        Err(_e) => return (StatusCode::BAD_REQUEST, "Invalid request format").into_response(),
+pub async fn get(req: S3GetVectorsRequest, state: AppState) -> Response {
+    let (bucket_name, index_name) = match super::schema::bucket_and_index_or_arn(&req.vector_bucket_name, &req.index_name, &req.index_arn) {
+        Ok(names) => names,
+        Err(message) => return super::schema::invalid(message),
     };
+    let index_name = super::aliases::resolve(&state.s3, &index_name).await;
     
-    let (bucket_name, index_name) = extract_bucket_and_index(
-        req.vector_bucket_name,
-        req.index_name,
-        req.index_arn
-    );
+    let (targets, partitioned) = match vector_indexes(&state.s3, &index_name).await {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
     
-    let mut vectors = Vec::new();
+    // Each vector is read from the first index holding it, newest partition first.
+    let mut found: Vec<Option<(&String, Value)>> = Vec::with_capacity(req.keys.len());
     
     for vector_id in &req.keys {
-        let key = format!("{}/vectors/{}.json", index_name, vector_id);
-        match state.s3.client.get_object().bucket(&bucket_name).key(&key).send().await {
-            Ok(output) => match output.body.collect().await {
-                Ok(data) => if let Ok(json_val) = serde_json::from_slice::<Value>(&data.into_bytes()) {
-                    let mut entry = json!({
-                        "key": vector_id
-                    });
-                    
-                    if req.return_data {
-                        entry["data"] = json_val.get("data").unwrap_or(&json!({})).clone();
-                    }
-                    
-                    if req.return_metadata {
-                        entry["metadata"] = json_val.get("metadata").unwrap_or(&json!({})).clone();
-                    }
-                    
-                    vectors.push(entry);
-                } else {
-                    // Vector exists but couldn't parse - still include key
-                    vectors.push(json!({"key": vector_id}));
-                },
-                Err(_) => {
-                    // Vector exists but couldn't read - still include key
-                    vectors.push(json!({"key": vector_id}));
-                },
-            },
-            Err(_) => {
-                // Vector doesn't exist - skip it (don't add to results)
+        let mut doc = None;
+        for target in targets.iter().rev() {
+            // Pinned versions come from the index's history; a pruned or unknown
+            // one leaves the key out like a missing vector.
+            if let Some(version) = req.versions.get(vector_id) {
+                if let Some(pinned) = crate::versions::get(&state.s3, &bucket_name, target, vector_id, *version).await {
+                    doc = Some((target, pinned));
+                    break;
+                }
+                continue;
+            }
+            let key = crate::ingest::record_object_key(target, vector_id);
+            if let Ok(data) = state.s3.get_bucket_object(&bucket_name, &key).await {
+                // Vector exists; if it couldn't be parsed still include the key
+                doc = Some((target, serde_json::from_slice::<Value>(&data).unwrap_or_else(|_| json!({"key": vector_id}))));
+                break;
+            }
+        }
+        found.push(doc);
+    }
+    
+    // Vectors without a record object (e.g. written before records existed) are
+    // looked up in shard metadata and staged slices.
+    let mut recovered: std::collections::HashMap<String, (&String, Value)> = Default::default();
+    for target in targets.iter().rev() {
+        let missing: Vec<String> = req.keys.iter().zip(&found)
+            .filter(|(id, doc)| doc.is_none() && !req.versions.contains_key(*id) && !recovered.contains_key(*id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if missing.is_empty() {
+            break;
+        }
+        match crate::query::lookup_vectors(&state.s3, target, &missing).await {
+            Ok(docs) => recovered.extend(docs.into_iter().map(|(id, doc)| (id, (target, doc)))),
+            Err(e) => tracing::warn!("Fallback vector lookup for index {} failed: {}", target, e),
+        }
+    }
+    
+    let mut vectors = Vec::new();
+    for (vector_id, doc) in req.keys.iter().zip(found) {
+        // Vectors that exist nowhere are skipped (not added to results)
+        let Some((target, doc)) = doc.or_else(|| recovered.remove(vector_id)).filter(|(_, doc)| !doc.is_null()) else { continue };
+        let mut entry = json!({
+            "key": vector_id
+        });
+        if partitioned {
+            entry["indexName"] = json!(target);
+        }
+        
+        if req.return_data {
+            entry["data"] = doc.get("data").unwrap_or(&json!({})).clone();
+        }
+        
+        if req.return_metadata {
+            entry["metadata"] = doc.get("metadata").unwrap_or(&json!({})).clone();
+        }
+        if let Some(version) = doc.get("version") {
+            entry["version"] = version.clone();
+        }
+        if req.return_version_history {
+            match crate::versions::list(&state.s3, &bucket_name, target, vector_id).await {
+                Ok(history) => entry["versionHistory"] = json!(history),
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list versions of {}: {}", vector_id, e)).into_response(),
             }
         }
+        
+        vectors.push(entry);
     }
     
     // AWS S3 Vectors GetVectors format per OpenAPI spec
@@ -203,123 +604,406 @@ pub async fn get(_bucket: String, body: Value, state: AppState) -> Response {
 }
 
 /// DeleteVectors - Delete specific vectors by ID
-pub async fn delete(_bucket: String, body: Value, state: AppState) -> Response {
-    let delete_request: S3DeleteVectorsRequest = match serde_json::from_value(body) {
-        Ok(r) => r,
-        Err(_e) => return (StatusCode::BAD_REQUEST, "Invalid request format").into_response(),
+pub async fn delete(delete_request: S3DeleteVectorsRequest, state: AppState) -> Response {
+    let (bucket_name, index_name) = match super::schema::bucket_and_index_or_arn(&delete_request.vector_bucket_name, &delete_request.index_name, &delete_request.index_arn) {
+        Ok(names) => names,
+        Err(message) => return super::schema::invalid(message),
     };
     
-    let (bucket_name, index_name) = extract_bucket_and_index(
-        delete_request.vector_bucket_name,
-        delete_request.index_name,
-        delete_request.index_arn
-    );
+    let (targets, partitioned) = match vector_indexes(&state.s3, &index_name).await {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
     
-    for vector_id in &delete_request.keys {
-        let vector_key = format!("{}/vectors/{}.json", index_name, vector_id);
-        let _ = state.s3.client
-            .delete_object()
-            .bucket(&bucket_name)
-            .key(&vector_key)
-            .send()
-            .await;
+    // Record the deletion first so queries stop returning these ids even if
+    // removing some record objects fails below. A parent's vectors may be in
+    // any of its partitions, so each one records it.
+    for target in &targets {
+        if let Err(e) = crate::deletions::record(&state.s3, target, &delete_request.keys).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record deletion: {}", e)).into_response();
+        }
     }
+    let mutation = crate::changelog::Mutation::Delete { keys: delete_request.keys.clone() };
+    if let Err(e) = crate::changelog::append(&state.s3, &bucket_name, &index_name, mutation).await {
+        tracing::warn!(index = %index_name, error = %e, "Failed to log DeleteVectors change");
+    }
+    
+    // Only the partitions holding a record of a key count as deleting it.
+    let removals: Vec<_> = delete_request.keys.iter()
+        .flat_map(|vector_id| targets.iter().map(move |target| (vector_id, target)))
+        .map(|(vector_id, target)| {
+            let (s3, bucket_name) = (state.s3.clone(), bucket_name.clone());
+            let vector_key = crate::ingest::record_object_key(target, vector_id);
+            let vector_id = vector_id.clone();
+            async move {
+                if partitioned && s3.get_bucket_object(&bucket_name, &vector_key).await.is_err() {
+                    return (vector_id, target, None);
+                }
+                let result = s3.delete_bucket_object(&bucket_name, &vector_key).await;
+                (vector_id, target, Some(result))
+            }
+        })
+        .collect();
+    let outcomes: Vec<(String, &String, Option<anyhow::Result<()>>)> = futures::stream::iter(removals)
+        .buffer_unordered(delete_concurrency())
+        .collect()
+        .await;
     
-    // AWS S3 Vectors DeleteVectors format per OpenAPI spec (empty response)
-    let body = json!({});
+    let mut deleted = Vec::new();
+    let mut errors = Vec::new();
+    let mut removed: std::collections::HashMap<&String, u64> = Default::default();
+    for (key, target, result) in outcomes {
+        match result {
+            Some(Ok(())) => {
+                *removed.entry(target).or_default() += 1;
+                if !deleted.contains(&key) {
+                    deleted.push(key);
+                }
+            }
+            Some(Err(e)) => errors.push(json!({ "key": key, "error": e.to_string() })),
+            None => {}
+        }
+    }
+    if super::quotas::config().is_some() {
+        for (target, count) in removed {
+            if let Err(e) = crate::usage::remove_vectors(&state.s3, &bucket_name, target, count).await {
+                tracing::warn!(index = %target, error = %e, "Failed to update usage ledger");
+            }
+        }
+    }
+    if !errors.is_empty() {
+        tracing::warn!("DeleteVectors on {}: {} of {} keys failed", index_name, errors.len(), errors.len() + deleted.len());
+    }
+    
+    // AWS S3 Vectors DeleteVectors returns an empty response per OpenAPI spec;
+    // the per-key report is an extension clients may ignore.
+    let body = json!({ "deleted": deleted, "errors": errors });
     (StatusCode::OK, Json(body)).into_response()
 }
 
-/// QueryVectors - Search for similar vectors
-/// QueryVectors - Search for similar vectors  
-pub async fn query(_bucket: String, body: Value, state: AppState) -> Response {
-    // Get parameters from body directly  
-    let index_name = body.get("indexName")
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-index");
-    
-    let query_vector = body.get("queryVector")
-        .or_else(|| body.get("vector"))
-        .and_then(|v| {
-            // Handle both direct array and nested format
-            if let Some(arr) = v.as_array() {
-                // Direct array format: [1.0, 2.0, 3.0]
-                arr.iter()
-                    .map(|v| v.as_f64())
-                    .collect::<Option<Vec<f64>>>()
-            } else if let Some(data_obj) = v.as_object() {
-                // Nested format: {"float32": [1.0, 2.0, 3.0]} or {"data": {"float32": [...]}}
-                if let Some(float32_arr) = data_obj.get("float32").and_then(|v| v.as_array()) {
-                    float32_arr.iter()
-                        .map(|v| v.as_f64())
-                        .collect::<Option<Vec<f64>>>()
-                } else if let Some(data_nested) = data_obj.get("data").and_then(|v| v.as_object()) {
-                    if let Some(float32_arr) = data_nested.get("float32").and_then(|v| v.as_array()) {
-                        float32_arr.iter()
-                            .map(|v| v.as_f64())
-                            .collect::<Option<Vec<f64>>>()
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        });
+/// Parallel object deletes per DeleteVectors call, from `VEC_DELETE_CONCURRENCY` (default 32).
+fn delete_concurrency() -> usize {
+    std::env::var("VEC_DELETE_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(32)
+}
+
+/// Most indexes a single QueryVectors call may fan out to with `indexNames`.
+const MAX_QUERY_INDEXES: usize = 32;
+/// Most ranked results one query can page through.
+const MAX_QUERY_WINDOW: usize = 1000;
+/// Index searches a fanned-out query runs at once.
+const QUERY_FANOUT_CONCURRENCY: usize = 8;
+
+/// Indexes a query targets: every entry of `indexNames`, or the single `indexName`.
+fn query_index_names(req: &S3QueryVectorsRequest) -> Vec<String> {
+    match (&req.index_names, &req.index_name) {
+        (Some(names), _) => names.clone(),
+        (None, Some(name)) => vec![name.clone()],
+        (None, None) => Vec::new(),
+    }
+}
+
+/// The query vector of a request: an array, `{"float32": [..]}` or
+/// `{"data": {"float32": [..]}}`.
+fn query_vector(vector: &Value) -> Option<Vec<f64>> {
+    let values = match vector {
+        Value::Array(values) => values,
+        Value::Object(fields) => match (fields.get("float32"), fields.get("data")) {
+            (Some(values), _) => values.as_array()?,
+            (None, Some(data)) => data.get("float32")?.as_array()?,
+            (None, None) => return None,
+        },
+        _ => return None,
+    };
+    values.iter().map(Value::as_f64).collect()
+}
+
+/// QueryVectors - Search for similar vectors, in one index or fanned out over
+/// several with `indexNames` or the partitions of a partitioned index
+pub async fn query(req: S3QueryVectorsRequest, state: AppState) -> Response {
+    match execute_query(&req, &state).await {
+        Ok(body) => (StatusCode::OK, Json(body)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// QueryVectors response body for `req`, or the error response to send.
+/// Shared by the REST handler and the Flight endpoint.
+pub(super) async fn execute_query(req: &S3QueryVectorsRequest, state: &AppState) -> Result<Value, Response> {
+    let multi_index = req.index_names.is_some();
+    let index_names = match query_index_names(req) {
+        names if !names.is_empty() && names.len() <= MAX_QUERY_INDEXES => names,
+        _ => {
+            let body = json!({
+                "error": format!("indexNames must list between 1 and {} index names", MAX_QUERY_INDEXES),
+                "code": "ValidationException"
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
+        }
+    };
     
-    let top_k = body.get("topK")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(10) as usize;
+    let top_k = req.top_k.unwrap_or(10) as usize;
     
-    let return_data = body.get("returnData").and_then(|v| v.as_bool()).unwrap_or(false);
-    let return_metadata = body.get("returnMetadata").and_then(|v| v.as_bool()).unwrap_or(false);
-    let metadata_filter = body.get("metadataFilter");
+    if let Some(token) = &req.next_token {
+        return query_page(req, state, token, top_k, &index_names).await;
+    }
+    
+    // Results before `offset` are ranked and skipped; with `resultWindow` the
+    // ranked results after this page are kept for nextToken continuations.
+    let offset = req.offset.unwrap_or(0) as usize;
+    let window = match req.result_window {
+        None if offset == 0 => top_k,
+        None => offset + top_k,
+        Some(window) if window as usize >= offset + top_k && window as usize <= MAX_QUERY_WINDOW => window as usize,
+        Some(_) => {
+            let body = json!({
+                "error": format!("resultWindow must be at least offset + topK and at most {}", MAX_QUERY_WINDOW),
+                "code": "ValidationException"
+            });
+            return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
+        }
+    };
+    if offset > 0 && window > MAX_QUERY_WINDOW {
+        let body = json!({
+            "error": format!("offset + topK must be at most {}", MAX_QUERY_WINDOW),
+            "code": "ValidationException"
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
+    }
+    
+    let query_vector = req.query_vector.as_ref().or(req.vector.as_ref()).and_then(query_vector);
+    let (return_data, return_metadata) = (req.return_data, req.return_metadata);
+    let metadata_filter = req.metadata_filter.as_ref().or(req.filter.as_ref());
     
     if query_vector.is_none() {
-        return (StatusCode::BAD_REQUEST, "Query vector is required").into_response();
+        return Err((StatusCode::BAD_REQUEST, "Query vector is required").into_response());
+    }
+    if let Some(Err(e)) = metadata_filter.map(|f| crate::metadata_filter::MetadataFilter::try_from(f.clone())) {
+        let body = json!({"error": format!("Invalid metadataFilter: {}", e), "code": "ValidationException"});
+        return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
     }
     
+    let fusion = req.fusion.unwrap_or_default();
+    let query_text = req.query_text.clone();
+    let vector_weight = match req.vector_weight {
+        None => 0.5,
+        Some(w) if (0.0..=1.0).contains(&w) => w as f32,
+        Some(_) => {
+            let body = json!({"error": "vectorWeight must be between 0 and 1", "code": "ValidationException"});
+            return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
+        }
+    };
+    
     let query_vector = query_vector.unwrap();
     
     let query_req = QueryRequest {
-        index: index_name.to_string(),
+        index: String::new(),
         embedding: query_vector.into_iter().map(|f| f as f32).collect(),
-        topk: top_k,
+        topk: window,
         nprobe: None,
+        explain: req.explain,
+        return_data,
+        exact_rerank: req.exact_rerank,
         filter: metadata_filter.cloned(),
+        text: query_text,
+        vector_weight,
+        fusion,
+        timeout_ms: None,
+        partial_on_timeout: super::query_timeout::partial_results(),
+        allow_partial_results: req.allow_partial_results,
     };
     
-    match crate::query::search(state.s3, query_req).await {
-        Ok(resp) => {
-            let empty_vec = vec![];
-            let results = resp.get("results").and_then(|r| r.as_array()).unwrap_or(&empty_vec);
-            let s3_results: Vec<Value> = results.iter().map(|result| {
-                let mut entry = json!({
-                    "key": result.get("id").unwrap_or(&json!("unknown"))
-                });
-                
-                // Always include distance/score in query results
-                entry["distance"] = result.get("score").unwrap_or(&json!(0.0)).clone();
-                
-                if return_metadata {
-                    entry["metadata"] = result.get("metadata").unwrap_or(&json!({})).clone();
+    // `(label, index)` pairs to search. Partitioned parents fan out to their
+    // partitions, pruned by the time filter, labelled with the partition name.
+    let mut targets: Vec<(String, String)> = Vec::new();
+    let mut partitioned = false;
+    for name in &index_names {
+        let index = super::aliases::resolve(&state.s3, name).await;
+        match crate::partitions::load(&state.s3, &index).await {
+            Some((_, partitioning)) => {
+                partitioned = true;
+                let partitions = match crate::partitions::list(&state.s3, &index, &partitioning).await {
+                    Ok(partitions) => partitions,
+                    Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list partitions of {}: {}", index, e)).into_response()),
+                };
+                targets.extend(partitioning.prune(&index, partitions, metadata_filter).into_iter().map(|p| (p.clone(), p)));
+            }
+            None => targets.push((name.clone(), index)),
+        }
+    }
+    let labelled = multi_index || partitioned;
+    
+    let mut requests = Vec::with_capacity(targets.len());
+    for (_, index) in &targets {
+        let mut query_req = QueryRequest { index: index.clone(), ..query_req.clone() };
+        // Matryoshka indexes cut queries exactly as they cut stored vectors.
+        if let Some((dim, truncation)) = crate::truncation::load(&state.s3, index).await {
+            match truncation.apply(dim, std::mem::take(&mut query_req.embedding)) {
+                Ok(embedding) => query_req.embedding = embedding,
+                Err(message) => {
+                    let body = json!({"error": format!("{}: {}", index, message), "code": "ValidationException"});
+                    return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
                 }
-                
-                if return_data {
-                    entry["data"] = result.get("embedding").unwrap_or(&json!({})).clone();
+            }
+        }
+        requests.push(query_req);
+    }
+    // Indexes searched after others get what is left of the timeout.
+    let deadline = super::query_timeout::timeout().map(|timeout| std::time::Instant::now() + timeout);
+    let searches = requests.into_iter().map(|mut query_req| {
+        query_req.timeout_ms = deadline.map(|deadline| deadline.saturating_duration_since(std::time::Instant::now()).as_millis() as u64);
+        crate::query::search(state.s3.clone(), query_req)
+    });
+    let responses = match futures::stream::iter(searches)
+        .buffered(QUERY_FANOUT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<Value>>>()
+    {
+        Ok(responses) => responses,
+        Err(e) if e.downcast_ref::<crate::integrity::IntegrityError>().is_some() => {
+            let body = json!({
+                "error": format!("Stored index data failed integrity verification: {}", e),
+                "code": "DataCorruption"
+            });
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response());
+        },
+        Err(e) if e.downcast_ref::<crate::query::QueryTimeout>().is_some() => {
+            let body = json!({"error": e.to_string(), "code": "QueryTimeout"});
+            return Err((StatusCode::GATEWAY_TIMEOUT, Json(body)).into_response());
+        },
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)).into_response()),
+    };
+    
+    let empty_vec = vec![];
+    let mut results: Vec<(&str, &Value)> = targets.iter().map(|(label, _)| label).zip(&responses)
+        .flat_map(|(name, resp)| {
+            let hits = resp.get("results").and_then(|r| r.as_array()).unwrap_or(&empty_vec);
+            hits.iter().map(move |hit| (name.as_str(), hit))
+        })
+        .collect();
+    if labelled {
+        // Each index is already ranked; merge on score across them.
+        // Ties order by key, then index, so pages and repeated queries are stable.
+        let score = |hit: &Value| hit.get("score").and_then(|s| s.as_f64()).unwrap_or(f64::MIN) as f32;
+        let key = |hit: &Value| hit.get("id").and_then(|k| k.as_str()).unwrap_or_default().to_string();
+        results.sort_by(|a, b| {
+            crate::fusion::rank(score(a.1), &key(a.1), score(b.1), &key(b.1)).then_with(|| a.0.cmp(b.0))
+        });
+        results.truncate(window);
+    }
+    
+    let s3_results: Vec<Value> = results.iter().map(|(index_name, result)| {
+        let mut entry = json!({
+            "key": result.get("id").unwrap_or(&json!("unknown"))
+        });
+        
+        // Always include distance/score in query results
+        entry["distance"] = result.get("score").unwrap_or(&json!(0.0)).clone();
+        
+        if labelled {
+            entry["indexName"] = json!(index_name);
+        }
+        
+        if return_metadata {
+            entry["metadata"] = result.get("metadata").unwrap_or(&json!({})).clone();
+        }
+        
+        if return_data {
+            entry["data"] = result.get("data")
+                .map(|embedding| json!({"float32": embedding}))
+                .unwrap_or_else(|| json!({}));
+        }
+        
+        entry
+    }).collect();
+    
+    let mut s3_results = s3_results;
+    let rest = s3_results.split_off((offset + top_k).min(s3_results.len()));
+    let page: Vec<Value> = s3_results.into_iter().skip(offset).collect();
+    let next_token = if rest.is_empty() {
+        None
+    } else {
+        let snapshot = crate::query_snapshots::QuerySnapshot {
+            bucket: Some(req.vector_bucket_name.clone()),
+            index_names: index_names.clone(),
+            expires_at: chrono::Utc::now() + crate::query_snapshots::ttl_from_env(),
+            results: rest,
+        };
+        match crate::query_snapshots::save(&state.s3, &snapshot).await {
+            Ok(id) => Some(encode_cursor(&QueryCursor { snapshot: id, offset: 0 })),
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()),
+        }
+    };
+    
+    // AWS S3 Vectors QueryVectors format per OpenAPI spec
+    let mut body = json!({"vectors": page});
+    if let Some(next_token) = next_token {
+        body["nextToken"] = json!(next_token);
+    }
+    if responses.iter().any(|resp| resp.get("timed_out").and_then(|t| t.as_bool()).unwrap_or(false)) {
+        body["timedOut"] = json!(true);
+    }
+    let failed_shards: Vec<Value> = targets.iter().map(|(label, _)| label).zip(&responses)
+        .flat_map(|(name, resp)| {
+            let failed = resp.get("failed_shards").and_then(|f| f.as_array()).unwrap_or(&empty_vec);
+            failed.iter().map(move |failure| {
+                let mut entry = json!({"shardId": failure["shard_id"], "error": failure["error"]});
+                if labelled {
+                    entry["indexName"] = json!(name);
                 }
-                
                 entry
-            }).collect();
-            
-            // AWS S3 Vectors QueryVectors format per OpenAPI spec
-            let body = json!({"vectors": s3_results});
-            (StatusCode::OK, Json(body)).into_response()
-        },
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)).into_response(),
+            })
+        })
+        .collect();
+    if !failed_shards.is_empty() {
+        body["failedShards"] = json!(failed_shards);
     }
+    let explains: Vec<Value> = targets.iter().map(|(label, _)| label).zip(&responses)
+        .filter_map(|(name, resp)| {
+            let mut explain = resp.get("explain")?.clone();
+            explain["latencyMs"] = resp.get("latency_ms").cloned().unwrap_or(Value::Null);
+            if labelled {
+                explain["indexName"] = json!(name);
+            }
+            Some(explain)
+        })
+        .collect();
+    if labelled && !explains.is_empty() {
+        body["explain"] = json!({ "indexes": explains });
+    } else if let Some(explain) = explains.into_iter().next() {
+        body["explain"] = explain;
+    }
+    Ok(body)
+}
+
+/// A QueryVectors page served from the snapshot a nextToken points into,
+/// without searching again.
+async fn query_page(req: &S3QueryVectorsRequest, state: &AppState, token: &str, top_k: usize, index_names: &[String]) -> Result<Value, Response> {
+    let invalid = |message: &str| {
+        (StatusCode::BAD_REQUEST, Json(json!({"error": message, "code": "ValidationException"}))).into_response()
+    };
+    let Some(cursor) = decode_cursor::<QueryCursor>(token) else {
+        return Err(invalid("Invalid nextToken"));
+    };
+    let Some(snapshot) = crate::query_snapshots::load(&state.s3, &cursor.snapshot).await else {
+        return Err(invalid("nextToken has expired"));
+    };
+    if snapshot.bucket.as_deref() != Some(req.vector_bucket_name.as_str()) || snapshot.index_names != index_names {
+        return Err(invalid("nextToken was issued for a different index"));
+    }
+    
+    let start = cursor.offset.min(snapshot.results.len());
+    let end = (start + top_k).min(snapshot.results.len());
+    let mut page = json!({"vectors": &snapshot.results[start..end]});
+    if end < snapshot.results.len() {
+        page["nextToken"] = json!(encode_cursor(&QueryCursor { snapshot: cursor.snapshot, offset: end }));
+    }
+    Ok(page)
 }
 
 // Direct handlers for S3 API routes
@@ -327,70 +1011,180 @@ use axum::extract::State;
 
 pub async fn list_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3ListVectorsRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    list(bucket, payload, state).await
+    list(req, state).await
 }
 
 pub async fn get_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3GetVectorsRequest>,
+) -> impl IntoResponse {
+    get(req, state).await
+}
+
+pub async fn scroll_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<ScrollVectorsRequest>,
+) -> impl IntoResponse {
+    scroll(req, state).await
+}
+
+pub async fn filter_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<FilterVectorsRequest>,
+) -> impl IntoResponse {
+    filter(req, state).await
+}
+
+pub async fn count_direct(
+    State(state): State<AppState>,
+    Typed(req): Typed<CountVectorsRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    get(bucket, payload, state).await
+    count(req, state).await
 }
 
 pub async fn delete_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3DeleteVectorsRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    delete(bucket, payload, state).await
+    delete(req, state).await
 }
 
 pub async fn put_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3PutVectorsRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    put(bucket, payload, state).await
+    put(req, state).await
 }
 
 pub async fn query_direct(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>
+    Typed(req): Typed<S3QueryVectorsRequest>,
 ) -> impl IntoResponse {
-    let bucket = payload.get("vectorBucketName")
-        .or_else(|| payload.get("Bucket"))
-        .or_else(|| payload.get("bucket"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("default-bucket")
-        .to_string();
-    
-    query(bucket, payload, state).await
+    query(req, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_cursor_round_trip() {
+        let cursor = ScrollCursor { index: "docs".to_string(), after: "doc-0042".to_string() };
+        assert_eq!(decode_cursor(&encode_cursor(&cursor)), Some(cursor));
+        assert_eq!(decode_cursor::<ScrollCursor>("not a cursor"), None);
+        let query = QueryCursor { snapshot: "1736942400-0b7e".to_string(), offset: 50 };
+        assert_eq!(decode_cursor::<ScrollCursor>(&encode_cursor(&query)), None);
+        assert_eq!(decode_cursor(&encode_cursor(&query)), Some(query));
+    }
+
+    #[test]
+    fn test_query_index_names() {
+        let request = |fields: Value| {
+            let mut body = json!({"vectorBucketName": "b", "queryVector": [1.0]});
+            body.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+            super::super::schema::parse::<S3QueryVectorsRequest>(body)
+        };
+        assert_eq!(query_index_names(&request(json!({"indexName": "a"})).unwrap()), vec!["a".to_string()]);
+        assert_eq!(query_index_names(&request(json!({"indexNames": ["a", "b"]})).unwrap()), vec!["a".to_string(), "b".to_string()]);
+        assert!(request(json!({"indexNames": ["a", 1]})).is_err());
+    }
+
+    #[test]
+    fn test_query_vector_formats() {
+        let expected = Some(vec![1.0, 2.0]);
+        assert_eq!(query_vector(&json!([1.0, 2.0])), expected);
+        assert_eq!(query_vector(&json!({"float32": [1.0, 2.0]})), expected);
+        assert_eq!(query_vector(&json!({"data": {"float32": [1.0, 2.0]}})), expected);
+        assert_eq!(query_vector(&json!({"float32": [1.0, "2"]})), None);
+    }
+
+    #[tokio::test]
+    async fn test_scroll_pages_through_every_record() {
+        let app = super::super::testing::TestApp::new();
+        for key in ["b", "a-b", "a", "c"] {
+            let record = json!({"key": key, "data": {"float32": [1.0, 0.0]}, "metadata": {}});
+            let object_key = crate::ingest::record_object_key("docs", key);
+            app.s3.put_bucket_object("default-bucket", &object_key, record.to_string().into()).await.unwrap();
+        }
+        let mut scrolled = Vec::new();
+        let mut body = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "maxResults": 3});
+        loop {
+            let (status, response) = app.post("/ScrollVectors", body.clone()).await;
+            assert_eq!(status, StatusCode::OK, "{}", response);
+            scrolled.extend(response["vectors"].as_array().unwrap().iter().map(|v| v["key"].as_str().unwrap().to_string()));
+            match response.get("nextToken") {
+                Some(token) => body["nextToken"] = token.clone(),
+                None => break,
+            }
+        }
+        scrolled.sort();
+        assert_eq!(scrolled, ["a", "a-b", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_partial_results_skip_failed_shards() {
+        let app = super::super::testing::TestApp::new();
+        app.create_index("docs", 2, "euclidean", json!({})).await;
+        app.put("docs", &[("a", vec![1.0, 0.0], json!({}))]).await;
+        app.put("docs", &[("b", vec![0.0, 1.0], json!({}))]).await;
+        let manifest = IndexManifest::from_slice(&app.s3.get_object("indexes/docs/manifest.json").await.unwrap()).unwrap();
+        assert_eq!(manifest.shards.len(), 2);
+        let broken = &manifest.shards[0];
+        app.s3.delete_object(&broken.metadata_path).await.unwrap();
+
+        let query = json!({"vectorBucketName": "default-bucket", "indexName": "docs", "queryVector": [1.0, 0.0], "topK": 2});
+        let (status, _) = app.post("/QueryVectors", query.clone()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let mut partial = query;
+        partial["allowPartialResults"] = json!(true);
+        let (status, response) = app.post("/QueryVectors", partial).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        assert_eq!(response["vectors"].as_array().unwrap().len(), 1);
+        let failed = response["failedShards"].as_array().unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["shardId"], broken.shard_id.as_str());
+        assert!(failed[0]["error"].as_str().unwrap().contains("metadata"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_delete_from_partitioned_index() {
+        let app = super::super::testing::TestApp::new();
+        app.create_index("events", 2, "euclidean", json!({"partitioning": {"granularity": "day", "timestampKey": "ts"}})).await;
+        app.put("events", &[("old", vec![1.0, 0.0], json!({"ts": 1736942400}))]).await;
+        app.put("events", &[("new", vec![0.9, 0.1], json!({"ts": 1737028800}))]).await;
+        assert_eq!(app.query("events", &[1.0, 0.0], 2, json!({})).await, vec!["old", "new"]);
+
+        let keys = json!({"vectorBucketName": "default-bucket", "indexName": "events", "keys": ["old", "missing"]});
+        let (status, response) = app.post("/DeleteVectors", keys.clone()).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        assert_eq!(response["deleted"], json!(["old"]));
+        assert_eq!(app.query("events", &[1.0, 0.0], 2, json!({})).await, vec!["new"]);
+
+        let (_, response) = app.post("/GetVectors", keys).await;
+        assert_eq!(response["vectors"], json!([]));
+        let (_, response) = app.post("/ListVectors", json!({"vectorBucketName": "default-bucket", "indexName": "events"})).await;
+        assert_eq!(response["vectors"], json!([{"key": "new", "indexName": "events.2025-01-16"}]));
+    }
+
+    #[tokio::test]
+    async fn test_bucket_aliases_name_the_bucket_listed() {
+        let app = super::super::testing::TestApp::new();
+        let record = json!({"key": "a", "data": {"float32": [1.0, 0.0]}, "metadata": {}});
+        let object_key = crate::ingest::record_object_key("docs", "a");
+        app.s3.put_bucket_object("other-bucket", &object_key, record.to_string().into()).await.unwrap();
+
+        let (status, response) = app.post("/ListVectors", json!({"Bucket": "other-bucket", "indexName": "docs"})).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        assert_eq!(response["vectors"], json!([{"key": "a"}]));
+        let scroll = json!({"operation": "ScrollVectors", "bucket": "other-bucket", "indexName": "docs"});
+        let (status, response) = app.post("/", scroll).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        assert_eq!(response["vectors"][0]["key"], "a");
+
+        let (status, _) = app.post("/ListVectors", json!({"indexName": "docs"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,139 @@
+//! Order in which an indexer pass builds the indexes with staged slices.
+//! Indexes go by their `indexingPriority` (higher first), then by how long
+//! they have waited: since their oldest pending slice was staged, or since
+//! their last turn. A turn builds at most `INDEXER_SLICES_PER_TURN` (default
+//! 64) of an index's slices, oldest first, and the index then waits behind
+//! the others of its priority, so a large backlog is built in turns with
+//! small indexes in between instead of ahead of them. Among indexes that
+//! have waited as long, the one with fewer slices pending goes first.
+
+use crate::minio::S3Client;
+use crate::model::CreateIndex;
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+const DEFAULT_SLICES_PER_TURN: usize = 64;
+
+/// Slices an index builds per turn, from `INDEXER_SLICES_PER_TURN`.
+pub fn slices_per_turn() -> usize {
+    std::env::var("INDEXER_SLICES_PER_TURN")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_SLICES_PER_TURN)
+}
+
+/// The configured `indexingPriority` of `index`; 0 when unset or the config
+/// can't be read.
+pub async fn priority(s3: &S3Client, index: &str) -> i32 {
+    let Ok(data) = s3.get_object(&format!("indexes/{}/config.json", index)).await else {
+        return 0;
+    };
+    CreateIndex::from_slice(&data).ok().and_then(|config| config.indexing_priority).unwrap_or(0)
+}
+
+struct Pending {
+    index: String,
+    priority: i32,
+    /// Staged slices and when they were staged, oldest first.
+    slices: VecDeque<(String, DateTime<Utc>)>,
+    last_turn: Option<DateTime<Utc>>,
+}
+
+impl Pending {
+    fn waiting_since(&self) -> DateTime<Utc> {
+        let oldest = self.slices.front().map_or(DateTime::<Utc>::MIN_UTC, |(_, at)| *at);
+        self.last_turn.map_or(oldest, |turn| turn.max(oldest))
+    }
+}
+
+/// Slices of one index to build next.
+#[derive(Debug, PartialEq)]
+pub struct Turn {
+    pub index: String,
+    pub slices: Vec<String>,
+    /// When the index started waiting for this turn.
+    pub waiting_since: DateTime<Utc>,
+    /// Slices of the index left for later turns.
+    pub remaining: usize,
+}
+
+pub struct BuildQueue {
+    pending: Vec<Pending>,
+    slices_per_turn: usize,
+}
+
+impl BuildQueue {
+    pub fn new(slices_per_turn: usize) -> Self {
+        BuildQueue { pending: Vec::new(), slices_per_turn: slices_per_turn.max(1) }
+    }
+
+    /// Queue the staged `slices` of `index`, with when each was staged.
+    pub fn push(&mut self, index: String, priority: i32, mut slices: Vec<(String, DateTime<Utc>)>) {
+        if slices.is_empty() {
+            return;
+        }
+        slices.sort_by(|(a, a_at), (b, b_at)| (a_at, a).cmp(&(b_at, b)));
+        self.pending.push(Pending { index, priority, slices: slices.into(), last_turn: None });
+    }
+
+    /// The turn that starts at `now`, or `None` once every slice was handed out.
+    pub fn next_turn(&mut self, now: DateTime<Utc>) -> Option<Turn> {
+        let (next, _) = self.pending.iter().enumerate().min_by_key(|(_, pending)| {
+            (std::cmp::Reverse(pending.priority), pending.waiting_since(), pending.slices.len(), pending.index.as_str())
+        })?;
+        let pending = &mut self.pending[next];
+        let waiting_since = pending.waiting_since();
+        let take = pending.slices.len().min(self.slices_per_turn);
+        let slices = pending.slices.drain(..take).map(|(slice, _)| slice).collect();
+        pending.last_turn = Some(now);
+        let (index, remaining) = (pending.index.clone(), pending.slices.len());
+        if remaining == 0 {
+            self.pending.swap_remove(next);
+        }
+        Some(Turn { index, slices, waiting_since, remaining })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_turns_go_by_priority_then_waiting() {
+        let start = DateTime::parse_from_rfc3339("2025-01-15T00:00:00Z").unwrap().to_utc();
+        let staged = |index: &str, count: usize, minute: i64| -> Vec<(String, DateTime<Utc>)> {
+            (0..count).map(|n| (format!("staged/{}/{}.jsonl", index, n), start + Duration::minutes(minute))).collect()
+        };
+        let mut queue = BuildQueue::new(2);
+        queue.push("huge".to_string(), 0, staged("huge", 5, 0));
+        queue.push("small".to_string(), 0, staged("small", 1, 1));
+        queue.push("later".to_string(), 0, staged("later", 1, 2));
+        queue.push("urgent".to_string(), 1, staged("urgent", 1, 3));
+        queue.push("idle".to_string(), 0, Vec::new());
+
+        let now = start + Duration::hours(1);
+        let mut turns = Vec::new();
+        while let Some(turn) = queue.next_turn(now + Duration::seconds(turns.len() as i64)) {
+            turns.push((turn.index, turn.slices.len(), turn.remaining));
+        }
+        let turns: Vec<_> = turns.iter().map(|(index, slices, remaining)| (index.as_str(), *slices, *remaining)).collect();
+        assert_eq!(
+            turns,
+            [("urgent", 1, 0), ("huge", 2, 3), ("small", 1, 0), ("later", 1, 0), ("huge", 2, 1), ("huge", 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_slices_are_built_oldest_first() {
+        let at = |minute: i64| DateTime::<Utc>::UNIX_EPOCH + Duration::minutes(minute);
+        let mut queue = BuildQueue::new(2);
+        queue.push("docs".to_string(), 0, vec![("c".to_string(), at(1)), ("b".to_string(), at(2)), ("a".to_string(), at(1))]);
+        let turn = queue.next_turn(at(10)).unwrap();
+        assert_eq!((turn.slices, turn.waiting_since), (vec!["a".to_string(), "c".to_string()], at(1)));
+        // The rest of the backlog waits from the turn before it.
+        assert_eq!(queue.next_turn(at(11)).unwrap().waiting_since, at(10));
+        assert!(queue.next_turn(at(12)).is_none());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::hedging::{self, LatencyWindow, INDEX_FILE_READS, OBJECT_READS};
+use crate::minio::S3Client;
+use crate::tempfiles::TempFile;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Local disk tier for immutable shard artifacts (index files, id maps, metadata).
+///
+/// S3 remains the source of truth; the cache only avoids re-downloading objects
+/// whose keys never change once written (shard ids are fresh UUIDs). Enabled by
+/// setting `VEC_CACHE_DIR`; `VEC_CACHE_MAX_MB` bounds its size and the least
+/// recently used files are evicted first. Files handed out by [`ShardCache::fetch`]
+/// are pinned until their [`LocalObject`] is dropped and are never evicted
+/// while read, so the cache can stay over its limit until they are released.
+pub struct ShardCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+    /// Live [`LocalObject`]s reading the file.
+    pins: usize,
+}
+
+/// Keeps a cached file from being evicted while a [`LocalObject`] reads it.
+struct Pin {
+    state: Arc<Mutex<CacheState>>,
+    name: String,
+}
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        if let Some(entry) = self.state.lock().unwrap().entries.get_mut(&self.name) {
+            entry.pins = entry.pins.saturating_sub(1);
+        }
+    }
+}
+
+/// A downloaded object on local disk. Temporary downloads (cache disabled) are
+/// removed when dropped; cached files stay for the next query.
+pub struct LocalObject {
+    path: PathBuf,
+    temporary: Option<TempFile>,
+    _pin: Option<Pin>,
+    /// True when the file was fetched from object storage by this call rather than
+    /// served from the cache, so callers know it still needs verifying.
+    pub fresh: bool,
+}
+
+impl LocalObject {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+static SHARD_CACHE: std::sync::OnceLock<Option<ShardCache>> = std::sync::OnceLock::new();
+
+/// The process-wide shard cache, or `None` when `VEC_CACHE_DIR` is unset.
+pub fn shard_cache() -> Option<&'static ShardCache> {
+    SHARD_CACHE
+        .get_or_init(|| match ShardCache::from_env() {
+            Ok(cache) => cache,
+            Err(e) => {
+                tracing::warn!("Shard cache disabled: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+impl ShardCache {
+    pub fn from_env() -> Result<Option<Self>> {
+        let dir = match std::env::var("VEC_CACHE_DIR") {
+            Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir),
+            _ => return Ok(None),
+        };
+        let max_mb = std::env::var("VEC_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10 * 1024);
+        Self::open(dir, max_mb * 1024 * 1024).map(Some)
+    }
+
+    /// Open (or create) a cache directory, adopting files left by a previous run
+    /// so restarts come back warm. Older files are considered less recently used.
+    pub fn open(dir: PathBuf, max_bytes: u64) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create cache directory {}", dir.display()))?;
+
+        let mut existing = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".part") {
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                existing.push((meta.modified().ok(), name, meta.len()));
+            }
+        }
+        existing.sort();
+
+        let mut state = CacheState::default();
+        for (_, name, size) in existing {
+            state.clock += 1;
+            state.total_bytes += size;
+            state.entries.insert(name, CacheEntry { size, last_used: state.clock, pins: 0 });
+        }
+        tracing::info!(
+            "Shard cache at {} holds {} files ({} bytes, limit {})",
+            dir.display(), state.entries.len(), state.total_bytes, max_bytes
+        );
+
+        let cache = Self { dir, max_bytes, state: Arc::new(Mutex::new(state)) };
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Object keys are flattened into a single directory level.
+    fn file_name(key: &str) -> String {
+        key.replace('/', "%2F")
+    }
+
+    /// Return a local path for `key`, downloading it into the cache on a miss.
+    pub async fn fetch(&self, s3: &S3Client, key: &str) -> Result<LocalObject> {
+        let name = Self::file_name(key);
+        let path = self.dir.join(&name);
+
+        if let Some(pin) = self.pin(&name) {
+            if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+                crate::metrics::get_metrics_collector().track_metric("cache.hit", 1.0);
+                return Ok(LocalObject { path, temporary: None, _pin: Some(pin), fresh: false });
+            }
+        }
+        crate::metrics::get_metrics_collector().track_metric("cache.miss", 1.0);
+
+        // Download next to the final file and rename, so concurrent readers never
+        // see a partially written object. A hedged download that loses is
+        // dropped with its part file.
+        let (part, size) = hedging::hedge(read_latencies(key), || async {
+            let part = PartFile(Some(self.dir.join(format!("{}.{}.part", name, uuid::Uuid::new_v4()))));
+            let size = s3.get_object_to_file(key, &part.path().to_string_lossy()).await?;
+            Ok((part, size))
+        })
+        .await?;
+        tokio::fs::rename(part.path(), &path)
+            .await
+            .with_context(|| format!("Failed to move {} into the cache", key))?;
+        part.keep();
+
+        // Pinned before eviction runs, so a file larger than the whole cache
+        // is still handed out and goes once it is released.
+        let pin = self.insert(name, size);
+        let (dir, max_bytes, state) = (self.dir.clone(), self.max_bytes, self.state.clone());
+        tokio::task::spawn_blocking(move || evict(&dir, max_bytes, &state)).await?;
+        Ok(LocalObject { path, temporary: None, _pin: Some(pin), fresh: true })
+    }
+
+    /// Drop a cached file, e.g. after it failed verification.
+    pub fn invalidate(&self, key: &str) {
+        let name = Self::file_name(key);
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.remove(&name) {
+            state.total_bytes -= entry.size;
+        }
+        let _ = std::fs::remove_file(self.dir.join(name));
+    }
+
+    /// Mark `name` used and pin it, if it is cached.
+    fn pin(&self, name: &str) -> Option<Pin> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let entry = state.entries.get_mut(name)?;
+        entry.last_used = clock;
+        entry.pins += 1;
+        Some(Pin { state: self.state.clone(), name: name.to_string() })
+    }
+
+    fn insert(&self, name: String, size: u64) -> Pin {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let last_used = state.clock;
+        // Readers of a file downloaded again keep their pins on it.
+        let pins = match state.entries.remove(&name) {
+            Some(old) => {
+                state.total_bytes -= old.size;
+                old.pins
+            }
+            None => 0,
+        };
+        state.entries.insert(name.clone(), CacheEntry { size, last_used, pins: pins + 1 });
+        state.total_bytes += size;
+        Pin { state: self.state.clone(), name }
+    }
+
+    fn evict(&self) {
+        evict(&self.dir, self.max_bytes, &self.state)
+    }
+}
+
+/// Remove least recently used files that nothing reads until the cache fits
+/// its size limit. Removing files blocks, so async callers run this on the
+/// blocking pool.
+fn evict(dir: &Path, max_bytes: u64, state: &Mutex<CacheState>) {
+    let mut state = state.lock().unwrap();
+    while state.total_bytes > max_bytes {
+        let Some(victim) = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.pins == 0)
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(name, _)| name.clone())
+        else {
+            break;
+        };
+        if let Some(entry) = state.entries.remove(&victim) {
+            state.total_bytes -= entry.size;
+            let _ = std::fs::remove_file(dir.join(&victim));
+            crate::metrics::get_metrics_collector().track_metric("cache.evictions", 1.0);
+        }
+    }
+}
+
+/// Latencies reads of `key` are hedged by: index files are far bigger than
+/// the other shard objects.
+fn read_latencies(key: &str) -> &'static LatencyWindow {
+    if key.ends_with(".faiss") {
+        &INDEX_FILE_READS
+    } else {
+        &OBJECT_READS
+    }
+}
+
+/// A download in progress in the cache directory, removed unless it was
+/// moved into place.
+struct PartFile(Option<PathBuf>);
+
+impl PartFile {
+    fn path(&self) -> &Path {
+        self.0.as_deref().expect("part file is kept only once moved")
+    }
+
+    /// The file was moved into place; leave it.
+    fn keep(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Fetch object bytes, through the shard cache when it is enabled.
+pub async fn get_object(s3: &S3Client, key: &str) -> Result<Bytes> {
+    Ok(get_object_with_status(s3, key).await?.0)
+}
+
+/// How an object was served, reported by query explain mode.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    #[default]
+    Disabled,
+}
+
+impl LocalObject {
+    pub fn cache_status(&self) -> CacheStatus {
+        match (self.temporary.is_some(), self.fresh) {
+            (true, _) => CacheStatus::Disabled,
+            (false, true) => CacheStatus::Miss,
+            (false, false) => CacheStatus::Hit,
+        }
+    }
+}
+
+/// [`get_object`], also telling whether the shard cache served it.
+pub async fn get_object_with_status(s3: &S3Client, key: &str) -> Result<(Bytes, CacheStatus)> {
+    match shard_cache() {
+        Some(cache) => {
+            let local = cache.fetch(s3, key).await?;
+            let bytes = Bytes::from(tokio::fs::read(local.path()).await?);
+            Ok((bytes, local.cache_status()))
+        }
+        None => Ok((hedging::hedge(read_latencies(key), || s3.get_object(key)).await?, CacheStatus::Disabled)),
+    }
+}
+
+/// Download an object to local disk, through the shard cache when it is enabled
+/// and otherwise to a temp file, which is removed once the result is dropped.
+pub async fn fetch_file(s3: &S3Client, key: &str) -> Result<LocalObject> {
+    match shard_cache() {
+        Some(cache) => cache.fetch(s3, key).await,
+        None => {
+            let suffix = Path::new(key).extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+            let temp = hedging::hedge(read_latencies(key), || async {
+                let temp = TempFile::new("object", &suffix);
+                s3.get_object_to_file(key, temp.path()).await?;
+                Ok(temp)
+            })
+            .await?;
+            Ok(LocalObject { path: PathBuf::from(temp.path()), temporary: Some(temp), _pin: None, fresh: true })
+        }
+    }
+}
+
+/// Discard a downloaded object that turned out to be bad.
+pub fn invalidate(key: &str) {
+    if let Some(cache) = shard_cache() {
+        cache.invalidate(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction_respects_size_limit() {
+        let dir = std::env::temp_dir().join(format!("shard-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ShardCache::open(dir.clone(), 10).unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.join(name), [0u8; 4]).unwrap();
+            drop(cache.insert(name.to_string(), 4));
+            cache.evict();
+        }
+        // "a" is oldest, so adding "c" (12 bytes > 10) evicts it.
+        assert!(!dir.join("a").exists());
+        assert!(cache.pin("b").is_some());
+
+        std::fs::write(dir.join("d"), [0u8; 4]).unwrap();
+        drop(cache.insert("d".to_string(), 4));
+        cache.evict();
+        // "b" was touched more recently than "c".
+        assert!(dir.join("b").exists());
+        assert!(!dir.join("c").exists());
+        assert_eq!(cache.state.lock().unwrap().total_bytes, 8);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_pinned_files_are_not_evicted() {
+        let dir = std::env::temp_dir().join(format!("shard-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ShardCache::open(dir.clone(), 10).unwrap();
+        std::fs::write(dir.join("a"), [0u8; 4]).unwrap();
+        let reading = cache.insert("a".to_string(), 4);
+        // Larger than the whole cache, and being returned.
+        std::fs::write(dir.join("big"), [0u8; 16]).unwrap();
+        let big = cache.insert("big".to_string(), 16);
+        cache.evict();
+        assert!(dir.join("a").exists() && dir.join("big").exists());
+
+        drop(reading);
+        cache.evict();
+        assert!(!dir.join("a").exists() && dir.join("big").exists());
+        drop(big);
+        cache.evict();
+        assert!(!dir.join("big").exists());
+        assert_eq!(cache.state.lock().unwrap().total_bytes, 0);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Vector mutations are logged one object per call as
+/// `changelog/<microseconds>-<uuid>.json`, so listing order is (close to)
+/// commit order and a reader can resume after the last key it applied.
+const CHANGELOG_PREFIX: &str = "changelog/";
+
+/// What a PutVectors or DeleteVectors call changed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum Mutation {
+    /// Vector-of-record documents as written (see [`crate::ingest::record_document`]).
+    Put { vectors: Vec<Value> },
+    Delete { keys: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub at: DateTime<Utc>,
+    pub bucket: String,
+    /// Index the call named; for partitioned indexes the parent, so a replica
+    /// partitions the vectors itself.
+    pub index: String,
+    #[serde(flatten)]
+    pub mutation: Mutation,
+}
+
+/// Logging is off unless `VEC_CHANGELOG=true`; replication needs it on the source.
+pub fn enabled() -> bool {
+    std::env::var("VEC_CHANGELOG").map(|v| v == "true" || v == "1").unwrap_or(false)
+}
+
+/// How long changes are kept for readers, from `VEC_CHANGELOG_RETENTION_HOURS` (default 72).
+pub fn retention_from_env() -> Duration {
+    let hours = std::env::var("VEC_CHANGELOG_RETENTION_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(72);
+    Duration::hours(hours.max(1))
+}
+
+fn change_key(change: &Change) -> String {
+    format!("{}{:020}-{}.json", CHANGELOG_PREFIX, change.at.timestamp_micros(), uuid::Uuid::new_v4())
+}
+
+/// When the change at `key` was logged.
+pub fn logged_at(key: &str) -> Option<DateTime<Utc>> {
+    let micros = key.strip_prefix(CHANGELOG_PREFIX)?.split_once('-')?.0.parse().ok()?;
+    DateTime::from_timestamp_micros(micros)
+}
+
+/// Log `mutation` of `index` in `bucket` when the change log is enabled.
+pub async fn append(s3: &S3Client, bucket: &str, index: &str, mutation: Mutation) -> Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let change = Change { at: Utc::now(), bucket: bucket.to_string(), index: index.to_string(), mutation };
+    s3.put_object(&change_key(&change), serde_json::to_vec(&change)?.into())
+        .await
+        .context("Failed to log change")
+}
+
+/// Keys of logged changes after `cursor` (the last key applied), oldest first.
+pub async fn list_after(s3: &S3Client, cursor: Option<&str>) -> Result<Vec<String>> {
+    let mut keys: Vec<String> = s3
+        .list_objects(CHANGELOG_PREFIX)
+        .await?
+        .into_iter()
+        .filter(|key| cursor.is_none_or(|cursor| key.as_str() > cursor))
+        .collect();
+    keys.sort();
+    Ok(keys)
+}
+
+pub async fn load(s3: &S3Client, key: &str) -> Result<Change> {
+    let data = s3.get_object(key).await.with_context(|| format!("Failed to read change {}", key))?;
+    serde_json::from_slice(&data).with_context(|| format!("Failed to parse change {}", key))
+}
+
+/// Delete changes older than the retention window, returning how many were removed.
+pub async fn sweep_expired(s3: &S3Client) -> Result<usize> {
+    let cutoff = Utc::now() - retention_from_env();
+    let mut swept = 0;
+    for key in s3.list_objects(CHANGELOG_PREFIX).await? {
+        if logged_at(&key).is_some_and(|at| at < cutoff) {
+            s3.delete_object(&key).await?;
+            swept += 1;
+        }
+    }
+    Ok(swept)
+}
+
+/// Sweep old changes in the background, hourly, when the change log is enabled.
+pub fn spawn_sweeper(s3: S3Client) {
+    if !enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match sweep_expired(&s3).await {
+                Ok(0) => {}
+                Ok(swept) => tracing::info!(swept, "Swept expired change log entries"),
+                Err(e) => tracing::warn!(error = %e, "Failed to sweep change log"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_keys_sort_by_time() {
+        let change = |at| Change {
+            at: DateTime::from_timestamp(at, 0).unwrap(),
+            bucket: "b".to_string(),
+            index: "docs".to_string(),
+            mutation: Mutation::Delete { keys: vec!["a".to_string()] },
+        };
+        let (earlier, later) = (change_key(&change(999)), change_key(&change(1_736_942_400)));
+        assert!(earlier < later);
+        assert_eq!(logged_at(&later), DateTime::from_timestamp(1_736_942_400, 0));
+
+        let json = serde_json::to_value(change(1)).unwrap();
+        assert_eq!((json["op"].as_str(), json["keys"][0].as_str()), (Some("delete"), Some("a")));
+        assert_eq!(serde_json::from_value::<Change>(json).unwrap(), change(1));
+    }
+}
@@ -0,0 +1,447 @@
+//! `genai-vectors check --index <name>`: an fsck for one index. Every shard
+//! the manifest lists must have its objects, match their recorded checksums,
+//! and have an id map whose rows line up with its metadata, raw vectors and
+//! Faiss index; counts in the manifest must add up; a key should live in one
+//! shard; and every per-vector record must be indexed (or staged) and not
+//! deleted. `--repair` fixes what can be fixed without the lost data: corrupt
+//! shards are dropped from the manifest (their vectors can be put again from
+//! their records), counts are rewritten, and records of deleted vectors are
+//! removed.
+
+use crate::deletions;
+use crate::faiss_utils::key_id;
+use crate::index_events::{self, IndexEvent, IndexEventKind};
+use crate::integrity::{self, sha256_file, sha256_hex};
+use crate::minio::S3Client;
+use crate::model::{IndexManifest, ShardInfo};
+use crate::raw_vectors::RawVectors;
+use crate::tempfiles::TempFile;
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use faiss::Index;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    /// An object the manifest names doesn't exist.
+    MissingObject,
+    /// An object can't be downloaded or parsed.
+    Unreadable,
+    ChecksumMismatch,
+    /// An id map row whose Faiss id isn't the hash of its key.
+    IdMapMismatch,
+    /// Metadata keyed by other vectors than the id map's.
+    MetadataMismatch,
+    /// Raw vectors or Faiss index holding another number of vectors than the id map.
+    SizeMismatch,
+    /// A shard or manifest vector count that doesn't add up.
+    CountMismatch,
+    /// A shard listed more than once in the manifest.
+    DuplicateShard,
+    /// A key indexed in more than one shard.
+    DuplicateKey,
+    /// A record of a vector deleted after it was written.
+    DeletedRecord,
+    /// A record of a vector in no shard and no staged slice.
+    UnindexedRecord,
+}
+
+impl IssueKind {
+    /// Whether the shard can't be searched correctly and repair drops it.
+    fn breaks_shard(self) -> bool {
+        matches!(
+            self,
+            IssueKind::MissingObject
+                | IssueKind::Unreadable
+                | IssueKind::ChecksumMismatch
+                | IssueKind::IdMapMismatch
+                | IssueKind::MetadataMismatch
+                | IssueKind::SizeMismatch
+        )
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub kind: IssueKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<String>,
+    /// Object or vector key the issue is about.
+    pub key: String,
+    pub detail: String,
+}
+
+impl Issue {
+    fn new(kind: IssueKind, shard: Option<&str>, key: &str, detail: impl Into<String>) -> Self {
+        Issue { kind, shard: shard.map(str::to_string), key: key.to_string(), detail: detail.into() }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckReport {
+    pub index_name: String,
+    pub shards: usize,
+    /// Rows of the id maps that could be read.
+    pub vectors: usize,
+    pub records: usize,
+    pub issues: Vec<Issue>,
+    /// Shards removed from the manifest by `--repair`.
+    pub dropped_shards: Vec<String>,
+    /// Whether `--repair` rewrote the manifest.
+    pub manifest_rewritten: bool,
+    /// Records of deleted vectors removed by `--repair`.
+    pub deleted_records: Vec<String>,
+}
+
+/// Contents of a shard, as far as they could be read.
+#[derive(Default)]
+struct ShardContents {
+    id_map: Option<Vec<(i64, String)>>,
+    metadata: Option<HashMap<String, Value>>,
+    vectors: Option<RawVectors>,
+    /// Vectors in the Faiss index.
+    ntotal: Option<usize>,
+}
+
+/// Issues between the parts of a shard: id map rows against their keys,
+/// metadata, raw vectors, Faiss index and the manifest's count.
+fn check_alignment(shard: &ShardInfo, contents: &ShardContents) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let shard_id = Some(shard.shard_id.as_str());
+    let Some(id_map) = &contents.id_map else { return issues };
+    let id_map_key = shard.index_path.replace("index.faiss", "id_map.json");
+    if let Some((_, key)) = id_map.iter().find(|(id, key)| *id != key_id(key)) {
+        issues.push(Issue::new(IssueKind::IdMapMismatch, shard_id, &id_map_key, format!("Row of {} has another key's Faiss id", key)));
+    }
+    if let Some(metadata) = &contents.metadata {
+        let keys: HashSet<&str> = id_map.iter().map(|(_, key)| key.as_str()).collect();
+        let missing = keys.iter().filter(|key| !metadata.contains_key(**key)).count();
+        let extra = metadata.keys().filter(|key| !keys.contains(key.as_str())).count();
+        if missing > 0 || extra > 0 {
+            let detail = format!("{} id map keys have no metadata, {} metadata entries aren't in the id map", missing, extra);
+            issues.push(Issue::new(IssueKind::MetadataMismatch, shard_id, &shard.metadata_path, detail));
+        }
+    }
+    let vectors_key = shard.vectors_path.as_deref().unwrap_or_default();
+    match &contents.vectors {
+        Some(RawVectors::Column { dim, values }) if values.len() != id_map.len() * dim => {
+            let detail = format!("{} rows for {} id map rows", values.len() / dim, id_map.len());
+            issues.push(Issue::new(IssueKind::SizeMismatch, shard_id, vectors_key, detail));
+        }
+        Some(RawVectors::Keyed(keyed)) if id_map.iter().any(|(_, key)| !keyed.contains_key(key)) => {
+            issues.push(Issue::new(IssueKind::SizeMismatch, shard_id, vectors_key, "Vectors missing for id map keys"));
+        }
+        _ => {}
+    }
+    if let Some(ntotal) = contents.ntotal.filter(|&ntotal| ntotal != id_map.len()) {
+        let detail = format!("Faiss index holds {} vectors for {} id map rows", ntotal, id_map.len());
+        issues.push(Issue::new(IssueKind::SizeMismatch, shard_id, &shard.index_path, detail));
+    }
+    if shard.vector_count != id_map.len() {
+        let detail = format!("Manifest counts {} vectors, the id map has {}", shard.vector_count, id_map.len());
+        issues.push(Issue::new(IssueKind::CountMismatch, shard_id, &id_map_key, detail));
+    }
+    issues
+}
+
+/// Keys found in more than one shard, one issue per key.
+fn duplicate_keys<'a>(shards: impl IntoIterator<Item = (&'a str, &'a [(i64, String)])>) -> Vec<Issue> {
+    let mut found: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (shard_id, id_map) in shards {
+        for (_, key) in id_map {
+            found.entry(key.as_str()).or_default().push(shard_id);
+        }
+    }
+    found
+        .into_iter()
+        .filter(|(_, shards)| shards.len() > 1)
+        .map(|(key, shards)| Issue::new(IssueKind::DuplicateKey, None, key, format!("In shards {}", shards.join(", "))))
+        .collect()
+}
+
+/// Download `key`, checking it against `expected`; `None` after recording why
+/// it couldn't be used.
+async fn fetch(s3: &S3Client, shard_id: &str, key: &str, expected: Option<&str>, issues: &mut Vec<Issue>) -> Option<bytes::Bytes> {
+    let data = match s3.get_object(key).await {
+        Ok(data) => data,
+        Err(e) => {
+            issues.push(Issue::new(IssueKind::Unreadable, Some(shard_id), key, format!("{:#}", e)));
+            return None;
+        }
+    };
+    if let Err(e) = integrity::verify(key, expected, &sha256_hex(&data)) {
+        issues.push(Issue::new(IssueKind::ChecksumMismatch, Some(shard_id), key, e.to_string()));
+        return None;
+    }
+    Some(data)
+}
+
+/// Parse a downloaded shard object, recording it as unreadable on failure.
+fn parse<T>(shard_id: &str, key: &str, parsed: Result<T>, issues: &mut Vec<Issue>) -> Option<T> {
+    parsed.map_err(|e| issues.push(Issue::new(IssueKind::Unreadable, Some(shard_id), key, format!("{:#}", e)))).ok()
+}
+
+async fn check_shard(s3: &S3Client, index: &str, dim: usize, shard: &ShardInfo, listed: &HashSet<String>) -> (Vec<Issue>, ShardContents) {
+    let mut issues = Vec::new();
+    let mut contents = ShardContents::default();
+    let id = shard.shard_id.as_str();
+    let id_map_key = shard.index_path.replace("index.faiss", "id_map.json");
+    let projection_key = shard.projection.as_ref().map(|file| format!("indexes/{}/{}", index, file));
+    let objects = [Some(&shard.index_path), Some(&id_map_key), Some(&shard.metadata_path), shard.vectors_path.as_ref(), shard.text_path.as_ref(), projection_key.as_ref()];
+    for key in objects.into_iter().flatten().filter(|key| !listed.contains(*key)) {
+        issues.push(Issue::new(IssueKind::MissingObject, Some(id), key, "Named by the manifest but not stored"));
+    }
+    if !issues.is_empty() {
+        return (issues, contents);
+    }
+    let checksums = shard.checksums.as_ref();
+
+    let local = TempFile::new("check", ".faiss");
+    match s3.get_object_to_file(&shard.index_path, local.path()).await {
+        Ok(_) => {
+            let checksum = sha256_file(local.path()).unwrap_or_default();
+            match integrity::verify(&shard.index_path, checksums.map(|c| c.index.as_str()), &checksum) {
+                Ok(()) => {
+                    let path = local.path().to_string();
+                    let ntotal = crate::faiss_pool::run("read", move || Ok(faiss::read_index(&path)?.ntotal() as usize)).await;
+                    contents.ntotal = parse(id, &shard.index_path, ntotal, &mut issues);
+                }
+                Err(e) => issues.push(Issue::new(IssueKind::ChecksumMismatch, Some(id), &shard.index_path, e.to_string())),
+            }
+        }
+        Err(e) => issues.push(Issue::new(IssueKind::Unreadable, Some(id), &shard.index_path, format!("{:#}", e))),
+    }
+    drop(local);
+
+    let decode = |data: &[u8]| shard.content_encoding.decode(data);
+    if let Some(data) = fetch(s3, id, &id_map_key, checksums.map(|c| c.id_map.as_str()), &mut issues).await {
+        let id_map = decode(&data).and_then(|data| Ok(serde_json::from_slice(&data)?));
+        contents.id_map = parse(id, &id_map_key, id_map, &mut issues);
+    }
+    if let Some(data) = fetch(s3, id, &shard.metadata_path, checksums.map(|c| c.metadata.as_str()), &mut issues).await {
+        let metadata = decode(&data).and_then(|data| Ok(serde_json::from_slice(&data)?));
+        contents.metadata = parse(id, &shard.metadata_path, metadata, &mut issues);
+    }
+    if let Some(key) = &shard.vectors_path {
+        // Raw vectors are stored unencoded.
+        if let Some(data) = fetch(s3, id, key, checksums.and_then(|c| c.vectors.as_deref()), &mut issues).await {
+            contents.vectors = parse(id, key, RawVectors::parse(key, &data, dim), &mut issues);
+        }
+    }
+    if let Some(key) = &shard.text_path {
+        fetch(s3, id, key, checksums.and_then(|c| c.text.as_deref()), &mut issues).await;
+    }
+    issues.extend(check_alignment(shard, &contents));
+    (issues, contents)
+}
+
+/// Ids of the vectors staged for `index` but not yet indexed.
+async fn staged_ids(s3: &S3Client, index: &str) -> Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+    for slice in s3.list_objects(&format!("staged/{}/", index)).await? {
+        match crate::indexer::read_slice(s3, &slice).await {
+            Ok(records) => ids.extend(records.into_iter().map(|record| record.id)),
+            // The indexer quarantines it; its records show up as unindexed.
+            Err(e) => tracing::warn!("Skipping unreadable slice {}: {:#}", slice, e),
+        }
+    }
+    Ok(ids)
+}
+
+/// Per-vector records of `index` in `bucket` that no shard or staged slice
+/// backs, or whose vector was deleted after they were written.
+async fn check_records(s3: &S3Client, bucket: &str, index: &str, indexed: &HashSet<&str>) -> Result<(usize, Vec<Issue>)> {
+    let prefix = format!("{}/vectors/", index);
+    let keys = s3.list_bucket_objects(bucket, &prefix).await?;
+    let deletions = deletions::load(s3, index).await?;
+    let staged = staged_ids(s3, index).await?;
+    let mut issues = Vec::new();
+    for key in &keys {
+        let Some(id) = key.strip_prefix(&prefix).and_then(|name| name.strip_suffix(".json")) else { continue };
+        if deletions.was_deleted(id) {
+            let record: Value = serde_json::from_slice(&s3.get_bucket_object(bucket, key).await?).unwrap_or_default();
+            let version = record.get("version").and_then(Value::as_i64).unwrap_or_default();
+            if deletions.is_deleted(id, DateTime::from_timestamp_micros(version).unwrap_or_default()) {
+                issues.push(Issue::new(IssueKind::DeletedRecord, None, key, format!("Vector {} was deleted after this record was written", id)));
+                continue;
+            }
+        }
+        if !indexed.contains(id) && !staged.contains(id) {
+            issues.push(Issue::new(IssueKind::UnindexedRecord, None, key, format!("Vector {} is in no shard or staged slice", id)));
+        }
+    }
+    Ok((keys.len(), issues))
+}
+
+/// Rewrite the manifest of `index` without the shards `dropped`, listing each
+/// shard once with its id map's vector count (`counts`). The manifest is
+/// re-read under the manifest lock, so shards added since the check are kept.
+async fn repair_manifest(s3: &S3Client, index: &str, dropped: &HashSet<String>, counts: &HashMap<String, usize>) -> Result<()> {
+    let _manifest_guard = crate::indexer::MANIFEST_LOCK.lock().await;
+    let manifest_key = format!("indexes/{}/manifest.json", index);
+    let mut manifest = IndexManifest::from_slice(&s3.get_object(&manifest_key).await?)?;
+    let mut seen = HashSet::new();
+    manifest.shards.retain(|shard| !dropped.contains(&shard.shard_id) && seen.insert(shard.shard_id.clone()));
+    for shard in &mut manifest.shards {
+        if let Some(&count) = counts.get(&shard.shard_id) {
+            shard.vector_count = count;
+        }
+    }
+    manifest.total_vectors = manifest.shards.iter().map(|shard| shard.vector_count).sum();
+    manifest.schema_version = crate::schema::MANIFEST_SCHEMA_VERSION;
+    let data = serde_json::to_vec(&manifest)?;
+    let event = IndexEvent::new(IndexEventKind::ManifestChanged, index, &data);
+    s3.put_object(&manifest_key, data.into()).await?;
+    index_events::publish(event);
+    Ok(())
+}
+
+/// Check `index`, whose per-vector records are in `bucket`; with `repair`,
+/// fix what the check found (see the module docs).
+pub async fn check(s3: &S3Client, bucket: &str, index: &str, repair: bool) -> Result<CheckReport> {
+    let manifest_key = format!("indexes/{}/manifest.json", index);
+    let data = s3.get_object(&manifest_key).await.context("Index has no manifest")?;
+    let manifest = IndexManifest::from_slice(&data).context("Failed to parse manifest")?;
+    let listed: HashSet<String> = s3.list_objects(&format!("indexes/{}/", index)).await?.into_iter().collect();
+    let mut report = CheckReport { index_name: index.to_string(), shards: manifest.shards.len(), ..Default::default() };
+
+    let mut seen = HashSet::new();
+    let mut id_maps = Vec::new();
+    for shard in &manifest.shards {
+        if !seen.insert(shard.shard_id.as_str()) {
+            report.issues.push(Issue::new(IssueKind::DuplicateShard, Some(shard.shard_id.as_str()), &manifest_key, "Listed more than once"));
+            continue;
+        }
+        let (issues, contents) = check_shard(s3, index, manifest.dim as usize, shard, &listed).await;
+        report.issues.extend(issues);
+        if let Some(id_map) = contents.id_map {
+            id_maps.push((shard.shard_id.clone(), id_map));
+        }
+    }
+    let counted: usize = manifest.shards.iter().map(|shard| shard.vector_count).sum();
+    if manifest.total_vectors != counted {
+        let detail = format!("Manifest totals {} vectors, its shards count {}", manifest.total_vectors, counted);
+        report.issues.push(Issue::new(IssueKind::CountMismatch, None, &manifest_key, detail));
+    }
+    report.vectors = id_maps.iter().map(|(_, id_map)| id_map.len()).sum();
+    report.issues.extend(duplicate_keys(id_maps.iter().map(|(shard, id_map)| (shard.as_str(), id_map.as_slice()))));
+    let indexed: HashSet<&str> = id_maps.iter().flat_map(|(_, id_map)| id_map.iter().map(|(_, key)| key.as_str())).collect();
+    let (records, issues) = check_records(s3, bucket, index, &indexed).await?;
+    report.records = records;
+    report.issues.extend(issues);
+
+    if repair {
+        let dropped: HashSet<String> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.kind.breaks_shard())
+            .filter_map(|issue| issue.shard.clone())
+            .collect();
+        let rewrite = !dropped.is_empty()
+            || report.issues.iter().any(|issue| matches!(issue.kind, IssueKind::CountMismatch | IssueKind::DuplicateShard));
+        if rewrite {
+            let counts: HashMap<String, usize> = id_maps.iter().map(|(shard, id_map)| (shard.clone(), id_map.len())).collect();
+            repair_manifest(s3, index, &dropped, &counts).await?;
+            report.dropped_shards = dropped.into_iter().collect();
+            report.dropped_shards.sort();
+            report.manifest_rewritten = true;
+        }
+        for issue in report.issues.iter().filter(|issue| issue.kind == IssueKind::DeletedRecord) {
+            s3.delete_bucket_object(bucket, &issue.key).await?;
+            report.deleted_records.push(issue.key.clone());
+        }
+    }
+    tracing::info!(
+        index,
+        shards = report.shards,
+        issues = report.issues.len(),
+        dropped = report.dropped_shards.len(),
+        deleted_records = report.deleted_records.len(),
+        "Checked index"
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::TestApp;
+    use bytes::Bytes;
+    use serde_json::json;
+
+    fn shard(id: &str, vector_count: usize) -> ShardInfo {
+        serde_json::from_value(json!({
+            "shard_id": id,
+            "index_path": format!("indexes/docs/shards/{}/index.faiss", id),
+            "metadata_path": format!("indexes/docs/shards/{}/metadata.json", id),
+            "vectors_path": format!("indexes/docs/shards/{}/vectors.f32", id),
+            "vector_count": vector_count, "metric": "cosine", "created_at": "20250115T000000",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_alignment() {
+        let id_map: Vec<(i64, String)> = ["a", "b"].iter().map(|key| (key_id(key), key.to_string())).collect();
+        let aligned = ShardContents {
+            id_map: Some(id_map.clone()),
+            metadata: Some(HashMap::from([("a".to_string(), json!({})), ("b".to_string(), json!({}))])),
+            vectors: Some(RawVectors::Column { dim: 2, values: vec![0.0; 4] }),
+            ntotal: Some(2),
+        };
+        assert!(check_alignment(&shard("s1", 2), &aligned).is_empty());
+
+        let mut swapped = id_map.clone();
+        swapped[0].1 = "c".to_string();
+        let misaligned = ShardContents {
+            id_map: Some(swapped),
+            metadata: Some(HashMap::from([("a".to_string(), json!({}))])),
+            vectors: Some(RawVectors::Column { dim: 2, values: vec![0.0; 2] }),
+            ntotal: Some(3),
+        };
+        let kinds: Vec<IssueKind> = check_alignment(&shard("s1", 5), &misaligned).into_iter().map(|issue| issue.kind).collect();
+        assert_eq!(
+            kinds,
+            [IssueKind::IdMapMismatch, IssueKind::MetadataMismatch, IssueKind::SizeMismatch, IssueKind::SizeMismatch, IssueKind::CountMismatch]
+        );
+
+        let other = [(key_id("b"), "b".to_string())];
+        let duplicates = duplicate_keys([("s1", id_map.as_slice()), ("s2", other.as_slice())]);
+        assert_eq!(duplicates, [Issue::new(IssueKind::DuplicateKey, None, "b", "In shards s1, s2")]);
+    }
+
+    #[tokio::test]
+    async fn test_check_finds_and_repairs_damage() {
+        let app = TestApp::new();
+        app.create_index("docs", 2, "cosine", json!({})).await;
+        app.put("docs", &[("a", vec![1.0, 0.0], json!({}))]).await;
+        app.put("docs", &[("b", vec![0.0, 1.0], json!({}))]).await;
+        let report = check(&app.s3, "default-bucket", "docs", false).await.unwrap();
+        assert!(report.issues.is_empty(), "{:?}", report);
+        assert_eq!((report.shards, report.vectors, report.records), (2, 2, 2));
+
+        // Corrupt one shard's metadata, leave a deleted vector's record
+        // behind and add a record nothing indexed.
+        let manifest = IndexManifest::from_slice(&app.s3.get_object("indexes/docs/manifest.json").await.unwrap()).unwrap();
+        let corrupt = &manifest.shards[0];
+        app.s3.put_object(&corrupt.metadata_path, Bytes::from_static(b"{}")).await.unwrap();
+        let a_record = app.s3.get_bucket_object("default-bucket", "docs/vectors/a.json").await.unwrap();
+        deletions::record(&app.s3, "docs", &["a".to_string()]).await.unwrap();
+        app.s3.put_bucket_object("default-bucket", "docs/vectors/a.json", a_record).await.unwrap();
+        app.s3.put_bucket_object("default-bucket", "docs/vectors/z.json", Bytes::from_static(b"{}")).await.unwrap();
+
+        let report = check(&app.s3, "default-bucket", "docs", true).await.unwrap();
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(kinds, [IssueKind::ChecksumMismatch, IssueKind::DeletedRecord, IssueKind::UnindexedRecord], "{:?}", report);
+        assert_eq!(report.dropped_shards, [corrupt.shard_id.as_str()]);
+        assert_eq!(report.deleted_records, ["docs/vectors/a.json"]);
+
+        let report = check(&app.s3, "default-bucket", "docs", false).await.unwrap();
+        assert_eq!(report.shards, 1);
+        assert!(report.issues.iter().all(|issue| issue.kind == IssueKind::UnindexedRecord), "{:?}", report);
+    }
+}
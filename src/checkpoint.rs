@@ -0,0 +1,167 @@
+//! Progress of an indexing run over staged slices, so a run that dies partway
+//! resumes where it stopped instead of building every shard again. As each
+//! shard of the run is stored, `indexes/<index>/checkpoint.json` records it
+//! and the slice rows it holds; the next run adds the recorded shards its
+//! manifest lacks and skips their rows. Rows are dropped from the checkpoint
+//! as their slices are deleted, and the checkpoint once none are left, so
+//! slices of the run built in later turns (see [`crate::build_queue`]) still
+//! skip theirs.
+
+use crate::minio::S3Client;
+use crate::model::ShardInfo;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A record of a run: the position of its slice among the run's slices, and
+/// its row in the slice.
+pub type Row = (usize, usize);
+
+pub fn key(index: &str) -> String {
+    format!("indexes/{}/checkpoint.json", index)
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// Dimension of the run's vectors.
+    pub dim: usize,
+    /// Shards the run stored, whether or not they made it into the manifest.
+    pub shards: Vec<ShardInfo>,
+    /// Rows of each staged slice the shards hold, as sorted `[start, end)`
+    /// ranges.
+    pub rows: BTreeMap<String, Vec<(usize, usize)>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Checkpoint {
+    /// The checkpoint an unfinished run of `index` left. One that can't be
+    /// read is ignored, and the run starts over as it would without one.
+    pub async fn load(s3: &S3Client, index: &str) -> Option<Self> {
+        let data = s3.get_object(&key(index)).await.ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                tracing::warn!(index, error = %e, "Ignoring unreadable indexing checkpoint");
+                None
+            }
+        }
+    }
+
+    /// Whether a stored shard holds `row` of `slice`.
+    pub fn holds(&self, slice: &str, row: usize) -> bool {
+        self.rows.get(slice).is_some_and(|ranges| ranges.iter().any(|&(start, end)| (start..end).contains(&row)))
+    }
+
+    fn add(&mut self, shard: ShardInfo, rows: impl IntoIterator<Item = (String, usize)>) {
+        for (slice, row) in rows {
+            let ranges = self.rows.entry(slice).or_default();
+            match ranges.iter_mut().find(|(_, end)| *end == row) {
+                Some((_, end)) => *end += 1,
+                None => ranges.push((row, row + 1)),
+            }
+        }
+        // Shards finish in any order; keep each slice's ranges sorted and merged.
+        for ranges in self.rows.values_mut() {
+            ranges.sort_unstable();
+            let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+            for &(start, end) in ranges.iter() {
+                match merged.last_mut() {
+                    Some((_, last)) if *last >= start => *last = (*last).max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *ranges = merged;
+        }
+        self.shards.push(shard);
+    }
+}
+
+/// The checkpoint of a run in progress, saved whenever one of its shards is
+/// stored.
+pub struct Tracker {
+    s3: S3Client,
+    index: String,
+    /// Paths of the run's slices, by position.
+    slices: Vec<String>,
+    checkpoint: tokio::sync::Mutex<Checkpoint>,
+}
+
+impl Tracker {
+    pub fn new(s3: &S3Client, index: &str, slices: Vec<String>, checkpoint: Checkpoint) -> Self {
+        Tracker { s3: s3.clone(), index: index.to_string(), slices, checkpoint: tokio::sync::Mutex::new(checkpoint) }
+    }
+
+    /// Record `shard` of `dim`-dimensional vectors, holding the run's `rows`.
+    pub async fn shard_stored(&self, dim: usize, shard: ShardInfo, rows: &[Row]) -> Result<()> {
+        let mut checkpoint = self.checkpoint.lock().await;
+        checkpoint.dim = dim;
+        checkpoint.add(shard, rows.iter().map(|&(slice, row)| (self.slices[slice].clone(), row)));
+        checkpoint.updated_at = Some(Utc::now());
+        self.s3.put_object(&key(&self.index), serde_json::to_vec(&*checkpoint)?.into()).await
+    }
+
+    /// Forget the rows of the `deleted` slices, and the checkpoint once no
+    /// slice with rows in it is left.
+    pub async fn finish(&self, deleted: &[String]) -> Result<()> {
+        let mut checkpoint = self.checkpoint.lock().await;
+        if checkpoint.shards.is_empty() {
+            return Ok(());
+        }
+        for slice in deleted {
+            checkpoint.rows.remove(slice);
+        }
+        if checkpoint.rows.is_empty() {
+            return self.s3.delete_object(&key(&self.index)).await;
+        }
+        checkpoint.updated_at = Some(Utc::now());
+        self.s3.put_object(&key(&self.index), serde_json::to_vec(&*checkpoint)?.into()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn shard(id: &str) -> ShardInfo {
+        serde_json::from_value(json!({
+            "shard_id": id, "index_path": format!("indexes/docs/shards/{}/index.faiss", id),
+            "metadata_path": format!("indexes/docs/shards/{}/metadata.json", id),
+            "vector_count": 2, "metric": "cosine", "created_at": "20250115T000000",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rows_are_merged_into_ranges() {
+        let mut checkpoint = Checkpoint::default();
+        let rows = |slice: &str, rows: std::ops::Range<usize>| rows.map(|row| (slice.to_string(), row)).collect::<Vec<_>>();
+        checkpoint.add(shard("s2"), rows("b", 0..2).into_iter().chain(rows("a", 4..6)));
+        checkpoint.add(shard("s1"), rows("a", 0..4));
+        assert_eq!(checkpoint.rows["a"], [(0, 6)]);
+        assert_eq!(checkpoint.rows["b"], [(0, 2)]);
+        assert!(checkpoint.holds("a", 5) && !checkpoint.holds("a", 6) && !checkpoint.holds("c", 0));
+    }
+
+    #[tokio::test]
+    async fn test_tracker_saves_and_finishes() {
+        let s3 = S3Client::in_memory("vectors");
+        let slices = vec!["staged/docs/1.jsonl".to_string(), "staged/docs/2.jsonl".to_string()];
+        let tracker = Tracker::new(&s3, "docs", slices.clone(), Checkpoint::default());
+        tracker.finish(&slices).await.unwrap();
+        tracker.shard_stored(2, shard("s1"), &[(0, 0), (0, 1), (1, 0)]).await.unwrap();
+        let saved = Checkpoint::load(&s3, "docs").await.unwrap();
+        assert_eq!((saved.dim, saved.shards.len()), (2, 1));
+        assert!(saved.holds("staged/docs/1.jsonl", 1));
+
+        // A slice left for a later turn keeps its rows.
+        tracker.finish(&slices[..1]).await.unwrap();
+        let saved = Checkpoint::load(&s3, "docs").await.unwrap();
+        assert!(!saved.holds("staged/docs/1.jsonl", 0) && saved.holds("staged/docs/2.jsonl", 0));
+        tracker.finish(&slices[1..]).await.unwrap();
+        assert!(Checkpoint::load(&s3, "docs").await.is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
+
+/// Heartbeat objects of live API replicas.
+const NODES_PREFIX: &str = "cluster/nodes/";
+
+/// Lightweight cluster mode: API replicas register a heartbeat object in S3 and
+/// place shards on a consistent-hash ring, so each shard is searched (and cached)
+/// by one replica and the receiving replica merges the results.
+///
+/// Enabled by `VEC_CLUSTER_ADVERTISE_URL`, the base URL other replicas use to reach
+/// this one (e.g. `http://10.0.0.5:8080`).
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    pub node_id: String,
+    pub advertise_url: String,
+    pub heartbeat_secs: u64,
+    pub virtual_nodes: usize,
+    /// Longest a call to another replica may take.
+    pub request_timeout_secs: u64,
+}
+
+impl ClusterConfig {
+    pub fn from_env() -> Option<Self> {
+        let advertise_url = std::env::var("VEC_CLUSTER_ADVERTISE_URL").ok()?;
+        let env = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        Some(Self {
+            node_id: std::env::var("VEC_CLUSTER_NODE_ID").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string()),
+            advertise_url: advertise_url.trim_end_matches('/').to_string(),
+            heartbeat_secs: env("VEC_CLUSTER_HEARTBEAT_SECS", 10).max(1),
+            virtual_nodes: env("VEC_CLUSTER_VIRTUAL_NODES", 64).max(1) as usize,
+            request_timeout_secs: env("VEC_CLUSTER_REQUEST_TIMEOUT_SECS", 30).max(1),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct NodeRecord {
+    node_id: String,
+    url: String,
+    heartbeat: DateTime<Utc>,
+}
+
+/// Consistent-hash ring of replica URLs; each replica owns `virtual_nodes` points
+/// so shards rebalance gradually as replicas join or leave.
+#[derive(Default, Debug)]
+pub struct HashRing {
+    points: Vec<(u64, String)>,
+}
+
+fn hash64(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+impl HashRing {
+    pub fn new(nodes: &[String], virtual_nodes: usize) -> Self {
+        let mut points: Vec<(u64, String)> = nodes
+            .iter()
+            .flat_map(|node| (0..virtual_nodes).map(move |i| (hash64(&format!("{}#{}", node, i)), node.clone())))
+            .collect();
+        points.sort();
+        Self { points }
+    }
+
+    pub fn owner(&self, key: &str) -> Option<&str> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let hash = hash64(key);
+        let idx = self.points.partition_point(|(point, _)| *point < hash) % self.points.len();
+        Some(&self.points[idx].1)
+    }
+}
+
+pub struct Cluster {
+    config: ClusterConfig,
+    ring: RwLock<HashRing>,
+    http: reqwest::Client,
+}
+
+static CLUSTER: std::sync::OnceLock<Cluster> = std::sync::OnceLock::new();
+
+/// The cluster membership of this process, or `None` when running standalone.
+pub fn cluster() -> Option<&'static Cluster> {
+    CLUSTER.get()
+}
+
+/// Join the cluster if configured and keep the heartbeat and ring up to date.
+pub fn start(s3: S3Client) {
+    let Some(config) = ClusterConfig::from_env() else {
+        return;
+    };
+    let ring = HashRing::new(std::slice::from_ref(&config.advertise_url), config.virtual_nodes);
+    let http = match reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(config.request_timeout_secs))
+        .build()
+    {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!("Cluster mode disabled, failed to build HTTP client: {}", e);
+            return;
+        }
+    };
+    let cluster = CLUSTER.get_or_init(|| Cluster {
+        config,
+        ring: RwLock::new(ring),
+        http,
+    });
+    tracing::info!("Cluster mode enabled as {} ({})", cluster.config.node_id, cluster.config.advertise_url);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(cluster.config.heartbeat_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = cluster.refresh(&s3).await {
+                tracing::warn!("Cluster heartbeat failed: {}", e);
+            }
+        }
+    });
+}
+
+impl Cluster {
+    /// Write our heartbeat and rebuild the ring from replicas seen recently.
+    /// Expired heartbeats are deleted, since node ids default to a fresh one
+    /// per start; a replica that was only slow writes its own again.
+    async fn refresh(&self, s3: &S3Client) -> Result<()> {
+        let record = NodeRecord {
+            node_id: self.config.node_id.clone(),
+            url: self.config.advertise_url.clone(),
+            heartbeat: Utc::now(),
+        };
+        let key = format!("{}{}.json", NODES_PREFIX, self.config.node_id);
+        s3.put_object(&key, serde_json::to_vec(&record)?.into()).await?;
+
+        let expiry = chrono::Duration::seconds((self.config.heartbeat_secs * 3) as i64);
+        let mut urls = vec![self.config.advertise_url.clone()];
+        for key in s3.list_objects(NODES_PREFIX).await? {
+            let Ok(data) = s3.get_object(&key).await else { continue };
+            let Ok(node) = serde_json::from_slice::<NodeRecord>(&data) else { continue };
+            if Utc::now() - node.heartbeat > expiry {
+                if let Err(e) = s3.delete_object(&key).await {
+                    tracing::debug!("Failed to delete expired heartbeat {}: {}", key, e);
+                }
+                continue;
+            }
+            if !urls.contains(&node.url) {
+                urls.push(node.url);
+            }
+        }
+        urls.sort();
+
+        let ring = HashRing::new(&urls, self.config.virtual_nodes);
+        *self.ring.write().unwrap() = ring;
+        crate::metrics::get_metrics_collector().track_metric("cluster.nodes", urls.len() as f64);
+        Ok(())
+    }
+
+    /// URL of the replica that owns `shard_id`, or `None` if that is this node.
+    pub fn remote_owner(&self, shard_id: &str) -> Option<String> {
+        let ring = self.ring.read().unwrap();
+        ring.owner(shard_id)
+            .filter(|owner| *owner != self.config.advertise_url)
+            .map(str::to_string)
+    }
+
+    pub async fn post_json(&self, base_url: &str, path: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        let mut request = self.http.post(format!("{}{}", base_url, path)).json(body);
+        if let Some(request_id) = crate::request_id::current() {
+            request = request.header(crate::request_id::REQUEST_ID_HEADER, request_id);
+        }
+        if let Some(secret) = crate::api::cluster_secret() {
+            request = request.header(crate::api::CLUSTER_SECRET_HEADER, secret);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach replica {}", base_url))?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Replica {} returned {}", base_url, response.status()));
+        }
+        response.json().await.context("Failed to parse replica response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_moves_few_shards_when_node_joins() {
+        let nodes: Vec<String> = (0..3).map(|i| format!("http://node-{}", i)).collect();
+        let ring = HashRing::new(&nodes, 64);
+        let mut grown = nodes.clone();
+        grown.push("http://node-3".to_string());
+        let grown_ring = HashRing::new(&grown, 64);
+
+        let shards: Vec<String> = (0..1000).map(|i| format!("shard-{}", i)).collect();
+        let moved = shards
+            .iter()
+            .filter(|s| ring.owner(s) != grown_ring.owner(s))
+            .count();
+        // Roughly a quarter of shards should move to the new node, never more than half.
+        assert!(moved > 100 && moved < 500, "moved {}", moved);
+        assert!(shards
+            .iter()
+            .filter(|s| ring.owner(s) != grown_ring.owner(s))
+            .all(|s| grown_ring.owner(s) == Some("http://node-3")));
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// Suffix appended to staged slice keys whose body is zstd compressed.
+pub const ZSTD_EXTENSION: &str = ".zst";
+
+/// Encoding of a stored artifact body, recorded in the manifest so readers
+/// never have to guess.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    #[default]
+    Identity,
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Encoding implied by an object key (`.zst` suffix for staged slices).
+    pub fn from_key(key: &str) -> Self {
+        if key.ends_with(ZSTD_EXTENSION) {
+            ContentEncoding::Zstd
+        } else {
+            ContentEncoding::Identity
+        }
+    }
+
+    pub fn decode(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Identity => Ok(data.to_vec()),
+            ContentEncoding::Zstd => zstd::decode_all(data).context("Failed to decompress zstd data"),
+        }
+    }
+
+    /// Wrap a reader so compressed files can be consumed line by line without
+    /// materialising the decompressed body.
+    pub fn reader<'a, R: Read + 'a>(self, inner: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            ContentEncoding::Identity => Box::new(inner),
+            ContentEncoding::Zstd => Box::new(zstd::stream::read::Decoder::new(inner)?),
+        })
+    }
+}
+
+/// Write-side compression settings, configured via `VEC_COMPRESSION` (`zstd` or `none`)
+/// and `VEC_COMPRESSION_LEVEL`.
+#[derive(Clone, Copy, Debug)]
+pub struct CompressionConfig {
+    pub encoding: ContentEncoding,
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            encoding: ContentEncoding::Identity,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn from_env() -> Self {
+        let encoding = match std::env::var("VEC_COMPRESSION").as_deref() {
+            Ok("zstd") => ContentEncoding::Zstd,
+            _ => ContentEncoding::Identity,
+        };
+        let level = std::env::var("VEC_COMPRESSION_LEVEL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+        Self { encoding, level }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self.encoding {
+            ContentEncoding::Identity => Ok(data.to_vec()),
+            ContentEncoding::Zstd => {
+                zstd::encode_all(data, self.level).context("Failed to compress data with zstd")
+            }
+        }
+    }
+
+    /// Slice key suffix matching the configured encoding.
+    pub fn extension(&self) -> &'static str {
+        match self.encoding {
+            ContentEncoding::Identity => "",
+            ContentEncoding::Zstd => ZSTD_EXTENSION,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zstd_round_trip() {
+        let config = CompressionConfig {
+            encoding: ContentEncoding::Zstd,
+            level: 3,
+        };
+        let data = b"{\"id\":\"a\",\"embedding\":[0.1,0.2]}\n".repeat(100);
+        let compressed = config.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(ContentEncoding::Zstd.decode(&compressed).unwrap(), data);
+        assert_eq!(ContentEncoding::from_key(&format!("slice-1.jsonl{}", config.extension())), ContentEncoding::Zstd);
+    }
+
+    #[test]
+    fn test_manifest_default_is_identity() {
+        let encoding: ContentEncoding = serde_json::from_str("\"zstd\"").unwrap();
+        assert_eq!(encoding, ContentEncoding::Zstd);
+        assert_eq!(ContentEncoding::default(), ContentEncoding::Identity);
+    }
+}
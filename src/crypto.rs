@@ -0,0 +1,259 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::collections::HashSet;
+
+/// Prefix of every envelope-encrypted object, so reads can detect sealed data
+/// regardless of the current configuration.
+const MAGIC: &[u8; 4] = b"GVE1";
+const NONCE_LEN: usize = 12;
+
+const PROVIDER_STATIC: u8 = 0;
+#[cfg(feature = "kms")]
+const PROVIDER_KMS: u8 = 1;
+
+/// Source of the master key used to wrap per-object data keys.
+pub enum KeyProvider {
+    /// 256-bit master key supplied via `VEC_ENCRYPTION_MASTER_KEY` (base64).
+    Static(Box<Aes256Gcm>),
+    /// Data keys generated and unwrapped by AWS KMS.
+    #[cfg(feature = "kms")]
+    Kms {
+        client: aws_sdk_kms::Client,
+        key_id: String,
+    },
+}
+
+enum BucketScope {
+    All,
+    Only(HashSet<String>),
+}
+
+/// Envelope encryption (AES-256-GCM) for vectors and metadata at rest.
+///
+/// Each object gets a fresh data key; the data key is wrapped by the master key and
+/// stored in the object header: `MAGIC | provider | wrapped_len (u16 BE) | wrapped | nonce | ciphertext`.
+pub struct Envelope {
+    provider: KeyProvider,
+    buckets: BucketScope,
+}
+
+impl Envelope {
+    /// Build from `VEC_ENCRYPTION_BUCKETS` (comma separated, `*` for all buckets) plus
+    /// either `VEC_ENCRYPTION_KMS_KEY_ID` or `VEC_ENCRYPTION_MASTER_KEY`.
+    /// Returns `None` when encryption is not configured.
+    pub async fn from_env() -> Result<Option<Self>> {
+        let buckets = match std::env::var("VEC_ENCRYPTION_BUCKETS") {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return Ok(None),
+        };
+        let buckets = if buckets.trim() == "*" {
+            BucketScope::All
+        } else {
+            BucketScope::Only(
+                buckets
+                    .split(',')
+                    .map(|b| b.trim().to_string())
+                    .filter(|b| !b.is_empty())
+                    .collect(),
+            )
+        };
+
+        #[cfg(feature = "kms")]
+        if let Ok(key_id) = std::env::var("VEC_ENCRYPTION_KMS_KEY_ID") {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            tracing::info!("Client-side encryption enabled with KMS key {}", key_id);
+            return Ok(Some(Self {
+                provider: KeyProvider::Kms {
+                    client: aws_sdk_kms::Client::new(&config),
+                    key_id,
+                },
+                buckets,
+            }));
+        }
+
+        let encoded = std::env::var("VEC_ENCRYPTION_MASTER_KEY")
+            .context("VEC_ENCRYPTION_BUCKETS is set but no VEC_ENCRYPTION_MASTER_KEY was provided")?;
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .context("VEC_ENCRYPTION_MASTER_KEY is not valid base64")?;
+        if key.len() != 32 {
+            return Err(anyhow::anyhow!(
+                "VEC_ENCRYPTION_MASTER_KEY must decode to 32 bytes, got {}",
+                key.len()
+            ));
+        }
+        tracing::info!("Client-side encryption enabled with static master key");
+        Ok(Some(Self {
+            provider: KeyProvider::Static(Box::new(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))),
+            buckets,
+        }))
+    }
+
+    #[cfg(test)]
+    fn with_static_key(master_key: [u8; 32]) -> Self {
+        Self {
+            provider: KeyProvider::Static(Box::new(Aes256Gcm::new(&master_key.into()))),
+            buckets: BucketScope::All,
+        }
+    }
+
+    pub fn applies_to(&self, bucket: &str) -> bool {
+        match &self.buckets {
+            BucketScope::All => true,
+            BucketScope::Only(buckets) => buckets.contains(bucket),
+        }
+    }
+
+    pub fn is_sealed(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    pub async fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let (provider_tag, data_key, wrapped) = self.new_data_key().await?;
+        let cipher = Aes256Gcm::new(&data_key);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt object"))?;
+
+        let wrapped_len = u16::try_from(wrapped.len()).context("Wrapped data key too large")?;
+        let mut out = Vec::with_capacity(MAGIC.len() + 3 + wrapped.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.push(provider_tag);
+        out.extend_from_slice(&wrapped_len.to_be_bytes());
+        out.extend_from_slice(&wrapped);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    pub async fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let header_len = MAGIC.len() + 3;
+        if !Self::is_sealed(sealed) || sealed.len() < header_len {
+            return Err(anyhow::anyhow!("Object is not envelope encrypted"));
+        }
+        let provider_tag = sealed[MAGIC.len()];
+        let wrapped_len = u16::from_be_bytes([sealed[MAGIC.len() + 1], sealed[MAGIC.len() + 2]]) as usize;
+        let body = &sealed[header_len..];
+        if body.len() < wrapped_len + NONCE_LEN {
+            return Err(anyhow::anyhow!("Encrypted object header is truncated"));
+        }
+        let (wrapped, rest) = body.split_at(wrapped_len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let data_key = self.unwrap_data_key(provider_tag, wrapped).await?;
+        Aes256Gcm::new(&data_key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt object: wrong key or corrupted data"))
+    }
+
+    async fn new_data_key(&self) -> Result<(u8, Key<Aes256Gcm>, Vec<u8>)> {
+        match &self.provider {
+            KeyProvider::Static(master) => {
+                let data_key = Aes256Gcm::generate_key(OsRng);
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                let mut wrapped = nonce.to_vec();
+                wrapped.extend(
+                    master
+                        .encrypt(&nonce, data_key.as_slice())
+                        .map_err(|_| anyhow::anyhow!("Failed to wrap data key"))?,
+                );
+                Ok((PROVIDER_STATIC, data_key, wrapped))
+            }
+            #[cfg(feature = "kms")]
+            KeyProvider::Kms { client, key_id } => {
+                let output = client
+                    .generate_data_key()
+                    .key_id(key_id)
+                    .key_spec(aws_sdk_kms::types::DataKeySpec::Aes256)
+                    .send()
+                    .await
+                    .context("KMS GenerateDataKey failed")?;
+                let plaintext = output.plaintext().context("KMS returned no plaintext data key")?;
+                let wrapped = output.ciphertext_blob().context("KMS returned no wrapped data key")?;
+                Ok((
+                    PROVIDER_KMS,
+                    *Key::<Aes256Gcm>::from_slice(plaintext.as_ref()),
+                    wrapped.as_ref().to_vec(),
+                ))
+            }
+        }
+    }
+
+    async fn unwrap_data_key(&self, provider_tag: u8, wrapped: &[u8]) -> Result<Key<Aes256Gcm>> {
+        match (&self.provider, provider_tag) {
+            (KeyProvider::Static(master), PROVIDER_STATIC) => {
+                if wrapped.len() < NONCE_LEN {
+                    return Err(anyhow::anyhow!("Wrapped data key is truncated"));
+                }
+                let (nonce, key) = wrapped.split_at(NONCE_LEN);
+                let data_key = master
+                    .decrypt(Nonce::from_slice(nonce), key)
+                    .map_err(|_| anyhow::anyhow!("Failed to unwrap data key: wrong master key"))?;
+                Ok(*Key::<Aes256Gcm>::from_slice(&data_key))
+            }
+            #[cfg(feature = "kms")]
+            (KeyProvider::Kms { client, .. }, PROVIDER_KMS) => {
+                let output = client
+                    .decrypt()
+                    .ciphertext_blob(aws_sdk_kms::primitives::Blob::new(wrapped))
+                    .send()
+                    .await
+                    .context("KMS Decrypt failed")?;
+                let plaintext = output.plaintext().context("KMS returned no plaintext data key")?;
+                Ok(*Key::<Aes256Gcm>::from_slice(plaintext.as_ref()))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Object was encrypted with a different key provider (tag {})",
+                provider_tag
+            )),
+        }
+    }
+}
+
+/// Object classes that carry embeddings or user metadata and are therefore encrypted:
+/// WAL segments, staged slices, per-vector JSON objects and their versions, shard metadata, id maps
+/// and shard vectors.
+pub fn is_sensitive_key(key: &str) -> bool {
+    key.starts_with("wal/")
+        || key.starts_with("staged/")
+        || key.starts_with("query-snapshots/")
+        || key.starts_with("changelog/")
+        || key.contains("/vectors/")
+        || key.contains("/versions/")
+        || key.ends_with("/metadata.json")
+        || key.ends_with("/id_map.json")
+        || key.ends_with("/vectors.json")
+        || key.ends_with("/vectors.f32")
+        || key.ends_with("/text.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_seal_open_round_trip() {
+        let envelope = Envelope::with_static_key([7u8; 32]);
+        let sealed = envelope.seal(b"{\"id\":\"a\"}").await.unwrap();
+        assert!(Envelope::is_sealed(&sealed));
+        assert_eq!(envelope.open(&sealed).await.unwrap(), b"{\"id\":\"a\"}");
+
+        let other = Envelope::with_static_key([8u8; 32]);
+        assert!(other.open(&sealed).await.is_err());
+    }
+
+    #[test]
+    fn test_sensitive_key_classes() {
+        assert!(is_sensitive_key("staged/idx/slice-1.jsonl"));
+        assert!(is_sensitive_key("indexes/idx/shards/s1/id_map.json"));
+        assert!(is_sensitive_key("idx/vectors/key.json"));
+        assert!(is_sensitive_key("indexes/idx/shards/s1/vectors.f32"));
+        assert!(is_sensitive_key("indexes/idx/shards/s1/text.json"));
+        assert!(is_sensitive_key("query-snapshots/1736942400-0b7e.json"));
+        assert!(!is_sensitive_key("indexes/idx/manifest.json"));
+        assert!(!is_sensitive_key("indexes/idx/shards/s1/index.faiss"));
+    }
+}
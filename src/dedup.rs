@@ -0,0 +1,116 @@
+use crate::distance;
+use crate::minio::S3Client;
+use crate::model::{CreateIndex, QueryRequest};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Neighbors rescored per incoming vector; more than one so that euclidean
+/// ranking and quantization error don't hide the most similar by angle.
+const NEIGHBORS: usize = 5;
+
+/// What PutVectors does with a vector too similar to one already in the index.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// Leave it out and report it.
+    Reject,
+    /// Write it anyway and report it.
+    Flag,
+}
+
+/// Near-duplicate suppression of an index, checked on every PutVectors.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Dedup {
+    pub mode: DedupMode,
+    /// Cosine similarity above which a vector counts as a duplicate, whatever
+    /// the index's distance metric.
+    pub threshold: f32,
+}
+
+/// Dedup settings of `index`, from its config.
+pub async fn load(s3: &S3Client, index: &str) -> Option<Dedup> {
+    let data = s3.get_object(&format!("indexes/{}/config.json", index)).await.ok()?;
+    CreateIndex::from_slice(&data).ok()?.dedup
+}
+
+/// A vector found to duplicate an existing one.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Duplicate {
+    pub key: String,
+    pub duplicate_of: String,
+    pub similarity: f32,
+}
+
+impl Dedup {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-1.0..=1.0).contains(&self.threshold) {
+            return Err(format!("dedup.threshold must be a cosine similarity between -1 and 1, got {}", self.threshold));
+        }
+        Ok(())
+    }
+
+    /// The most similar of `candidates` to `embedding`, if it exceeds the threshold.
+    pub fn closest<'a>(&self, embedding: &[f32], candidates: impl IntoIterator<Item = (&'a str, &'a [f32])>) -> Option<(String, f32)> {
+        candidates
+            .into_iter()
+            .map(|(key, other)| (key, distance::cosine(embedding, other)))
+            .filter(|(_, similarity)| *similarity > self.threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(key, similarity)| (key.to_string(), similarity))
+    }
+
+    /// Indexed vector of `index`, other than `key` itself, that `embedding`
+    /// duplicates, if any. The nearest neighbors are rescored exactly against
+    /// their raw vectors, so this needs an index that stores them; vectors
+    /// still staged for indexing are not seen.
+    pub async fn find_in_index(&self, s3: &S3Client, index: &str, key: &str, embedding: &[f32]) -> Result<Option<(String, f32)>> {
+        let request = QueryRequest {
+            index: index.to_string(),
+            embedding: embedding.to_vec(),
+            topk: NEIGHBORS,
+            nprobe: None,
+            explain: false,
+            return_data: true,
+            exact_rerank: true,
+            filter: None,
+            text: None,
+            vector_weight: 0.5,
+            fusion: Default::default(),
+            timeout_ms: None,
+            partial_on_timeout: false,
+            allow_partial_results: false,
+        };
+        let response = crate::query::search(s3.clone(), request).await?;
+        let hits: Vec<(String, Vec<f32>)> = response
+            .get("results")
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|hit| {
+                let key = hit.get("id")?.as_str().filter(|k| *k != key)?.to_string();
+                let data = hit.get("data")?.as_array()?.iter().map(|v| v.as_f64().map(|f| f as f32)).collect::<Option<_>>()?;
+                Some((key, data))
+            })
+            .collect();
+        Ok(self.closest(embedding, hits.iter().map(|(key, data)| (key.as_str(), data.as_slice()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_above_threshold() {
+        let dedup = Dedup { mode: DedupMode::Reject, threshold: 0.95 };
+        let a = [1.0, 0.0];
+        let b = [0.99, 0.05];
+        let c = [0.0, 1.0];
+        let found = dedup.closest(&[1.0, 0.01], [("a", &a[..]), ("b", &b[..]), ("c", &c[..])]).unwrap();
+        assert_eq!(found.0, "a");
+        assert!(dedup.closest(&[0.7, 0.7], [("a", &a[..]), ("c", &c[..])]).is_none());
+        assert!(Dedup { mode: DedupMode::Flag, threshold: 1.5 }.validate().is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// One DeleteVectors call, stored at `indexes/<index>/deletions/<ts>-<uuid>.json`.
+/// Shards are immutable, so deleted ids are hidden at query time and dropped by
+/// the indexer when it builds shards from slices staged before the deletion.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionBatch {
+    pub ids: Vec<String>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+fn deletions_prefix(index: &str) -> String {
+    format!("indexes/{}/deletions/", index)
+}
+
+/// Latest deletion time of every deleted id in an index. [`compact`] stores
+/// one of these at `indexes/<index>/deletions/folded-<ts>-<uuid>.json` in
+/// place of the batches it folds.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Deletions {
+    deleted_at: HashMap<String, DateTime<Utc>>,
+}
+
+impl Deletions {
+    fn add(&mut self, batch: DeletionBatch) {
+        for id in batch.ids {
+            let entry = self.deleted_at.entry(id).or_insert(batch.deleted_at);
+            *entry = (*entry).max(batch.deleted_at);
+        }
+    }
+
+    fn merge(&mut self, other: Deletions) {
+        for (id, deleted_at) in other.deleted_at {
+            let entry = self.deleted_at.entry(id).or_insert(deleted_at);
+            *entry = (*entry).max(deleted_at);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deleted_at.is_empty()
+    }
+
+    /// Whether `id` was ever deleted, whatever was written since.
+    pub fn was_deleted(&self, id: &str) -> bool {
+        self.deleted_at.contains_key(id)
+    }
+
+    /// Whether a copy of `id` written at `written_at` was deleted afterwards;
+    /// vectors put again after their deletion stay visible.
+    pub fn is_deleted(&self, id: &str, written_at: DateTime<Utc>) -> bool {
+        self.deleted_at.get(id).is_some_and(|deleted_at| written_at < *deleted_at)
+    }
+}
+
+/// Shard `created_at` values are formatted `%Y%m%dT%H%M%S`; unparseable ones are
+/// treated as older than any deletion.
+pub fn parse_shard_time(created_at: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(created_at, "%Y%m%dT%H%M%S")
+        .map(|t| t.and_utc())
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+fn object_key(index: &str, name: &str, at: DateTime<Utc>) -> String {
+    format!("{}{}{}-{}.json", deletions_prefix(index), name, at.format("%Y%m%dT%H%M%S%3f"), uuid::Uuid::new_v4())
+}
+
+pub async fn record(s3: &S3Client, index: &str, ids: &[String]) -> Result<DeletionBatch> {
+    let batch = DeletionBatch { ids: ids.to_vec(), deleted_at: Utc::now() };
+    let key = object_key(index, "", batch.deleted_at);
+    s3.put_object(&key, serde_json::to_vec(&batch)?.into()).await?;
+    Ok(batch)
+}
+
+/// Deletion objects are never rewritten, so the deletions of an index are
+/// kept with the keys they were read from and only new keys are read.
+struct Loaded {
+    keys: HashSet<String>,
+    deletions: Arc<Deletions>,
+}
+
+fn loaded() -> &'static Mutex<HashMap<String, Loaded>> {
+    static LOADED: OnceLock<Mutex<HashMap<String, Loaded>>> = OnceLock::new();
+    LOADED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Times `load` lists again when an object it listed was removed by a
+/// concurrent [`compact`] before it was read.
+const LOAD_ATTEMPTS: usize = 3;
+
+async fn read(s3: &S3Client, key: &str) -> Result<Deletions> {
+    let data = s3.get_object(key).await?;
+    let name = key.rsplit('/').next().unwrap_or(key);
+    let mut deletions = Deletions::default();
+    if name.starts_with("folded-") {
+        deletions.merge(serde_json::from_slice(&data).with_context(|| format!("Failed to parse {}", key))?);
+    } else {
+        deletions.add(serde_json::from_slice(&data).with_context(|| format!("Failed to parse {}", key))?);
+    }
+    Ok(deletions)
+}
+
+async fn read_all<'a>(s3: &S3Client, keys: impl IntoIterator<Item = &'a String>) -> Result<Deletions> {
+    let parts = futures::future::try_join_all(keys.into_iter().map(|key| read(s3, key))).await?;
+    let mut deletions = Deletions::default();
+    for part in parts {
+        deletions.merge(part);
+    }
+    Ok(deletions)
+}
+
+pub async fn load(s3: &S3Client, index: &str) -> Result<Arc<Deletions>> {
+    let cache_key = format!("{}/{}", s3.bucket(), index);
+    let mut attempt = 1;
+    loop {
+        let keys: HashSet<String> = s3.list_objects(&deletions_prefix(index)).await?.into_iter().collect();
+        // Start from what was read before when none of it was compacted away.
+        let (mut deletions, known) = match loaded().lock().unwrap().get(&cache_key) {
+            Some(loaded) if loaded.keys == keys => return Ok(loaded.deletions.clone()),
+            Some(loaded) if loaded.keys.is_subset(&keys) => ((*loaded.deletions).clone(), loaded.keys.clone()),
+            _ => (Deletions::default(), HashSet::new()),
+        };
+        match read_all(s3, keys.difference(&known)).await {
+            Ok(read) => deletions.merge(read),
+            Err(e) if attempt < LOAD_ATTEMPTS => {
+                tracing::debug!(index, "Listing deletions again after a failed read: {}", e);
+                attempt += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+        let deletions = Arc::new(deletions);
+        loaded().lock().unwrap().insert(cache_key, Loaded { keys, deletions: deletions.clone() });
+        return Ok(deletions);
+    }
+}
+
+/// Fold every deletion object of `index` into one, so loading its deletions
+/// reads one object however many DeleteVectors calls came before. Returns the
+/// number of objects folded.
+pub async fn compact(s3: &S3Client, index: &str) -> Result<usize> {
+    let keys = s3.list_objects(&deletions_prefix(index)).await?;
+    if keys.len() < 2 {
+        return Ok(0);
+    }
+    let deletions = read_all(s3, &keys).await?;
+    let folded = object_key(index, "folded-", Utc::now());
+    // The folded object is stored before the ones it replaces are removed,
+    // so every deletion is listed in one or the other throughout.
+    s3.put_object(&folded, serde_json::to_vec(&deletions)?.into()).await?;
+    for key in &keys {
+        s3.delete_object(key).await?;
+    }
+    Ok(keys.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_reput_after_delete_is_visible() {
+        let deleted_at = parse_shard_time("20250101T120000");
+        let mut deletions = Deletions::default();
+        deletions.add(DeletionBatch { ids: vec!["a".to_string()], deleted_at });
+
+        assert!(deletions.is_deleted("a", deleted_at - Duration::seconds(1)));
+        assert!(!deletions.is_deleted("a", deleted_at + Duration::seconds(1)));
+        assert!(!deletions.is_deleted("b", deleted_at - Duration::seconds(1)));
+        assert!(deletions.is_deleted("a", parse_shard_time("not-a-time")));
+    }
+
+    #[tokio::test]
+    async fn test_compaction_keeps_every_deletion() {
+        let s3 = S3Client::in_memory("vectors");
+        record(&s3, "docs", &["a".to_string(), "b".to_string()]).await.unwrap();
+        record(&s3, "docs", &["a".to_string()]).await.unwrap();
+        record(&s3, "docs", &["c".to_string()]).await.unwrap();
+        let before = load(&s3, "docs").await.unwrap();
+
+        assert_eq!(compact(&s3, "docs").await.unwrap(), 3);
+        assert_eq!(s3.list_objects(&deletions_prefix("docs")).await.unwrap().len(), 1);
+        assert_eq!(compact(&s3, "docs").await.unwrap(), 0);
+        let after = load(&s3, "docs").await.unwrap();
+        assert_eq!(after.deleted_at, before.deleted_at);
+
+        record(&s3, "docs", &["d".to_string()]).await.unwrap();
+        let loaded = load(&s3, "docs").await.unwrap();
+        assert!(["a", "b", "c", "d"].iter().all(|id| loaded.deleted_at.contains_key(*id)));
+    }
+}
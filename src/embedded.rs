@@ -0,0 +1,156 @@
+use crate::api::S3CreateIndexRequest;
+use crate::changelog::Mutation;
+use crate::dedup::{DedupMode, Duplicate};
+use crate::ingest::{record_document, record_object_key};
+use crate::minio::S3Client;
+use crate::model::{CreateIndex, QueryRequest, VectorRecord};
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Vector bucket the API files records under when a request names none.
+pub(crate) const DEFAULT_BUCKET: &str = "default-bucket";
+
+/// The database in-process, for services that embed the crate instead of
+/// running the HTTP server. Indexes are created, written and searched through
+/// the same config validation, indexer and query code as the API, so either
+/// can open the other's indexes. Puts build their shards before returning,
+/// so each call should carry a batch. Conditional writes, quotas and
+/// partitioned indexes stay with the API.
+#[derive(Clone)]
+pub struct VectorDb {
+    s3: S3Client,
+}
+
+/// What a [`VectorDb::put`] wrote.
+#[derive(Debug, Default)]
+pub struct PutOutcome {
+    pub written: usize,
+    /// Vectors found to duplicate another on an index with dedup; written
+    /// anyway unless its mode rejects them.
+    pub duplicates: Vec<Duplicate>,
+}
+
+impl VectorDb {
+    /// A handle on the indexes of the bucket `s3` points at.
+    pub fn open(s3: S3Client) -> Self {
+        VectorDb { s3 }
+    }
+
+    /// Create an index as CreateIndex does, with the same validation; creating
+    /// one again with the same parameters returns the existing config.
+    pub async fn create_index(&self, request: &S3CreateIndexRequest) -> Result<CreateIndex> {
+        let config = crate::api::indices::index_config(request).map_err(anyhow::Error::msg)?;
+        let key = format!("indexes/{}/config.json", config.name);
+        if let Ok(data) = self.s3.get_object(&key).await {
+            let existing = CreateIndex::from_slice(&data).context("Failed to parse index config")?;
+            if !crate::api::indices::same_index_parameters(&existing, &config) {
+                return Err(anyhow::anyhow!("Index {} already exists with different parameters", config.name));
+            }
+            return Ok(existing);
+        }
+        self.s3.put_object(&key, serde_json::to_vec(&config)?.into()).await?;
+        Ok(config)
+    }
+
+    /// Write `vectors` to `index` and build them into shards, truncated,
+    /// deduplicated and versioned as its config says, like PutVectors.
+    pub async fn put(&self, index: &str, vectors: Vec<VectorRecord>) -> Result<PutOutcome> {
+        let (index, config) = self.config(index).await?;
+        let bucket = config.vector_bucket_name.as_deref().unwrap_or(DEFAULT_BUCKET);
+        let mut outcome = PutOutcome::default();
+        let mut accepted: Vec<VectorRecord> = Vec::with_capacity(vectors.len());
+        for mut record in vectors {
+            if let Some(truncation) = config.truncation {
+                record.embedding = truncation
+                    .apply(config.dim as usize, record.embedding)
+                    .map_err(|message| anyhow::anyhow!("{}: {}", record.id, message))?;
+            }
+            if let Some(dedup) = config.dedup {
+                let earlier = accepted.iter().filter(|r| r.id != record.id).map(|r| (r.id.as_str(), r.embedding.as_slice()));
+                let found = match dedup.closest(&record.embedding, earlier) {
+                    Some(found) => Some(found),
+                    None => dedup.find_in_index(&self.s3, &index, &record.id, &record.embedding).await.unwrap_or_else(|e| {
+                        tracing::warn!(index = %index, error = %e, "Duplicate check failed, accepting vector");
+                        None
+                    }),
+                };
+                if let Some((duplicate_of, similarity)) = found {
+                    outcome.duplicates.push(Duplicate { key: record.id.clone(), duplicate_of, similarity });
+                    if dedup.mode == DedupMode::Reject {
+                        continue;
+                    }
+                }
+            }
+            accepted.push(record);
+        }
+        if accepted.is_empty() {
+            return Ok(outcome);
+        }
+
+        let records: Vec<Value> = accepted.iter().map(record_document).collect();
+        outcome.written = accepted.len();
+        crate::metrics::get_metrics_collector().record_ingest(
+            &index,
+            accepted.len() as u64,
+            accepted.iter().map(|v| v.embedding.len() as u64 * 4).sum(),
+        );
+        crate::indexer::build_shards(&self.s3, &index, accepted).await?;
+        for record in &records {
+            let Some(id) = record.get("key").and_then(Value::as_str) else { continue };
+            self.s3
+                .put_bucket_object(bucket, &record_object_key(&index, id), serde_json::to_vec(record)?.into())
+                .await?;
+        }
+        if let Some(versioning) = config.versioning {
+            if let Err(e) = versioning.record(&self.s3, bucket, &index, &records).await {
+                tracing::warn!(index = %index, error = %e, "Failed to record vector versions");
+            }
+        }
+        if let Err(e) = crate::changelog::append(&self.s3, bucket, &index, Mutation::Put { vectors: records }).await {
+            tracing::warn!(index = %index, error = %e, "Failed to log put");
+        }
+        Ok(outcome)
+    }
+
+    /// Search `index` (for `request.index`); the query is truncated like
+    /// stored vectors on Matryoshka indexes. Returns the query service's
+    /// `{"results": [{"id", "score", "metadata", "data"?}], "took_ms"}`.
+    pub async fn query(&self, index: &str, mut request: QueryRequest) -> Result<Value> {
+        let (index, config) = self.config(index).await?;
+        if let Some(truncation) = config.truncation {
+            request.embedding = truncation.apply(config.dim as usize, request.embedding).map_err(anyhow::Error::msg)?;
+        }
+        request.index = index;
+        crate::query::search(self.s3.clone(), request).await
+    }
+
+    /// Delete `keys` from `index`. Queries stop returning them at once; their
+    /// record objects are removed after.
+    pub async fn delete(&self, index: &str, keys: &[String]) -> Result<()> {
+        let (index, config) = self.config(index).await?;
+        let bucket = config.vector_bucket_name.as_deref().unwrap_or(DEFAULT_BUCKET);
+        crate::deletions::record(&self.s3, &index, keys).await?;
+        if let Err(e) = crate::changelog::append(&self.s3, bucket, &index, Mutation::Delete { keys: keys.to_vec() }).await {
+            tracing::warn!(index = %index, error = %e, "Failed to log delete");
+        }
+        for key in keys {
+            self.s3.delete_bucket_object(bucket, &record_object_key(&index, key)).await?;
+        }
+        Ok(())
+    }
+
+    /// The index `name` (or the alias) refers to, and its config.
+    async fn config(&self, name: &str) -> Result<(String, CreateIndex)> {
+        let index = crate::api::aliases::resolve(&self.s3, name).await;
+        let data = self
+            .s3
+            .get_object(&format!("indexes/{}/config.json", index))
+            .await
+            .with_context(|| format!("Index not found: {}", index))?;
+        let config = CreateIndex::from_slice(&data).context("Failed to parse index config")?;
+        if config.partitioning.is_some() {
+            return Err(anyhow::anyhow!("Index {} is partitioned; write and query it through the API", index));
+        }
+        Ok((index, config))
+    }
+}
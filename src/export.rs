@@ -0,0 +1,190 @@
+use crate::jobs::CancelToken;
+use crate::minio::S3Client;
+use crate::tempfiles::TempFile;
+use anyhow::{Context, Result};
+use arrow::array::{Int64Array, ListArray, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Float32Type, Schema};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::sync::Arc;
+
+/// Rows per exported parquet file.
+const ROWS_PER_PART: usize = 50_000;
+
+/// One vector as exported: metadata is kept as a JSON string because its keys
+/// vary between vectors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportRow {
+    pub id: String,
+    pub embedding: Vec<f32>,
+    pub metadata: String,
+    pub version: Option<i64>,
+}
+
+impl ExportRow {
+    /// Row for a vector-of-record document (see [`crate::ingest::record_document`]).
+    pub fn from_record(doc: &Value) -> Option<Self> {
+        Some(ExportRow {
+            id: doc.get("key")?.as_str()?.to_string(),
+            embedding: doc
+                .get("data")?
+                .get("float32")?
+                .as_array()?
+                .iter()
+                .map(|v| v.as_f64().map(|f| f as f32))
+                .collect::<Option<_>>()?,
+            metadata: doc.get("metadata").cloned().unwrap_or_else(|| serde_json::json!({})).to_string(),
+            version: doc.get("version").and_then(|v| v.as_i64()),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub index_name: String,
+    /// `exports/<index>/<ts>/`, holding `part-NNNNN.parquet` files and a
+    /// `_SUCCESS` marker written last.
+    pub prefix: String,
+    pub vectors: usize,
+    pub files: Vec<String>,
+}
+
+pub fn export_prefix(index: &str, at: DateTime<Utc>) -> String {
+    format!("exports/{}/{}/", index, at.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Arrow schema of exported rows, shared with the Flight endpoint.
+pub fn schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new(
+            "embedding",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            false,
+        ),
+        Field::new("metadata", DataType::Utf8, false),
+        Field::new("version", DataType::Int64, true),
+    ]))
+}
+
+pub fn record_batch(rows: &[ExportRow]) -> Result<RecordBatch> {
+    Ok(RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.id.as_str()))),
+            Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(
+                rows.iter().map(|r| Some(r.embedding.iter().map(|&f| Some(f)).collect::<Vec<_>>())),
+            )),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.metadata.as_str()))),
+            Arc::new(Int64Array::from(rows.iter().map(|r| r.version).collect::<Vec<_>>())),
+        ],
+    )?)
+}
+
+/// Write `rows` as one parquet file at `path`.
+pub fn write_parquet(rows: &[ExportRow], path: &str) -> Result<()> {
+    let batch = record_batch(rows)?;
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(WriterProperties::builder().build()))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+async fn upload_part(s3: &S3Client, prefix: &str, part: usize, rows: &[ExportRow]) -> Result<String> {
+    let key = format!("{}part-{:05}.parquet", prefix, part);
+    let local = TempFile::new("export", ".parquet");
+    write_parquet(rows, local.path())?;
+    let uploaded = s3.upload_file(&key, local.path()).await;
+    drop(local);
+    uploaded.with_context(|| format!("Failed to upload {}", key))?;
+    Ok(key)
+}
+
+/// Export every vector of record of `index` in `bucket` under a new
+/// `exports/<index>/<ts>/` prefix. Cancelling leaves the files written so far
+/// but no `_SUCCESS` marker.
+pub async fn export_index(s3: &S3Client, bucket: &str, index: &str, cancel: &CancelToken) -> Result<ExportReport> {
+    let prefix = export_prefix(index, Utc::now());
+    let records_prefix = format!("{}/vectors/", index);
+    let mut keys = s3.list_bucket_objects(bucket, &records_prefix).await?;
+    keys.sort();
+
+    let mut report = ExportReport { index_name: index.to_string(), prefix: prefix.clone(), vectors: 0, files: Vec::new() };
+    let mut rows = Vec::with_capacity(ROWS_PER_PART.min(keys.len()));
+    for key in &keys {
+        cancel.check()?;
+        // Vectors deleted while the export runs are skipped.
+        let Ok(data) = s3.get_bucket_object(bucket, key).await else { continue };
+        match serde_json::from_slice::<Value>(&data).ok().as_ref().and_then(ExportRow::from_record) {
+            Some(row) => rows.push(row),
+            None => tracing::warn!(key = %key, "Skipping unreadable vector record in export"),
+        }
+        if rows.len() == ROWS_PER_PART {
+            report.files.push(upload_part(s3, &prefix, report.files.len(), &rows).await?);
+            report.vectors += rows.len();
+            rows.clear();
+            crate::jobs::report_progress(json!({
+                "phase": "exporting",
+                "vectorsExported": report.vectors,
+                "vectors": keys.len(),
+                "files": report.files.len(),
+            }));
+        }
+    }
+    if !rows.is_empty() || report.files.is_empty() {
+        report.files.push(upload_part(s3, &prefix, report.files.len(), &rows).await?);
+        report.vectors += rows.len();
+    }
+    s3.put_object(&format!("{}_SUCCESS", prefix), Bytes::new()).await?;
+    tracing::info!(index, prefix = %prefix, vectors = report.vectors, files = report.files.len(), "Exported index");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float32Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_row_from_record() {
+        let doc = json!({"key": "a", "data": {"float32": [1.0, 2.5]}, "metadata": {"lang": "en"}, "version": 7});
+        let row = ExportRow::from_record(&doc).unwrap();
+        assert_eq!(row.embedding, vec![1.0, 2.5]);
+        assert_eq!((row.metadata.as_str(), row.version), ("{\"lang\":\"en\"}", Some(7)));
+        assert!(ExportRow::from_record(&json!({"key": "b"})).is_none());
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let rows = vec![
+            ExportRow { id: "a".to_string(), embedding: vec![1.0, 2.0], metadata: "{}".to_string(), version: Some(1) },
+            ExportRow { id: "b".to_string(), embedding: vec![3.0, 4.0], metadata: "{}".to_string(), version: None },
+        ];
+        let path = format!("/tmp/export-test-{}.parquet", uuid::Uuid::new_v4());
+        write_parquet(&rows, &path).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap().build().unwrap();
+        let batch = reader.into_iter().next().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let embeddings = batch.column(1).as_any().downcast_ref::<ListArray>().unwrap();
+        let second = embeddings.value(1);
+        assert_eq!(second.as_any().downcast_ref::<Float32Array>().unwrap().values().to_vec(), vec![3.0, 4.0]);
+        assert!(batch.column(3).is_null(1));
+    }
+
+    #[test]
+    fn test_export_prefix() {
+        let at = DateTime::from_timestamp(1736942400, 0).unwrap();
+        assert_eq!(export_prefix("docs", at), "exports/docs/20250115T120000Z/");
+    }
+}
@@ -0,0 +1,89 @@
+//! Faiss calls made while serving (loading a shard's index, searching it)
+//! block for milliseconds to seconds, and on a runtime thread that stalls
+//! every request the thread serves. They run on tokio's blocking pool
+//! instead, at most `VEC_FAISS_THREADS` (default one per core) at once so a
+//! burst of queries queues rather than oversubscribing the CPU. Index builds
+//! have their own pool, see [`crate::governor`].
+
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+/// Calls waiting for a slot.
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+fn slots() -> &'static Semaphore {
+    static SLOTS: OnceLock<Semaphore> = OnceLock::new();
+    SLOTS.get_or_init(|| {
+        let threads = std::env::var("VEC_FAISS_THREADS").ok().and_then(|v| v.trim().parse::<usize>().ok());
+        Semaphore::new(threads.filter(|&n| n > 0).unwrap_or_else(num_cpus::get))
+    })
+}
+
+/// Counts a call as queued until dropped, including when the caller gives up
+/// waiting (a query timing out).
+struct Queued;
+
+impl Queued {
+    fn enter() -> Self {
+        let queued = QUEUED.fetch_add(1, Ordering::Relaxed) + 1;
+        crate::metrics::get_metrics_collector().track_metric("faiss.queued", queued as f64);
+        Queued
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        QUEUED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Run the blocking Faiss `call` (named `op` in errors) off the runtime
+/// threads once a slot is free. The slot is held until `call` returns, even
+/// when the caller stops waiting for it.
+pub async fn run<T: Send + 'static>(op: &'static str, call: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    let queued_at = Instant::now();
+    let queued = Queued::enter();
+    let permit = slots().acquire().await.expect("the Faiss pool is never closed");
+    drop(queued);
+    let waited = queued_at.elapsed();
+    crate::metrics::get_metrics_collector().track_metric("faiss.queue_wait_ms", waited.as_secs_f64() * 1000.0);
+    tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        call()
+    })
+    .await
+    .with_context(|| format!("Faiss {} panicked", op))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_calls_are_bounded_by_slots() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let calls = (0..slots().available_permits() * 3).map(|_| {
+            let (running, most) = (running.clone(), most.clone());
+            tokio::spawn(run("search", move || {
+                most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(10));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }))
+        });
+        for call in futures::future::join_all(calls).await {
+            call.unwrap().unwrap();
+        }
+        assert!(most.load(Ordering::SeqCst) <= num_cpus::get());
+
+        let failed = run("search", || -> Result<()> { panic!("bad index") }).await;
+        assert!(failed.unwrap_err().to_string().contains("Faiss search panicked"));
+        assert_eq!(run("read", || Ok(7)).await.unwrap(), 7);
+    }
+}
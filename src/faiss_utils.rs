@@ -1,49 +1,142 @@
 use anyhow::{Context, Result};
-use faiss::{index::IndexImpl, MetricType, Idx, index_factory, Index};
+use faiss::{index::{autotune::ParameterSpace, FromInnerPtr, IndexImpl}, MetricType, Idx, Index};
+use std::ffi::{CStr, CString};
+use sha2::{Digest, Sha256};
+
+/// IVF lists probed per query when a request sets none; Faiss's own default
+/// of 1 misses most neighbours.
+pub const DEFAULT_NPROBE: usize = 8;
+/// HNSW candidate list size, raised to k for larger result sets.
+const DEFAULT_EF_SEARCH: usize = 64;
+/// Neighbours per node of the HNSW graphs shards are built with.
+pub const DEFAULT_HNSW_M: usize = 32;
+
+extern "C" {
+    // From the OpenMP runtime Faiss is linked with.
+    fn omp_set_num_threads(num_threads: std::os::raw::c_int);
+}
+
+/// OpenMP threads Faiss trains and adds vectors with in calls made from the
+/// current thread. Left alone it takes every core for each call, which
+/// oversubscribes the CPU when several shards build at once.
+pub fn set_omp_threads(threads: usize) {
+    // SAFETY: only sets the team size of the calling thread's parallel regions.
+    unsafe { omp_set_num_threads(threads.max(1) as std::os::raw::c_int) }
+}
+
+/// Faiss id of the vector stored under `key`: the first 63 bits of its
+/// SHA-256, so it is the same in every shard and build, never negative (Faiss
+/// pads results with -1) and needs no positional bookkeeping.
+pub fn key_id(key: &str) -> i64 {
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes) >> 1) as i64
+}
+
+/// Canonical name of a distance metric, accepting the spellings configs and
+/// manifests written by earlier versions used (any case, `l2`, `angular`, ...).
+pub fn canonical_metric(metric: &str) -> Option<&'static str> {
+    match metric.to_lowercase().as_str() {
+        "cosine" | "angular" => Some("cosine"),
+        "dotproduct" | "inner_product" => Some("dotproduct"),
+        "euclidean" | "l2" => Some("euclidean"),
+        "manhattan" | "l1" => Some("manhattan"),
+        "chebyshev" | "linf" => Some("chebyshev"),
+        _ => None,
+    }
+}
+
+/// Faiss metric code for an index's distance metric. Cosine and dot product
+/// both search by inner product, and neither has vectors normalized here:
+/// cosine indexes expect unit-length embeddings, dot product ones keep
+/// magnitudes. L1 and Linf aren't modelled by `faiss::MetricType`, so codes
+/// come from the C API.
+fn metric_code(metric: &str) -> Result<faiss_sys::FaissMetricType> {
+    match canonical_metric(metric) {
+        Some("cosine" | "dotproduct") => Ok(MetricType::InnerProduct.code()),
+        Some("euclidean") => Ok(MetricType::L2.code()),
+        Some("manhattan") => Ok(faiss_sys::FaissMetricType_METRIC_L1),
+        Some("chebyshev") => Ok(faiss_sys::FaissMetricType_METRIC_Linf),
+        _ => Err(anyhow::anyhow!("Unsupported metric: {}", metric)),
+    }
+}
+
+/// Whether `index` searches by `metric`. Cosine and dot product indexes can't
+/// be told apart, both being inner product.
+pub fn index_has_metric(index: &IndexImpl, metric: &str) -> bool {
+    // `Index::metric_type` panics on the metrics `MetricType` doesn't model.
+    // SAFETY: `index` owns a live Faiss index.
+    let code = unsafe { faiss_sys::faiss_Index_metric_type(index.inner_ptr()) };
+    metric_code(metric).is_ok_and(|expected| expected == code)
+}
+
+/// The vector stored under Faiss id `id`, decoded from `index`. Shards are
+/// `IDMap2` indexes, which reconstruct by id; PQ codes give back an
+/// approximation. `None` when `id` isn't in the index.
+pub fn reconstruct(index: &IndexImpl, id: i64) -> Option<Vec<f32>> {
+    let mut vector = vec![0.0; index.d() as usize];
+    // SAFETY: `index` owns a live Faiss index and `vector` holds one of its vectors.
+    let status = unsafe { faiss_sys::faiss_Index_reconstruct(index.inner_ptr(), id, vector.as_mut_ptr()) };
+    (status == 0).then_some(vector)
+}
+
+/// Whether IVF-PQ can index `metric`. PQ distance tables only exist for
+/// inner product and L2, so L1 and Linf need HNSW over full vectors.
+pub fn ivfpq_supports(metric: &str) -> bool {
+    metric_code(metric).is_ok_and(|code| code == MetricType::InnerProduct.code() || code == MetricType::L2.code())
+}
+
+/// `faiss::index_factory`, for every metric [`metric_code`] knows.
+fn new_index(dimension: usize, description: &str, metric: &str) -> Result<IndexImpl> {
+    let metric = metric_code(metric)?;
+    let description_c = CString::new(description)?;
+    let mut index = std::ptr::null_mut();
+    // SAFETY: the description is a valid C string and Faiss sets `index` to a
+    // new index it hands over to us whenever it returns 0.
+    unsafe {
+        let status = faiss_sys::faiss_index_factory(&mut index, dimension as i32, description_c.as_ptr(), metric);
+        if status != 0 {
+            let error = faiss_sys::faiss_get_last_error();
+            let message = if error.is_null() { "unknown error".into() } else { CStr::from_ptr(error).to_string_lossy() };
+            return Err(anyhow::anyhow!("Failed to create Faiss index {}: {}", description, message));
+        }
+        Ok(IndexImpl::from_inner_ptr(index))
+    }
+}
 
 /// Build a complete IVF-PQ index with training and vector addition.
+/// `vectors` are row-major, `dimension` values per row, and `ids[i]` is the
+/// Faiss id of row `i`; rows go to Faiss without being copied.
 pub fn build_ivfpq_index(
     dimension: usize,
     nlist: usize,
     m: usize,
     nbits: usize,
     metric: &str,
-    vectors: &[Vec<f32>],
+    vectors: &[f32],
+    ids: &[i64],
 ) -> Result<IndexImpl> {
-    if vectors.is_empty() {
+    let count = vectors.len() / dimension;
+    if count == 0 {
         return Err(anyhow::anyhow!("Cannot build index with empty vectors"));
     }
 
-    let metric_type = match metric.to_lowercase().as_str() {
-        "cosine" | "angular" => MetricType::InnerProduct,
-        "euclidean" | "l2" => MetricType::L2,
-        _ => return Err(anyhow::anyhow!("Unsupported metric: {}", metric)),
-    };
-
-    let index_description = format!("IVF{},PQ{}x{}", nlist, m, nbits);
-    let mut index = index_factory(dimension as u32, &index_description, metric_type)?;
-
-    let training_size = calculate_optimal_training_size(vectors.len(), nlist);
-    if training_size > vectors.len() {
+    let training_size = calculate_optimal_training_size(count, nlist);
+    if training_size > count {
         return Err(anyhow::anyhow!(
             "Insufficient vectors for training: need {}, have {}",
             training_size,
-            vectors.len()
+            count
         ));
     }
 
-    let training_vectors = &vectors[..training_size];
-    let flat_training_vectors: Vec<f32> = training_vectors.iter().flat_map(|v| v.iter().cloned()).collect();
-    index.train(&flat_training_vectors).context("Failed to train Faiss IVF-PQ index")?;
-
-    let flat_vectors: Vec<f32> = vectors.iter().flat_map(|v| v.iter().cloned()).collect();
-    let ids: Vec<i64> = (0..vectors.len() as i64).collect();
-    let faiss_ids: Vec<Idx> = ids.iter().map(|&id| Idx::from(id)).collect();
-    index.add_with_ids(&flat_vectors, &faiss_ids).context("Failed to add vectors to Faiss index")?;
+    let mut index = train_ivfpq_index(dimension, nlist, m, nbits, metric, vectors, training_size)?;
+    add_vectors(&mut index, vectors, ids)?;
 
     tracing::info!(
         "Built Faiss IVF-PQ index: {} vectors, {} dims, {} clusters, {}x{} PQ, trained on {} vectors",
-        vectors.len(),
+        count,
         dimension,
         nlist,
         m,
@@ -54,32 +147,107 @@ pub fn build_ivfpq_index(
     Ok(index)
 }
 
-/// Build a complete HNSW-Flat index with vector addition.
+/// Train an empty IVF-PQ index on `training_size` rows of `vectors`. Indexes
+/// are wrapped in an `IDMap2` so vectors can be removed and reconstructed by id.
+pub fn train_ivfpq_index(
+    dimension: usize,
+    nlist: usize,
+    m: usize,
+    nbits: usize,
+    metric: &str,
+    vectors: &[f32],
+    training_size: usize,
+) -> Result<IndexImpl> {
+    if !ivfpq_supports(metric) {
+        return Err(anyhow::anyhow!("IVF-PQ doesn't support the {} metric", metric));
+    }
+
+    let index_description = format!("IDMap2,IVF{},PQ{}x{}", nlist, m, nbits);
+    let mut index = new_index(dimension, &index_description, metric)?;
+
+    // Vectors often arrive sorted (by customer, by time), so a prefix would
+    // skew the centroids; train on a uniform sample instead.
+    let sample = reservoir_sample(vectors.len() / dimension, training_size, &mut SplitMix64::new(training_seed()));
+    let mut training_vectors = Vec::with_capacity(sample.len() * dimension);
+    for row in sample {
+        training_vectors.extend_from_slice(&vectors[row * dimension..(row + 1) * dimension]);
+    }
+    index.train(&training_vectors).context("Failed to train Faiss IVF-PQ index")?;
+    Ok(index)
+}
+
+/// Add the row-major `vectors` to a trained index under the Faiss ids `ids`.
+pub fn add_vectors(index: &mut IndexImpl, vectors: &[f32], ids: &[i64]) -> Result<()> {
+    let faiss_ids: Vec<Idx> = ids.iter().map(|&id| Idx::from(id)).collect();
+    index.add_with_ids(vectors, &faiss_ids).context("Failed to add vectors to Faiss index")
+}
+
+/// Small, fast PRNG for sampling; not for anything security related.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound`; `bound` must be non-zero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Seed for training samples: `VEC_TRAINING_SEED` for reproducible builds,
+/// otherwise random per training.
+pub fn training_seed() -> u64 {
+    std::env::var("VEC_TRAINING_SEED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0)
+}
+
+/// Positions of `k` items drawn uniformly without replacement from `n`, in
+/// ascending order (reservoir sampling, algorithm R).
+pub fn reservoir_sample(n: usize, k: usize, rng: &mut SplitMix64) -> Vec<usize> {
+    let mut reservoir: Vec<usize> = (0..k.min(n)).collect();
+    for i in reservoir.len()..n {
+        let j = rng.below(i + 1);
+        if j < k {
+            reservoir[j] = i;
+        }
+    }
+    reservoir.sort_unstable();
+    reservoir
+}
+
+/// Build a complete HNSW-Flat index with vector addition, from row-major
+/// `vectors` as for [`build_ivfpq_index`]. HNSW can't take ids itself, so it
+/// is wrapped in an `IDMap2`.
 pub fn build_hnsw_flat_index(
     dimension: usize,
     metric: &str,
-    vectors: &[Vec<f32>],
+    vectors: &[f32],
+    ids: &[i64],
     m: usize,
 ) -> Result<IndexImpl> {
-    if vectors.is_empty() {
+    if vectors.len() < dimension {
         return Err(anyhow::anyhow!("Cannot build HNSW index with empty vectors"));
     }
 
-    let metric_type = match metric.to_lowercase().as_str() {
-        "cosine" | "angular" => MetricType::InnerProduct,
-        "euclidean" | "l2" => MetricType::L2,
-        _ => return Err(anyhow::anyhow!("Unsupported metric for HNSW: {}", metric)),
-    };
-
-    let index_description = format!("HNSW{},Flat", m);
-    let mut index = index_factory(dimension as u32, &index_description, metric_type)?;
-
-    let flat_vectors: Vec<f32> = vectors.iter().flat_map(|v| v.iter().cloned()).collect();
-    index.add(&flat_vectors)?;
+    let index_description = format!("IDMap2,HNSW{},Flat", m);
+    let mut index = new_index(dimension, &index_description, metric)?;
+    add_vectors(&mut index, vectors, ids)?;
 
     tracing::info!(
         "Built Faiss HNSW index: {} vectors, {} dims, M={}",
-        vectors.len(),
+        vectors.len() / dimension,
         dimension,
         m
     );
@@ -87,42 +255,52 @@ pub fn build_hnsw_flat_index(
     Ok(index)
 }
 
+/// Build an exhaustive (flat) index over row-major `vectors`, wrapped in an
+/// `IDMap2` like the others; nothing to train or tune.
+pub fn build_flat_index(dimension: usize, metric: &str, vectors: &[f32], ids: &[i64]) -> Result<IndexImpl> {
+    if vectors.len() < dimension {
+        return Err(anyhow::anyhow!("Cannot build flat index with empty vectors"));
+    }
+
+    let mut index = new_index(dimension, "IDMap2,Flat", metric)?;
+    add_vectors(&mut index, vectors, ids)?;
+
+    tracing::info!("Built Faiss flat index: {} vectors, {} dims", vectors.len() / dimension, dimension);
+
+    Ok(index)
+}
+
 /// Search an index for similar vectors.
 pub fn search_index(
     index: &mut IndexImpl,
+    algorithm: &str,
     query: &[f32],
     k: usize,
-    nprobe: Option<usize>,
+    nprobe: usize,
 ) -> Result<(Vec<f32>, Vec<i64>)> {
-    // Set nprobe if it's an IVF index (best effort)
-    if let Some(nprobe_val) = nprobe {
-        tracing::debug!("Setting nprobe to {} for search", nprobe_val);
-        // Note: Direct nprobe setting would require more specific index types
+    // Search parameters aren't saved with the index, so set them on every load.
+    let params = ParameterSpace::new()?;
+    match algorithm {
+        "hnsw_flat" => {
+            let ef_search = k.max(DEFAULT_EF_SEARCH);
+            params.set_index_parameter(index, "efSearch", ef_search as f64).context("Failed to set efSearch")?;
+        }
+        "flat" => {}
+        _ => params.set_index_parameter(index, "nprobe", nprobe as f64).context("Failed to set nprobe")?,
     }
 
     let search_result = index.search(query, k)?;
-    let labels = search_result.labels;
-    let valid_results: Vec<(f32, i64)> = search_result
-        .distances
+    Ok(label_ids(search_result.distances, search_result.labels))
+}
+
+/// Pair Faiss results with their ids as `i64`, dropping the empty labels
+/// Faiss pads short result lists with.
+pub fn label_ids(distances: Vec<f32>, labels: Vec<Idx>) -> (Vec<f32>, Vec<i64>) {
+    distances
         .into_iter()
-        .zip(labels.into_iter())
-        .filter_map(|(dist, label)| {
-            // Try to convert Idx to i64 - Idx(0) represents the inner value
-            if label >= Idx::from(0i64) {
-                // Assuming Idx wraps i64, access inner value via debug representation
-                let label_str = format!("{:?}", label);
-                if let Ok(label_i64) = label_str.trim_start_matches("Idx(").trim_end_matches(")").parse::<i64>() {
-                    Some((dist, label_i64))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .collect();
-    let (filtered_distances, filtered_labels) = valid_results.into_iter().unzip();
-    Ok((filtered_distances, filtered_labels))
+        .zip(labels)
+        .filter_map(|(distance, label)| label.get().map(|id| (distance, id as i64)))
+        .unzip()
 }
 
 /// Calculate optimal nlist based on dataset size.
@@ -190,4 +368,92 @@ pub fn calculate_optimal_pq_params(dimension: usize, target_compression: f64) ->
         8
     };
     (m, nbits)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{Fixture, RECALL_K};
+
+    /// Lowest recall@10 each algorithm may reach on the golden fixtures. A
+    /// build or search change that drops below one degraded search quality.
+    const GOLDEN_RECALL: [(&str, f64); 3] = [("flat", 1.0), ("hnsw_flat", 0.95), ("ivfpq", 0.8)];
+
+    #[test]
+    fn test_golden_recall_per_algorithm() {
+        // Faiss's own clustering is seeded; this fixes the training sample.
+        std::env::set_var("VEC_TRAINING_SEED", "7");
+        for metric in ["euclidean", "cosine"] {
+            let fixture = Fixture::generate(42, 5_000, 32, 50, metric);
+            let (dim, vectors, ids) = (fixture.dim, &fixture.vectors, &fixture.ids);
+            for (algorithm, minimum) in GOLDEN_RECALL {
+                let mut index = match algorithm {
+                    "flat" => build_flat_index(dim, metric, vectors, ids),
+                    "hnsw_flat" => build_hnsw_flat_index(dim, metric, vectors, ids, DEFAULT_HNSW_M),
+                    _ => build_ivfpq_index(dim, 32, 8, 6, metric, vectors, ids),
+                }
+                .unwrap();
+                let results: Vec<Vec<i64>> = fixture
+                    .queries
+                    .iter()
+                    .map(|query| search_index(&mut index, algorithm, query, RECALL_K, DEFAULT_NPROBE).unwrap().1)
+                    .collect();
+                let recall = fixture.recall(&results);
+                assert!(
+                    recall >= minimum,
+                    "{} recall@{} on {} fixtures fell to {:.3}, below {}",
+                    algorithm, RECALL_K, metric, recall, minimum
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ivfpq_metrics() {
+        assert!(ivfpq_supports("cosine") && ivfpq_supports("dotproduct") && ivfpq_supports("euclidean"));
+        assert!(!ivfpq_supports("manhattan") && !ivfpq_supports("chebyshev") && !ivfpq_supports("hamming"));
+    }
+
+    #[test]
+    fn test_canonical_metric_spellings() {
+        assert_eq!(canonical_metric("COSINE"), Some("cosine"));
+        assert_eq!(canonical_metric("L2"), Some("euclidean"));
+        assert_eq!(canonical_metric("inner_product"), Some("dotproduct"));
+        assert_eq!(canonical_metric("hamming"), None);
+    }
+
+    #[test]
+    fn test_reservoir_sample_spans_input() {
+        let mut rng = SplitMix64::new(42);
+        let sample = reservoir_sample(10_000, 500, &mut rng);
+        assert_eq!(sample.len(), 500);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        // A prefix would stop at 499; a uniform sample reaches the second half.
+        assert!(sample.iter().filter(|&&i| i >= 5_000).count() > 150);
+        assert_eq!(reservoir_sample(3, 10, &mut rng), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_key_ids_are_stable_and_non_negative() {
+        assert_eq!(key_id("doc-1"), key_id("doc-1"));
+        assert_ne!(key_id("doc-1"), key_id("doc-2"));
+        assert!((0..1_000).all(|i| key_id(&format!("k{}", i)) >= 0));
+    }
+
+    #[test]
+    fn test_reconstruct_by_key_id() {
+        let vectors = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let ids = [key_id("a"), key_id("b")];
+        let index = build_flat_index(3, "euclidean", &vectors, &ids).unwrap();
+        assert_eq!(reconstruct(&index, key_id("b")), Some(vec![0.0, 1.0, 0.0]));
+        assert_eq!(reconstruct(&index, key_id("c")), None);
+    }
+
+    #[test]
+    fn test_label_ids_drops_empty_labels() {
+        let labels = vec![Idx::new(7), Idx::none(), Idx::new(0)];
+        let (distances, ids) = label_ids(vec![0.9, 0.0, 0.5], labels);
+        assert_eq!(distances, vec![0.9, 0.5]);
+        assert_eq!(ids, vec![7, 0]);
+    }
+}
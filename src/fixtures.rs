@@ -0,0 +1,121 @@
+//! Seeded synthetic datasets for tests that measure search quality. Vectors
+//! are drawn around random cluster centres, like real embeddings, and every
+//! query gets a neighbourhood planted close to it so recall has something
+//! to find; the exact neighbours are computed by brute force.
+
+use crate::distance;
+use crate::faiss_utils::SplitMix64;
+use crate::reduction::gaussian;
+
+/// Neighbours recall is measured over.
+pub const RECALL_K: usize = 10;
+const CLUSTERS: usize = 16;
+/// Spread of the cluster centres, relative to the unit spread within one.
+const CENTRE_SCALE: f32 = 4.0;
+/// Spread of a planted neighbourhood around its query.
+const PLANTED_SCALE: f32 = 0.1;
+
+pub struct Fixture {
+    pub dim: usize,
+    /// Row-major, `dim` values per row; row `i` has the id `ids[i] == i`.
+    pub vectors: Vec<f32>,
+    pub ids: Vec<i64>,
+    pub queries: Vec<Vec<f32>>,
+    /// Exact `RECALL_K` nearest ids of each query, best first.
+    pub truth: Vec<Vec<i64>>,
+}
+
+impl Fixture {
+    /// `count` vectors of `dim` dimensions (`RECALL_K` of them planted around
+    /// each of the `queries`), the same for the same `seed`. Cosine fixtures
+    /// are unit length, as cosine indexes expect.
+    pub fn generate(seed: u64, count: usize, dim: usize, queries: usize, metric: &str) -> Self {
+        assert!(count >= queries * RECALL_K, "{} vectors can't hold {} planted neighbourhoods", count, queries);
+        let mut rng = SplitMix64::new(seed);
+        let centres: Vec<Vec<f32>> = (0..CLUSTERS)
+            .map(|_| (0..dim).map(|_| gaussian(&mut rng) * CENTRE_SCALE).collect())
+            .collect();
+        let around = |centre: &[f32], scale: f32, rng: &mut SplitMix64| -> Vec<f32> {
+            centre.iter().map(|x| x + gaussian(rng) * scale).collect()
+        };
+
+        let mut rows: Vec<Vec<f32>> = Vec::with_capacity(count);
+        let mut query_rows = Vec::with_capacity(queries);
+        for _ in 0..queries {
+            let centre = &centres[(rng.next_u64() % CLUSTERS as u64) as usize];
+            let query = around(centre, 1.0, &mut rng);
+            rows.extend((0..RECALL_K).map(|_| around(&query, PLANTED_SCALE, &mut rng)));
+            query_rows.push(query);
+        }
+        while rows.len() < count {
+            let centre = &centres[(rng.next_u64() % CLUSTERS as u64) as usize];
+            rows.push(around(centre, 1.0, &mut rng));
+        }
+        if metric == "cosine" {
+            rows.iter_mut().chain(query_rows.iter_mut()).for_each(|row| normalize(row));
+        }
+
+        let vectors: Vec<f32> = rows.concat();
+        let truth = query_rows.iter().map(|query| exact_neighbours(&vectors, dim, metric, query)).collect();
+        Fixture { dim, vectors, ids: (0..count as i64).collect(), queries: query_rows, truth }
+    }
+
+    /// Share of the true neighbours found, over all queries; `results[q]` are
+    /// the ids returned for query `q`.
+    pub fn recall(&self, results: &[Vec<i64>]) -> f64 {
+        let found: usize = self
+            .truth
+            .iter()
+            .zip(results)
+            .map(|(truth, result)| truth.iter().filter(|id| result.iter().take(RECALL_K).any(|r| r == *id)).count())
+            .sum();
+        found as f64 / (self.truth.len() * RECALL_K) as f64
+    }
+}
+
+fn normalize(row: &mut [f32]) {
+    let norm = distance::dot(row, row).sqrt();
+    if norm > 0.0 {
+        row.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Ids of the `RECALL_K` rows closest to `query`, best first.
+fn exact_neighbours(vectors: &[f32], dim: usize, metric: &str, query: &[f32]) -> Vec<i64> {
+    let mut scored: Vec<(f32, i64)> = vectors
+        .chunks_exact(dim)
+        .enumerate()
+        .map(|(id, row)| match metric {
+            // Higher is closer; negate so everything sorts ascending.
+            "cosine" => (-distance::cosine(query, row), id as i64),
+            "dotproduct" => (-distance::dot(query, row), id as i64),
+            _ => (distance::l2_squared(query, row), id as i64),
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().take(RECALL_K).map(|(_, id)| id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures_are_seeded_and_plant_the_neighbours() {
+        let fixture = Fixture::generate(7, 600, 16, 5, "euclidean");
+        assert_eq!(fixture.vectors.len(), 600 * 16);
+        assert_eq!(fixture.vectors, Fixture::generate(7, 600, 16, 5, "euclidean").vectors);
+        assert_ne!(fixture.vectors, Fixture::generate(8, 600, 16, 5, "euclidean").vectors);
+        // Query q's neighbourhood is rows q * RECALL_K onwards.
+        for (q, truth) in fixture.truth.iter().enumerate() {
+            let mut truth = truth.clone();
+            truth.sort_unstable();
+            assert_eq!(truth, (q * RECALL_K..(q + 1) * RECALL_K).map(|id| id as i64).collect::<Vec<_>>());
+        }
+        assert_eq!(fixture.recall(&fixture.truth), 1.0);
+        assert_eq!(fixture.recall(&[]), 0.0);
+
+        let cosine = Fixture::generate(7, 600, 16, 5, "cosine");
+        assert!((distance::dot(&cosine.vectors[..16], &cosine.vectors[..16]) - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,251 @@
+//! Garbage collection of index artifacts nothing references. Shard files,
+//! shared quantizers and projections are written before the manifest (or
+//! `quantizer.json` / `projection.json`) that points at them, and never
+//! deleted when a later manifest drops them, so failed and superseded builds
+//! leave objects behind. An artifact is reachable when the index's manifest
+//! lists its shard, or a shard or descriptor names its file; unreachable ones
+//! older than a safety window are reported or deleted.
+
+use crate::minio::S3Client;
+use crate::model::IndexManifest;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const INDEX_PREFIX: &str = "indexes/";
+/// Directories under `indexes/<index>/` holding artifacts. Everything else
+/// there (config, manifest, descriptors, deletions) is live by definition.
+const ARTIFACT_DIRS: [&str; 3] = ["shards/", "quantizer/", "projection/"];
+/// Descriptors naming the index's current shared artifact in their `file`.
+const DESCRIPTORS: [&str; 2] = ["quantizer.json", "projection.json"];
+
+/// What a collection found, and deleted unless it was a dry run.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    pub dry_run: bool,
+    /// Indexes whose artifacts were checked.
+    pub indexes: usize,
+    /// Artifacts of those indexes kept: reachable, or too new to collect.
+    pub kept: usize,
+    pub unreachable: Vec<String>,
+    /// Indexes left alone because their manifest or a descriptor couldn't be
+    /// read.
+    pub skipped: Vec<String>,
+    pub deleted: usize,
+}
+
+/// Index of an artifact key under `indexes/<index>/`.
+fn artifact_index(key: &str) -> Option<&str> {
+    let (index, rest) = key.strip_prefix(INDEX_PREFIX)?.split_once('/')?;
+    ARTIFACT_DIRS.iter().any(|dir| rest.starts_with(dir)).then_some(index)
+}
+
+/// Objects one index's manifest and descriptors reach: every object of a
+/// listed shard's directory, and the files its shards and descriptors name.
+#[derive(Debug, Default)]
+struct Roots {
+    keys: HashSet<String>,
+    dirs: Vec<String>,
+}
+
+impl Roots {
+    fn new(index: &str, manifest: &IndexManifest, descriptor_files: &[String]) -> Self {
+        let mut roots = Roots::default();
+        for shard in &manifest.shards {
+            roots.dirs.push(format!("{}{}/shards/{}/", INDEX_PREFIX, index, shard.shard_id));
+            let paths = [Some(&shard.index_path), Some(&shard.metadata_path), shard.vectors_path.as_ref(), shard.text_path.as_ref()];
+            roots.keys.extend(paths.into_iter().flatten().cloned());
+            roots.keys.insert(shard.index_path.replace("index.faiss", "id_map.json"));
+            for file in shard.quantizer.iter().chain(&shard.projection) {
+                roots.keys.insert(format!("{}{}/{}", INDEX_PREFIX, index, file));
+            }
+        }
+        for file in descriptor_files {
+            roots.keys.insert(format!("{}{}/{}", INDEX_PREFIX, index, file));
+        }
+        roots
+    }
+
+    fn reach(&self, key: &str) -> bool {
+        self.keys.contains(key) || self.dirs.iter().any(|dir| key.starts_with(dir.as_str()))
+    }
+}
+
+/// Artifacts among `objects` that the roots of their index don't reach and
+/// that were last written before `cutoff`. Indexes without roots are skipped.
+fn unreachable(objects: &[(String, DateTime<Utc>)], roots: &HashMap<String, Roots>, cutoff: DateTime<Utc>) -> Vec<String> {
+    objects
+        .iter()
+        .filter(|(key, modified)| {
+            let roots = artifact_index(key).and_then(|index| roots.get(index));
+            roots.is_some_and(|roots| !roots.reach(key)) && *modified < cutoff
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// `file` named by the descriptor `key` of an index, if it has one.
+async fn descriptor_file(s3: &S3Client, key: &str, listed: &HashSet<&str>) -> Result<Option<String>> {
+    if !listed.contains(key) {
+        return Ok(None);
+    }
+    let descriptor: Value = serde_json::from_slice(&s3.get_object(key).await?)?;
+    let file = descriptor.get("file").and_then(Value::as_str).with_context(|| format!("{} names no file", key))?;
+    Ok(Some(file.to_string()))
+}
+
+async fn load_roots(s3: &S3Client, index: &str, listed: &HashSet<&str>) -> Result<Roots> {
+    let data = s3.get_object(&format!("{}{}/manifest.json", INDEX_PREFIX, index)).await?;
+    let mut manifest = IndexManifest::from_slice(&data)?;
+    // Shards an interrupted indexing run stored only reach the manifest once
+    // the run resumes.
+    if listed.contains(crate::checkpoint::key(index).as_str()) {
+        if let Some(checkpoint) = crate::checkpoint::Checkpoint::load(s3, index).await {
+            manifest.shards.extend(checkpoint.shards);
+        }
+    }
+    let mut files = Vec::new();
+    for descriptor in DESCRIPTORS {
+        files.extend(descriptor_file(s3, &format!("{}{}/{}", INDEX_PREFIX, index, descriptor), listed).await?);
+    }
+    Ok(Roots::new(index, &manifest, &files))
+}
+
+/// Find the artifacts no manifest reaches that are older than `window` as of
+/// `now`, and delete them unless `dry_run`. Objects are listed before
+/// manifests are read, so artifacts of a build that finishes meanwhile are
+/// reached by its new manifest; `window` must cover builds still writing
+/// theirs, and readers still holding a manifest that dropped a shard.
+pub async fn collect(s3: &S3Client, window: Duration, now: DateTime<Utc>, dry_run: bool) -> Result<GcReport> {
+    let objects = s3.list_objects_modified(INDEX_PREFIX).await?;
+    let listed: HashSet<&str> = objects.iter().map(|(key, _)| key.as_str()).collect();
+    let mut indexes: BTreeMap<&str, usize> = BTreeMap::new();
+    for index in objects.iter().filter_map(|(key, _)| artifact_index(key)) {
+        *indexes.entry(index).or_default() += 1;
+    }
+
+    let mut report = GcReport { dry_run, ..Default::default() };
+    let mut roots = HashMap::new();
+    for (index, artifacts) in indexes {
+        match load_roots(s3, index, &listed).await {
+            Ok(index_roots) => {
+                roots.insert(index.to_string(), index_roots);
+                report.indexes += 1;
+                report.kept += artifacts;
+            }
+            Err(e) => {
+                tracing::warn!("Skipping garbage collection of index {}: {:#}", index, e);
+                report.skipped.push(index.to_string());
+            }
+        }
+    }
+    report.unreachable = unreachable(&objects, &roots, now - window);
+    report.kept -= report.unreachable.len();
+
+    if !dry_run {
+        for key in &report.unreachable {
+            s3.delete_object(key).await?;
+            report.deleted += 1;
+        }
+    }
+    crate::metrics::get_metrics_collector().track_metric("gc.unreachable_objects", report.unreachable.len() as f64);
+    tracing::info!(
+        "Garbage collection{}: {} unreachable objects in {} indexes ({} skipped), deleted {}",
+        if dry_run { " (dry run)" } else { "" },
+        report.unreachable.len(),
+        report.indexes,
+        report.skipped.len(),
+        report.deleted
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+
+    fn manifest(index: &str, shards: &[(&str, Option<&str>)]) -> Value {
+        let shards: Vec<Value> = shards
+            .iter()
+            .map(|(id, quantizer)| {
+                json!({
+                    "shard_id": id,
+                    "index_path": format!("indexes/{}/shards/{}/index.faiss", index, id),
+                    "metadata_path": format!("indexes/{}/shards/{}/metadata.json", index, id),
+                    "vector_count": 1, "metric": "cosine", "created_at": "20250115T000000",
+                    "quantizer": quantizer,
+                })
+            })
+            .collect();
+        json!({"index_name": index, "dim": 2, "metric": "cosine", "total_vectors": shards.len(), "shards": shards})
+    }
+
+    #[test]
+    fn test_reachability() {
+        let at = |hour: u32| DateTime::parse_from_rfc3339(&format!("2025-01-15T{:02}:00:00Z", hour)).unwrap().to_utc();
+        let manifest = IndexManifest::from_slice(manifest("docs", &[("live", Some("quantizer/q1.faiss"))]).to_string().as_bytes()).unwrap();
+        let roots = HashMap::from([("docs".to_string(), Roots::new("docs", &manifest, &["quantizer/q2.faiss".to_string()]))]);
+        let objects: Vec<_> = [
+            ("indexes/docs/manifest.json", 1),
+            ("indexes/docs/deletions/1-a.json", 1),
+            ("indexes/docs/shards/live/index.faiss", 1),
+            ("indexes/docs/shards/live/id_map.json", 1),
+            ("indexes/docs/shards/dead/index.faiss", 1),
+            ("indexes/docs/shards/building/index.faiss", 9),
+            ("indexes/docs/quantizer/q1.faiss", 1),
+            ("indexes/docs/quantizer/q2.faiss", 1),
+            ("indexes/docs/quantizer/q0.faiss", 1),
+            ("indexes/docs/projection/p0.f32", 1),
+            ("indexes/lost/shards/s1/index.faiss", 1),
+        ]
+        .into_iter()
+        .map(|(key, hour)| (key.to_string(), at(hour)))
+        .collect();
+        assert_eq!(
+            unreachable(&objects, &roots, at(6)),
+            ["indexes/docs/shards/dead/index.faiss", "indexes/docs/quantizer/q0.faiss", "indexes/docs/projection/p0.f32"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_skips_indexes_it_cannot_read() {
+        let s3 = S3Client::in_memory("vectors");
+        let put = |key: &'static str, data: String| {
+            let s3 = s3.clone();
+            async move { s3.put_object(key, Bytes::from(data)).await.unwrap() }
+        };
+        put("indexes/docs/manifest.json", manifest("docs", &[("live", None)]).to_string()).await;
+        put("indexes/docs/quantizer.json", json!({"file": "quantizer/q1.faiss"}).to_string()).await;
+        put("indexes/broken/manifest.json", "{".to_string()).await;
+        // Stored by an indexing run that hasn't resumed yet.
+        let pending = manifest("docs", &[("pending", None)])["shards"].clone();
+        put("indexes/docs/checkpoint.json", json!({"dim": 2, "shards": pending, "rows": {}}).to_string()).await;
+        for key in [
+            "indexes/docs/shards/live/index.faiss",
+            "indexes/docs/shards/pending/index.faiss",
+            "indexes/docs/shards/dead/index.faiss",
+            "indexes/docs/quantizer/q1.faiss",
+            "indexes/broken/shards/s1/index.faiss",
+        ] {
+            put(key, "x".to_string()).await;
+        }
+
+        let report = collect(&s3, Duration::hours(24), Utc::now(), true).await.unwrap();
+        assert!(report.unreachable.is_empty(), "{:?}", report);
+        let later = Utc::now() + Duration::days(2);
+        let report = collect(&s3, Duration::hours(24), later, true).await.unwrap();
+        assert_eq!(report.unreachable, ["indexes/docs/shards/dead/index.faiss"]);
+        assert_eq!((report.indexes, report.kept, report.skipped.as_slice()), (1, 3, ["broken".to_string()].as_slice()));
+        assert!(s3.get_object("indexes/docs/shards/dead/index.faiss").await.is_ok());
+
+        let report = collect(&s3, Duration::hours(24), later, false).await.unwrap();
+        assert_eq!(report.deleted, 1);
+        assert!(s3.get_object("indexes/docs/shards/dead/index.faiss").await.is_err());
+        assert!(s3.get_object("indexes/docs/quantizer/q1.faiss").await.is_ok());
+    }
+}
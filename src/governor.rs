@@ -0,0 +1,248 @@
+//! Keeps indexing from starving the queries a process serves. Faiss builds
+//! run on a dedicated rayon pool of `VEC_INDEXING_THREADS` threads at a lower
+//! scheduling priority (`VEC_INDEXING_NICE`), not on the runtime threads
+//! serving requests, and data prep within a build is spread over the same
+//! threads; shard uploads share `VEC_INDEXING_IO_MBPS` of bandwidth;
+//! and with `VEC_QUERY_LATENCY_TARGET_MS` set, builds wait between shards
+//! while recent queries are slower than the target.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Queries older than this no longer count towards the latency target.
+const QUERY_WINDOW: Duration = Duration::from_secs(10);
+/// Queries needed in the window before indexing yields to them.
+const MIN_QUERIES: usize = 5;
+const MAX_QUERIES: usize = 1024;
+const PAUSE_STEP: Duration = Duration::from_millis(100);
+
+#[derive(Debug, PartialEq)]
+struct GovernorConfig {
+    /// `VEC_INDEXING_THREADS` (default half the cores, at least one).
+    threads: usize,
+    /// `VEC_INDEXING_NICE` (default 10, `0` to keep the process's priority);
+    /// only applied on Linux.
+    nice: i32,
+    /// `VEC_INDEXING_IO_MBPS`; `None` (the default, or `0`) is unlimited.
+    io_bytes_per_sec: Option<u64>,
+    /// `VEC_QUERY_LATENCY_TARGET_MS`; `None` (the default, or `0`) never
+    /// pauses indexing.
+    latency_target: Option<Duration>,
+    /// `VEC_INDEXING_MAX_PAUSE_MS` (default 5000): longest a build waits for
+    /// queries before going on anyway.
+    max_pause: Duration,
+}
+
+impl GovernorConfig {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let number = |name: &str| var(name).and_then(|v| v.trim().parse::<u64>().ok());
+        GovernorConfig {
+            threads: number("VEC_INDEXING_THREADS")
+                .filter(|&n| n > 0)
+                .map_or_else(|| (num_cpus::get() / 2).max(1), |n| n as usize),
+            nice: number("VEC_INDEXING_NICE").map_or(10, |n| n.min(19) as i32),
+            io_bytes_per_sec: number("VEC_INDEXING_IO_MBPS").filter(|&n| n > 0).map(|n| n * 1024 * 1024),
+            latency_target: number("VEC_QUERY_LATENCY_TARGET_MS").filter(|&ms| ms > 0).map(Duration::from_millis),
+            max_pause: Duration::from_millis(number("VEC_INDEXING_MAX_PAUSE_MS").unwrap_or(5000)),
+        }
+    }
+}
+
+fn config() -> &'static GovernorConfig {
+    static CONFIG: OnceLock<GovernorConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| GovernorConfig::from_vars(|name| std::env::var(name).ok()))
+}
+
+/// Shards a build may have in flight at once: one per indexing thread.
+pub fn indexing_threads() -> usize {
+    config().threads
+}
+
+/// OpenMP threads each of `concurrent` builds may use so that together they
+/// stay within the indexing threads.
+pub fn build_threads(concurrent: usize) -> usize {
+    (config().threads / concurrent.max(1)).max(1)
+}
+
+fn pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config().threads)
+            .thread_name(|n| format!("indexing-{}", n))
+            .start_handler(|_| lower_priority(config().nice))
+            .build()
+            .expect("Failed to start indexing threads")
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn lower_priority(nice: i32) {
+    if nice == 0 {
+        return;
+    }
+    // Linux applies PRIO_PROCESS to a single thread when given its id.
+    let thread = unsafe { libc::gettid() } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, thread, nice) } != 0 {
+        tracing::warn!(nice, error = %std::io::Error::last_os_error(), "Failed to lower indexing thread priority");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_nice: i32) {}
+
+/// Run the CPU-bound `build` on the indexing pool, where rayon's parallel
+/// iterators also use the pool. Panics are passed on to the caller.
+pub async fn run<T: Send + 'static>(build: impl FnOnce() -> T + Send + 'static) -> T {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    pool().spawn(move || {
+        let _ = sender.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(build)));
+    });
+    match receiver.await.expect("indexing jobs always report back") {
+        Ok(value) => value,
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+/// How long a transfer of `bytes` has to wait to stay within `rate` bytes per
+/// second, given when the transfers before it are paced to end (`next`).
+fn reserve(next: &mut Option<Instant>, now: Instant, bytes: u64, rate: u64) -> Duration {
+    let start = next.map_or(now, |next| next.max(now));
+    *next = Some(start + Duration::from_secs_f64(bytes as f64 / rate as f64));
+    start - now
+}
+
+/// Wait until `bytes` of indexer I/O fit the configured bandwidth.
+pub async fn throttle_io(bytes: u64) {
+    static NEXT: Mutex<Option<Instant>> = Mutex::new(None);
+    let Some(rate) = config().io_bytes_per_sec else { return };
+    let wait = reserve(&mut NEXT.lock().unwrap(), Instant::now(), bytes, rate);
+    if !wait.is_zero() {
+        crate::metrics::get_metrics_collector().track_metric("indexing.io_throttled_ms", wait.as_millis() as f64);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Latencies of the queries of the last [`QUERY_WINDOW`].
+struct RecentQueries {
+    samples: Mutex<VecDeque<(Instant, Duration)>>,
+}
+
+static QUERIES: RecentQueries = RecentQueries { samples: Mutex::new(VecDeque::new()) };
+
+impl RecentQueries {
+    fn record(&self, at: Instant, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == MAX_QUERIES {
+            samples.pop_front();
+        }
+        samples.push_back((at, latency));
+    }
+
+    /// 95th percentile latency of the queries in the window before `now`,
+    /// once there are enough of them.
+    fn p95(&self, now: Instant) -> Option<Duration> {
+        let mut samples = self.samples.lock().unwrap();
+        while samples.front().is_some_and(|(at, _)| now.duration_since(*at) > QUERY_WINDOW) {
+            samples.pop_front();
+        }
+        if samples.len() < MIN_QUERIES {
+            return None;
+        }
+        let mut latencies: Vec<Duration> = samples.iter().map(|(_, latency)| *latency).collect();
+        latencies.sort_unstable();
+        Some(latencies[(latencies.len() * 95).div_ceil(100) - 1])
+    }
+}
+
+/// Note how long a query took, for [`yield_to_queries`].
+pub fn record_query(latency: Duration) {
+    if config().latency_target.is_some() {
+        QUERIES.record(Instant::now(), latency);
+    }
+}
+
+/// Before building a shard: wait while recent queries miss the latency
+/// target, up to the longest pause.
+pub async fn yield_to_queries() {
+    let Some(target) = config().latency_target else { return };
+    let start = Instant::now();
+    while QUERIES.p95(Instant::now()).is_some_and(|p95| p95 > target) && start.elapsed() < config().max_pause {
+        tokio::time::sleep(PAUSE_STEP).await;
+    }
+    let paused = start.elapsed();
+    if paused >= PAUSE_STEP {
+        tracing::debug!(paused_ms = paused.as_millis() as u64, "Indexing yielded to queries");
+        crate::metrics::get_metrics_collector().track_metric("indexing.paused_ms", paused.as_millis() as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_governor_comes_from_env() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            GovernorConfig::from_vars(crate::api::testing::env(pairs))
+        };
+        let defaults = vars(&[]);
+        assert_eq!(defaults.threads, (num_cpus::get() / 2).max(1));
+        assert_eq!((defaults.nice, defaults.io_bytes_per_sec, defaults.latency_target), (10, None, None));
+        let config = vars(&[
+            ("VEC_INDEXING_THREADS", "2"),
+            ("VEC_INDEXING_NICE", "40"),
+            ("VEC_INDEXING_IO_MBPS", "8"),
+            ("VEC_QUERY_LATENCY_TARGET_MS", "50"),
+        ]);
+        assert_eq!((config.threads, config.nice), (2, 19));
+        assert_eq!(config.io_bytes_per_sec, Some(8 * 1024 * 1024));
+        assert_eq!(config.latency_target, Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_builds_share_the_indexing_threads() {
+        assert_eq!(build_threads(1), config().threads);
+        assert_eq!(build_threads(config().threads * 2), 1);
+    }
+
+    #[tokio::test]
+    async fn test_builds_run_on_the_indexing_pool() {
+        let thread = run(|| std::thread::current().name().map(str::to_string)).await;
+        assert!(thread.is_some_and(|name| name.starts_with("indexing-")));
+        let threads = run(|| {
+            use rayon::prelude::*;
+            (0..64).into_par_iter().map(|_| std::thread::current().name().map(str::to_string)).collect::<Vec<_>>()
+        })
+        .await;
+        assert!(threads.iter().all(|name| name.as_deref().is_some_and(|name| name.starts_with("indexing-"))));
+        let panicked = tokio::spawn(run(|| panic!("build failed"))).await;
+        assert!(panicked.is_err());
+        assert_eq!(run(|| 7).await, 7);
+    }
+
+    #[test]
+    fn test_io_is_paced() {
+        let (now, mut next) = (Instant::now(), None);
+        assert_eq!(reserve(&mut next, now, 1000, 1000), Duration::ZERO);
+        assert_eq!(reserve(&mut next, now, 500, 1000), Duration::from_secs(1));
+        assert_eq!(reserve(&mut next, now + Duration::from_secs(5), 10, 1000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_recent_query_latency() {
+        let queries = RecentQueries { samples: Mutex::new(VecDeque::new()) };
+        let start = Instant::now();
+        for ms in 1..MIN_QUERIES as u64 {
+            queries.record(start, Duration::from_millis(ms));
+        }
+        assert_eq!(queries.p95(start), None);
+        for ms in MIN_QUERIES as u64..=100 {
+            queries.record(start, Duration::from_millis(ms));
+        }
+        assert_eq!(queries.p95(start), Some(Duration::from_millis(95)));
+        // Slow queries stop holding indexing back once they age out.
+        assert_eq!(queries.p95(start + QUERY_WINDOW + Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Latencies kept per window; older ones are forgotten, so the delay follows
+/// the store as it speeds up or slows down.
+const WINDOW: usize = 512;
+/// Samples needed before a window's percentile is trusted enough to hedge on.
+const MIN_SAMPLES: usize = 20;
+
+/// Hedged reads: when an object-store read takes longer than the
+/// `VEC_HEDGE_PERCENTILE` (default 95th) of recent reads of its kind, the same
+/// read is issued again and whichever finishes first is used, the other
+/// being dropped. Stragglers then cost about one percentile delay instead of
+/// their whole latency, for roughly `100 - percentile`% more requests.
+#[derive(Debug, PartialEq)]
+struct HedgeConfig {
+    /// `None` (`VEC_HEDGE_PERCENTILE=0`) never hedges.
+    percentile: Option<f64>,
+    /// `VEC_HEDGE_MIN_DELAY_MS` (default 10): never hedge sooner than this.
+    min_delay: Duration,
+}
+
+impl HedgeConfig {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let percentile = var("VEC_HEDGE_PERCENTILE").and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(95.0);
+        HedgeConfig {
+            percentile: (percentile > 0.0).then_some(percentile.min(99.9)),
+            min_delay: Duration::from_millis(var("VEC_HEDGE_MIN_DELAY_MS").and_then(|v| v.trim().parse().ok()).unwrap_or(10)),
+        }
+    }
+}
+
+fn config() -> &'static HedgeConfig {
+    static CONFIG: OnceLock<HedgeConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| HedgeConfig::from_vars(|name| std::env::var(name).ok()))
+}
+
+/// Recent latencies of one kind of read. Reads of different sizes belong in
+/// different windows, or the big ones would set the delay for the small.
+pub struct LatencyWindow {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+/// Reads of shard metadata, id maps, raw vectors and text indexes.
+pub static OBJECT_READS: LatencyWindow = LatencyWindow::new();
+/// Reads of Faiss index files.
+pub static INDEX_FILE_READS: LatencyWindow = LatencyWindow::new();
+
+impl LatencyWindow {
+    pub const fn new() -> Self {
+        LatencyWindow { samples: Mutex::new(VecDeque::new()) }
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// The `percentile` of the latencies in the window, once it has enough.
+    fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.samples.lock().unwrap().iter().copied().collect();
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = ((percentile / 100.0) * samples.len() as f64).ceil() as usize;
+        Some(samples[rank.clamp(1, samples.len()) - 1])
+    }
+}
+
+impl Default for LatencyWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `attempt()`, recording how long it took in `window` when it succeeds.
+async fn timed<T>(window: &LatencyWindow, attempt: impl Future<Output = Result<T>>) -> Result<T> {
+    let start = Instant::now();
+    let result = attempt.await;
+    if result.is_ok() {
+        window.record(start.elapsed());
+    }
+    result
+}
+
+/// Run `attempt`, and run it again if the first hasn't finished within the
+/// hedge delay of `window`; the first to succeed wins and the other is
+/// dropped, cancelling its request. Fails only when both attempts do.
+/// `attempt` must be safe to run twice at once, e.g. by downloading to its own
+/// temp file.
+pub async fn hedge<T, F, Fut>(window: &LatencyWindow, attempt: F) -> Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let config = config();
+    let Some(delay) = config.percentile.and_then(|p| window.percentile(p)).map(|d| d.max(config.min_delay)) else {
+        return timed(window, attempt()).await;
+    };
+    let first = timed(window, attempt());
+    tokio::pin!(first);
+    tokio::select! {
+        result = &mut first => return result,
+        _ = tokio::time::sleep(delay) => {}
+    }
+
+    crate::metrics::get_metrics_collector().track_metric("storage.hedged_reads", 1.0);
+    let second = timed(window, attempt());
+    tokio::pin!(second);
+    tokio::select! {
+        result = &mut first => match result {
+            Ok(value) => Ok(value),
+            Err(_) => second.await,
+        },
+        result = &mut second => {
+            crate::metrics::get_metrics_collector().track_metric("storage.hedge_wins", 1.0);
+            match result {
+                Ok(value) => Ok(value),
+                Err(_) => first.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_hedging_is_configured_by_percentile() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            HedgeConfig::from_vars(crate::api::testing::env(pairs))
+        };
+        assert_eq!(vars(&[]), HedgeConfig { percentile: Some(95.0), min_delay: Duration::from_millis(10) });
+        assert_eq!(vars(&[("VEC_HEDGE_PERCENTILE", "0")]).percentile, None);
+        assert_eq!(vars(&[("VEC_HEDGE_PERCENTILE", "100")]).percentile, Some(99.9));
+    }
+
+    #[test]
+    fn test_window_percentile() {
+        let window = LatencyWindow::new();
+        for ms in 1..MIN_SAMPLES as u64 {
+            window.record(Duration::from_millis(ms));
+        }
+        assert_eq!(window.percentile(95.0), None);
+        for ms in MIN_SAMPLES as u64..=100 {
+            window.record(Duration::from_millis(ms));
+        }
+        assert_eq!(window.percentile(95.0), Some(Duration::from_millis(95)));
+        assert_eq!(window.percentile(50.0), Some(Duration::from_millis(50)));
+        for _ in 0..WINDOW {
+            window.record(Duration::from_millis(7));
+        }
+        assert_eq!(window.percentile(99.0), Some(Duration::from_millis(7)));
+    }
+
+    #[tokio::test]
+    async fn test_stragglers_are_hedged() {
+        let window = LatencyWindow::new();
+        for _ in 0..MIN_SAMPLES {
+            window.record(Duration::from_millis(20));
+        }
+        // The first attempt straggles; the hedge issued after 20 ms wins.
+        let attempts = AtomicUsize::new(0);
+        let start = Instant::now();
+        let winner = hedge(&window, || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt == 0 {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+            Ok(attempt)
+        })
+        .await
+        .unwrap();
+        assert_eq!((winner, attempts.load(Ordering::SeqCst)), (1, 2));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // A failed hedge leaves the first attempt to finish.
+        let attempts = AtomicUsize::new(0);
+        let winner = hedge(&window, || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt == 0 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(attempt)
+            } else {
+                Err(anyhow::anyhow!("throttled"))
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(winner, 0);
+
+        // Fast reads never hedge.
+        let attempts = AtomicUsize::new(0);
+        hedge(&window, || async { Ok(attempts.fetch_add(1, Ordering::SeqCst)) }).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
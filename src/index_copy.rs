@@ -0,0 +1,133 @@
+use crate::jobs::CancelToken;
+use crate::minio::S3Client;
+use crate::trash::{index_prefixes, record_prefixes};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+/// Objects whose content names the index and must be rewritten, not byte-copied.
+fn needs_rewrite(key: &str, index: &str) -> bool {
+    key == format!("indexes/{}/manifest.json", index) || key == format!("indexes/{}/config.json", index)
+}
+
+/// Map a key of index `src` to the matching key of index `dst`.
+pub fn translate_key(key: &str, src: &str, dst: &str) -> Option<String> {
+    index_prefixes(src)
+        .into_iter()
+        .chain(record_prefixes(src))
+        .zip(index_prefixes(dst).into_iter().chain(record_prefixes(dst)))
+        .find_map(|(from, to)| key.strip_prefix(&from).map(|rest| format!("{}{}", to, rest)))
+}
+
+/// Point a manifest or index config at a different index name: the `name` /
+/// `index_name` fields and every shard path inside the manifest.
+pub fn translate_document(doc: &mut Value, src: &str, dst: &str) {
+    for field in ["name", "index_name"] {
+        if doc.get(field).and_then(|v| v.as_str()) == Some(src) {
+            doc[field] = Value::String(dst.to_string());
+        }
+    }
+    if let Some(shards) = doc.get_mut("shards").and_then(|s| s.as_array_mut()) {
+        for shard in shards {
+            for field in ["index_path", "metadata_path", "vectors_path", "text_path"] {
+                let translated = shard
+                    .get(field)
+                    .and_then(|v| v.as_str())
+                    .and_then(|path| translate_key(path, src, dst));
+                if let Some(path) = translated {
+                    shard[field] = Value::String(path);
+                }
+            }
+        }
+    }
+}
+
+/// Duplicate index `src` as `dst` with server-side copies, its records within
+/// `vector_bucket`. Manifest and config are rewritten last so `dst` only
+/// becomes queryable once all shards are in place, which also makes cancelling
+/// between objects safe.
+pub async fn copy_index(s3: &S3Client, src: &str, dst: &str, vector_bucket: &str, cancel: &CancelToken) -> Result<usize> {
+    if s3.get_object(&format!("indexes/{}/config.json", src)).await.is_err() {
+        return Err(anyhow::anyhow!("Index {} not found", src));
+    }
+    if s3.get_object(&format!("indexes/{}/config.json", dst)).await.is_ok() {
+        return Err(anyhow::anyhow!("Index {} already exists", dst));
+    }
+
+    let mut documents = Vec::new();
+    let mut objects = Vec::new();
+    for prefix in index_prefixes(src) {
+        for key in s3.list_objects(&prefix).await? {
+            if needs_rewrite(&key, src) {
+                documents.push(key);
+            } else {
+                objects.push((s3.bucket(), key));
+            }
+        }
+    }
+    for prefix in record_prefixes(src) {
+        objects.extend(s3.list_bucket_objects(vector_bucket, &prefix).await?.into_iter().map(|key| (vector_bucket, key)));
+    }
+
+    let total = objects.len() + documents.len();
+    for (copied, (bucket, key)) in objects.iter().enumerate() {
+        cancel.check()?;
+        if let Some(target) = translate_key(key, src, dst) {
+            s3.copy_bucket_object(bucket, key, &target).await?;
+        }
+        crate::jobs::report_progress(json!({"phase": "copying", "objectsCopied": copied + 1, "objects": total}));
+    }
+    // Manifest before config: the config is what makes the index visible.
+    documents.sort_by_key(|key| key.ends_with("/config.json"));
+    for key in &documents {
+        let mut doc: Value = serde_json::from_slice(&s3.get_object(key).await?)
+            .with_context(|| format!("Failed to parse {}", key))?;
+        translate_document(&mut doc, src, dst);
+        let target = translate_key(key, src, dst).context("Unexpected index document key")?;
+        s3.put_object(&target, serde_json::to_vec(&doc)?.into()).await?;
+    }
+
+    let copied = objects.len() + documents.len();
+    tracing::info!("Copied index {} to {} ({} objects)", src, dst, copied);
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_translate_manifest() {
+        let mut manifest = json!({
+            "index_name": "prod",
+            "shards": [{
+                "shard_id": "s1",
+                "index_path": "indexes/prod/shards/s1/index.faiss",
+                "metadata_path": "indexes/prod/shards/s1/metadata.json"
+            }]
+        });
+        translate_document(&mut manifest, "prod", "staging");
+        assert_eq!(manifest["index_name"], "staging");
+        assert_eq!(manifest["shards"][0]["index_path"], "indexes/staging/shards/s1/index.faiss");
+        assert_eq!(manifest["shards"][0]["metadata_path"], "indexes/staging/shards/s1/metadata.json");
+
+        assert_eq!(translate_key("prod/vectors/a.json", "prod", "staging").as_deref(), Some("staging/vectors/a.json"));
+        assert_eq!(translate_key("indexes/production/config.json", "prod", "staging"), None);
+    }
+
+    #[tokio::test]
+    async fn test_copy_includes_records_in_the_vector_bucket() {
+        let s3 = S3Client::in_memory("vectors");
+        let config = json!({"name": "prod", "dim": 2, "metric": "cosine", "nlist": 1, "m": 1, "nbits": 8, "default_nprobe": 1});
+        s3.put_object("indexes/prod/config.json", config.to_string().into()).await.unwrap();
+        s3.put_object("indexes/prod/shards/s1/index.faiss", "x".into()).await.unwrap();
+        s3.put_bucket_object("mine", "prod/vectors/a b.json", "{}".into()).await.unwrap();
+
+        let copied = copy_index(&s3, "prod", "staging", "mine", &CancelToken::default()).await.unwrap();
+        assert_eq!(copied, 3);
+        assert!(s3.get_bucket_object("mine", "staging/vectors/a b.json").await.is_ok());
+        assert!(s3.get_object("indexes/staging/shards/s1/index.faiss").await.is_ok());
+        let config: Value = serde_json::from_slice(&s3.get_object("indexes/staging/config.json").await.unwrap()).unwrap();
+        assert_eq!(config["name"], "staging");
+    }
+}
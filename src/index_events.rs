@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+/// Events a slow subscriber may fall behind by before it skips ahead.
+const EVENT_BUFFER: usize = 1024;
+
+/// Why an index's manifest changed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexEventKind {
+    /// New shards were built and added to the manifest.
+    Indexed,
+    /// The manifest changed some other way, e.g. shards were dropped or the
+    /// manifest was rewritten by another process.
+    ManifestChanged,
+}
+
+/// A new manifest version of an index, pushed to `GET /events` subscribers
+/// so caches of the index (its query results, its shard list) know to
+/// refresh. Every event means the manifest changed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexEvent {
+    #[serde(rename = "type")]
+    pub kind: IndexEventKind,
+    pub index: String,
+    /// Digest of the manifest as written; equal on every replica that has
+    /// read the same manifest.
+    pub manifest_version: String,
+    pub shards: usize,
+    pub total_vectors: usize,
+    pub at: DateTime<Utc>,
+}
+
+impl IndexEvent {
+    /// Event for `manifest`, the bytes of `index`'s new manifest.
+    pub fn new(kind: IndexEventKind, index: &str, manifest: &[u8]) -> Self {
+        let parsed: Value = serde_json::from_slice(manifest).unwrap_or_default();
+        IndexEvent {
+            kind,
+            index: index.to_string(),
+            manifest_version: manifest_version(manifest),
+            shards: parsed.get("shards").and_then(Value::as_array).map_or(0, Vec::len),
+            total_vectors: parsed.get("total_vectors").and_then(Value::as_u64).unwrap_or(0) as usize,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// Short content digest of a manifest.
+pub fn manifest_version(manifest: &[u8]) -> String {
+    crate::integrity::sha256_hex(manifest)[..16].to_string()
+}
+
+fn channel() -> &'static broadcast::Sender<IndexEvent> {
+    static CHANNEL: OnceLock<broadcast::Sender<IndexEvent>> = OnceLock::new();
+    CHANNEL.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+/// Tell this process's subscribers about `event`; a no-op without any.
+pub fn publish(event: IndexEvent) {
+    tracing::debug!(index = %event.index, kind = ?event.kind, version = %event.manifest_version, "Index event");
+    let _ = channel().send(event);
+}
+
+/// Events published from now on. Leaders publish the manifests they write;
+/// followers the changed manifests their poll picks up.
+pub fn subscribe() -> broadcast::Receiver<IndexEvent> {
+    channel().subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_describe_the_manifest() {
+        let before = br#"{"shards": [{"shard_id": "s1"}], "total_vectors": 10}"#;
+        let after = br#"{"shards": [{"shard_id": "s1"}, {"shard_id": "s2"}], "total_vectors": 25}"#;
+        let event = IndexEvent::new(IndexEventKind::Indexed, "docs", after);
+        assert_eq!((event.kind, event.shards, event.total_vectors), (IndexEventKind::Indexed, 2, 25));
+        assert_eq!(event.manifest_version, manifest_version(after));
+        assert_ne!(event.manifest_version, manifest_version(before));
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "Indexed");
+        assert_eq!(json["totalVectors"], 25);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_published_events() {
+        let mut events = subscribe();
+        publish(IndexEvent::new(IndexEventKind::ManifestChanged, "index-events-test", b"{}"));
+        // Other tests may publish concurrently.
+        loop {
+            let event = events.recv().await.unwrap();
+            if event.index == "index-events-test" {
+                assert_eq!(event.kind, IndexEventKind::ManifestChanged);
+                break;
+            }
+        }
+    }
+}
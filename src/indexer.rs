@@ -1,35 +1,67 @@
 use crate::{minio::S3Client, model::*};
 use crate::faiss_utils::{
-    build_hnsw_flat_index, build_ivfpq_index, calculate_optimal_nlist,
-    calculate_optimal_pq_params,
+    add_vectors, build_flat_index, build_hnsw_flat_index, build_ivfpq_index, calculate_optimal_training_size,
+    canonical_metric, index_has_metric, key_id, training_seed, SplitMix64,
 };
+use crate::build_queue::{self, BuildQueue};
+use crate::checkpoint::{Checkpoint, Row, Tracker};
+use crate::compression::{CompressionConfig, ContentEncoding};
+use crate::index_events::{self, IndexEvent, IndexEventKind};
+use crate::integrity::{sha256_file, sha256_hex, ShardChecksums};
 use crate::metrics::get_metrics_collector;
+use crate::quantizer::SharedQuantizer;
+use crate::spool::{memory_budget_from_env, Spool};
+use crate::tempfiles::TempFile;
+use crate::raw_vectors;
+use crate::text_index::{self, TextIndex};
+use crate::tuning::{self, DataProfile, IndexParameters, Thresholds};
 use anyhow::{Context, Result};
-use arrow::array::{Array, Float32Array, ListArray, StringArray};
-use chrono::Utc;
+use arrow::array::{Array, Float32Array, ListArray, RecordBatch, StringArray, TimestampNanosecondArray};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rayon::prelude::*;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::fs::File;
+use std::io::{BufRead, BufReader};
+use tracing::Instrument;
 use uuid::Uuid;
 
 pub async fn run_once() -> Result<()> {
     let _bucket = std::env::var("VEC_BUCKET")?;
     let s3 = S3Client::from_env().await?;
+    run(&s3).await
+}
 
-    let staged_objects = s3.list_objects("staged/").await?;
-    let mut index_slices: HashMap<String, Vec<String>> = HashMap::new();
+/// One indexer pass over `s3`: build every staged slice into shards, in the
+/// order of [`crate::build_queue`], and purge deleted indexes whose retention
+/// has expired.
+pub async fn run(s3: &S3Client) -> Result<()> {
+    let staged_objects = s3.list_objects_modified("staged/").await?;
+    let mut index_slices: HashMap<String, Vec<(String, DateTime<Utc>)>> = HashMap::new();
 
-    for object_key in staged_objects {
+    for (object_key, staged_at) in staged_objects {
         if let Some(index_name) = extract_index_name_from_path(&object_key) {
-            index_slices.entry(index_name).or_default().push(object_key);
+            index_slices.entry(index_name).or_default().push((object_key, staged_at));
         }
     }
 
-    for (index_name, slice_paths) in index_slices {
-        if !slice_paths.is_empty() {
-            process_index_slices(&s3, &index_name, slice_paths).await?;
-        }
+    let mut queue = BuildQueue::new(build_queue::slices_per_turn());
+    for (index_name, slices) in index_slices {
+        let priority = build_queue::priority(s3, &index_name).await;
+        queue.push(index_name, priority, slices);
+    }
+    while let Some(turn) = queue.next_turn(Utc::now()) {
+        let waited = (Utc::now() - turn.waiting_since).num_milliseconds().max(0);
+        get_metrics_collector().track_metric("indexer.turn_wait_ms", waited as f64);
+        tracing::info!(index = %turn.index, slices = turn.slices.len(), remaining = turn.remaining, waited_ms = waited, "Indexing turn");
+        process_index_slices(s3, &turn.index, turn.slices).await?;
+    }
+
+    match crate::trash::purge_expired(s3).await {
+        Ok(0) => {}
+        Ok(purged) => tracing::info!("Purged {} expired deleted indexes", purged),
+        Err(e) => tracing::warn!("Failed to purge deleted indexes: {}", e),
     }
 
     Ok(())
@@ -51,95 +83,238 @@ async fn process_index_slices(
     slice_paths: Vec<String>,
 ) -> Result<()> {
     let _measurement = crate::measure_operation!("indexer.process_index_slices");
-    tracing::info!(
-        "Processing {} slices for index {}",
-        slice_paths.len(),
-        index_name
-    );
+    tracing::info!(index = index_name, slices = slice_paths.len(), "Processing slices");
 
     get_metrics_collector().track_metric("indexer.slices_count", slice_paths.len() as f64);
 
-    let mut all_vectors = Vec::new();
-    let mut metadata = HashMap::new();
-    let mut vector_ids = Vec::new();
-
-    let estimated_capacity = slice_paths.len() * 1000;
-    all_vectors.reserve(estimated_capacity);
-    metadata.reserve(estimated_capacity);
-    vector_ids.reserve(estimated_capacity);
-
+    // Slices are read one at a time into a spool that keeps what fits the
+    // memory budget and spills the rest to disk; shards are then built a
+    // budget's worth at a time, so backlogs of any size fit in memory.
+    let mut spool = Spool::new(memory_budget_from_env());
     let load_start = std::time::Instant::now();
-    for slice_path in &slice_paths {
-        if slice_path.ends_with(".parquet") {
-            let local_path = format!("/tmp/{}", slice_path.split('/').last().unwrap_or("slice.parquet"));
-            let slice_data = s3.get_object(slice_path).await?;
-            std::fs::write(&local_path, &slice_data)?;
-            let file = File::open(&local_path)?;
-            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-            let reader = builder.build()?;
-
-            for batch in reader {
-                let batch = batch?;
-                let id_array = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
-                let embedding_array = batch.column(1).as_any().downcast_ref::<ListArray>().unwrap();
-                let meta_array = batch.column(2).as_any().downcast_ref::<StringArray>().unwrap();
-
-                for i in 0..batch.num_rows() {
-                    let id = id_array.value(i).to_string();
-                    let meta: serde_json::Value = serde_json::from_str(meta_array.value(i))?;
-                    let embedding_list = embedding_array.value(i);
-                    let embedding_values = embedding_list.as_any().downcast_ref::<Float32Array>().unwrap();
-                    let embedding: Vec<f32> = embedding_values.values().to_vec();
-                    all_vectors.push(embedding);
-                    metadata.insert(id.clone(), meta);
-                    vector_ids.push(id);
+    let deletions = crate::deletions::load(s3, index_name).await?;
+    // A run that stopped partway left the shards it stored; they are added
+    // now and their rows skipped.
+    let checkpoint = Checkpoint::load(s3, index_name).await.unwrap_or_default();
+    if !checkpoint.shards.is_empty() {
+        tracing::info!(index = index_name, shards = checkpoint.shards.len(), "Resuming an interrupted indexing run");
+        add_checkpointed_shards(s3, index_name, &checkpoint).await?;
+    }
+    // Records must have the index's dimension, or for an index without a
+    // config yet that of the first slice loaded.
+    let mut dim = configured_dimension(s3, index_name).await?;
+    let run_slices = slice_paths.clone();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut loaded = Vec::with_capacity(slice_paths.len());
+    let slices = slice_paths.len();
+    for (read, slice_path) in slice_paths.into_iter().enumerate() {
+        crate::jobs::report_progress(serde_json::json!({"phase": "loading", "index": index_name, "slicesRead": read, "slices": slices}));
+        // A slice that can't be read, or holds a vector of another dimension,
+        // is quarantined; the rest of the index is still built.
+        let records = match read_slice(s3, &slice_path).await.and_then(|records| check_dimensions(&slice_path, records, &mut dim)) {
+            Ok(records) => records,
+            Err(e) => {
+                tracing::error!(index = index_name, slice = %slice_path, error = %format!("{:#}", e), "Quarantining unusable slice");
+                get_metrics_collector().track_metric("indexer.slices_quarantined", 1.0);
+                if let Err(e) = crate::quarantine::quarantine(s3, index_name, &slice_path, &e).await {
+                    tracing::warn!(index = index_name, slice = %slice_path, error = %e, "Failed to quarantine slice, leaving it staged");
                 }
+                continue;
             }
-            tokio::fs::remove_file(&local_path).await?;
-        } else {
-            let slice_data = s3.get_object(slice_path).await?;
-            let slice_text = String::from_utf8(slice_data.to_vec())?;
-            for line in slice_text.lines() {
-                if !line.trim().is_empty() {
-                    let record: VectorRecord = serde_json::from_str(line)?;
-                    all_vectors.push(record.embedding.clone());
-                    metadata.insert(record.id.clone(), record.meta);
-                    vector_ids.push(record.id);
-                }
+        };
+        for (row, record) in records.into_iter().enumerate() {
+            // Held by a shard of the interrupted run, or deleted while the
+            // slice was still staged.
+            if checkpoint.holds(&slice_path, row) || deletions.is_deleted(&record.id, record.created_at) {
+                continue;
             }
+            spool.push(record)?;
+            rows.push((read, row));
         }
+        loaded.push(slice_path);
     }
+    crate::jobs::report_progress(serde_json::json!({"phase": "loading", "index": index_name, "slicesRead": slices, "slices": slices}));
 
     let load_duration = load_start.elapsed();
     get_metrics_collector()
         .track_metric("indexer.vector_loading_time_ms", load_duration.as_millis() as f64);
-    get_metrics_collector().track_metric("indexer.vectors_loaded", all_vectors.len() as f64);
+    get_metrics_collector().track_metric("indexer.vectors_loaded", spool.len() as f64);
+    get_metrics_collector().track_metric("indexer.vectors_spilled", spool.spilled() as f64);
 
-    if all_vectors.is_empty() {
+    let tracker = std::sync::Arc::new(Tracker::new(s3, index_name, run_slices, checkpoint));
+    if spool.is_empty() {
         tracing::warn!("No vectors found in slices for index {}", index_name);
-        return Ok(());
+    } else {
+        if spool.spilled() > 0 {
+            tracing::info!(index = index_name, vectors = spool.len(), spilled = spool.spilled(), "Slices exceed the indexer memory budget, building in chunks");
+        }
+        let mut chunks = spool.finish()?;
+        let mut rows = rows.into_iter();
+        while let Some(records) = chunks.next_chunk()? {
+            let chunk_rows: Vec<Row> = rows.by_ref().take(records.len()).collect();
+            build_tracked_shards(s3, index_name, records, Some((&tracker, &chunk_rows))).await?;
+        }
     }
 
-    let config = get_or_create_index_config(s3, index_name, all_vectors[0].len()).await?;
-    const MAX_VECTORS_PER_SHARD: usize = 50_000;
-    let total_vectors = all_vectors.len();
+    for slice_path in &loaded {
+        s3.delete_object(slice_path).await?;
+    }
+    tracker.finish(&loaded).await?;
+
+    // Queries read every deletion object of the index; fold them into one
+    // each time its shards are rebuilt.
+    match crate::deletions::compact(s3, index_name).await {
+        Ok(0) => {}
+        Ok(folded) => tracing::info!(index = index_name, folded, "Compacted deletions"),
+        Err(e) => tracing::warn!(index = index_name, "Failed to compact deletions: {}", e),
+    }
+    Ok(())
+}
+
+/// Serializes manifest updates of this process, so shards built from slices
+/// and from bulk PutVectors calls at the same time are all kept.
+pub(crate) static MANIFEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+pub const MAX_VECTORS_PER_SHARD: usize = 50_000;
+/// Vectors below which `hybrid` indexes build HNSW shards, unless configured.
+pub const DEFAULT_HNSW_THRESHOLD: usize = 100_000;
+/// Memory a shard task holds per vector besides its Faiss build: the key,
+/// Faiss id and id map entry, and metadata as serialized for upload.
+const SHARD_TASK_ROW_BYTES: usize = 256;
+
+/// Build shards of `index_name` from `records` and add them to its manifest.
+/// Slices go through here once staged; bulk PutVectors batches come straight
+/// from the request, skipping the WAL and slice. Returns the shards built.
+pub async fn build_shards(s3: &S3Client, index_name: &str, records: Vec<VectorRecord>) -> Result<usize> {
+    build_tracked_shards(s3, index_name, records, None).await
+}
+
+/// [`build_shards`], saving each shard to the checkpoint of a run over staged
+/// slices as it is stored; `rows` are where `records` came from in the run.
+async fn build_tracked_shards(
+    s3: &S3Client,
+    index_name: &str,
+    records: Vec<VectorRecord>,
+    progress: Option<(&std::sync::Arc<Tracker>, &[Row])>,
+) -> Result<usize> {
+    let Some(dim) = records.first().map(|r| r.embedding.len()) else {
+        return Ok(0);
+    };
+    // One contiguous row-major buffer, shared by the shard tasks and handed
+    // to Faiss in place.
+    let mut all_vectors = Vec::with_capacity(records.len() * dim);
+    let mut metadata = HashMap::with_capacity(records.len());
+    let mut vector_ids = Vec::with_capacity(records.len());
+    let mut texts = Vec::with_capacity(records.len());
+    for record in records {
+        if record.embedding.len() != dim {
+            return Err(anyhow::anyhow!(
+                "Vector {} has {} dimensions, expected {}",
+                record.id,
+                record.embedding.len(),
+                dim
+            ));
+        }
+        all_vectors.extend_from_slice(&record.embedding);
+        metadata.insert(record.id.clone(), record.meta);
+        vector_ids.push(record.id);
+        texts.push(record.text);
+    }
+    let all_vectors = std::sync::Arc::new(all_vectors);
+
+    let config = get_or_create_index_config(s3, index_name, dim).await?;
+    let projection = match config.reduction {
+        Some(reduction) => crate::reduction::ensure(s3, index_name, dim, reduction, &all_vectors).await?,
+        None => None,
+    };
+    let (indexed_vectors, indexed_dim) = match &projection {
+        Some(projection) => {
+            let (projection, all_vectors) = (projection.clone(), all_vectors.clone());
+            let dim_out = projection.dim_out();
+            (std::sync::Arc::new(crate::governor::run(move || projection.apply(&all_vectors)).await), dim_out)
+        }
+        None => (all_vectors.clone(), dim),
+    };
+    let total_vectors = vector_ids.len();
+    // Profile what Faiss will index and settle the structure for every shard
+    // of the build in one place.
+    let existing_vectors = load_or_create_manifest(s3, index_name, &config).await?.total_vectors;
+    let profile = DataProfile::measure(
+        &indexed_vectors,
+        indexed_dim,
+        existing_vectors + total_vectors,
+        &mut SplitMix64::new(training_seed()),
+    );
+    let mut recommendation = tuning::recommend(
+        &profile,
+        &config.metric,
+        config.algorithm.as_deref(),
+        Thresholds {
+            flat: config.flat_threshold.unwrap_or(tuning::DEFAULT_FLAT_THRESHOLD),
+            hnsw: config.hnsw_threshold.unwrap_or(DEFAULT_HNSW_THRESHOLD),
+        },
+    );
+    if let Some(settings) = &config.ivfpq {
+        recommendation.pin(settings);
+    }
+    tracing::info!(
+        index = index_name,
+        algorithm = recommendation.parameters.algorithm(),
+        effective_dimension = profile.effective_dimension,
+        rationale = ?recommendation.rationale,
+        "Chose index parameters"
+    );
+    let quantizer = match recommendation.parameters {
+        IndexParameters::Ivfpq { m, nbits, .. } => crate::quantizer::ensure(
+            s3,
+            index_name,
+            indexed_dim as u32,
+            &config.metric,
+            indexed_vectors.clone(),
+            MAX_VECTORS_PER_SHARD,
+            (config.ivfpq.and_then(|settings| settings.nlist).map(|nlist| nlist as usize), m, nbits),
+        )
+        .await?
+        .map(std::sync::Arc::new),
+        _ => None,
+    };
     let num_shards = (total_vectors + MAX_VECTORS_PER_SHARD - 1) / MAX_VECTORS_PER_SHARD;
     get_metrics_collector().track_metric("indexer.shards_created", num_shards as f64);
     get_metrics_collector()
         .track_metric("indexer.vectors_per_shard", (total_vectors as f64) / (num_shards as f64));
-    let max_concurrent_shards = std::cmp::min(num_shards, num_cpus::get().max(1));
+    let max_concurrent_shards = max_concurrent_shards(num_shards);
+    let build_threads = crate::governor::build_threads(max_concurrent_shards);
+    let shard_memory = shard_task_memory(
+        &recommendation.parameters,
+        total_vectors.min(MAX_VECTORS_PER_SHARD),
+        dim,
+        indexed_dim,
+        config.store_raw_vectors,
+    );
+    get_metrics_collector().track_metric("indexer.shard_memory_estimate_mb", (shard_memory / (1024 * 1024)) as f64);
     let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(max_concurrent_shards));
+    let reporter = crate::jobs::reporter();
+    let shards_built = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
     tracing::info!(
-        "Processing {} shards in parallel with max {} concurrent tasks",
+        "Processing {} shards in parallel with max {} concurrent tasks, about {} MB each ({} MB together)",
         num_shards,
-        max_concurrent_shards
+        max_concurrent_shards,
+        shard_memory / (1024 * 1024),
+        shard_memory * max_concurrent_shards / (1024 * 1024)
     );
     let mut shard_tasks = Vec::new();
     for shard_index in 0..num_shards {
         let start_idx = shard_index * MAX_VECTORS_PER_SHARD;
         let end_idx = std::cmp::min(start_idx + MAX_VECTORS_PER_SHARD, total_vectors);
-        let shard_vectors = all_vectors[start_idx..end_idx].to_vec();
+        let shard_vectors = ShardVectors {
+            all: all_vectors.clone(),
+            dim,
+            indexed: indexed_vectors.clone(),
+            indexed_dim,
+            rows: start_idx..end_idx,
+        };
         let shard_ids_slice = vector_ids[start_idx..end_idx].to_vec();
+        let shard_texts = texts[start_idx..end_idx].to_vec();
         let shard_metadata: HashMap<String, Value> = shard_ids_slice
             .iter()
             .filter_map(|id| metadata.get(id).map(|meta| (id.clone(), meta.clone())))
@@ -149,46 +324,322 @@ async fn process_index_slices(
         let index_name_clone = index_name.to_string();
         let config_clone = config.clone();
         let semaphore_clone = semaphore.clone();
+        let parameters = recommendation.parameters.clone();
+        let quantizer_clone = quantizer.clone();
+        let projection_file = projection.as_ref().map(|p| p.file.clone());
+        let reporter = reporter.clone();
+        let shards_built = shards_built.clone();
+        let index_progress = index_name.to_string();
+        let checkpoint = progress.map(|(tracker, rows)| (tracker.clone(), rows[start_idx..end_idx].to_vec()));
         let task = tokio::spawn(async move {
             let _permit = semaphore_clone.acquire().await.unwrap();
-            process_single_shard(
+            crate::governor::yield_to_queries().await;
+            let built = process_single_shard(
                 s3_clone,
                 index_name_clone,
                 shard_id,
                 shard_vectors,
                 shard_ids_slice,
                 shard_metadata,
+                shard_texts,
                 config_clone,
+                parameters,
+                quantizer_clone,
+                projection_file,
+                build_threads,
                 shard_index,
                 num_shards,
             )
-            .await
-        });
+            .await;
+            if let (Some(reporter), Ok(_)) = (&reporter, &built) {
+                let done = shards_built.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                reporter.progress(serde_json::json!({"phase": "building", "index": index_progress, "shardsBuilt": done, "shards": num_shards}));
+            }
+            // A failed save only costs a rebuild of the shard if the run dies.
+            if let (Some((tracker, rows)), Ok(shard)) = (&checkpoint, &built) {
+                if let Err(e) = tracker.shard_stored(dim, shard.clone(), rows).await {
+                    tracing::warn!(index = %index_progress, shard = %shard.shard_id, error = %format!("{:#}", e), "Failed to checkpoint shard");
+                }
+            }
+            built
+        }.in_current_span());
         shard_tasks.push(task);
     }
     let shard_results: Result<Vec<_>, _> = futures::future::try_join_all(shard_tasks).await;
     let shard_infos = shard_results.context("Failed to process shards in parallel")?;
-    let mut final_manifest = load_or_create_manifest(s3, index_name, &config).await?;
-    for shard_info_result in shard_infos {
-        let shard_info = shard_info_result?;
-        final_manifest.total_vectors += shard_info.vector_count;
-        final_manifest.shards.push(shard_info);
+    let shard_infos = shard_infos.into_iter().collect::<Result<Vec<_>>>()?;
+    let _manifest_guard = MANIFEST_LOCK.lock().await;
+    let manifest = load_or_create_manifest(s3, index_name, &config).await?;
+    add_shards(s3, index_name, &config, manifest, shard_infos, Some(recommendation)).await?;
+
+    tracing::info!(
+        index = index_name,
+        vectors = total_vectors,
+        shards = num_shards,
+        "Built shards"
+    );
+    Ok(num_shards)
+}
+
+/// Add `shards` to `manifest` and store it, along with the tuning of the
+/// build that made them if given. Callers hold [`MANIFEST_LOCK`].
+async fn add_shards(
+    s3: &S3Client,
+    index_name: &str,
+    config: &CreateIndex,
+    mut manifest: IndexManifest,
+    shards: Vec<ShardInfo>,
+    recommendation: Option<tuning::Recommendation>,
+) -> Result<()> {
+    if canonical_metric(&manifest.metric) != canonical_metric(&config.metric) {
+        return Err(anyhow::anyhow!(
+            "Index {} has shards built with the {} metric but its config now says {}; not adding shards of another metric",
+            index_name,
+            manifest.metric,
+            config.metric
+        ));
+    }
+    for shard in shards {
+        manifest.total_vectors += shard.vector_count;
+        manifest.shards.push(shard);
     }
+    if recommendation.is_some() {
+        manifest.tuning = recommendation;
+    }
+    manifest.schema_version = crate::schema::MANIFEST_SCHEMA_VERSION;
     let manifest_key = format!("indexes/{}/manifest.json", index_name);
-    let manifest_data = serde_json::to_vec(&final_manifest)?;
+    let manifest_data = serde_json::to_vec(&manifest)?;
+    let event = IndexEvent::new(IndexEventKind::Indexed, index_name, &manifest_data);
     s3.put_object(&manifest_key, manifest_data.into()).await?;
+    index_events::publish(event);
+    Ok(())
+}
+
+/// Add the shards an interrupted run stored that its manifest doesn't list.
+async fn add_checkpointed_shards(s3: &S3Client, index_name: &str, checkpoint: &Checkpoint) -> Result<()> {
+    let _manifest_guard = MANIFEST_LOCK.lock().await;
+    let config = get_or_create_index_config(s3, index_name, checkpoint.dim).await?;
+    let manifest = load_or_create_manifest(s3, index_name, &config).await?;
+    let missing: Vec<ShardInfo> = checkpoint
+        .shards
+        .iter()
+        .filter(|shard| !manifest.shards.iter().any(|listed| listed.shard_id == shard.shard_id))
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(index = index_name, shards = missing.len(), "Adding shards of an interrupted indexing run");
+    add_shards(s3, index_name, &config, manifest, missing, None).await
+}
 
-    for slice_path in slice_paths {
-        s3.delete_object(&slice_path).await?;
+/// Outcome of [`repair_metrics`].
+#[derive(Debug, Default)]
+pub struct MetricRepair {
+    pub metric: String,
+    /// Shards whose recorded metric was rewritten.
+    pub relabeled: Vec<String>,
+    /// Shards whose Faiss index uses another metric.
+    pub mismatched: Vec<String>,
+    /// Whether `mismatched` shards were dropped from the manifest.
+    pub dropped: bool,
+}
+
+/// Bring `index_name`'s manifest in line with the metric of its config, for
+/// manifests written before queries refused mixed metrics. Shards recorded
+/// under another spelling of the metric (`L2`, `COSINE`), or whose Faiss
+/// index turns out to use it anyway, are relabeled. Shards really built with
+/// another metric can't be searched alongside the rest: the repair fails
+/// naming them, or with `drop_mismatched` removes them from the manifest so
+/// their vectors can be put again. Nothing is written on a `dry_run`.
+pub async fn repair_metrics(s3: &S3Client, index_name: &str, drop_mismatched: bool, dry_run: bool) -> Result<MetricRepair> {
+    let _manifest_guard = MANIFEST_LOCK.lock().await;
+    let manifest_key = format!("indexes/{}/manifest.json", index_name);
+    let data = s3.get_object(&manifest_key).await.context("Index has no manifest")?;
+    let mut manifest = IndexManifest::from_slice(&data).context("Failed to parse manifest")?;
+    let configured = match s3.get_object(&format!("indexes/{}/config.json", index_name)).await {
+        Ok(data) => CreateIndex::from_slice(&data).context("Failed to parse index config")?.metric,
+        Err(_) => manifest.metric.clone(),
+    };
+    let metric = canonical_metric(&configured)
+        .with_context(|| format!("Index {} has unsupported distance metric '{}'", index_name, configured))?;
+
+    let mut repair = MetricRepair { metric: metric.to_string(), ..Default::default() };
+    for shard in &mut manifest.shards {
+        if shard.metric == metric {
+            continue;
+        }
+        let same_metric = canonical_metric(&shard.metric) == Some(metric) || {
+            let local = TempFile::new("repair", ".faiss");
+            s3.get_object_to_file(&shard.index_path, local.path()).await?;
+            let path = local.path().to_string();
+            crate::faiss_pool::run("read", move || Ok(index_has_metric(&faiss::read_index(&path)?, metric))).await?
+        };
+        if same_metric {
+            tracing::info!(index = index_name, shard = %shard.shard_id, from = %shard.metric, to = metric, "Relabeling shard metric");
+            shard.metric = metric.to_string();
+            repair.relabeled.push(shard.shard_id.clone());
+        } else {
+            repair.mismatched.push(shard.shard_id.clone());
+        }
     }
+    if !repair.mismatched.is_empty() {
+        if !drop_mismatched {
+            return Err(anyhow::anyhow!(
+                "Shards {:?} of index {} were built with a metric other than {}; re-run with --drop-mismatched to remove them and put their vectors again",
+                repair.mismatched,
+                index_name,
+                metric
+            ));
+        }
+        manifest.shards.retain(|shard| {
+            let keep = !repair.mismatched.contains(&shard.shard_id);
+            if !keep {
+                tracing::warn!(index = index_name, shard = %shard.shard_id, vectors = shard.vector_count, "Dropping shard built with another metric");
+            }
+            keep
+        });
+        manifest.total_vectors = manifest.shards.iter().map(|shard| shard.vector_count).sum();
+        repair.dropped = true;
+    }
+    let changed = manifest.metric != metric || !repair.relabeled.is_empty() || repair.dropped;
+    manifest.metric = metric.to_string();
+    manifest.schema_version = crate::schema::MANIFEST_SCHEMA_VERSION;
+    if changed && !dry_run {
+        s3.put_object(&manifest_key, serde_json::to_vec(&manifest)?.into()).await?;
+    }
+    Ok(repair)
+}
 
-    tracing::info!(
-        "Successfully processed {} vectors for index {} into {} shards",
-        all_vectors.len(),
-        index_name,
-        num_shards
-    );
-    Ok(())
+/// Load every record of a staged slice (parquet or, possibly compressed, JSONL).
+/// Dimension in the config of `index_name`; `None` until it has one.
+async fn configured_dimension(s3: &S3Client, index_name: &str) -> Result<Option<usize>> {
+    match s3.get_object(&format!("indexes/{}/config.json", index_name)).await {
+        Ok(data) => Ok(Some(CreateIndex::from_slice(&data).context("Failed to parse index config")?.dim as usize)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// `records` of `slice_path` if each has `dim` dimensions; an unknown `dim`
+/// is taken from the first record of a slice that passes.
+fn check_dimensions(slice_path: &str, records: Vec<VectorRecord>, dim: &mut Option<usize>) -> Result<Vec<VectorRecord>> {
+    let expected = dim.or_else(|| records.first().map(|r| r.embedding.len()));
+    if let Some(record) = records.iter().find(|r| Some(r.embedding.len()) != expected) {
+        return Err(anyhow::anyhow!(
+            "Vector {} in slice {} has {} dimensions, expected {}",
+            record.id,
+            slice_path,
+            record.embedding.len(),
+            expected.unwrap_or_default()
+        ));
+    }
+    *dim = expected;
+    Ok(records)
+}
+
+pub(crate) async fn read_slice(s3: &S3Client, slice_path: &str) -> Result<Vec<VectorRecord>> {
+    let data = s3.get_object(slice_path).await?;
+    decode_slice(slice_path, data)
+}
+
+/// Records of the staged slice `data` read from `slice_path`, whose name
+/// gives the format. Slices that aren't what the ingestor writes are an
+/// error naming the slice, never a panic.
+pub fn decode_slice(slice_path: &str, data: Bytes) -> Result<Vec<VectorRecord>> {
+    let records = if slice_path.ends_with(".parquet") {
+        decode_parquet_slice(data)
+    } else {
+        decode_jsonl_slice(slice_path, data)
+    };
+    records.with_context(|| format!("Failed to read slice {}", slice_path))
+}
+
+/// Records of a parquet slice. Columns are found by name, as the ingestor
+/// writes them; `created_at` and `text` may be missing from older slices.
+fn decode_parquet_slice(data: Bytes) -> Result<Vec<VectorRecord>> {
+    let mut records = Vec::new();
+    // Slices are small enough to decode straight from memory.
+    let builder = ParquetRecordBatchReaderBuilder::try_new(data)?;
+    let reader = builder.build()?;
+
+    let mut row = 0;
+    for batch in reader {
+        let batch = batch?;
+        let id_array = required_column::<StringArray>(&batch, "id", "Utf8")?;
+        let embedding_array = required_column::<ListArray>(&batch, "embedding", "List(Float32)")?;
+        // Embeddings of the whole batch are one Float32 buffer; each row is
+        // copied out of it once, by offset.
+        let embedding_values = embedding_array
+            .values()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .with_context(|| {
+                format!("Slice column embedding is {}, expected List(Float32)", embedding_array.data_type())
+            })?
+            .values();
+        let embedding_offsets = embedding_array.value_offsets();
+        let meta_array = required_column::<StringArray>(&batch, "meta", "Utf8")?;
+        let created_at_array =
+            slice_column::<TimestampNanosecondArray>(&batch, "created_at", "Timestamp(Nanosecond)")?;
+        let text_array = slice_column::<StringArray>(&batch, "text", "Utf8")?;
+
+        for i in 0..batch.num_rows() {
+            let id = id_array
+                .is_valid(i)
+                .then(|| id_array.value(i).to_string())
+                .with_context(|| format!("Slice row {} has no id", row))?;
+            let meta: serde_json::Value = if meta_array.is_null(i) {
+                serde_json::json!({})
+            } else {
+                serde_json::from_str(meta_array.value(i))
+                    .with_context(|| format!("Slice row {} ({}) has invalid metadata", row, id))?
+            };
+            let embedding_range = embedding_offsets[i] as usize..embedding_offsets[i + 1] as usize;
+            let embedding = embedding_values
+                .get(embedding_range)
+                .with_context(|| format!("Slice row {} ({}) has embedding offsets out of bounds", row, id))?
+                .to_vec();
+            let created_at = created_at_array
+                .filter(|a| a.is_valid(i))
+                .map(|a| DateTime::from_timestamp_nanos(a.value(i)))
+                .unwrap_or_else(Utc::now);
+            let text = text_array.filter(|a| a.is_valid(i)).map(|a| a.value(i).to_string());
+            records.push(VectorRecord { id, embedding, meta, created_at, text });
+            row += 1;
+        }
+    }
+    Ok(records)
+}
+
+/// Records of a JSONL slice, compressed as its extension says.
+fn decode_jsonl_slice(slice_path: &str, data: Bytes) -> Result<Vec<VectorRecord>> {
+    let mut records = Vec::new();
+    let encoding = ContentEncoding::from_key(slice_path);
+    let reader = BufReader::new(encoding.reader(data.as_ref())?);
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line).with_context(|| format!("Slice line {} is not a record", number + 1))?);
+        }
+    }
+    Ok(records)
+}
+
+/// The slice column `name` as the array type the ingestor writes it with
+/// (`expected`, for the error), or `None` when the slice has no such column.
+fn slice_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str, expected: &str) -> Result<Option<&'a T>> {
+    let Some(column) = batch.column_by_name(name) else {
+        return Ok(None);
+    };
+    column
+        .as_any()
+        .downcast_ref::<T>()
+        .map(Some)
+        .with_context(|| format!("Slice column {} is {}, expected {}", name, column.data_type(), expected))
+}
+
+fn required_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str, expected: &str) -> Result<&'a T> {
+    slice_column(batch, name, expected)?.with_context(|| format!("Slice has no {} column", name))
 }
 
 fn extract_index_name_from_path(path: &str) -> Option<String> {
@@ -204,46 +655,32 @@ async fn get_or_create_index_config(
     s3: &S3Client,
     index_name: &str,
     dimension: usize,
-) -> Result<IndexConfig> {
+) -> Result<CreateIndex> {
     let config_key = format!("indexes/{}/config.json", index_name);
     match s3.get_object(&config_key).await {
         Ok(data) => {
-            let config: IndexConfig =
-                serde_json::from_slice(&data).context("Failed to parse index config")?;
+            let config =
+                CreateIndex::from_slice(&data).context("Failed to parse index config")?;
             tracing::info!("Loaded existing index config for index: {}", index_name);
             Ok(config)
         }
         Err(e) => {
             tracing::warn!("Failed to load index config: {}, creating optimized config based on dataset characteristics", e);
-            
-            // Try to load the CreateIndex config to get metadata configuration
-            let create_index_config_key = format!("indexes/{}/config.json", index_name);
-            let non_filterable_keys = match s3.get_object(&create_index_config_key).await {
-                Ok(data) => {
-                    if let Ok(create_index) = serde_json::from_slice::<crate::model::CreateIndex>(&data) {
-                        create_index.non_filterable_metadata_keys
-                    } else {
-                        Vec::new()
-                    }
-                }
-                Err(_) => Vec::new(),
-            };
-            
-            // Estimate total dataset size from previous manifests or current batch
-            let estimated_total_vectors = estimate_total_dataset_size(s3, index_name, dimension * 100).await;
-            
-            // Calculate optimal parameters for real Faiss IVF-PQ based on estimated size
-            let optimal_nlist = calculate_optimal_nlist(estimated_total_vectors);
-            let (optimal_m, optimal_nbits) = calculate_optimal_pq_params(dimension, 0.85);
-            let feasible_nlist = std::cmp::min(optimal_nlist, dimension / 4);
-            let config = IndexConfig {
+
+            // The same placeholders CreateIndex records; builds choose the
+            // parameters they use from the data (see `crate::tuning`).
+            let (nlist, m, nbits) = tuning::defaults(dimension);
+            let config = CreateIndex {
+                schema_version: crate::schema::CONFIG_SCHEMA_VERSION,
                 name: index_name.to_string(),
                 dim: dimension as u32,
                 metric: "cosine".to_string(),
-                nlist: feasible_nlist as u32,
-                m: optimal_m as u32,
-                nbits: optimal_nbits as u32,
-                non_filterable_metadata_keys: non_filterable_keys,
+                nlist: nlist as u32,
+                m: m as u32,
+                nbits: nbits as u32,
+                default_nprobe: Some(crate::faiss_utils::DEFAULT_NPROBE as u32),
+                store_raw_vectors: true,
+                ..Default::default()
             };
             let config_data = serde_json::to_vec(&config)?;
             s3.put_object(&config_key, config_data.into()).await?;
@@ -253,83 +690,133 @@ async fn get_or_create_index_config(
     }
 }
 
-async fn estimate_total_dataset_size(
-    s3: &S3Client,
-    index_name: &str,
-    default_estimate: usize,
-) -> usize {
-    let manifest_key = format!("indexes/{}/manifest.json", index_name);
-    match s3.get_object(&manifest_key).await {
-        Ok(data) => {
-            if let Ok(manifest) = serde_json::from_slice::<IndexManifest>(&data) {
-                let projected_size = (manifest.total_vectors as f64 * 1.5) as usize;
-                return projected_size.max(1000);
-            }
-        }
-        Err(_) => {
-            if let Ok(staged_objects) = s3.list_objects("staged/").await {
-                let staged_count = staged_objects.len();
-                if staged_count > 0 {
-                    return (staged_count * 1000).max(1000);
-                }
-            }
-        }
+/// Shards a build works on at once: at most one per indexing thread, fewer
+/// with `INDEXER_MAX_CONCURRENT_SHARDS` for pods short of memory.
+fn max_concurrent_shards(num_shards: usize) -> usize {
+    let configured = std::env::var("INDEXER_MAX_CONCURRENT_SHARDS").ok().and_then(|v| v.trim().parse::<usize>().ok());
+    num_shards.min(crate::governor::indexing_threads()).min(configured.filter(|&n| n > 0).unwrap_or(usize::MAX))
+}
+
+/// Rough peak memory, in bytes, of one shard task of `rows` vectors: the
+/// Faiss build over `indexed_dim` dimensions, its rows' keys and metadata,
+/// and the raw vector column when the index stores one.
+fn shard_task_memory(parameters: &IndexParameters, rows: usize, dim: usize, indexed_dim: usize, store_raw_vectors: bool) -> usize {
+    let raw_vectors = if store_raw_vectors { rows * dim * 4 } else { 0 };
+    parameters.build_memory(rows, indexed_dim) + rows * SHARD_TASK_ROW_BYTES + raw_vectors
+}
+
+/// Rows of one shard within the buffer of a whole build.
+#[derive(Clone)]
+struct ShardVectors {
+    all: std::sync::Arc<Vec<f32>>,
+    dim: usize,
+    /// What Faiss indexes: `all` projected to `indexed_dim` dimensions when
+    /// the index reduces them, otherwise `all` itself.
+    indexed: std::sync::Arc<Vec<f32>>,
+    indexed_dim: usize,
+    rows: std::ops::Range<usize>,
+}
+
+impl ShardVectors {
+    fn as_slice(&self) -> &[f32] {
+        &self.all[self.rows.start * self.dim..self.rows.end * self.dim]
+    }
+
+    fn indexed(&self) -> &[f32] {
+        &self.indexed[self.rows.start * self.indexed_dim..self.rows.end * self.indexed_dim]
     }
-    default_estimate
 }
 
 async fn process_single_shard(
     s3: S3Client,
     index_name: String,
     shard_id: String,
-    shard_vectors: Vec<Vec<f32>>,
+    shard_vectors: ShardVectors,
     shard_ids_slice: Vec<String>,
     shard_metadata: HashMap<String, Value>,
-    config: IndexConfig,
+    shard_texts: Vec<Option<String>>,
+    config: CreateIndex,
+    parameters: IndexParameters,
+    quantizer: Option<std::sync::Arc<SharedQuantizer>>,
+    projection: Option<String>,
+    build_threads: usize,
     shard_index: usize,
     total_shards: usize,
 ) -> Result<ShardInfo> {
     let shard_start = std::time::Instant::now();
-    let manifest = load_or_create_manifest(&s3, &index_name, &config).await?;
-    let total_vectors = manifest.total_vectors + shard_vectors.len();
-    let algorithm_name = config.algorithm.as_deref().unwrap_or("ivfpq");
-    let hnsw_threshold = config.hnsw_threshold.unwrap_or(100_000);
-    let use_hnsw = match algorithm_name {
-        "hnsw_flat" => true,
-        "ivfpq" => false,
-        "hybrid" => total_vectors < hnsw_threshold,
-        _ => false,
-    };
-
-    let (index, algorithm_used) = if use_hnsw {
-        let m = 32;
-        let index = build_hnsw_flat_index(
-            config.dim as usize,
-            &config.metric,
-            &shard_vectors,
-            m,
-        )?;
-        (index, "hnsw_flat".to_string())
-    } else {
-        let shard_nlist = calculate_optimal_nlist(shard_vectors.len());
-        let (optimal_m, optimal_nbits) =
-            calculate_optimal_pq_params(config.dim as usize, 0.85);
-        let index = build_ivfpq_index(
-            config.dim as usize,
-            shard_nlist,
-            optimal_m,
-            optimal_nbits,
-            &config.metric,
-            &shard_vectors,
-        )?;
-        (index, "ivfpq".to_string())
+    let count = shard_vectors.rows.len();
+    // Building a shard keeps cores busy for seconds to minutes, so it runs
+    // on the indexing pool rather than the threads serving queries.
+    let local = TempFile::new("shard", ".faiss");
+    let build = {
+        let shard_vectors = shard_vectors.clone();
+        let keys = shard_ids_slice.clone();
+        let metric = config.metric.clone();
+        let quantizer = quantizer.clone();
+        let shard_id = shard_id.clone();
+        let path = local.path().to_string();
+        move || -> Result<(String, String, Vec<i64>)> {
+            crate::faiss_utils::set_omp_threads(build_threads);
+            let faiss_ids: Vec<i64> = keys.par_iter().map(|key| key_id(key)).collect();
+            let indexed_dim = shard_vectors.indexed_dim;
+            let indexed_vectors = shard_vectors.indexed();
+            let (index, algorithm_used) = match (parameters, &quantizer) {
+                (IndexParameters::Flat, _) => {
+                    let index = build_flat_index(indexed_dim, &metric, indexed_vectors, &faiss_ids)?;
+                    (index, "flat".to_string())
+                }
+                (IndexParameters::HnswFlat { m }, _) => {
+                    let index = build_hnsw_flat_index(
+                        indexed_dim,
+                        &metric,
+                        indexed_vectors,
+                        &faiss_ids,
+                        m,
+                    )?;
+                    (index, "hnsw_flat".to_string())
+                }
+                (IndexParameters::Ivfpq { .. }, Some(quantizer)) => {
+                    let mut index = faiss::read_index(quantizer.local.path())?;
+                    add_vectors(&mut index, indexed_vectors, &faiss_ids)?;
+                    tracing::info!(
+                        shard = %shard_id,
+                        vectors = count,
+                        nlist = quantizer.info.nlist,
+                        "Built IVF-PQ shard on the shared quantizer"
+                    );
+                    (index, "ivfpq".to_string())
+                }
+                // The last shard of a build can be too small to train.
+                (IndexParameters::Ivfpq { .. }, None) if count < tuning::MIN_IVFPQ_VECTORS => {
+                    tracing::info!(shard = %shard_id, vectors = count, "Too few vectors to train IVF-PQ, building the shard flat");
+                    let index = build_flat_index(indexed_dim, &metric, indexed_vectors, &faiss_ids)?;
+                    (index, "flat".to_string())
+                }
+                (IndexParameters::Ivfpq { nlist, m, nbits }, None) => {
+                    // Sized for a full shard; the last one of a build may be smaller.
+                    let shard_nlist = nlist.min(tuning::nlist_for(count));
+                    let shard_nbits = nbits.min(tuning::pq_nbits(calculate_optimal_training_size(count, shard_nlist)));
+                    let index = build_ivfpq_index(
+                        indexed_dim,
+                        shard_nlist,
+                        m,
+                        shard_nbits,
+                        &metric,
+                        indexed_vectors,
+                        &faiss_ids,
+                    )?;
+                    (index, "ivfpq".to_string())
+                }
+            };
+            faiss::write_index(&index, &path)?;
+            Ok((algorithm_used, sha256_file(&path)?, faiss_ids))
+        }
     };
-
-    let local_path = format!("/tmp/{}.faiss", shard_id);
-    faiss::write_index(&index, &local_path)?;
+    let (algorithm_used, index_checksum, faiss_ids) = crate::governor::run(build).await?;
+    let shard_vectors = shard_vectors.as_slice();
     let index_object_path = format!("indexes/{}/shards/{}/index.faiss", index_name, shard_id);
-    let index_data = std::fs::read(&local_path)?;
-    s3.put_object(&index_object_path, index_data.into()).await?;
+    crate::governor::throttle_io(std::fs::metadata(local.path())?.len()).await;
+    s3.upload_file(&index_object_path, local.path()).await?;
     tracing::info!(
         "Uploaded shard {} ({}/{}): algorithm={}",
         shard_id,
@@ -337,29 +824,69 @@ async fn process_single_shard(
         total_shards,
         algorithm_used
     );
-    std::fs::remove_file(&local_path)?;
+    drop(local);
 
-    let faiss_ids: Vec<i64> = (0..shard_ids_slice.len() as i64).collect();
+    // Rows of the id map (and raw vector column) follow the order vectors
+    // were added in; the Faiss ids are hashes of their keys.
     let id_map: Vec<(i64, String)> = faiss_ids
-        .iter()
-        .cloned()
+        .into_iter()
         .zip(shard_ids_slice.iter().cloned())
         .collect();
-    let id_map_data = serde_json::to_vec(&id_map)?;
+    let compression = CompressionConfig::from_env();
+    let id_map_data = compression.compress(&serde_json::to_vec(&id_map)?)?;
+    let id_map_checksum = sha256_hex(&id_map_data);
     let id_map_path = format!("indexes/{}/shards/{}/id_map.json", index_name, shard_id);
+    crate::governor::throttle_io(id_map_data.len() as u64).await;
     s3.put_object(&id_map_path, id_map_data.into()).await?;
     let metadata_path = format!("indexes/{}/shards/{}/metadata.json", index_name, shard_id);
-    let metadata_data = serde_json::to_vec(&shard_metadata)?;
+    let metadata_data = compression.compress(&serde_json::to_vec(&shard_metadata)?)?;
+    let metadata_checksum = sha256_hex(&metadata_data);
+    crate::governor::throttle_io(metadata_data.len() as u64).await;
     s3.put_object(&metadata_path, metadata_data.into()).await?;
+    // IVF-PQ only keeps quantized codes, so full-precision embeddings for
+    // returnData and exact reranking are stored next to the index.
+    let (vectors_path, vectors_checksum) = if config.store_raw_vectors {
+        let vectors_path = format!("indexes/{}/shards/{}/{}", index_name, shard_id, raw_vectors::COLUMN_FILE);
+        let vectors_data = raw_vectors::encode_column(shard_vectors);
+        let vectors_checksum = sha256_hex(&vectors_data);
+        crate::governor::throttle_io(vectors_data.len() as u64).await;
+        s3.put_object(&vectors_path, vectors_data.into()).await?;
+        (Some(vectors_path), Some(vectors_checksum))
+    } else {
+        (None, None)
+    };
+    let (text_path, text_checksum) = match TextIndex::build(&shard_texts) {
+        Some(text_index) => {
+            let text_path = format!("indexes/{}/shards/{}/{}", index_name, shard_id, text_index::TEXT_INDEX_FILE);
+            let text_data = compression.compress(&serde_json::to_vec(&text_index)?)?;
+            let text_checksum = sha256_hex(&text_data);
+            crate::governor::throttle_io(text_data.len() as u64).await;
+            s3.put_object(&text_path, text_data.into()).await?;
+            (Some(text_path), Some(text_checksum))
+        }
+        None => (None, None),
+    };
 
     let shard_info = ShardInfo {
         shard_id: shard_id.clone(),
         index_path: index_object_path,
         metadata_path,
+        vectors_path,
+        text_path,
         vector_count: shard_ids_slice.len(),
         metric: config.metric.clone(),
         created_at: Utc::now().format("%Y%m%dT%H%M%S").to_string(),
         algorithm: algorithm_used,
+        quantizer: quantizer.map(|q| q.info.file.clone()),
+        projection,
+        checksums: Some(ShardChecksums {
+            index: index_checksum,
+            id_map: id_map_checksum,
+            metadata: metadata_checksum,
+            vectors: vectors_checksum,
+            text: text_checksum,
+        }),
+        content_encoding: compression.encoding,
     };
     let total_shard_time = shard_start.elapsed();
     tracing::info!(
@@ -375,14 +902,15 @@ async fn process_single_shard(
 async fn load_or_create_manifest(
     s3: &S3Client,
     index_name: &str,
-    config: &IndexConfig,
+    config: &CreateIndex,
 ) -> Result<IndexManifest> {
     let manifest_key = format!("indexes/{}/manifest.json", index_name);
     match s3.get_object(&manifest_key).await {
         Ok(data) => {
-            serde_json::from_slice::<IndexManifest>(&data).context("Failed to parse existing manifest")
+            IndexManifest::from_slice(&data).context("Failed to parse existing manifest")
         }
         Err(_) => Ok(IndexManifest {
+            schema_version: crate::schema::MANIFEST_SCHEMA_VERSION,
             index_name: index_name.to_string(),
             dim: config.dim,
             metric: config.metric.clone(),
@@ -390,43 +918,118 @@ async fn load_or_create_manifest(
             total_vectors: 0,
             algorithm: config.algorithm.clone(),
             hnsw_threshold: config.hnsw_threshold,
+            tuning: None,
         }),
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone)]
-struct IndexConfig {
-    name: String,
-    dim: u32,
-    metric: String,
-    nlist: u32,
-    m: u32,
-    nbits: u32,
-    #[serde(default)]
-    non_filterable_metadata_keys: Vec<String>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
 
-#[derive(serde::Deserialize, serde::Serialize)]
-struct IndexManifest {
-    index_name: String,
-    dim: u32,
-    metric: String,
-    shards: Vec<ShardInfo>,
-    total_vectors: usize,
-    #[serde(default)]
-    algorithm: Option<String>,
-    #[serde(default)]
-    hnsw_threshold: Option<usize>,
-}
+    fn parquet(columns: Vec<(&str, Arc<dyn Array>)>) -> Bytes {
+        let schema = Schema::new(
+            columns.iter().map(|(name, column)| Field::new(*name, column.data_type().clone(), true)).collect::<Vec<_>>(),
+        );
+        let batch = RecordBatch::try_new(Arc::new(schema), columns.into_iter().map(|(_, column)| column).collect()).unwrap();
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        Bytes::from(buffer)
+    }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-struct ShardInfo {
-    shard_id: String,
-    index_path: String,
-    metadata_path: String,
-    vector_count: usize,
-    metric: String,
-    created_at: String,
-    #[serde(default)]
-    algorithm: String,
+    #[test]
+    fn test_malformed_slices_are_errors() {
+        let ids: Arc<dyn Array> = Arc::new(StringArray::from(vec!["a"]));
+        let numbers: Arc<dyn Array> = Arc::new(Int32Array::from(vec![1]));
+        let wrong_types = parquet(vec![("id", numbers.clone()), ("embedding", numbers.clone()), ("meta", ids.clone())]);
+        let error = decode_slice("staged/idx/slice.parquet", wrong_types).unwrap_err();
+        assert!(error.to_string().contains("staged/idx/slice.parquet"), "{}", error);
+        assert!(format!("{:#}", error).contains("column id is Int32, expected Utf8"), "{:#}", error);
+        let too_few = parquet(vec![("id", ids)]);
+        let error = decode_slice("staged/idx/slice.parquet", too_few).unwrap_err();
+        assert!(format!("{:#}", error).contains("no embedding column"), "{:#}", error);
+
+        assert!(decode_slice("staged/idx/slice.parquet", Bytes::from_static(b"PAR1 not really")).is_err());
+        let error = decode_slice("staged/idx/slice.jsonl", Bytes::from_static(b"\n{\"id\": 1}\n")).unwrap_err();
+        assert!(format!("{:#}", error).contains("line 2"), "{:#}", error);
+        assert!(decode_slice("staged/idx/slice.jsonl.zst", Bytes::from_static(b"\x28\xb5\x2f\xfd")).is_err());
+        assert!(decode_slice("staged/idx/slice.jsonl", Bytes::from_static(b"\n  \n")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_slice_columns_are_found_by_name() {
+        let embeddings = ListArray::from_iter_primitive::<arrow::datatypes::Float32Type, _, _>(vec![
+            Some(vec![Some(1.0), Some(0.0)]),
+            Some(vec![Some(0.0), Some(1.0)]),
+        ]);
+        let slice = parquet(vec![
+            ("meta", Arc::new(StringArray::from(vec![Some(r#"{"genre":"news"}"#), None]))),
+            ("embedding", Arc::new(embeddings)),
+            ("id", Arc::new(StringArray::from(vec!["a", "b"]))),
+        ]);
+        let records = decode_slice("staged/idx/slice.parquet", slice).unwrap();
+        assert_eq!(records.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(records[0].embedding, [1.0, 0.0]);
+        assert_eq!(records[0].meta["genre"], "news");
+        assert_eq!(records[1].meta, serde_json::json!({}));
+        assert!(records[1].text.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_runs_resume_from_the_checkpoint() {
+        let s3 = S3Client::in_memory("vectors");
+        let slice = "staged/docs/slice-1.jsonl";
+        let lines: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|id| serde_json::json!({"id": id, "embedding": [1.0, 0.5], "meta": {}}).to_string())
+            .collect();
+        s3.put_object(slice, Bytes::from(lines.join("\n"))).await.unwrap();
+        // The run before stored a shard of the first two rows, then died.
+        let stored = serde_json::json!({
+            "shard_id": "s0", "index_path": "indexes/docs/shards/s0/index.faiss",
+            "metadata_path": "indexes/docs/shards/s0/metadata.json",
+            "vector_count": 2, "metric": "cosine", "created_at": "20250115T000000",
+        });
+        let checkpoint = serde_json::json!({"dim": 2, "shards": [stored], "rows": {slice: [[0, 2]]}});
+        s3.put_object(&crate::checkpoint::key("docs"), Bytes::from(checkpoint.to_string())).await.unwrap();
+
+        run(&s3).await.unwrap();
+        let manifest = IndexManifest::from_slice(&s3.get_object("indexes/docs/manifest.json").await.unwrap()).unwrap();
+        assert_eq!(manifest.shards.len(), 2);
+        assert_eq!(manifest.shards[0].shard_id, "s0");
+        assert_eq!((manifest.shards[1].vector_count, manifest.total_vectors), (1, 3));
+        assert!(Checkpoint::load(&s3, "docs").await.is_none());
+        assert!(s3.list_objects("staged/").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_slices_of_another_dimension_are_quarantined() {
+        let s3 = S3Client::in_memory("vectors");
+        let config = CreateIndex { name: "docs".to_string(), dim: 2, metric: "cosine".to_string(), ..Default::default() };
+        s3.put_object("indexes/docs/config.json", Bytes::from(serde_json::to_vec(&config).unwrap())).await.unwrap();
+        let slice = |rows: &[(&str, Vec<f32>)]| {
+            let lines: Vec<String> = rows
+                .iter()
+                .map(|(id, embedding)| serde_json::json!({"id": id, "embedding": embedding, "meta": {}}).to_string())
+                .collect();
+            Bytes::from(lines.join("\n"))
+        };
+        s3.put_object("staged/docs/slice-1.jsonl", slice(&[("a", vec![1.0, 0.0]), ("b", vec![1.0, 0.0, 0.0])])).await.unwrap();
+        s3.put_object("staged/docs/slice-2.jsonl", slice(&[("c", vec![0.0, 1.0])])).await.unwrap();
+
+        run(&s3).await.unwrap();
+        let manifest = IndexManifest::from_slice(&s3.get_object("indexes/docs/manifest.json").await.unwrap()).unwrap();
+        assert_eq!(manifest.total_vectors, 1);
+        assert!(s3.list_objects("staged/").await.unwrap().is_empty());
+        let reports = crate::quarantine::list(&s3, Some("docs")).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].slice, "slice-1.jsonl");
+        assert!(reports[0].error.contains("Vector b"), "{}", reports[0].error);
+    }
 }
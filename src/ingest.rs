@@ -1,4 +1,6 @@
 use crate::{minio::S3Client, model::*, indexer};
+use crate::compression::CompressionConfig;
+use crate::tempfiles::TempFile;
 use anyhow::Result;
 use arrow::array::{ListArray, RecordBatch, StringArray, TimestampNanosecondArray};
 use arrow::datatypes::{DataType, Field, Float32Type, Schema, TimeUnit};
@@ -6,13 +8,76 @@ use bytes::Bytes;
 use chrono::Utc;
 use parquet::arrow::ArrowWriter;
 use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::sync::{Arc, Mutex};
 use tokio::{fs, io::AsyncWriteExt, time::Instant};
+use tracing::Instrument;
 
 pub const SLICE_ROW_LIMIT: usize = 5000;
 pub const SLICE_AGE_LIMIT_S: u64 = 30;
 
+/// Key of the per-vector JSON object that serves ListVectors/GetVectors.
+pub fn record_object_key(index: &str, id: &str) -> String {
+    format!("{}/vectors/{}.json", index, id)
+}
+
+/// The vector of record in S3 Vectors shape: `{key, data: {float32}, metadata}`.
+/// Every ingest path writes this, so reads never depend on how a vector arrived.
+/// `version` is the write time in microseconds and backs [`PutCondition`];
+/// `text` is only present for vectors written with one.
+pub fn record_document(record: &VectorRecord) -> serde_json::Value {
+    let mut doc = serde_json::json!({
+        "key": record.id,
+        "data": { "float32": record.embedding },
+        "metadata": record.meta,
+        "version": record.created_at.timestamp_micros(),
+    });
+    if let Some(text) = &record.text {
+        doc["text"] = serde_json::json!(text);
+    }
+    doc
+}
+
+/// Optional PutVectors precondition, checked against the vector's record object.
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PutCondition {
+    /// Only insert if the key doesn't exist yet.
+    #[serde(default)]
+    pub if_not_exists: bool,
+    /// Only overwrite if the stored record has exactly this version.
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteConflict {
+    pub key: String,
+    pub reason: &'static str,
+    pub current_version: Option<i64>,
+}
+
+impl PutCondition {
+    fn is_set(&self) -> bool {
+        self.if_not_exists || self.expected_version.is_some()
+    }
+
+    /// Check the condition against the current record (`None` if the key is absent).
+    pub fn evaluate(&self, key: &str, existing: Option<&serde_json::Value>) -> Option<WriteConflict> {
+        let current_version = existing.and_then(|doc| doc.get("version")).and_then(|v| v.as_i64());
+        let reason = if self.if_not_exists && existing.is_some() {
+            "KeyExists"
+        } else if self.expected_version.is_some_and(|want| current_version != Some(want)) {
+            "VersionMismatch"
+        } else {
+            return None;
+        };
+        Some(WriteConflict { key: key.to_string(), reason, current_version })
+    }
+}
+
 pub struct Buffer {
     rows: Vec<VectorRecord>,
     first_seen: Instant,
@@ -40,6 +105,11 @@ pub struct Ingestor {
     s3: S3Client,
     bucket: String,
     slice_format: SliceFormat,
+    compression: CompressionConfig,
+    /// Buffered rows that trigger a slice; `SLICE_ROW_LIMIT` by default.
+    slice_row_limit: usize,
+    /// Serialises conditional writes so check-then-write is atomic within this process.
+    record_lock: tokio::sync::Mutex<()>,
 }
 
 impl Ingestor {
@@ -54,18 +124,30 @@ impl Ingestor {
             s3,
             bucket,
             slice_format,
+            compression: CompressionConfig::from_env(),
+            slice_row_limit: SLICE_ROW_LIMIT,
+            record_lock: tokio::sync::Mutex::new(()),
         }
     }
 
+    /// Write a slice once `rows` vectors are buffered (at least one), e.g. 1
+    /// so every append is staged for the indexer at once.
+    pub fn with_slice_row_limit(mut self, rows: usize) -> Self {
+        self.slice_row_limit = rows.max(1);
+        self
+    }
+
     pub async fn append(&self, vecs: Vec<VectorRecord>, index: &str) -> anyhow::Result<()> {
         let mut wal_bytes = Vec::new();
         for rec in &vecs {
             wal_bytes.extend(serde_json::to_vec(rec)?);
             wal_bytes.push(b'\n');
         }
+        let (vector_count, byte_count) = (vecs.len() as u64, wal_bytes.len() as u64);
         self.s3
             .append_object(&self.bucket, "wal/current.ndjson", Bytes::from(wal_bytes))
             .await?;
+        crate::metrics::get_metrics_collector().record_ingest(index, vector_count, byte_count);
 
         let slice_rows = {
             let mut guard = self.buf.lock().unwrap();
@@ -74,7 +156,7 @@ impl Ingestor {
             }
             guard.rows.extend(vecs);
 
-            if guard.rows.len() >= SLICE_ROW_LIMIT
+            if guard.rows.len() >= self.slice_row_limit
                 || guard.first_seen.elapsed().as_secs() >= SLICE_AGE_LIMIT_S
             {
                 Some(std::mem::take(&mut guard.rows))
@@ -89,52 +171,84 @@ impl Ingestor {
         Ok(())
     }
 
+    /// Write per-vector record objects (see [`record_document`]) into `bucket`.
+    pub async fn write_records(&self, bucket: &str, index: &str, records: &[serde_json::Value]) -> Result<()> {
+        for record in records {
+            let Some(id) = record.get("key").and_then(|k| k.as_str()) else { continue };
+            self.s3
+                .put_bucket_object(bucket, &record_object_key(index, id), serde_json::to_vec(record)?.into())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Hold while checking conditions and writing the records they guard.
+    pub async fn lock_records(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.record_lock.lock().await
+    }
+
+    /// Evaluate `condition` for one key; unconditional writes skip the read.
+    pub async fn check_condition(&self, bucket: &str, index: &str, id: &str, condition: PutCondition) -> Option<WriteConflict> {
+        if !condition.is_set() {
+            return None;
+        }
+        let existing = self.s3
+            .get_bucket_object(bucket, &record_object_key(index, id))
+            .await
+            .ok()
+            .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok());
+        condition.evaluate(id, existing.as_ref())
+    }
+
     async fn write_slice(&self, rows: Vec<VectorRecord>, index: &str) -> Result<()> {
         let ts = Utc::now().format("%Y%m%dT%H%M%S%3f");
         
-        let (key, local_path) = match self.slice_format {
+        let (key, local) = match self.slice_format {
             SliceFormat::JsonLines => {
-                let key = format!("staged/{}/slice-{}.jsonl", index, ts);
-                let local_path = "/tmp/slice.jsonl";
-                let mut tmp = fs::File::create(local_path).await?;
+                let key = format!("staged/{}/slice-{}.jsonl{}", index, ts, self.compression.extension());
+                let local = TempFile::new("slice", ".jsonl");
+                let mut lines = Vec::new();
                 for r in &rows {
-                    tmp.write_all(serde_json::to_string(r)?.as_bytes()).await?;
-                    tmp.write_u8(b'\n').await?;
+                    lines.extend(serde_json::to_vec(r)?);
+                    lines.push(b'\n');
                 }
+                let mut tmp = fs::File::create(local.path()).await?;
+                tmp.write_all(&self.compression.compress(&lines)?).await?;
                 tmp.sync_all().await?;
-                (key, local_path.to_string())
+                (key, local)
             }
             SliceFormat::Parquet => {
                 let key = format!("staged/{}/slice-{}.parquet", index, ts);
-                let local_path = self.write_parquet_slice(&rows).await?;
-                (key, local_path)
+                let local = self.write_parquet_slice(&rows).await?;
+                (key, local)
             }
         };
 
-        self.s3.put_file(&self.bucket, &key, &local_path).await?;
-        tokio::fs::remove_file(&local_path).await?;
+        self.s3.put_file(&self.bucket, &key, local.path()).await?;
+        drop(local);
+        crate::metrics::get_metrics_collector().record_slice_flush(index);
 
-        tracing::debug!("Wrote {} vectors to slice: {}", rows.len(), key);
+        tracing::debug!(index, slice = %key, vectors = rows.len(), "Wrote slice");
 
         // Enhanced callback indexing - trigger immediately after slice upload
         let s3_clone = self.s3.clone();
         let key_clone = key.clone();
         tokio::spawn(async move {
-            tracing::info!("Triggering immediate indexing for slice: {}", key_clone);
+            tracing::info!(slice = %key_clone, "Triggering immediate indexing for slice");
             // Add a small delay to ensure object is fully written
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             
             if let Err(e) = indexer::trigger_indexing_for_slice(s3_clone, key_clone).await {
-                tracing::error!("Failed to trigger indexing for slice: {}", e);
+                tracing::error!(error = %e, "Failed to trigger indexing for slice");
             } else {
                 tracing::info!("Successfully triggered indexing callback");
             }
-        });
+        }.in_current_span());
         
         Ok(())
     }
 
-    async fn write_parquet_slice(&self, rows: &[VectorRecord]) -> Result<String> {
+    async fn write_parquet_slice(&self, rows: &[VectorRecord]) -> Result<TempFile> {
         let schema = Arc::new(Schema::new(vec![
             Field::new("id", DataType::Utf8, false),
             Field::new(
@@ -148,6 +262,7 @@ impl Ingestor {
                 DataType::Timestamp(TimeUnit::Nanosecond, None),
                 false,
             ),
+            Field::new("text", DataType::Utf8, true),
         ]));
 
         let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
@@ -164,19 +279,41 @@ impl Ingestor {
         ));
         let meta_array = Arc::new(StringArray::from(metas));
         let created_at_array = Arc::new(TimestampNanosecondArray::from(created_ats));
+        let text_array = Arc::new(StringArray::from(rows.iter().map(|r| r.text.clone()).collect::<Vec<_>>()));
 
         let batch = RecordBatch::try_new(
             schema.clone(),
-            vec![id_array, embedding_array, meta_array, created_at_array],
+            vec![id_array, embedding_array, meta_array, created_at_array, text_array],
         )?;
 
-        let local_path = format!("/tmp/slice-{}.parquet", Utc::now().timestamp_nanos_opt().unwrap_or(0));
-        let file = File::create(&local_path)?;
+        let local = TempFile::new("slice", ".parquet");
+        let file = File::create(local.path())?;
         let props = WriterProperties::builder().build();
         let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
         writer.write(&batch)?;
         writer.close()?;
         
-        Ok(local_path)
+        Ok(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_condition() {
+        let existing = serde_json::json!({"key": "a", "version": 7});
+        let if_absent = PutCondition { if_not_exists: true, expected_version: None };
+        assert!(if_absent.evaluate("a", None).is_none());
+        assert_eq!(if_absent.evaluate("a", Some(&existing)).unwrap().reason, "KeyExists");
+
+        let at_version = PutCondition { if_not_exists: false, expected_version: Some(7) };
+        assert!(at_version.evaluate("a", Some(&existing)).is_none());
+        let stale = PutCondition { expected_version: Some(6), ..at_version };
+        let conflict = stale.evaluate("a", Some(&existing)).unwrap();
+        assert_eq!((conflict.reason, conflict.current_version), ("VersionMismatch", Some(7)));
+        assert!(at_version.evaluate("a", None).is_some());
+        assert!(PutCondition::default().evaluate("a", Some(&existing)).is_none());
     }
 }
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+/// SHA-256 checksums of the artifacts that make up a shard, recorded in the manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ShardChecksums {
+    pub index: String,
+    pub id_map: String,
+    pub metadata: String,
+    /// Shards written before raw vectors were stored have no vectors file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vectors: Option<String>,
+    /// Only shards with vector texts have a BM25 index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Raised when a downloaded artifact does not match the checksum recorded at write time.
+#[derive(Debug, thiserror::Error)]
+pub enum IntegrityError {
+    #[error("Checksum mismatch for {key}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        key: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Hash a file in fixed-size chunks so large shard indexes are never read into memory whole.
+pub fn sha256_file(path: &str) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {} for checksum", path))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compare a computed checksum with the recorded one. Artifacts written before
+/// checksums were recorded (`expected` is `None`) are accepted as-is.
+pub fn verify(key: &str, expected: Option<&str>, actual: &str) -> Result<(), IntegrityError> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(actual) => {
+            Err(IntegrityError::ChecksumMismatch {
+                key: key.to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex_known_value() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_verify_detects_mismatch_and_accepts_legacy() {
+        let actual = sha256_hex(b"shard");
+        assert!(verify("k", Some(&actual), &actual).is_ok());
+        assert!(verify("k", None, &actual).is_ok());
+        assert!(matches!(
+            verify("k", Some("deadbeef"), &actual),
+            Err(IntegrityError::ChecksumMismatch { .. })
+        ));
+    }
+}
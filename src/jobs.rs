@@ -0,0 +1,439 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+/// Long-running admin operations run as jobs recorded at `jobs/<id>.json`, so
+/// any replica can report their status and accept cancellation.
+const JOBS_PREFIX: &str = "jobs/";
+
+/// How often a running job re-reads its record to notice cancellation requested
+/// through another replica, and renews its lease.
+const CANCEL_POLL_SECS: u64 = 5;
+
+/// Running jobs hold a lease at `job-leases/<id>.json`, renewed as they poll
+/// for cancellation. A job whose lease is older than this lost its replica
+/// and is marked failed by [`reclaim_orphaned`].
+const LEASES_PREFIX: &str = "job-leases/";
+const LEASE_SECS: i64 = 60;
+
+/// Events a slow subscriber may fall behind by before it skips ahead.
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        self != JobStatus::Running
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub job_id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub cancel_requested: bool,
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Last progress the job reported, once it has finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Value>,
+}
+
+/// What a running job tells those watching it, e.g. over
+/// `GET /jobs/<id>/events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobEvent {
+    /// How far the job has got: `{"phase": .., <counters>}`.
+    Progress(Value),
+    /// Results available before the job finishes.
+    Partial(Value),
+}
+
+/// Handle on the event stream of the running job; cloned into tasks and
+/// blocking sections the job spawns, which don't inherit the task-local.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    events: broadcast::Sender<JobEvent>,
+    latest: Arc<Mutex<Option<Value>>>,
+}
+
+impl ProgressReporter {
+    fn new() -> Self {
+        ProgressReporter { events: broadcast::channel(EVENT_BUFFER).0, latest: Arc::default() }
+    }
+
+    pub fn progress(&self, progress: Value) {
+        *self.latest.lock().unwrap() = Some(progress.clone());
+        let _ = self.events.send(JobEvent::Progress(progress));
+    }
+
+    pub fn partial(&self, result: Value) {
+        let _ = self.events.send(JobEvent::Partial(result));
+    }
+
+    fn latest(&self) -> Option<Value> {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+tokio::task_local! {
+    static REPORTER: ProgressReporter;
+}
+
+/// Reporter of the job the current task runs, if any.
+pub fn reporter() -> Option<ProgressReporter> {
+    REPORTER.try_with(ProgressReporter::clone).ok()
+}
+
+/// Report progress of the job the current task runs; a no-op outside jobs,
+/// so code shared with the API and the indexer CLI can report freely.
+pub fn report_progress(progress: Value) {
+    if let Some(reporter) = reporter() {
+        reporter.progress(progress);
+    }
+}
+
+struct RunningJob {
+    token: CancelToken,
+    reporter: ProgressReporter,
+}
+
+/// Handed to a job's body; long loops call [`CancelToken::check`] between steps.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(anyhow::anyhow!("Job cancelled"));
+        }
+        Ok(())
+    }
+
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Jobs running in this process, for immediate local cancellation and for
+/// subscribing to their events.
+fn running_jobs() -> &'static Mutex<HashMap<String, RunningJob>> {
+    static RUNNING: OnceLock<Mutex<HashMap<String, RunningJob>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Events of `job_id` if it runs in this process: its latest progress and a
+/// receiver of what follows, which closes once the job's outcome is recorded.
+pub fn subscribe(job_id: &str) -> Option<(Option<Value>, broadcast::Receiver<JobEvent>)> {
+    let running = running_jobs().lock().unwrap();
+    let job = running.get(job_id)?;
+    Some((job.reporter.latest(), job.reporter.events.subscribe()))
+}
+
+fn job_key(job_id: &str) -> String {
+    format!("{}{}.json", JOBS_PREFIX, job_id)
+}
+
+async fn save(s3: &S3Client, job: &Job) -> Result<()> {
+    s3.put_object(&job_key(&job.job_id), serde_json::to_vec(job)?.into()).await
+}
+
+fn lease_key(job_id: &str) -> String {
+    format!("{}{}.json", LEASES_PREFIX, job_id)
+}
+
+/// Kept in its own object so renewals never overwrite a cancellation request.
+async fn renew_lease(s3: &S3Client, job_id: &str) -> Result<()> {
+    s3.put_object(&lease_key(job_id), serde_json::to_vec(&Utc::now())?.into()).await
+}
+
+async fn lease(s3: &S3Client, job_id: &str) -> Option<DateTime<Utc>> {
+    let data = s3.get_object(&lease_key(job_id)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+pub async fn get(s3: &S3Client, job_id: &str) -> Result<Job> {
+    let data = s3.get_object(&job_key(job_id)).await
+        .with_context(|| format!("Job not found: {}", job_id))?;
+    serde_json::from_slice(&data).context("Failed to parse job record")
+}
+
+/// All job records, newest first.
+pub async fn list(s3: &S3Client) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for key in s3.list_objects(JOBS_PREFIX).await? {
+        let data = s3.get_object(&key).await?;
+        match serde_json::from_slice::<Job>(&data) {
+            Ok(job) => jobs.push(job),
+            Err(e) => tracing::warn!("Skipping unreadable job record {}: {}", key, e),
+        }
+    }
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+    Ok(jobs)
+}
+
+/// Record a job and run `body` in the background. Its `Ok` value becomes the
+/// job result; an error after cancellation marks the job cancelled, and a
+/// panic marks it failed.
+pub async fn spawn<F, Fut>(s3: &S3Client, kind: &str, body: F) -> Result<Job>
+where
+    F: FnOnce(CancelToken) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Value>> + Send + 'static,
+{
+    let now = Utc::now();
+    let job = Job {
+        job_id: uuid::Uuid::new_v4().to_string(),
+        kind: kind.to_string(),
+        status: JobStatus::Running,
+        created_at: now,
+        updated_at: now,
+        cancel_requested: false,
+        result: None,
+        error: None,
+        progress: None,
+    };
+    save(s3, &job).await?;
+    renew_lease(s3, &job.job_id).await?;
+
+    let token = CancelToken::default();
+    let reporter = ProgressReporter::new();
+    running_jobs()
+        .lock()
+        .unwrap()
+        .insert(job.job_id.clone(), RunningJob { token: token.clone(), reporter: reporter.clone() });
+
+    let s3 = s3.clone();
+    let job_id = job.job_id.clone();
+    tokio::spawn(async move {
+        let watcher = tokio::spawn(watch(s3.clone(), job_id.clone(), token.clone()));
+        let outcome = match tokio::spawn(REPORTER.scope(reporter.clone(), body(token.clone())).in_current_span()).await {
+            Ok(outcome) => outcome,
+            Err(e) => Err(anyhow::anyhow!("Job stopped unexpectedly: {}", e)),
+        };
+        watcher.abort();
+        let _ = watcher.await;
+
+        // Subscribers are let go only once the outcome is recorded, so the
+        // record they read next is final.
+        let finished = async {
+            let mut job = get(&s3, &job_id).await?;
+            match outcome {
+                Ok(result) => {
+                    job.status = JobStatus::Succeeded;
+                    job.result = Some(result);
+                }
+                Err(e) => {
+                    job.status = if token.is_cancelled() { JobStatus::Cancelled } else { JobStatus::Failed };
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.progress = reporter.latest();
+            job.updated_at = Utc::now();
+            tracing::info!(job_id = %job.job_id, kind = %job.kind, status = ?job.status, "Job finished");
+            save(&s3, &job).await
+        };
+        match finished.await {
+            Ok(()) => {
+                let _ = s3.delete_object(&lease_key(&job_id)).await;
+            }
+            Err(e) => tracing::error!("Failed to record outcome of job {}: {}", job_id, e),
+        }
+        running_jobs().lock().unwrap().remove(&job_id);
+    }.in_current_span());
+    Ok(job)
+}
+
+async fn watch(s3: S3Client, job_id: String, token: CancelToken) {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(CANCEL_POLL_SECS)).await;
+        if let Err(e) = renew_lease(&s3, &job_id).await {
+            tracing::warn!("Failed to renew lease of job {}: {}", job_id, e);
+        }
+        if !token.is_cancelled() && matches!(get(&s3, &job_id).await, Ok(job) if job.cancel_requested) {
+            token.cancel();
+        }
+    }
+}
+
+/// Mark failed the running jobs whose replica stopped renewing their lease,
+/// e.g. because it crashed or restarted. Returns the jobs reclaimed.
+pub async fn reclaim_orphaned(s3: &S3Client) -> Result<usize> {
+    let expired_before = Utc::now() - chrono::Duration::seconds(LEASE_SECS);
+    let mut reclaimed = 0;
+    for mut job in list(s3).await? {
+        if job.status.is_finished() || running_jobs().lock().unwrap().contains_key(&job.job_id) {
+            continue;
+        }
+        if lease(s3, &job.job_id).await.is_some_and(|renewed| renewed > expired_before) {
+            continue;
+        }
+        job.status = JobStatus::Failed;
+        job.error = Some("Job was abandoned by the replica running it".to_string());
+        job.updated_at = Utc::now();
+        tracing::warn!(job_id = %job.job_id, kind = %job.kind, "Reclaimed orphaned job");
+        save(s3, &job).await?;
+        let _ = s3.delete_object(&lease_key(&job.job_id)).await;
+        reclaimed += 1;
+    }
+    Ok(reclaimed)
+}
+
+/// Reclaim orphaned jobs in the background, once per lease period.
+pub fn spawn_reaper(s3: S3Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(LEASE_SECS as u64));
+        loop {
+            interval.tick().await;
+            match reclaim_orphaned(&s3).await {
+                Ok(0) => {}
+                Ok(reclaimed) => tracing::info!(reclaimed, "Reclaimed orphaned jobs"),
+                Err(e) => tracing::warn!(error = %e, "Failed to reclaim orphaned jobs"),
+            }
+        }
+    });
+}
+
+/// Ask a running job to stop. Jobs stop at their next cancellation check.
+pub async fn cancel(s3: &S3Client, job_id: &str) -> Result<Job> {
+    let mut job = get(s3, job_id).await?;
+    if job.status.is_finished() {
+        return Err(anyhow::anyhow!("Job {} already finished ({:?})", job_id, job.status));
+    }
+    job.cancel_requested = true;
+    job.updated_at = Utc::now();
+    save(s3, &job).await?;
+    if let Some(running) = running_jobs().lock().unwrap().get(job_id) {
+        running.token.cancel();
+    }
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token_and_status() {
+        let token = CancelToken::default();
+        assert!(token.check().is_ok());
+        token.clone().cancel();
+        assert!(token.check().is_err());
+
+        assert!(!JobStatus::Running.is_finished());
+        assert!(JobStatus::Cancelled.is_finished());
+        assert_eq!(serde_json::to_value(JobStatus::Succeeded).unwrap(), "Succeeded");
+    }
+
+    #[tokio::test]
+    async fn test_running_jobs_stream_progress() {
+        let s3 = S3Client::in_memory("vectors");
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let job = spawn(&s3, "Test", move |_cancel| async move {
+            report_progress(serde_json::json!({"done": 1}));
+            released.await?;
+            let reporter = reporter().unwrap();
+            tokio::spawn(async move { reporter.partial(serde_json::json!({"rows": 3})) }).await?;
+            report_progress(serde_json::json!({"done": 2}));
+            Ok(serde_json::json!({"ok": true}))
+        })
+        .await
+        .unwrap();
+
+        let (latest, mut events) = loop {
+            match subscribe(&job.job_id) {
+                Some((Some(latest), events)) => break (latest, events),
+                _ => tokio::task::yield_now().await,
+            }
+        };
+        assert_eq!(latest, serde_json::json!({"done": 1}));
+        release.send(()).unwrap();
+        assert_eq!(events.recv().await.unwrap(), JobEvent::Partial(serde_json::json!({"rows": 3})));
+        assert_eq!(events.recv().await.unwrap(), JobEvent::Progress(serde_json::json!({"done": 2})));
+        assert!(events.recv().await.is_err());
+
+        let job = get(&s3, &job.job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.progress, Some(serde_json::json!({"done": 2})));
+        assert!(subscribe(&job.job_id).is_none());
+        report_progress(serde_json::json!({"outside": "a job"}));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_job_fails() {
+        let s3 = S3Client::in_memory("vectors");
+        let job = spawn(&s3, "Test", |_cancel| async move {
+            if true {
+                panic!("boom");
+            }
+            Ok(serde_json::json!({}))
+        })
+        .await
+        .unwrap();
+
+        let job = loop {
+            match get(&s3, &job.job_id).await.unwrap() {
+                job if job.status.is_finished() => break job,
+                _ => tokio::task::yield_now().await,
+            }
+        };
+        assert_eq!(job.status, JobStatus::Failed);
+        while subscribe(&job.job_id).is_some() {
+            tokio::task::yield_now().await;
+        }
+        assert!(s3.get_object(&lease_key(&job.job_id)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jobs_without_a_live_lease_are_reclaimed() {
+        let s3 = S3Client::in_memory("vectors");
+        let now = Utc::now();
+        let running = |job_id: &str| Job {
+            job_id: job_id.to_string(),
+            kind: "Test".to_string(),
+            status: JobStatus::Running,
+            created_at: now,
+            updated_at: now,
+            cancel_requested: false,
+            result: None,
+            error: None,
+            progress: None,
+        };
+        for job_id in ["crashed", "stale", "live"] {
+            save(&s3, &running(job_id)).await.unwrap();
+        }
+        let stale = now - chrono::Duration::seconds(LEASE_SECS + 1);
+        s3.put_object(&lease_key("stale"), serde_json::to_vec(&stale).unwrap().into()).await.unwrap();
+        renew_lease(&s3, "live").await.unwrap();
+
+        assert_eq!(reclaim_orphaned(&s3).await.unwrap(), 2);
+        for (job_id, status) in [("crashed", JobStatus::Failed), ("stale", JobStatus::Failed), ("live", JobStatus::Running)] {
+            assert_eq!(get(&s3, job_id).await.unwrap().status, status);
+        }
+        assert_eq!(reclaim_orphaned(&s3).await.unwrap(), 0);
+    }
+}
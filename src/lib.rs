@@ -3,17 +3,64 @@
 //! A production-grade vector database built with Rust for scalable similarity search.
 
 pub mod api;
+pub mod build_queue;
+pub mod cache;
+pub mod changelog;
+pub mod check;
+pub mod checkpoint;
+pub mod cluster;
+pub mod compression;
+pub mod crypto;
+pub mod dedup;
+pub mod deletions;
+pub mod embedded;
+pub mod export;
+pub mod faiss_pool;
 pub mod faiss_utils;
+pub mod gc;
+pub mod governor;
+pub mod hedging;
+#[cfg(test)]
+mod fixtures;
+pub mod index_copy;
+pub mod index_events;
 pub mod indexer;
 pub mod ingest;
-pub mod metadata_filter;
+pub mod integrity;
+pub mod jobs;
+pub mod lifecycle;
 pub mod metrics;
+pub mod migrate;
 pub mod minio;
 pub mod model;
+pub mod partitions;
+#[cfg(feature = "python")]
+mod python;
+pub mod quantizer;
+pub mod quarantine;
 pub mod query;
+pub mod query_snapshots;
+pub mod raw_vectors;
+pub mod reduction;
+pub mod replica;
+pub mod replication;
+pub mod request_id;
+pub mod schema;
+pub mod shadow;
+pub mod spool;
+pub mod storage;
+pub mod tempfiles;
+pub mod trash;
+pub mod truncation;
+pub mod tuning;
+pub mod usage;
+pub mod versions;
+pub mod warmup;
 
+pub use genai_vectors_core::{distance, fusion, metadata_filter, text_index};
 pub use model::*;
 pub use minio::S3Client;
+pub use embedded::VectorDb;
 
 /// Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
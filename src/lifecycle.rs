@@ -0,0 +1,246 @@
+//! Retention for the artifacts the store leaves behind: WAL segments other
+//! than the current one, exports past their retention, shard files no
+//! manifest reaches (see [`crate::gc`]), and scratch files of crashed processes in
+//! [`tempfiles::dir`]. Run by `genai-vectors lifecycle`; `--dry-run` reports
+//! what would go without deleting anything.
+
+use crate::gc;
+use crate::minio::S3Client;
+use crate::tempfiles;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+const WAL_PREFIX: &str = "wal/";
+const CURRENT_WAL: &str = "wal/current.ndjson";
+const EXPORT_PREFIX: &str = "exports/";
+
+/// How long each kind of artifact is kept; `None` (a setting of `0`) keeps
+/// them forever.
+#[derive(Debug, PartialEq)]
+pub struct LifecyclePolicy {
+    /// `VEC_WAL_RETENTION_HOURS` (default 72).
+    pub wal_retention: Option<Duration>,
+    /// `VEC_EXPORT_RETENTION_DAYS` (default 0), counted from the export's
+    /// timestamp.
+    pub export_retention: Option<Duration>,
+    /// `VEC_ORPHAN_GRACE_HOURS` (default 24): the safety window of
+    /// [`gc::collect`].
+    pub orphan_grace: Option<Duration>,
+    /// `VEC_TMP_RETENTION_HOURS` (default 24).
+    pub temp_retention: Option<Duration>,
+}
+
+impl LifecyclePolicy {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |name: &str, default: i64| {
+            let value = var(name).and_then(|v| v.trim().parse::<i64>().ok()).unwrap_or(default);
+            (value > 0).then_some(value)
+        };
+        LifecyclePolicy {
+            wal_retention: setting("VEC_WAL_RETENTION_HOURS", 72).map(Duration::hours),
+            export_retention: setting("VEC_EXPORT_RETENTION_DAYS", 0).map(Duration::days),
+            orphan_grace: setting("VEC_ORPHAN_GRACE_HOURS", 24).map(Duration::hours),
+            temp_retention: setting("VEC_TMP_RETENTION_HOURS", 24).map(Duration::hours),
+        }
+    }
+}
+
+/// What a run deleted, or on a dry run would have.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleReport {
+    pub dry_run: bool,
+    pub wal_segments: Vec<String>,
+    pub expired_exports: Vec<String>,
+    /// Index artifacts no manifest reaches, see [`crate::gc`].
+    pub unreachable_objects: Vec<String>,
+    pub temp_files: Vec<String>,
+    /// Objects and files removed; 0 on a dry run.
+    pub deleted: usize,
+}
+
+/// WAL objects written before `cutoff`, except the one still appended to.
+fn expired_wal(objects: &[(String, DateTime<Utc>)], cutoff: DateTime<Utc>) -> Vec<String> {
+    objects
+        .iter()
+        .filter(|(key, modified)| key != CURRENT_WAL && *modified < cutoff)
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// Objects of exports taken before `cutoff`, by the timestamp in their
+/// `exports/<index>/<ts>/` prefix so an export is removed whole.
+fn expired_exports(objects: &[(String, DateTime<Utc>)], cutoff: DateTime<Utc>) -> Vec<String> {
+    objects
+        .iter()
+        .filter(|(key, _)| {
+            let taken = key.strip_prefix(EXPORT_PREFIX).and_then(|rest| rest.split('/').nth(1));
+            taken
+                .and_then(|ts| NaiveDateTime::parse_from_str(ts, "%Y%m%dT%H%M%SZ").ok())
+                .is_some_and(|taken| taken.and_utc() < cutoff)
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// Scratch files in `dir` last written before `cutoff`. Only names a
+/// [`tempfiles::TempFile`] would have are considered, as the directory may
+/// be shared.
+fn stale_temp_files(dir: &Path, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+    let mut stale = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let name = entry.file_name();
+        if !metadata.is_file() || !name.to_str().is_some_and(tempfiles::is_temp_name) {
+            continue;
+        }
+        if DateTime::<Utc>::from(metadata.modified()?) < cutoff {
+            stale.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+/// Apply `policy` as of `now`, deleting what it expires unless `dry_run`.
+pub async fn run(s3: &S3Client, policy: &LifecyclePolicy, now: DateTime<Utc>, dry_run: bool) -> Result<LifecycleReport> {
+    let mut report = LifecycleReport { dry_run, ..Default::default() };
+    if let Some(retention) = policy.wal_retention {
+        report.wal_segments = expired_wal(&s3.list_objects_modified(WAL_PREFIX).await?, now - retention);
+    }
+    if let Some(retention) = policy.export_retention {
+        report.expired_exports = expired_exports(&s3.list_objects_modified(EXPORT_PREFIX).await?, now - retention);
+    }
+    if let Some(grace) = policy.orphan_grace {
+        report.unreachable_objects = gc::collect(s3, grace, now, true).await?.unreachable;
+    }
+    if let Some(retention) = policy.temp_retention {
+        report.temp_files = stale_temp_files(tempfiles::dir(), now - retention)?;
+    }
+
+    if !dry_run {
+        for key in report.wal_segments.iter().chain(&report.expired_exports).chain(&report.unreachable_objects) {
+            s3.delete_object(key).await?;
+            report.deleted += 1;
+        }
+        for path in &report.temp_files {
+            match std::fs::remove_file(path) {
+                Ok(()) => report.deleted += 1,
+                Err(e) => tracing::warn!("Failed to remove temp file {}: {}", path, e),
+            }
+        }
+    }
+    tracing::info!(
+        "Lifecycle{}: {} WAL segments, {} export objects, {} unreachable objects, {} temp files; deleted {}",
+        if dry_run { " (dry run)" } else { "" },
+        report.wal_segments.len(),
+        report.expired_exports.len(),
+        report.unreachable_objects.len(),
+        report.temp_files.len(),
+        report.deleted
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+
+    #[test]
+    fn test_policy_comes_from_env() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            LifecyclePolicy::from_vars(crate::api::testing::env(pairs))
+        };
+        let defaults = LifecyclePolicy {
+            wal_retention: Some(Duration::hours(72)),
+            export_retention: None,
+            orphan_grace: Some(Duration::hours(24)),
+            temp_retention: Some(Duration::hours(24)),
+        };
+        assert_eq!(vars(&[]), defaults);
+        let policy = vars(&[("VEC_WAL_RETENTION_HOURS", "0"), ("VEC_EXPORT_RETENTION_DAYS", "30")]);
+        assert_eq!((policy.wal_retention, policy.export_retention), (None, Some(Duration::days(30))));
+    }
+
+    #[test]
+    fn test_expiry_plans() {
+        let at = |hour: u32| DateTime::parse_from_rfc3339(&format!("2025-01-15T{:02}:00:00Z", hour)).unwrap().to_utc();
+        let objects = |keys: &[(&str, u32)]| keys.iter().map(|(key, hour)| (key.to_string(), at(*hour))).collect::<Vec<_>>();
+
+        let wal = objects(&[("wal/current.ndjson", 1), ("wal/2025-01-15T01.ndjson", 1), ("wal/2025-01-15T09.ndjson", 9)]);
+        assert_eq!(expired_wal(&wal, at(6)), ["wal/2025-01-15T01.ndjson"]);
+
+        // Exports expire by the time they were taken, not when each file landed.
+        let exports = objects(&[
+            ("exports/docs/20250115T010000Z/part-0.parquet", 8),
+            ("exports/docs/20250115T010000Z/manifest.json", 8),
+            ("exports/docs/20250115T090000Z/part-0.parquet", 9),
+            ("exports/docs/unknown/part-0.parquet", 1),
+        ]);
+        assert_eq!(expired_exports(&exports, at(6)).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_and_run_deletes() {
+        let s3 = S3Client::in_memory("vectors");
+        let manifest = json!({
+            "index_name": "docs", "dim": 2, "metric": "cosine", "total_vectors": 1,
+            "shards": [{"shard_id": "live", "index_path": "indexes/docs/shards/live/index.faiss",
+                        "metadata_path": "indexes/docs/shards/live/metadata.json", "vector_count": 1,
+                        "metric": "cosine", "created_at": "2025-01-15T00:00:00Z"}],
+        });
+        s3.put_object("indexes/docs/manifest.json", Bytes::from(manifest.to_string())).await.unwrap();
+        for key in [
+            "indexes/docs/shards/live/index.faiss",
+            "indexes/docs/shards/dead/index.faiss",
+            "wal/current.ndjson",
+            "wal/old.ndjson",
+            "exports/docs/20200101T000000Z/part-0.parquet",
+        ] {
+            s3.put_object(key, Bytes::from_static(b"x")).await.unwrap();
+        }
+        let policy = LifecyclePolicy {
+            export_retention: Some(Duration::days(30)),
+            temp_retention: None,
+            ..LifecyclePolicy::from_vars(|_| None)
+        };
+        // Nothing is old enough to go yet.
+        assert!(run(&s3, &policy, Utc::now(), true).await.unwrap().unreachable_objects.is_empty());
+        let later = Utc::now() + Duration::days(7);
+
+        let report = run(&s3, &policy, later, true).await.unwrap();
+        assert_eq!(report.wal_segments, ["wal/old.ndjson"]);
+        assert_eq!(report.expired_exports, ["exports/docs/20200101T000000Z/part-0.parquet"]);
+        assert_eq!(report.unreachable_objects, ["indexes/docs/shards/dead/index.faiss"]);
+        assert_eq!(report.deleted, 0);
+        assert!(s3.get_object("wal/old.ndjson").await.is_ok());
+
+        let report = run(&s3, &policy, later, false).await.unwrap();
+        assert_eq!(report.deleted, 3);
+        assert!(s3.get_object("indexes/docs/shards/dead/index.faiss").await.is_err());
+        assert!(s3.get_object("indexes/docs/shards/live/index.faiss").await.is_ok());
+        assert!(s3.get_object("wal/current.ndjson").await.is_ok());
+    }
+
+    #[test]
+    fn test_only_old_temp_files_are_stale() {
+        let dir = std::env::temp_dir().join(format!("lifecycle-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let temp = dir.join(format!("shard-{}.faiss", uuid::Uuid::new_v4()));
+        std::fs::write(&temp, b"x").unwrap();
+        std::fs::write(dir.join("keep.txt"), b"x").unwrap();
+        let later = Utc::now() + Duration::hours(1);
+        assert_eq!(stale_temp_files(&dir, later).unwrap(), [temp.to_string_lossy().into_owned()]);
+        assert!(stale_temp_files(&dir, Utc::now() - Duration::hours(1)).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,57 @@
 mod api;
+mod build_queue;
+mod cache;
+mod changelog;
+mod check;
+mod checkpoint;
+mod cluster;
+mod compression;
+mod crypto;
+mod dedup;
+mod deletions;
+mod export;
+mod faiss_pool;
 mod faiss_utils;
+mod gc;
+mod governor;
+mod hedging;
+#[cfg(test)]
+mod fixtures;
 mod ingest;
 mod indexer;
-mod metadata_filter;
+mod index_copy;
+mod index_events;
+mod integrity;
+mod jobs;
+mod lifecycle;
 mod metrics;
+mod migrate;
+mod quantizer;
+mod quarantine;
 mod query;
+mod query_snapshots;
+mod raw_vectors;
+mod reduction;
+mod replica;
+mod replication;
 mod model;
+mod partitions;
 mod minio;
+mod request_id;
+mod schema;
+mod shadow;
+mod spool;
+mod storage;
+mod tempfiles;
+mod trash;
+mod truncation;
+mod tuning;
+mod usage;
+mod versions;
+mod warmup;
 
 use clap::{Parser, Subcommand};
-use tracing::Level;
+use genai_vectors_core::{distance, fusion, metadata_filter, text_index};
 
 #[derive(Parser)]
 struct Cli {
@@ -23,11 +65,100 @@ enum Cmd {
     Api,
     /// Run indexer loop once (train/merge) – scheduled via CronJob
     Indexer,
+    /// Copy an index to another S3 endpoint; the source comes from the usual AWS_* / VEC_BUCKET env
+    Migrate {
+        #[arg(long)]
+        index: String,
+        /// Index name on the target (defaults to the source name)
+        #[arg(long)]
+        target_index: Option<String>,
+        #[arg(long, env = "MIGRATE_TARGET_ENDPOINT_URL")]
+        target_endpoint: String,
+        #[arg(long, env = "MIGRATE_TARGET_BUCKET")]
+        target_bucket: String,
+        #[arg(long, env = "MIGRATE_TARGET_ACCESS_KEY_ID")]
+        target_access_key: String,
+        #[arg(long, env = "MIGRATE_TARGET_SECRET_ACCESS_KEY")]
+        target_secret_key: String,
+        /// Progress file; rerun with the same file to resume
+        #[arg(long)]
+        state_file: Option<String>,
+        /// Skip reading objects back from the target to verify checksums
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Make every shard of an index carry the index's distance metric, for manifests queries refuse as mixed
+    RepairMetrics {
+        #[arg(long)]
+        index: String,
+        /// Remove shards whose Faiss index was built with another metric, instead of failing
+        #[arg(long)]
+        drop_mismatched: bool,
+        /// Report what would change without writing the manifest
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check an index's shards, checksums, id maps and per-vector records, printing a JSON report
+    Check {
+        #[arg(long)]
+        index: String,
+        /// Vector bucket holding the index's per-vector records
+        #[arg(long, default_value = "default-bucket")]
+        bucket: String,
+        /// Drop corrupt shards from the manifest, fix its counts and delete records of deleted vectors
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Rewrite an index's manifest and config in the current schema; readers upgrade older ones on every load
+    UpgradeSchema {
+        #[arg(long)]
+        index: String,
+        /// Report the versions found without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete old WAL segments, expired exports, orphaned shard files and stale temp files (VEC_*_RETENTION_*)
+    Lifecycle {
+        /// Report what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete index artifacts (shard files, quantizers, projections) that no manifest reaches
+    Gc {
+        /// Only collect objects at least this old, so builds still writing their manifest keep theirs
+        #[arg(long, env = "VEC_ORPHAN_GRACE_HOURS", default_value_t = 24)]
+        older_than_hours: i64,
+        /// Report unreachable objects without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Replay this deployment's change log (VEC_CHANGELOG=true) against another one's API
+    Replicate {
+        #[arg(long, env = "REPLICATION_TARGET_URL")]
+        target_url: String,
+        #[arg(long, env = "REPLICATION_TARGET_API_KEY")]
+        target_api_key: Option<String>,
+        #[arg(long, value_enum, default_value = "source-wins")]
+        conflict_policy: replication::ConflictPolicy,
+        /// Progress file; restart with the same file to resume
+        #[arg(long, default_value = "replication.state.json")]
+        state_file: String,
+        #[arg(long, default_value_t = 5)]
+        poll_interval_secs: u64,
+    },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+fn main() -> anyhow::Result<()> {
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    // Unset, tokio runs one worker per core (or TOKIO_WORKER_THREADS).
+    if let Some(threads) = std::env::var("VEC_WORKER_THREADS").ok().and_then(|v| v.parse::<usize>().ok()).filter(|&n| n > 0) {
+        runtime.worker_threads(threads);
+    }
+    runtime.enable_all().build()?.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    request_id::init_logging();
     
     // Show which backend is being used
     tracing::info!("🚀 Vector Database Starting");
@@ -40,6 +171,87 @@ async fn main() -> anyhow::Result<()> {
     match Cli::parse().cmd {
         Cmd::Api => api::run().await?,
         Cmd::Indexer => indexer::run_once().await?,
+        Cmd::Migrate {
+            index,
+            target_index,
+            target_endpoint,
+            target_bucket,
+            target_access_key,
+            target_secret_key,
+            state_file,
+            no_verify,
+        } => {
+            let source = minio::S3Client::from_env().await?;
+            let target = minio::S3Client::connect(minio::S3Settings {
+                endpoint: target_endpoint,
+                credentials: minio::S3Credentials::Static {
+                    access_key: target_access_key,
+                    secret_key: target_secret_key,
+                },
+                assume_role: None,
+                bucket: target_bucket,
+            })
+            .await?;
+            let opts = migrate::MigrateOptions {
+                state_file: state_file.unwrap_or_else(|| format!("migrate-{}.state.json", index)),
+                index,
+                target_index,
+                verify: !no_verify,
+            };
+            let report = migrate::migrate_index(&source, &target, &opts).await?;
+            tracing::info!("Migrated {} objects, {} already present", report.copied, report.skipped);
+        }
+        Cmd::RepairMetrics { index, drop_mismatched, dry_run } => {
+            let s3 = minio::S3Client::from_env().await?;
+            let repair = indexer::repair_metrics(&s3, &index, drop_mismatched, dry_run).await?;
+            tracing::info!(
+                "Index {} metric {}: relabeled {:?}, dropped {:?}{}",
+                index,
+                repair.metric,
+                repair.relabeled,
+                if repair.dropped { repair.mismatched.as_slice() } else { &[] },
+                if dry_run { " (dry run)" } else { "" }
+            );
+        }
+        Cmd::Check { index, bucket, repair } => {
+            let s3 = minio::S3Client::from_env().await?;
+            let report = check::check(&s3, &bucket, &index, repair).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Cmd::UpgradeSchema { index, dry_run } => {
+            let s3 = minio::S3Client::from_env().await?;
+            let upgrade = schema::rewrite(&s3, &index, dry_run).await?;
+            tracing::info!(
+                "Index {}: manifest version {:?}, config version {:?}, {}",
+                index,
+                upgrade.manifest_version,
+                upgrade.config_version,
+                if upgrade.rewritten { "rewritten" } else { "nothing written" }
+            );
+        }
+        Cmd::Lifecycle { dry_run } => {
+            let s3 = minio::S3Client::from_env().await?;
+            let policy = lifecycle::LifecyclePolicy::from_env();
+            let report = lifecycle::run(&s3, &policy, chrono::Utc::now(), dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Cmd::Gc { older_than_hours, dry_run } => {
+            let s3 = minio::S3Client::from_env().await?;
+            let window = chrono::Duration::hours(older_than_hours.max(1));
+            let report = gc::collect(&s3, window, chrono::Utc::now(), dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Cmd::Replicate { target_url, target_api_key, conflict_policy, state_file, poll_interval_secs } => {
+            let source = minio::S3Client::from_env().await?;
+            let opts = replication::ReplicationOptions {
+                target_url,
+                target_api_key,
+                policy: conflict_policy,
+                state_file,
+                poll_interval: std::time::Duration::from_secs(poll_interval_secs.max(1)),
+            };
+            replication::run(&source, &opts).await?;
+        }
     }
     Ok(())
 }
@@ -1,5 +1,7 @@
 use std::time::Instant;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 
@@ -65,10 +67,14 @@ pub struct QueryMetrics {
     pub recall_estimate: Option<f64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LatencyBreakdown {
     pub index_load_ms: f64,
     pub search_ms: f64,
+    /// Round trips of shards searched by their owner replica in cluster mode,
+    /// which don't report their own load/search split.
+    #[serde(default)]
+    pub remote_search_ms: f64,
     pub result_merge_ms: f64,
     pub total_ms: f64,
 }
@@ -83,10 +89,152 @@ pub struct IndexingMetrics {
     pub throughput_vectors_per_sec: f64,
 }
 
+/// Running aggregate of one `track_metric` name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricStat {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub last: f64,
+}
+
+/// One served query, kept for the admin dashboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub index_name: String,
+    pub topk: usize,
+    pub results: usize,
+    pub took_ms: f64,
+}
+
+const RECENT_QUERY_LIMIT: usize = 50;
+
+/// Ingest rates are averaged over this trailing window.
+const INGEST_RATE_WINDOW_SECS: u64 = 60;
+
+/// Ingest accounting for one index on this replica, as reported by GetIndexStats.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestStats {
+    pub vectors_total: u64,
+    pub bytes_total: u64,
+    pub slices_flushed: u64,
+    /// Averaged over the last `INGEST_RATE_WINDOW_SECS`.
+    pub vectors_per_sec: f64,
+    pub bytes_per_sec: f64,
+    /// Vectors in the WAL that are not yet in a staged slice.
+    pub wal_lag_vectors: u64,
+    /// Age of the oldest of those vectors.
+    pub wal_lag_secs: f64,
+    pub last_flush_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct IngestCounters {
+    stats: IngestStats,
+    /// `(when, vectors, bytes)` of the appends inside the rate window.
+    window: VecDeque<(Instant, u64, u64)>,
+    oldest_pending: Option<Instant>,
+}
+
+impl IngestCounters {
+    fn trim(&mut self, now: Instant) {
+        while self.window.front().is_some_and(|(at, _, _)| now.duration_since(*at).as_secs() >= INGEST_RATE_WINDOW_SECS) {
+            self.window.pop_front();
+        }
+    }
+
+    fn snapshot(&mut self, now: Instant) -> IngestStats {
+        self.trim(now);
+        let window = INGEST_RATE_WINDOW_SECS as f64;
+        IngestStats {
+            vectors_per_sec: self.window.iter().map(|(_, v, _)| *v).sum::<u64>() as f64 / window,
+            bytes_per_sec: self.window.iter().map(|(_, _, b)| *b).sum::<u64>() as f64 / window,
+            wal_lag_secs: self.oldest_pending.map_or(0.0, |at| now.duration_since(at).as_secs_f64()),
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// Bucket upper bounds (seconds) of the query phase histograms.
+const LATENCY_BUCKETS_SECS: [f64; 14] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Fixed-bucket histogram rendered in the Prometheus text format. Observations
+/// are lock-free, so concurrent queries never wait on each other to record.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Observations per bucket (not cumulative); the last entry is `+Inf`.
+    counts: Vec<AtomicU64>,
+    /// `f64` bits of the sum of observations.
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: (0..=LATENCY_BUCKETS_SECS.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn observe(&self, value: f64) {
+        let bucket = LATENCY_BUCKETS_SECS.iter().position(|le| value <= *le).unwrap_or(LATENCY_BUCKETS_SECS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, name: &str, labels: &str, out: &mut String) {
+        use std::fmt::Write;
+        let mut cumulative = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let le = LATENCY_BUCKETS_SECS.get(i).map(|b| b.to_string()).unwrap_or_else(|| "+Inf".to_string());
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, f64::from_bits(self.sum.load(Ordering::Relaxed)));
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// Phases of a query, as labelled in `genai_vectors_query_phase_seconds`.
+const QUERY_PHASES: [&str; 5] = ["index_load", "search", "remote_search", "merge", "total"];
+
+/// `track_metric` aggregates are spread over this many independently locked
+/// maps, picked by metric name, so unrelated metrics never contend.
+const METRIC_SHARDS: usize = 16;
+
+/// Finished operations kept for summaries; older ones are dropped first.
+const MAX_RETAINED_OPERATIONS: usize = 10_000;
+
 /// Thread-safe performance metrics collector
 pub struct MetricsCollector {
-    metrics: std::sync::Arc<std::sync::Mutex<Vec<PerformanceMetrics>>>,
-    current_operation: std::sync::Arc<std::sync::Mutex<Option<OperationTracker>>>,
+    metrics: Mutex<VecDeque<PerformanceMetrics>>,
+    simple_metrics: [Mutex<HashMap<String, MetricStat>>; METRIC_SHARDS],
+    recent_queries: Mutex<VecDeque<QueryLogEntry>>,
+    /// One histogram per entry of `QUERY_PHASES`.
+    query_phases: [Histogram; 5],
+    ingest: Mutex<HashMap<String, IngestCounters>>,
+}
+
+/// An operation in progress, returned by [`MetricsCollector::start_operation`].
+/// Each caller holds its own guard, so concurrent operations are attributed
+/// correctly. Recorded on [`finish`](Self::finish), or with no extra data when
+/// dropped unfinished.
+pub struct OperationGuard<'a> {
+    collector: &'a MetricsCollector,
+    tracker: Option<OperationTracker>,
+    query_metrics: Option<QueryMetrics>,
 }
 
 struct OperationTracker {
@@ -97,11 +245,68 @@ struct OperationTracker {
     config: IndexConfig,
 }
 
+impl OperationGuard<'_> {
+    /// Attach query-specific metrics to this operation
+    pub fn record_query_metrics(&mut self, query_metrics: QueryMetrics) {
+        self.query_metrics = Some(query_metrics);
+    }
+
+    /// Finish tracking and record metrics
+    pub fn finish(mut self, additional_data: HashMap<String, f64>) {
+        self.record(&additional_data);
+    }
+
+    fn record(&mut self, additional_data: &HashMap<String, f64>) {
+        let Some(tracker) = self.tracker.take() else { return };
+        let duration = tracker.start_time.elapsed();
+        let current_memory = MetricsCollector::get_memory_usage_mb();
+        let value = |key: &str| additional_data.get(key).copied().unwrap_or(0.0);
+
+        let metrics = PerformanceMetrics {
+            timestamp: Utc::now(),
+            operation_type: tracker.operation_type,
+            index_name: tracker.index_name,
+            vector_count: value("vector_count") as usize,
+            dimension: value("dimension") as usize,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            cpu_time_ms: MetricsCollector::get_cpu_time_ms(),
+            index_config: tracker.config,
+            memory_usage_mb: current_memory,
+            peak_memory_mb: current_memory.max(tracker.start_memory as f64),
+            query_metrics: self.query_metrics.take(),
+            indexing_metrics: None,
+            error_count: value("error_count") as u32,
+            error_rate: value("error_rate"),
+        };
+
+        let mut metrics_vec = self.collector.metrics.lock().unwrap();
+        if metrics_vec.len() == MAX_RETAINED_OPERATIONS {
+            metrics_vec.pop_front();
+        }
+        metrics_vec.push_back(metrics);
+    }
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        self.record(&HashMap::new());
+    }
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MetricsCollector {
     pub fn new() -> Self {
         Self {
-            metrics: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
-            current_operation: std::sync::Arc::new(std::sync::Mutex::new(None)),
+            metrics: Mutex::new(VecDeque::new()),
+            simple_metrics: Default::default(),
+            recent_queries: Mutex::new(VecDeque::new()),
+            query_phases: Default::default(),
+            ingest: Mutex::new(HashMap::new()),
         }
     }
     
@@ -109,68 +314,31 @@ impl MetricsCollector {
     pub fn start_operation(&self, 
                           operation_type: OperationType, 
                           index_name: String, 
-                          config: IndexConfig) {
-        let tracker = OperationTracker {
-            operation_type,
-            index_name,
-            start_time: Instant::now(),
-            start_memory: Self::get_memory_usage_mb() as u64,
-            config,
-        };
-        
-        let mut current = self.current_operation.lock().unwrap();
-        *current = Some(tracker);
-    }
-    
-    /// Finish tracking and record metrics
-    pub fn finish_operation(&self, additional_data: HashMap<String, f64>) {
-        let mut current = self.current_operation.lock().unwrap();
-        if let Some(tracker) = current.take() {
-            let duration = tracker.start_time.elapsed();
-            let current_memory = Self::get_memory_usage_mb();
-            
-            let metrics = PerformanceMetrics {
-                timestamp: Utc::now(),
-                operation_type: tracker.operation_type,
-                index_name: tracker.index_name,
-                vector_count: *additional_data.get("vector_count").unwrap_or(&0.0) as usize,
-                dimension: *additional_data.get("dimension").unwrap_or(&0.0) as usize,
-                duration_ms: duration.as_secs_f64() * 1000.0,
-                cpu_time_ms: Self::get_cpu_time_ms(),
-                index_config: tracker.config,
-                memory_usage_mb: current_memory,
-                peak_memory_mb: current_memory.max(tracker.start_memory as f64),
-                query_metrics: None,
-                indexing_metrics: None,
-                error_count: *additional_data.get("error_count").unwrap_or(&0.0) as u32,
-                error_rate: *additional_data.get("error_rate").unwrap_or(&0.0),
-            };
-            
-            let mut metrics_vec = self.metrics.lock().unwrap();
-            metrics_vec.push(metrics);
-        }
-    }
-    
-    /// Record query-specific metrics
-    pub fn record_query_metrics(&self, query_metrics: QueryMetrics) {
-        let mut metrics_vec = self.metrics.lock().unwrap();
-        if let Some(last_metric) = metrics_vec.last_mut() {
-            last_metric.query_metrics = Some(query_metrics);
-        }
-    }
-    
-    /// Record indexing-specific metrics
-    pub fn record_indexing_metrics(&self, indexing_metrics: IndexingMetrics) {
-        let mut metrics_vec = self.metrics.lock().unwrap();
-        if let Some(last_metric) = metrics_vec.last_mut() {
-            last_metric.indexing_metrics = Some(indexing_metrics);
+                          config: IndexConfig) -> OperationGuard<'_> {
+        OperationGuard {
+            collector: self,
+            tracker: Some(OperationTracker {
+                operation_type,
+                index_name,
+                start_time: Instant::now(),
+                start_memory: Self::get_memory_usage_mb() as u64,
+                config,
+            }),
+            query_metrics: None,
         }
     }
     
     /// Get all collected metrics
     pub fn get_metrics(&self) -> Vec<PerformanceMetrics> {
         let metrics = self.metrics.lock().unwrap();
-        metrics.clone()
+        metrics.iter().cloned().collect()
+    }
+    
+    fn metric_shard(&self, name: &str) -> &Mutex<HashMap<String, MetricStat>> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        name.hash(&mut hasher);
+        &self.simple_metrics[hasher.finish() as usize % METRIC_SHARDS]
     }
     
     /// Track a simple metric value
@@ -179,8 +347,94 @@ impl MetricsCollector {
         // store these in a time-series database or metrics collection system
         tracing::debug!("Metric {}: {}", name, value);
         
-        // You could also store these in a separate hashmap for simple metrics
-        // that don't need the full PerformanceMetrics structure
+        let mut simple = self.metric_shard(name).lock().unwrap();
+        match simple.get_mut(name) {
+            Some(stat) => {
+                stat.count += 1;
+                stat.sum += value;
+                stat.min = stat.min.min(value);
+                stat.max = stat.max.max(value);
+                stat.last = value;
+            }
+            None => {
+                simple.insert(name.to_string(), MetricStat { count: 1, sum: value, min: value, max: value, last: value });
+            }
+        }
+    }
+    
+    /// Aggregates of every metric passed to `track_metric`, by name
+    pub fn metric_stats(&self) -> std::collections::BTreeMap<String, MetricStat> {
+        self.simple_metrics
+            .iter()
+            .flat_map(|shard| shard.lock().unwrap().clone())
+            .collect()
+    }
+    
+    /// Remember a served query; only the most recent ones are kept
+    pub fn record_recent_query(&self, entry: QueryLogEntry) {
+        let mut recent = self.recent_queries.lock().unwrap();
+        if recent.len() == RECENT_QUERY_LIMIT {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+    
+    /// Count vectors appended to the WAL for `index`
+    pub fn record_ingest(&self, index: &str, vectors: u64, bytes: u64) {
+        let now = Instant::now();
+        let mut ingest = self.ingest.lock().unwrap();
+        let counters = ingest.entry(index.to_string()).or_default();
+        counters.trim(now);
+        counters.window.push_back((now, vectors, bytes));
+        counters.stats.vectors_total += vectors;
+        counters.stats.bytes_total += bytes;
+        counters.stats.wal_lag_vectors += vectors;
+        counters.oldest_pending.get_or_insert(now);
+    }
+    
+    /// Count a staged slice written for `index`. The ingest buffer is shared by
+    /// every index and flushed whole, so no index has WAL lag afterwards.
+    pub fn record_slice_flush(&self, index: &str) {
+        let mut ingest = self.ingest.lock().unwrap();
+        let counters = ingest.entry(index.to_string()).or_default();
+        counters.stats.slices_flushed += 1;
+        counters.stats.last_flush_at = Some(Utc::now());
+        for counters in ingest.values_mut() {
+            counters.stats.wal_lag_vectors = 0;
+            counters.oldest_pending = None;
+        }
+    }
+    
+    /// Ingest accounting for `index`; all zero if it saw no writes on this replica
+    pub fn ingest_stats(&self, index: &str) -> IngestStats {
+        let mut ingest = self.ingest.lock().unwrap();
+        ingest.get_mut(index).map(|counters| counters.snapshot(Instant::now())).unwrap_or_default()
+    }
+    
+    /// Feed a query's latency breakdown into the phase histograms
+    pub fn record_latency_breakdown(&self, breakdown: &LatencyBreakdown) {
+        let phases = [breakdown.index_load_ms, breakdown.search_ms, breakdown.remote_search_ms, breakdown.result_merge_ms, breakdown.total_ms];
+        for (histogram, ms) in self.query_phases.iter().zip(phases) {
+            histogram.observe(ms / 1000.0);
+        }
+    }
+    
+    /// Histograms in the Prometheus text exposition format, served at `/metrics`
+    pub fn prometheus_text(&self) -> String {
+        let name = "genai_vectors_query_phase_seconds";
+        let mut out = format!(
+            "# HELP {} Query latency by phase (index_load: S3/cache and Faiss deserialisation, search: Faiss compute, remote_search: round trips to shard owners, merge: cross-shard merge).\n# TYPE {} histogram\n",
+            name, name
+        );
+        for (phase, histogram) in QUERY_PHASES.iter().zip(self.query_phases.iter()) {
+            histogram.render(name, &format!("phase=\"{}\"", phase), &mut out);
+        }
+        out
+    }
+    
+    /// Recently served queries, newest first
+    pub fn recent_queries(&self) -> Vec<QueryLogEntry> {
+        self.recent_queries.lock().unwrap().iter().rev().cloned().collect()
     }
     
     /// Start monitoring background processes
@@ -219,13 +473,19 @@ impl MetricsCollector {
         }
     }
     
-    /// Export metrics to JSON file
-    pub fn export_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let metrics = self.get_metrics();
-        let json = serde_json::to_string_pretty(&metrics)?;
-        std::fs::write(filename, json)?;
-        tracing::info!("Exported {} metrics to {}", metrics.len(), filename);
-        Ok(())
+    /// Aggregates to export: every tracked metric plus per-operation summaries
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let summaries = [
+            ("vectorQuery", OperationType::VectorQuery),
+            ("indexTraining", OperationType::IndexTraining),
+            ("vectorInsertion", OperationType::VectorInsertion),
+        ];
+        MetricsSnapshot {
+            timestamp: Utc::now(),
+            replica: std::env::var("HOSTNAME").ok(),
+            metrics: self.metric_stats(),
+            summaries: summaries.into_iter().map(|(name, op)| (name.to_string(), self.get_summary(op))).collect(),
+        }
     }
     
     /// Clear all collected metrics
@@ -296,7 +556,7 @@ pub static METRICS_COLLECTOR: std::sync::OnceLock<MetricsCollector> = std::sync:
 
 /// Get the global metrics collector
 pub fn get_metrics_collector() -> &'static MetricsCollector {
-    METRICS_COLLECTOR.get_or_init(|| MetricsCollector::new())
+    METRICS_COLLECTOR.get_or_init(MetricsCollector::new)
 }
 
 /// Simple macro for measuring operation duration
@@ -361,15 +621,11 @@ impl Default for MonitoringConfig {
 /// Automated performance monitoring and alerting
 pub struct PerformanceMonitor {
     config: MonitoringConfig,
-    last_export: std::sync::Arc<std::sync::Mutex<Instant>>,
 }
 
 impl PerformanceMonitor {
     pub fn new(config: MonitoringConfig) -> Self {
-        Self {
-            config,
-            last_export: std::sync::Arc::new(std::sync::Mutex::new(Instant::now())),
-        }
+        Self { config }
     }
     
     /// Check metrics against thresholds and generate alerts
@@ -426,24 +682,6 @@ impl PerformanceMonitor {
         alerts
     }
     
-    /// Periodic export of metrics
-    pub fn maybe_export_metrics(&self) {
-        let mut last_export = self.last_export.lock().unwrap();
-        let now = Instant::now();
-        
-        if now.duration_since(*last_export).as_secs() >= self.config.export_interval_seconds {
-            let collector = get_metrics_collector();
-            let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-            let filename = format!("metrics_export_{}.json", timestamp);
-            
-            if let Err(e) = collector.export_to_file(&filename) {
-                tracing::error!("Failed to export metrics: {}", e);
-            }
-            
-            *last_export = now;
-        }
-    }
-    
     /// Generate performance report
     pub fn generate_report(&self) -> PerformanceReport {
         let collector = get_metrics_collector();
@@ -515,3 +753,154 @@ pub struct PerformanceReport {
     pub alerts: Vec<Alert>,
     pub recommendations: Vec<String>,
 }
+
+/// Aggregated metrics exported by one replica at one point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub replica: Option<String>,
+    pub metrics: std::collections::BTreeMap<String, MetricStat>,
+    pub summaries: std::collections::BTreeMap<String, MetricsSummary>,
+}
+
+/// Snapshots are appended to one object per UTC day, `metrics/<yyyy-mm-dd>.json`,
+/// in the service bucket so history survives restarts.
+const METRICS_PREFIX: &str = "metrics/";
+
+fn daily_key(date: chrono::NaiveDate) -> String {
+    format!("{}{}.json", METRICS_PREFIX, date.format("%Y-%m-%d"))
+}
+
+/// Append the collector's current aggregates to today's export.
+pub async fn export_snapshot(s3: &crate::minio::S3Client) -> anyhow::Result<()> {
+    let snapshot = get_metrics_collector().snapshot();
+    let key = daily_key(snapshot.timestamp.date_naive());
+    let mut snapshots: Vec<MetricsSnapshot> = match s3.get_object(&key).await {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    snapshots.push(snapshot);
+    s3.put_object(&key, serde_json::to_vec(&snapshots)?.into()).await
+}
+
+/// Daily exports dated before `cutoff`. Other objects under `metrics/`, such as
+/// an index that happens to be named `metrics`, never match.
+fn expired_exports(keys: &[String], cutoff: chrono::NaiveDate) -> Vec<String> {
+    keys.iter()
+        .filter(|key| {
+            key.strip_prefix(METRICS_PREFIX)
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+                .is_some_and(|date| date < cutoff)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Delete daily exports older than `retention_days`.
+pub async fn prune_exports(s3: &crate::minio::S3Client, retention_days: i64) -> anyhow::Result<usize> {
+    let cutoff = Utc::now().date_naive() - chrono::Duration::days(retention_days);
+    let expired = expired_exports(&s3.list_objects(METRICS_PREFIX).await?, cutoff);
+    for key in &expired {
+        s3.delete_object(key).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Export metrics in the background every `VEC_METRICS_EXPORT_SECS` (default
+/// 300, 0 disables) and prune exports older than `VEC_METRICS_RETENTION_DAYS`
+/// (default 30).
+pub fn spawn_exporter(s3: crate::minio::S3Client) {
+    let env = |name: &str, default: i64| {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let export_secs = env("VEC_METRICS_EXPORT_SECS", 300);
+    let retention_days = env("VEC_METRICS_RETENTION_DAYS", 30).max(1);
+    if export_secs <= 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(export_secs as u64));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = export_snapshot(&s3).await {
+                tracing::warn!(error = %e, "Failed to export metrics");
+            }
+            match prune_exports(&s3, retention_days).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(pruned, "Pruned expired metrics exports"),
+                Err(e) => tracing::warn!(error = %e, "Failed to prune metrics exports"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_render_is_cumulative() {
+        let histogram = Histogram::default();
+        histogram.observe(0.002);
+        histogram.observe(0.002);
+        histogram.observe(60.0);
+        let mut out = String::new();
+        histogram.render("q", "phase=\"search\"", &mut out);
+        assert!(out.contains("q_bucket{phase=\"search\",le=\"0.001\"} 0\n"));
+        assert!(out.contains("q_bucket{phase=\"search\",le=\"0.0025\"} 2\n"));
+        assert!(out.contains("q_bucket{phase=\"search\",le=\"30\"} 2\n"));
+        assert!(out.contains("q_bucket{phase=\"search\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("q_count{phase=\"search\"} 3\n"));
+    }
+
+    #[test]
+    fn test_concurrent_operations_keep_attribution() {
+        let collector = MetricsCollector::new();
+        let config = || IndexConfig { nlist: 1, nprobe: None, m: 1, nbits: 8, metric: "cosine".to_string(), shard_size: 1 };
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let collector = &collector;
+                scope.spawn(move || {
+                    let guard = collector.start_operation(OperationType::VectorQuery, format!("index-{}", i), config());
+                    for _ in 0..100 {
+                        collector.track_metric("query.topk", 10.0);
+                    }
+                    guard.finish(HashMap::from([("vector_count".to_string(), i as f64)]));
+                });
+            }
+        });
+        let metrics = collector.get_metrics();
+        assert_eq!(metrics.len(), 8);
+        assert!(metrics.iter().all(|m| m.index_name == format!("index-{}", m.vector_count)));
+        assert_eq!(collector.metric_stats()["query.topk"].count, 800);
+    }
+
+    #[test]
+    fn test_expired_exports() {
+        let keys = vec![
+            "metrics/2025-01-01.json".to_string(),
+            "metrics/2025-01-15.json".to_string(),
+            "metrics/vectors/2025-01-01.json".to_string(),
+            "metrics/notes.json".to_string(),
+        ];
+        let cutoff = chrono::NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        assert_eq!(expired_exports(&keys, cutoff), vec!["metrics/2025-01-01.json".to_string()]);
+    }
+
+    #[test]
+    fn test_ingest_stats_track_wal_lag() {
+        let collector = MetricsCollector::new();
+        collector.record_ingest("a", 30, 3000);
+        collector.record_ingest("b", 10, 1000);
+        let stats = collector.ingest_stats("a");
+        assert_eq!((stats.vectors_total, stats.bytes_total, stats.wal_lag_vectors), (30, 3000, 30));
+        assert_eq!(stats.vectors_per_sec, 30.0 / INGEST_RATE_WINDOW_SECS as f64);
+
+        collector.record_slice_flush("b");
+        assert_eq!(collector.ingest_stats("a").wal_lag_vectors, 0);
+        assert_eq!(collector.ingest_stats("b").slices_flushed, 1);
+        assert_eq!(collector.ingest_stats("missing"), IngestStats::default());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::index_copy::{translate_document, translate_key};
+use crate::integrity::sha256_file;
+use crate::minio::S3Client;
+use crate::tempfiles::TempFile;
+use crate::trash::{index_prefixes, record_prefixes};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Copy one index between two object stores (e.g. MinIO → AWS).
+pub struct MigrateOptions {
+    pub index: String,
+    /// Name of the index on the target; defaults to the source name.
+    pub target_index: Option<String>,
+    /// Local file recording finished objects so an interrupted run can resume.
+    pub state_file: String,
+    /// Re-read every object from the target and compare checksums.
+    pub verify: bool,
+}
+
+/// Progress of a migration: target key → SHA-256 of the bytes written there.
+#[derive(Serialize, Deserialize, Default)]
+struct MigrationState {
+    completed: HashMap<String, String>,
+}
+
+impl MigrationState {
+    fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+        serde_json::from_slice(&data).context("Failed to parse migration state file")
+    }
+
+    /// Write-then-rename so a crash never leaves a truncated state file.
+    fn save(&self, path: &str) -> Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub copied: usize,
+    pub skipped: usize,
+}
+
+/// Checksums recorded in the source manifest, keyed by source object key.
+fn expected_checksums(manifest: &Value) -> HashMap<String, String> {
+    let mut expected = HashMap::new();
+    for shard in manifest.get("shards").and_then(|s| s.as_array()).into_iter().flatten() {
+        let (Some(index_path), Some(checksums)) = (shard.get("index_path").and_then(|v| v.as_str()), shard.get("checksums")) else {
+            continue;
+        };
+        let id_map_path = index_path.replace("index.faiss", "id_map.json");
+        let metadata_path = shard.get("metadata_path").and_then(|v| v.as_str()).unwrap_or_default();
+        let vectors_path = shard.get("vectors_path").and_then(|v| v.as_str()).unwrap_or_default();
+        let text_path = shard.get("text_path").and_then(|v| v.as_str()).unwrap_or_default();
+        for (key, field) in [
+            (index_path, "index"),
+            (id_map_path.as_str(), "id_map"),
+            (metadata_path, "metadata"),
+            (vectors_path, "vectors"),
+            (text_path, "text"),
+        ] {
+            if let Some(sum) = checksums.get(field).and_then(|v| v.as_str()) {
+                expected.insert(key.to_string(), sum.to_string());
+            }
+        }
+    }
+    expected
+}
+
+pub async fn migrate_index(source: &S3Client, target: &S3Client, opts: &MigrateOptions) -> Result<MigrationReport> {
+    let src = opts.index.as_str();
+    let dst = opts.target_index.as_deref().unwrap_or(src);
+    let manifest_key = format!("indexes/{}/manifest.json", src);
+    let config_key = format!("indexes/{}/config.json", src);
+
+    let manifest: Option<Value> = match source.get_object(&manifest_key).await {
+        Ok(data) => Some(serde_json::from_slice(&data).context("Failed to parse source manifest")?),
+        Err(_) => None,
+    };
+    let expected = manifest.as_ref().map(expected_checksums).unwrap_or_default();
+
+    let mut keys = Vec::new();
+    for prefix in index_prefixes(src).into_iter().chain(record_prefixes(src)) {
+        keys.extend(source.list_objects(&prefix).await?);
+    }
+    keys.retain(|key| *key != manifest_key && *key != config_key);
+
+    let mut state = MigrationState::load(&opts.state_file)?;
+    let mut report = MigrationReport::default();
+    let scratch = TempFile::new("migrate", "");
+    let tmp = scratch.path();
+
+    for key in &keys {
+        let target_key = translate_key(key, src, dst).context("Unexpected source key")?;
+        if state.completed.contains_key(&target_key) {
+            report.skipped += 1;
+            continue;
+        }
+
+        source.get_object_to_file(key, tmp).await
+            .with_context(|| format!("Failed to download {}", key))?;
+        let checksum = sha256_file(tmp)?;
+        if let Some(want) = expected.get(key) {
+            if *want != checksum {
+                return Err(anyhow::anyhow!("Source object {} is corrupted: expected {}, got {}", key, want, checksum));
+            }
+        }
+        let uploaded = target.upload_file(&target_key, tmp).await;
+        let _ = std::fs::remove_file(tmp);
+        uploaded.with_context(|| format!("Failed to upload {}", target_key))?;
+
+        if opts.verify {
+            verify_target(target, &target_key, &checksum, tmp).await?;
+        }
+        state.completed.insert(target_key, checksum);
+        state.save(&opts.state_file)?;
+        report.copied += 1;
+        if report.copied % 100 == 0 {
+            tracing::info!("Migrated {} objects ({} skipped)", report.copied, report.skipped);
+        }
+    }
+
+    // Manifest and config go last, translated to the target name, so the target
+    // index only becomes visible once every shard has arrived.
+    if let Some(mut manifest) = manifest {
+        translate_document(&mut manifest, src, dst);
+        target.put_object(&format!("indexes/{}/manifest.json", dst), serde_json::to_vec(&manifest)?.into()).await?;
+    }
+    if let Ok(data) = source.get_object(&config_key).await {
+        let mut config: Value = serde_json::from_slice(&data).context("Failed to parse source config")?;
+        translate_document(&mut config, src, dst);
+        target.put_object(&format!("indexes/{}/config.json", dst), serde_json::to_vec(&config)?.into()).await?;
+    }
+
+    tracing::info!(
+        "Migration of {} → {} complete: {} copied, {} already done",
+        src, dst, report.copied, report.skipped
+    );
+    Ok(report)
+}
+
+async fn verify_target(target: &S3Client, key: &str, checksum: &str, tmp: &str) -> Result<()> {
+    target.get_object_to_file(key, tmp).await
+        .with_context(|| format!("Failed to read back {}", key))?;
+    let actual = sha256_file(tmp);
+    let _ = std::fs::remove_file(tmp);
+    let actual = actual?;
+    if actual != checksum {
+        return Err(anyhow::anyhow!("Target object {} does not match source: expected {}, got {}", key, checksum, actual));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrity::sha256_hex;
+    use serde_json::json;
+
+    #[test]
+    fn test_expected_checksums_from_manifest() {
+        let manifest = json!({
+            "shards": [{
+                "index_path": "indexes/a/shards/s1/index.faiss",
+                "metadata_path": "indexes/a/shards/s1/metadata.json",
+                "vectors_path": "indexes/a/shards/s1/vectors.json",
+                "checksums": {"index": "i", "id_map": "m", "metadata": sha256_hex(b"x"), "vectors": "v"}
+            }, {
+                "index_path": "indexes/a/shards/s2/index.faiss",
+                "metadata_path": "indexes/a/shards/s2/metadata.json"
+            }]
+        });
+        let expected = expected_checksums(&manifest);
+        assert_eq!(expected.len(), 4);
+        assert_eq!(expected["indexes/a/shards/s1/id_map.json"], "m");
+        assert_eq!(expected["indexes/a/shards/s1/vectors.json"], "v");
+        assert_eq!(expected["indexes/a/shards/s1/metadata.json"], sha256_hex(b"x"));
+    }
+}
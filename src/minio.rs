@@ -1,62 +1,395 @@
 use anyhow::{Context, Result};
-use aws_config::Region;
+use aws_config::default_provider::credentials::DefaultCredentialsChain;
+use aws_config::sts::AssumeRoleProvider;
+use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::{config::Builder, Client, primitives::ByteStream};
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextRef;
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::config::{ConfigBag, Credentials, Intercept, RuntimeComponents, SharedCredentialsProvider};
+use aws_smithy_http_client::pool::{self, ConnectionEvent, ConnectionPool, ConnectionProtocol};
+use aws_smithy_http_client::tls::{self, rustls_provider::CryptoMode};
+use aws_sdk_s3::types::{ChecksumAlgorithm, CompletedMultipartUpload, CompletedPart};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use crate::crypto::{is_sensitive_key, Envelope};
+#[cfg(any(feature = "gcs", feature = "azure", test))]
+use crate::storage::BlobStore;
+use crate::storage::StorageBackend;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// S3 rejects multipart parts smaller than 5 MiB (except the last one).
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Tuning for multipart uploads of large files (shard indexes, parquet slices).
+#[derive(Clone, Debug)]
+pub struct MultipartConfig {
+    /// Files at or above this size are uploaded in parts.
+    pub threshold_bytes: u64,
+    pub part_size_bytes: usize,
+    /// Number of parts in flight at once; bounds memory to `part_size * concurrency`.
+    pub concurrency: usize,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: 64 * 1024 * 1024,
+            part_size_bytes: 16 * 1024 * 1024,
+            concurrency: 4,
+        }
+    }
+}
+
+impl MultipartConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let mb = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Self {
+            threshold_bytes: mb("S3_MULTIPART_THRESHOLD_MB")
+                .map(|v| v * 1024 * 1024)
+                .unwrap_or(defaults.threshold_bytes),
+            part_size_bytes: mb("S3_MULTIPART_PART_SIZE_MB")
+                .map(|v| (v as usize * 1024 * 1024).max(MIN_PART_SIZE))
+                .unwrap_or(defaults.part_size_bytes),
+            concurrency: mb("S3_MULTIPART_CONCURRENCY")
+                .map(|v| (v as usize).max(1))
+                .unwrap_or(defaults.concurrency),
+        }
+    }
+}
+
+/// Tuning for the HTTP client under the S3 SDK. Queries fetch shards in
+/// parallel, so the pool must hold enough connections per host to keep them
+/// from queueing behind each other. HTTPS endpoints that offer HTTP/2 are
+/// spoken to over it (negotiated by ALPN), multiplexing requests on fewer
+/// connections; plain-HTTP endpoints use HTTP/1.1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct S3HttpConfig {
+    /// `S3_MAX_CONNECTIONS_PER_HOST`: open connections (idle, active or
+    /// connecting) to one endpoint; requests beyond it wait. `None` (`0`, the
+    /// default) is unlimited.
+    pub max_connections_per_host: Option<usize>,
+    /// `S3_POOL_IDLE_TIMEOUT_SECS` (default 90): idle connections are closed
+    /// after this long; `0` keeps them open.
+    pub pool_idle_timeout: Option<Duration>,
+    /// `S3_CONNECT_TIMEOUT_MS`; the SDK's (3.1 s) when unset.
+    pub connect_timeout: Option<Duration>,
+    /// `S3_READ_TIMEOUT_MS`: longest wait for the first byte of a response.
+    pub read_timeout: Option<Duration>,
+    /// `S3_OPERATION_TIMEOUT_MS`: whole calls, retries included.
+    pub operation_timeout: Option<Duration>,
+}
+
+impl Default for S3HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_host: None,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            connect_timeout: None,
+            read_timeout: None,
+            operation_timeout: None,
+        }
+    }
+}
+
+impl S3HttpConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let number = |name: &str| var(name).and_then(|v| v.trim().parse::<u64>().ok());
+        let millis = |name: &str| number(name).filter(|&ms| ms > 0).map(Duration::from_millis);
+        Self {
+            max_connections_per_host: number("S3_MAX_CONNECTIONS_PER_HOST").filter(|&n| n > 0).map(|n| n as usize),
+            pool_idle_timeout: match number("S3_POOL_IDLE_TIMEOUT_SECS") {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Self::default().pool_idle_timeout,
+            },
+            connect_timeout: millis("S3_CONNECT_TIMEOUT_MS"),
+            read_timeout: millis("S3_READ_TIMEOUT_MS"),
+            operation_timeout: millis("S3_OPERATION_TIMEOUT_MS"),
+        }
+    }
+
+    fn http_client(&self) -> Result<pool::Client> {
+        let mut builder = ConnectionPool::builder()
+            .idle_timeout(self.pool_idle_timeout)
+            .event_listener(record_connection);
+        builder.set_max_connections_per_host(self.max_connections_per_host);
+        let pool = builder
+            .tls_provider(tls::Provider::Rustls(CryptoMode::AwsLc))
+            .build_https()
+            .context("Failed to build the S3 connection pool")?;
+        Ok(pool::Client::new(&pool)?)
+    }
+
+    fn timeouts(&self) -> TimeoutConfig {
+        let mut timeouts = TimeoutConfig::builder();
+        timeouts
+            .set_connect_timeout(self.connect_timeout)
+            .set_read_timeout(self.read_timeout)
+            .set_operation_timeout(self.operation_timeout);
+        timeouts.build()
+    }
+}
+
+/// Connection metrics of the S3 pool. Set against `s3.requests`,
+/// `s3.connections_opened` shows how often requests found no connection to
+/// reuse.
+fn record_connection(event: &ConnectionEvent<'_>) {
+    let metrics = crate::metrics::get_metrics_collector();
+    match event {
+        ConnectionEvent::Opened(opened) => {
+            metrics.track_metric("s3.connections_opened", 1.0);
+            metrics.track_metric("s3.connect_time_ms", opened.stats().total_duration().as_secs_f64() * 1000.0);
+            if opened.connection().protocol() == ConnectionProtocol::Http2 {
+                metrics.track_metric("s3.http2_connections", 1.0);
+            }
+        }
+        ConnectionEvent::EstablishmentFailed(_) => metrics.track_metric("s3.connect_failures", 1.0),
+        _ => {}
+    }
+}
+
+/// Counts every request the SDK sends, retries included, as `s3.requests`.
+#[derive(Debug)]
+struct CountRequests;
+
+impl Intercept for CountRequests {
+    fn name(&self) -> &'static str {
+        "CountRequests"
+    }
+
+    fn read_before_transmit(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), aws_sdk_s3::error::BoxError> {
+        crate::metrics::get_metrics_collector().track_metric("s3.requests", 1.0);
+        Ok(())
+    }
+}
 
 #[derive(Clone)]
 pub struct S3Client {
     pub client: Client,
     bucket: String,
+    multipart: MultipartConfig,
+    envelope: Option<Arc<Envelope>>,
+    /// Set when `VEC_STORAGE_BACKEND` selects GCS or Azure; object calls go there
+    /// instead of `client`.
+    #[cfg(any(feature = "gcs", feature = "azure", test))]
+    blob: Option<Arc<BlobStore>>,
+}
+
+/// Where and as whom to connect; `from_env` reads the standard variables, while
+/// tools like `migrate` build a second set for the target endpoint.
+#[derive(Clone, Debug)]
+pub struct S3Settings {
+    pub endpoint: String,
+    pub credentials: S3Credentials,
+    /// `S3_ASSUME_ROLE_ARN`: a role to assume with `credentials`.
+    pub assume_role: Option<AssumeRole>,
+    pub bucket: String,
+}
+
+/// Who the client authenticates as.
+#[derive(Clone, Debug, PartialEq)]
+pub enum S3Credentials {
+    /// Fixed keys: `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`, MinIO's
+    /// defaults when unset.
+    Static { access_key: String, secret_key: String },
+    /// `S3_CREDENTIALS=default`: the SDK's provider chain (environment,
+    /// profile, web identity token as with EKS IRSA, container and instance
+    /// credentials), refreshed before they expire. Chosen without
+    /// `S3_CREDENTIALS` when `AWS_WEB_IDENTITY_TOKEN_FILE` is set and
+    /// `AWS_ACCESS_KEY_ID` is not.
+    Default,
+}
+
+/// A role assumed through STS; its temporary credentials are renewed before
+/// they expire.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssumeRole {
+    pub role_arn: String,
+    /// `S3_ASSUME_ROLE_SESSION_NAME` (default `genai-vectors`).
+    pub session_name: String,
+    /// `S3_ASSUME_ROLE_EXTERNAL_ID`, when the role's trust policy requires one.
+    pub external_id: Option<String>,
+    /// `S3_STS_ENDPOINT_URL`, e.g. MinIO's own endpoint; the regional AWS STS
+    /// endpoint of `AWS_REGION` when unset.
+    pub sts_endpoint: Option<String>,
+}
+
+impl S3Settings {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let access_key = var("AWS_ACCESS_KEY_ID");
+        let credentials = match var("S3_CREDENTIALS").as_deref() {
+            Some("default") => S3Credentials::Default,
+            None if access_key.is_none() && var("AWS_WEB_IDENTITY_TOKEN_FILE").is_some() => S3Credentials::Default,
+            _ => S3Credentials::Static {
+                access_key: access_key.unwrap_or_else(|| "minioadmin".to_string()),
+                secret_key: var("AWS_SECRET_ACCESS_KEY").unwrap_or_else(|| "minioadmin".to_string()),
+            },
+        };
+        Self {
+            endpoint: var("AWS_ENDPOINT_URL")
+                .unwrap_or_else(|| "http://minio:9000".to_string()),
+            credentials,
+            assume_role: var("S3_ASSUME_ROLE_ARN").map(|role_arn| AssumeRole {
+                role_arn,
+                session_name: var("S3_ASSUME_ROLE_SESSION_NAME").unwrap_or_else(|| "genai-vectors".to_string()),
+                external_id: var("S3_ASSUME_ROLE_EXTERNAL_ID"),
+                sts_endpoint: var("S3_STS_ENDPOINT_URL"),
+            }),
+            bucket: var("VEC_BUCKET")
+                .unwrap_or_else(|| "vectors".to_string()),
+        }
+    }
+
+    /// Provider of the credentials to sign requests with. The client caches
+    /// what it returns and asks again shortly before they expire.
+    async fn credentials_provider(&self) -> SharedCredentialsProvider {
+        // STS is regional; S3 requests keep signing for us-east-1, which is
+        // what MinIO expects.
+        let region = Region::new(std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()));
+        let base = match &self.credentials {
+            S3Credentials::Static { access_key, secret_key } => SharedCredentialsProvider::new(Credentials::new(
+                access_key.clone(),
+                secret_key.clone(),
+                None,
+                None,
+                "static",
+            )),
+            S3Credentials::Default => {
+                SharedCredentialsProvider::new(DefaultCredentialsChain::builder().region(region.clone()).build().await)
+            }
+        };
+        let Some(role) = &self.assume_role else { return base };
+
+        // An explicit endpoint, so STS calls never follow `AWS_ENDPOINT_URL`
+        // to the S3 endpoint.
+        let sts_endpoint = role.sts_endpoint.clone().unwrap_or_else(|| format!("https://sts.{}.amazonaws.com", region));
+        let sts = aws_config::defaults(BehaviorVersion::latest()).region(region).endpoint_url(sts_endpoint).load().await;
+        let mut provider = AssumeRoleProvider::builder(role.role_arn.clone())
+            .session_name(role.session_name.clone())
+            .configure(&sts);
+        if let Some(external_id) = &role.external_id {
+            provider = provider.external_id(external_id.clone());
+        }
+        SharedCredentialsProvider::new(provider.build_from_provider(base).await)
+    }
 }
 
 impl S3Client {
     pub async fn from_env() -> Result<Self> {
-        let endpoint = std::env::var("AWS_ENDPOINT_URL")
-            .unwrap_or_else(|_| "http://minio:9000".to_string());
-        let access_key = std::env::var("AWS_ACCESS_KEY_ID")
-            .unwrap_or_else(|_| "minioadmin".to_string());
-        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY")
-            .unwrap_or_else(|_| "minioadmin".to_string());
-        let bucket_name = std::env::var("VEC_BUCKET")
-            .unwrap_or_else(|_| "vectors".to_string());
-
-        let creds = aws_sdk_s3::config::Credentials::new(
-            access_key,
-            secret_key,
-            None,
-            None,
-            "static",
-        );
+        Self::connect(S3Settings::from_env()).await
+    }
+
+    pub async fn connect(settings: S3Settings) -> Result<Self> {
+        let creds = settings.credentials_provider().await;
+        let S3Settings { endpoint, bucket: bucket_name, .. } = settings;
 
+        let http = S3HttpConfig::from_env();
         let config = Builder::new()
             .endpoint_url(endpoint)
             .region(Region::new("us-east-1"))
             .credentials_provider(creds)
             .force_path_style(true)
+            .http_client(http.http_client()?)
+            .timeout_config(http.timeouts())
+            .interceptor(CountRequests)
             .build();
 
         let client = Client::from_conf(config);
 
-        // Ensure bucket exists
-        let _response = client
-            .create_bucket()
-            .bucket(&bucket_name)
-            .send()
-            .await;
+        let backend = StorageBackend::from_env()?;
+        #[cfg(not(any(feature = "gcs", feature = "azure")))]
+        if backend != StorageBackend::S3 {
+            return Err(anyhow::anyhow!(
+                "Storage backend {:?} requires building with the `gcs` or `azure` feature",
+                backend
+            ));
+        }
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        let blob = match backend {
+            StorageBackend::S3 => None,
+            backend => {
+                tracing::info!("Using {:?} storage backend for bucket {}", backend, bucket_name);
+                Some(Arc::new(BlobStore::new(backend)?))
+            }
+        };
+        if backend == StorageBackend::S3 {
+            // Ensure bucket exists
+            let _response = client
+                .create_bucket()
+                .bucket(&bucket_name)
+                .send()
+                .await;
+        }
 
         Ok(Self {
             client,
             bucket: bucket_name,
+            multipart: MultipartConfig::from_env(),
+            envelope: Envelope::from_env().await?.map(Arc::new),
+            #[cfg(any(feature = "gcs", feature = "azure", test))]
+            blob,
         })
     }
 
+    /// A client over a fresh in-memory store, so tests can run the write, index
+    /// and query paths without MinIO. Calls that go to the S3 API directly
+    /// (bucket management) fail.
+    #[cfg(test)]
+    pub fn in_memory(bucket: &str) -> Self {
+        let config = Builder::new().region(Region::new("us-east-1")).build();
+        Self {
+            client: Client::from_conf(config),
+            bucket: bucket.to_string(),
+            multipart: MultipartConfig::default(),
+            envelope: None,
+            blob: Some(Arc::new(
+                BlobStore::new(StorageBackend::Memory).expect("the memory backend is always available"),
+            )),
+        }
+    }
+
     pub async fn put_object(&self, key: &str, data: Bytes) -> Result<()> {
-        tracing::info!("🔍 MinIO put_object attempt - bucket: {}, key: {}, data_size: {}", &self.bucket, key, data.len());
+        self.put_bucket_object(&self.bucket, key, data).await
+    }
+
+    pub async fn get_object(&self, key: &str) -> Result<Bytes> {
+        self.get_bucket_object(&self.bucket, key).await
+    }
+
+    /// Put an object into an explicit bucket (e.g. per-vector JSON in the caller's
+    /// vector bucket), encrypting it when client-side encryption covers that bucket.
+    pub async fn put_bucket_object(&self, bucket: &str, key: &str, data: Bytes) -> Result<()> {
+        let data = match self.envelope_for(bucket, key) {
+            Some(envelope) => Bytes::from(envelope.seal(&data).await?),
+            None => data,
+        };
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.put(bucket, key, data).await;
+        }
+        tracing::info!("🔍 MinIO put_object attempt - bucket: {}, key: {}, data_size: {}", bucket, key, data.len());
         
         match self.client
             .put_object()
-            .bucket(&self.bucket)
+            .bucket(bucket)
             .key(key)
             .body(ByteStream::from(data))
             .send()
@@ -73,10 +406,15 @@ impl S3Client {
         }
     }
 
-    pub async fn get_object(&self, key: &str) -> Result<Bytes> {
+    pub async fn get_bucket_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            let data = blob.get(bucket, key).await?;
+            return self.maybe_open(key, data).await;
+        }
         let response = self.client
             .get_object()
-            .bucket(&self.bucket)
+            .bucket(bucket)
             .key(key)
             .send()
             .await
@@ -86,15 +424,216 @@ impl S3Client {
             .body
             .collect()
             .await
-            .context("Failed to read object body")?;
+            .context("Failed to read object body")?
+            .into_bytes();
+
+        self.maybe_open(key, data).await
+    }
+
+    /// Encrypted objects are recognised by their envelope header, so data written
+    /// while encryption was enabled stays readable even if the bucket scope changes.
+    async fn maybe_open(&self, key: &str, data: Bytes) -> Result<Bytes> {
+        if !Envelope::is_sealed(&data) {
+            return Ok(data);
+        }
+        let envelope = self.envelope.as_ref().with_context(|| {
+            format!("Object {} is encrypted but no encryption key is configured", key)
+        })?;
+        Ok(Bytes::from(envelope.open(&data).await?))
+    }
+
+    fn envelope_for(&self, bucket: &str, key: &str) -> Option<&Envelope> {
+        self.envelope
+            .as_deref()
+            .filter(|envelope| envelope.applies_to(bucket) && is_sensitive_key(key))
+    }
+
+    /// Stream an object straight to a local file instead of buffering it in memory.
+    /// Returns the number of bytes written; a partially written file is removed on error.
+    pub async fn get_object_to_file(&self, key: &str, file_path: &str) -> Result<u64> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        let copied = match &self.blob {
+            Some(blob) => blob.get_to_file(&self.bucket, key, file_path).await?,
+            None => self.stream_s3_object_to_file(key, file_path).await?,
+        };
+        #[cfg(not(any(feature = "gcs", feature = "azure", test)))]
+        let copied = self.stream_s3_object_to_file(key, file_path).await?;
+
+        // Sealed objects are small (slices, metadata) and must be decrypted whole.
+        let mut magic = [0u8; 4];
+        let mut file = fs::File::open(file_path).await?;
+        if file.read_exact(&mut magic).await.is_ok() && Envelope::is_sealed(&magic) {
+            let sealed = fs::read(file_path).await?;
+            let plain = self.maybe_open(key, Bytes::from(sealed)).await?;
+            fs::write(file_path, &plain).await?;
+            return Ok(plain.len() as u64);
+        }
+        Ok(copied)
+    }
+
+    async fn stream_s3_object_to_file(&self, key: &str, file_path: &str) -> Result<u64> {
+        let response = self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .context("Failed to get object")?;
 
-        Ok(data.into_bytes())
+        let mut body = response.body;
+        let mut file = fs::File::create(file_path).await
+            .with_context(|| format!("Failed to create file {}", file_path))?;
+
+        let copied: Result<u64> = async {
+            let mut written = 0u64;
+            while let Some(chunk) = body.try_next().await.context("Failed to read object body")? {
+                file.write_all(&chunk).await.context("Failed to write object to file")?;
+                written += chunk.len() as u64;
+            }
+            file.flush().await?;
+            Ok(written)
+        }
+        .await;
+
+        if copied.is_err() {
+            let _ = fs::remove_file(file_path).await;
+        }
+        copied
     }
 
     pub async fn put_file(&self, _bucket: &str, key: &str, file_path: &str) -> Result<()> {
-        let data = fs::read(file_path).await
-            .context("Failed to read file")?;
-        self.put_object(key, Bytes::from(data)).await
+        self.upload_file(key, file_path).await
+    }
+
+    /// Upload a local file, switching to a multipart upload once the file reaches
+    /// the configured threshold so large shard indexes are never held in memory whole.
+    pub async fn upload_file(&self, key: &str, file_path: &str) -> Result<()> {
+        let file_size = fs::metadata(file_path).await
+            .with_context(|| format!("Failed to stat file {}", file_path))?
+            .len();
+
+        // GCS and Azure uploads go through object_store as a single request.
+        if file_size < self.multipart.threshold_bytes
+            || self.envelope_for(&self.bucket, key).is_some()
+            || self.uses_blob_store()
+        {
+            let data = fs::read(file_path).await
+                .context("Failed to read file")?;
+            return self.put_object(key, Bytes::from(data)).await;
+        }
+
+        self.put_file_multipart(key, file_path, file_size).await
+    }
+
+    async fn put_file_multipart(&self, key: &str, file_path: &str, file_size: u64) -> Result<()> {
+        let part_size = self.multipart.part_size_bytes as u64;
+        let part_count = file_size.div_ceil(part_size);
+        tracing::info!(
+            "🔍 MinIO multipart upload - key: {}, size: {}, parts: {}",
+            key, file_size, part_count
+        );
+
+        let upload = self.client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .checksum_algorithm(ChecksumAlgorithm::Crc32)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to start multipart upload {}: {:?}", key, e))?;
+        let upload_id = upload.upload_id()
+            .context("Multipart upload response is missing an upload id")?
+            .to_string();
+
+        let parts = stream::iter(0..part_count)
+            .map(|index| {
+                let offset = index * part_size;
+                let len = part_size.min(file_size - offset) as usize;
+                self.upload_part(key, &upload_id, file_path, index as i32 + 1, offset, len)
+            })
+            .buffer_unordered(self.multipart.concurrency)
+            .try_collect::<Vec<CompletedPart>>()
+            .await;
+
+        let mut parts = match parts {
+            Ok(parts) => parts,
+            Err(e) => {
+                self.abort_multipart(key, &upload_id).await;
+                return Err(e);
+            }
+        };
+        parts.sort_by_key(|part| part.part_number());
+
+        let completed = self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await;
+
+        match completed {
+            Ok(_) => {
+                tracing::info!("✅ MinIO multipart upload success - key: {}", key);
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!("❌ MinIO multipart upload failed - key: {}, detailed_error: {:?}", key, e);
+                self.abort_multipart(key, &upload_id).await;
+                Err(anyhow::anyhow!("Failed to complete multipart upload {}: {:?}", key, e))
+            }
+        }
+    }
+
+    /// Read one part from disk and upload it. The SDK computes a CRC32 for the body
+    /// and the server rejects the part if it doesn't match what it received.
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        file_path: &str,
+        part_number: i32,
+        offset: u64,
+        len: usize,
+    ) -> Result<CompletedPart> {
+        let mut file = fs::File::open(file_path).await
+            .with_context(|| format!("Failed to open file {}", file_path))?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut buf = vec![0u8; len];
+        file.read_exact(&mut buf).await
+            .with_context(|| format!("Failed to read part {} of {}", part_number, file_path))?;
+
+        let output = self.client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .checksum_algorithm(ChecksumAlgorithm::Crc32)
+            .body(ByteStream::from(buf))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to upload part {} of {}: {:?}", part_number, key, e))?;
+
+        Ok(CompletedPart::builder()
+            .part_number(part_number)
+            .set_e_tag(output.e_tag().map(str::to_string))
+            .set_checksum_crc32(output.checksum_crc32().map(str::to_string))
+            .build())
+    }
+
+    async fn abort_multipart(&self, key: &str, upload_id: &str) {
+        if let Err(e) = self.client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+        {
+            tracing::warn!("Failed to abort multipart upload {} ({}): {:?}", key, upload_id, e);
+        }
     }
 
     pub async fn append_object(&self, _bucket: &str, key: &str, data: Bytes) -> Result<()> {
@@ -104,6 +643,10 @@ impl S3Client {
     }
 
     pub async fn list_buckets(&self) -> Result<Vec<String>> {
+        // GCS/Azure credentials are not guaranteed to allow enumerating buckets.
+        if self.uses_blob_store() {
+            return Ok(vec![self.bucket.clone()]);
+        }
         let response = self.client
             .list_buckets()
             .send()
@@ -121,30 +664,120 @@ impl S3Client {
         Ok(bucket_names)
     }
 
+    /// Whether object calls go to GCS or Azure rather than S3.
+    fn uses_blob_store(&self) -> bool {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if self.blob.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// The configured bucket, holding everything but per-vector records.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
     pub async fn list_objects(&self, prefix: &str) -> Result<Vec<String>> {
-        let response = self.client
+        self.list_bucket_objects(&self.bucket, prefix).await
+    }
+
+    /// Every key under `prefix` of `bucket`, paging past S3's 1000 keys per
+    /// response.
+    pub async fn list_bucket_objects(&self, bucket: &str, prefix: &str) -> Result<Vec<String>> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.list(bucket, prefix).await;
+        }
+        let mut keys = Vec::new();
+        let mut pages = self.client.list_objects_v2().bucket(bucket).prefix(prefix).into_paginator().send();
+        while let Some(page) = pages.next().await {
+            keys.extend(page.context("Failed to list objects")?.contents.unwrap_or_default().into_iter().filter_map(|object| object.key));
+        }
+        Ok(keys)
+    }
+
+    /// Up to `limit` keys under `prefix` of `bucket` that sort after
+    /// `start_after`, in key order, paging as needed; for walking a prefix
+    /// a page at a time.
+    pub async fn list_bucket_objects_after(&self, bucket: &str, prefix: &str, start_after: Option<&str>, limit: usize) -> Result<Vec<String>> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.list_after(bucket, prefix, start_after, limit).await;
+        }
+        let mut keys = Vec::new();
+        let mut pages = self.client
             .list_objects_v2()
-            .bucket(&self.bucket)
+            .bucket(bucket)
             .prefix(prefix)
-            .send()
-            .await
-            .context("Failed to list objects")?;
-        
-        let mut keys = Vec::new();
-        if let Some(contents) = response.contents {
-            for object in contents {
-                if let Some(key) = object.key {
-                    keys.push(key);
-                }
+            .set_start_after(start_after.map(str::to_string))
+            .max_keys(limit.min(1000) as i32)
+            .into_paginator()
+            .send();
+        while let Some(page) = pages.next().await {
+            keys.extend(page.context("Failed to list objects")?.contents.unwrap_or_default().into_iter().filter_map(|object| object.key));
+            if keys.len() >= limit {
+                break;
             }
         }
+        keys.truncate(limit);
         Ok(keys)
     }
 
+    /// Keys under `prefix` with when each was last written, for retention
+    /// policies.
+    pub async fn list_objects_modified(&self, prefix: &str) -> Result<Vec<(String, DateTime<Utc>)>> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.list_modified(&self.bucket, prefix).await;
+        }
+        let mut objects = Vec::new();
+        let mut pages = self.client.list_objects_v2().bucket(&self.bucket).prefix(prefix).into_paginator().send();
+        while let Some(page) = pages.next().await {
+            for object in page.context("Failed to list objects")?.contents.unwrap_or_default() {
+                if let (Some(key), Some(modified)) = (object.key, object.last_modified) {
+                    let at = DateTime::from_timestamp(modified.secs(), modified.subsec_nanos()).unwrap_or_default();
+                    objects.push((key, at));
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Server-side copy within the configured bucket; object bytes (including any
+    /// encryption envelope) are copied as-is without passing through this process.
+    pub async fn copy_object(&self, src_key: &str, dst_key: &str) -> Result<()> {
+        self.copy_bucket_object(&self.bucket, src_key, dst_key).await
+    }
+
+    pub async fn copy_bucket_object(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<()> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.copy(bucket, src_key, dst_key).await;
+        }
+        self.client
+            .copy_object()
+            .bucket(bucket)
+            .copy_source(copy_source(bucket, src_key))
+            .key(dst_key)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to copy object {} to {}: {:?}", src_key, dst_key, e))?;
+        Ok(())
+    }
+
     pub async fn delete_object(&self, key: &str) -> Result<()> {
+        self.delete_bucket_object(&self.bucket, key).await
+    }
+
+    pub async fn delete_bucket_object(&self, bucket: &str, key: &str) -> Result<()> {
+        #[cfg(any(feature = "gcs", feature = "azure", test))]
+        if let Some(blob) = &self.blob {
+            return blob.delete(bucket, key).await;
+        }
         self.client
             .delete_object()
-            .bucket(&self.bucket)
+            .bucket(bucket)
             .key(key)
             .send()
             .await
@@ -152,3 +785,75 @@ impl S3Client {
         Ok(())
     }
 }
+
+/// `x-amz-copy-source` of `key` in `bucket`: the key URL-encoded, keeping
+/// its `/` separators.
+fn copy_source(bucket: &str, key: &str) -> String {
+    let mut source = format!("{}/", bucket);
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => source.push(byte as char),
+            _ => source.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_source_is_url_encoded() {
+        assert_eq!(copy_source("vectors", "docs/vectors/a-1.json"), "vectors/docs/vectors/a-1.json");
+        assert_eq!(copy_source("vectors", "docs/vectors/my key+é.json"), "vectors/docs/vectors/my%20key%2B%C3%A9.json");
+    }
+
+    #[test]
+    fn test_http_config_comes_from_env() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            S3HttpConfig::from_vars(crate::api::testing::env(pairs))
+        };
+        assert_eq!(vars(&[]), S3HttpConfig::default());
+        let config = vars(&[
+            ("S3_MAX_CONNECTIONS_PER_HOST", "128"),
+            ("S3_POOL_IDLE_TIMEOUT_SECS", "0"),
+            ("S3_CONNECT_TIMEOUT_MS", "500"),
+            ("S3_OPERATION_TIMEOUT_MS", "0"),
+        ]);
+        assert_eq!(config.max_connections_per_host, Some(128));
+        assert_eq!(config.pool_idle_timeout, None);
+        assert_eq!(config.connect_timeout, Some(Duration::from_millis(500)));
+        assert_eq!((config.read_timeout, config.operation_timeout), (None, None));
+        assert_eq!(vars(&[("S3_MAX_CONNECTIONS_PER_HOST", "0")]).max_connections_per_host, None);
+    }
+
+    #[test]
+    fn test_credentials_come_from_env() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            S3Settings::from_vars(crate::api::testing::env(pairs))
+        };
+        let minio = S3Credentials::Static { access_key: "minioadmin".to_string(), secret_key: "minioadmin".to_string() };
+        assert_eq!(vars(&[]).credentials, minio);
+        assert_eq!(vars(&[]).assume_role, None);
+        assert_eq!(vars(&[("S3_CREDENTIALS", "default")]).credentials, S3Credentials::Default);
+
+        // IRSA injects a web identity token; explicit keys still win.
+        let irsa = vars(&[("AWS_WEB_IDENTITY_TOKEN_FILE", "/var/run/secrets/token"), ("AWS_ROLE_ARN", "arn:aws:iam::1:role/pod")]);
+        assert_eq!(irsa.credentials, S3Credentials::Default);
+        let keys = vars(&[("AWS_WEB_IDENTITY_TOKEN_FILE", "/var/run/secrets/token"), ("AWS_ACCESS_KEY_ID", "AKID")]);
+        assert!(matches!(keys.credentials, S3Credentials::Static { access_key, .. } if access_key == "AKID"));
+
+        let role = vars(&[("S3_ASSUME_ROLE_ARN", "arn:aws:iam::1:role/vectors"), ("S3_ASSUME_ROLE_EXTERNAL_ID", "ext")]);
+        assert_eq!(
+            role.assume_role,
+            Some(AssumeRole {
+                role_arn: "arn:aws:iam::1:role/vectors".to_string(),
+                session_name: "genai-vectors".to_string(),
+                external_id: Some("ext".to_string()),
+                sts_endpoint: None,
+            })
+        );
+        assert_eq!(role.credentials, minio);
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::compression::ContentEncoding;
+use crate::integrity::ShardChecksums;
 
 pub const SLICE_ROW_LIMIT: usize = 1_000;  // flush after 1k rows
 pub const SLICE_AGE_LIMIT_S: u64 = 30;     // or 30-second age
@@ -11,27 +13,152 @@ pub struct FilterableKey {
     pub key_type: String,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// An index's `config.json`, written by CreateIndex and read by the indexer
+/// and query paths alike.
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct CreateIndex {
+    /// Layout version, see [`crate::schema`].
+    #[serde(default)]
+    pub schema_version: u32,
     pub name: String,
     pub dim: u32,
-    pub metric: String, // "cosine" | "euclidean"
+    pub metric: String, // "cosine" | "euclidean" | "dotproduct" | "manhattan" | "chebyshev"
+    /// IVF-PQ parameters as created: the configured ones, or placeholders
+    /// builds don't use (see `ivfpq`).
     pub nlist: u32,
     pub m: u32,
     pub nbits: u32,
-    /// Optional default nprobe value used by the query service; if not provided the service will derive one.
+    /// nprobe for queries that don't set one; `faiss_utils::DEFAULT_NPROBE` when unset.
     pub default_nprobe: Option<u32>,
+    /// IVF-PQ parameters fixed at creation; builds tune any left unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ivfpq: Option<crate::tuning::IvfpqSettings>,
     #[serde(default)]
     pub non_filterable_metadata_keys: Vec<String>,
+    /// Vector bucket the index belongs to; `None` for indexes created before this was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_bucket_name: Option<String>,
+    /// Keep full-precision embeddings next to each shard for returnData and exact
+    /// reranking. Indexes created before this was configurable always stored them.
+    #[serde(default = "default_store_raw_vectors")]
+    pub store_raw_vectors: bool,
+    /// Set on a parent index whose vectors live in rolling time partitions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partitioning: Option<crate::partitions::Partitioning>,
+    /// Near-duplicate suppression on PutVectors; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup: Option<crate::dedup::Dedup>,
+    /// Keep prior versions of overwritten vectors; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versioning: Option<crate::versions::Versioning>,
+    /// Shard index structure, read by the indexer; chosen per build when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// Index size up to which shards are built flat; `tuning::DEFAULT_FLAT_THRESHOLD` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flat_threshold: Option<usize>,
+    /// Index size at which hybrid and automatic indexes move from HNSW to
+    /// IVF-PQ; `indexer::DEFAULT_HNSW_THRESHOLD` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_threshold: Option<usize>,
+    /// Dimensionality reduction applied before indexing; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reduction: Option<crate::reduction::Reduction>,
+    /// Matryoshka truncation of incoming embeddings and queries; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncation: Option<crate::truncation::Truncation>,
+    /// Order among indexes with slices pending, higher first; 0 when unset.
+    /// See [`crate::build_queue`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexing_priority: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize)]
+fn default_store_raw_vectors() -> bool {
+    true
+}
+
+impl CreateIndex {
+    /// Parse a `config.json` of any schema version, upgraded to the current one.
+    pub fn from_slice(data: &[u8]) -> anyhow::Result<Self> {
+        let mut config: serde_json::Value = serde_json::from_slice(data)?;
+        crate::schema::upgrade_config(&mut config)?;
+        Ok(serde_json::from_value(config)?)
+    }
+}
+
+/// An index's `manifest.json`: its shards, written by the indexer and read by
+/// the query path.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexManifest {
+    /// Layout version, see [`crate::schema`].
+    #[serde(default)]
+    pub schema_version: u32,
+    pub index_name: String,
+    pub dim: u32,
+    pub metric: String,
+    pub shards: Vec<ShardInfo>,
+    pub total_vectors: usize,
+    #[serde(default)]
+    pub algorithm: Option<String>,
+    #[serde(default)]
+    pub hnsw_threshold: Option<usize>,
+    /// Profile, parameters and rationale of the latest build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuning: Option<crate::tuning::Recommendation>,
+}
+
+impl IndexManifest {
+    /// Parse a `manifest.json` of any schema version, upgraded to the current one.
+    pub fn from_slice(data: &[u8]) -> anyhow::Result<Self> {
+        let mut manifest: serde_json::Value = serde_json::from_slice(data)?;
+        crate::schema::upgrade_manifest(&mut manifest)?;
+        Ok(serde_json::from_value(manifest)?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShardInfo {
+    pub shard_id: String,
+    pub index_path: String,
+    pub metadata_path: String,
+    /// Raw vector column (see [`crate::raw_vectors`]), stored unencoded; `None`
+    /// when the index doesn't store raw vectors or the shard predates them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vectors_path: Option<String>,
+    /// BM25 index of the shard's vector texts; `None` when none had text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_path: Option<String>,
+    pub vector_count: usize,
+    pub metric: String,
+    pub created_at: String,
+    /// Shards written before the algorithm was recorded are IVF-PQ.
+    #[serde(default)]
+    pub algorithm: String,
+    /// Shared quantizer (see [`crate::quantizer`]) the shard's vectors were
+    /// added to; `None` when it trained its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantizer: Option<String>,
+    /// Projection (see [`crate::reduction`]) of the vectors its Faiss index
+    /// holds; `None` when they keep every dimension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<String>,
+    #[serde(default)]
+    pub checksums: Option<ShardChecksums>,
+    /// Encoding of metadata.json and id_map.json; the Faiss index is stored raw.
+    #[serde(default)]
+    pub content_encoding: ContentEncoding,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct VectorRecord {
     pub id: String,
     pub embedding: Vec<f32>,
     pub meta: serde_json::Value,
     #[serde(default = "Utc::now", with = "chrono::serde::ts_microseconds")]
     pub created_at: DateTime<Utc>,
+    /// Free text indexed for BM25 alongside the embedding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -40,13 +167,140 @@ pub struct PutVectors {
     pub vectors: Vec<VectorRecord>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct QueryRequest {
     pub index: String,
     pub embedding: Vec<f32>,
     pub topk: usize,
     #[serde(default)]
     pub nprobe: Option<u32>,
+    /// Return per-shard search statistics alongside the results.
+    #[serde(default)]
+    pub explain: bool,
+    /// Attach each hit's full-precision embedding from the shard's vectors file.
+    #[serde(default)]
+    pub return_data: bool,
+    /// Rescore candidates against stored raw vectors instead of trusting
+    /// quantized distances; shards without raw vectors keep their Faiss scores.
+    #[serde(default)]
+    pub exact_rerank: bool,
     #[serde(default)]
     pub filter: Option<serde_json::Value>,
+    /// Hybrid mode: also match this text against the shards' BM25 indexes and
+    /// rank on a blend of both scores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Share of the vector score in hybrid ranking, the rest going to BM25.
+    #[serde(default = "default_vector_weight")]
+    pub vector_weight: f32,
+    /// How results of different shards are merged.
+    #[serde(default)]
+    pub fusion: crate::fusion::Fusion,
+    /// Time the search may take, in milliseconds; shards not searched by
+    /// then are cancelled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// On timeout, return what the shards searched so far found, flagged
+    /// `timed_out`, instead of failing with [`crate::query::QueryTimeout`].
+    #[serde(default)]
+    pub partial_on_timeout: bool,
+    /// Skip shards that fail, listing them as `failed_shards`, instead of
+    /// failing the query.
+    #[serde(default)]
+    pub allow_partial_results: bool,
+}
+
+fn default_vector_weight() -> f32 {
+    0.5
+}
+
+impl QueryRequest {
+    /// The `topk` nearest neighbours of `embedding` in `index`, everything
+    /// else at its default.
+    pub fn new(index: &str, embedding: Vec<f32>, topk: usize) -> Self {
+        QueryRequest {
+            index: index.to_string(),
+            embedding,
+            topk,
+            nprobe: None,
+            explain: false,
+            return_data: false,
+            exact_rerank: false,
+            filter: None,
+            text: None,
+            vector_weight: default_vector_weight(),
+            fusion: Default::default(),
+            timeout_ms: None,
+            partial_on_timeout: false,
+            allow_partial_results: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_legacy_manifest_reads_with_defaults_and_round_trips() {
+        let legacy = json!({
+            "index_name": "idx",
+            "dim": 4,
+            "metric": "cosine",
+            "shards": [{
+                "shard_id": "s0",
+                "index_path": "indexes/idx/shards/s0/index.faiss",
+                "metadata_path": "indexes/idx/shards/s0/metadata.json",
+                "vector_count": 3,
+                "metric": "cosine",
+                "created_at": "20250101T000000",
+            }],
+            "total_vectors": 3,
+        });
+        let manifest = IndexManifest::from_slice(&serde_json::to_vec(&legacy).unwrap()).unwrap();
+        assert_eq!(manifest.schema_version, crate::schema::MANIFEST_SCHEMA_VERSION);
+        let shard = &manifest.shards[0];
+        assert_eq!(shard.algorithm, "ivfpq");
+        assert!(shard.vectors_path.is_none() && shard.checksums.is_none());
+        assert_eq!(shard.content_encoding, ContentEncoding::default());
+
+        let written = serde_json::to_value(&manifest).unwrap();
+        let reread: IndexManifest = serde_json::from_value(written.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reread).unwrap(), written);
+        // Unset optional fields stay out of the written manifest.
+        assert!(written["shards"][0].get("quantizer").is_none() && written.get("tuning").is_none());
+
+        let mut newer = legacy;
+        newer["schema_version"] = json!(crate::schema::MANIFEST_SCHEMA_VERSION + 1);
+        assert!(IndexManifest::from_slice(&serde_json::to_vec(&newer).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_query_request_defaults_match_the_wire_format() {
+        let parsed: QueryRequest = serde_json::from_value(json!({"index": "idx", "embedding": [1.0], "topk": 3})).unwrap();
+        let built = QueryRequest::new("idx", vec![1.0], 3);
+        assert_eq!(serde_json::to_value(&built).unwrap(), serde_json::to_value(&parsed).unwrap());
+    }
+
+    #[test]
+    fn test_index_config_round_trips() {
+        // As written before store_raw_vectors and the optional settings existed.
+        let legacy = json!({
+            "name": "idx", "dim": 8, "metric": "cosine", "nlist": 256, "m": 8, "nbits": 8, "default_nprobe": 8,
+        });
+        let config = CreateIndex::from_slice(&serde_json::to_vec(&legacy).unwrap()).unwrap();
+        assert_eq!(config.schema_version, crate::schema::CONFIG_SCHEMA_VERSION);
+        assert!(config.store_raw_vectors && config.ivfpq.is_none() && config.algorithm.is_none());
+
+        let configured = CreateIndex {
+            ivfpq: Some(crate::tuning::IvfpqSettings { nlist: Some(64), ..Default::default() }),
+            hnsw_threshold: Some(50_000),
+            ..config
+        };
+        let written = serde_json::to_value(&configured).unwrap();
+        assert_eq!(written["ivfpq"], json!({"nlist": 64}));
+        let reread: CreateIndex = serde_json::from_value(written.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reread).unwrap(), written);
+    }
 }
@@ -0,0 +1,210 @@
+use crate::minio::S3Client;
+use crate::model::CreateIndex;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Length of the `.<yyyy-mm-dd>` suffix that names a partition after its parent.
+pub const PARTITION_SUFFIX_LEN: usize = 11;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Day,
+    /// ISO weeks, starting on Monday.
+    Week,
+}
+
+impl Granularity {
+    fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    fn length(self) -> Duration {
+        match self {
+            Granularity::Day => Duration::days(1),
+            Granularity::Week => Duration::weeks(1),
+        }
+    }
+}
+
+/// Rolling time partitions of a parent index, stored in its config. The parent
+/// holds no vectors itself: writes go to child indexes named
+/// `<parent>.<yyyy-mm-dd>` after the first day of their period, created on
+/// first use, and queries fan out to the children.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Partitioning {
+    pub granularity: Granularity,
+    /// Metadata key holding each vector's timestamp in epoch seconds. Vectors
+    /// without one go to the partition of the time they were written.
+    pub timestamp_key: String,
+}
+
+impl Partitioning {
+    /// Child index for a vector with `metadata`, written at `now`.
+    pub fn partition_for(&self, parent: &str, metadata: &Value, now: DateTime<Utc>) -> String {
+        let at = metadata
+            .get(&self.timestamp_key)
+            .and_then(|v| v.as_f64())
+            .and_then(|secs| DateTime::from_timestamp(secs.floor() as i64, 0))
+            .unwrap_or(now);
+        self.child_name(parent, self.granularity.start_of(at.date_naive()))
+    }
+
+    fn child_name(&self, parent: &str, start: NaiveDate) -> String {
+        format!("{}.{}", parent, start.format("%Y-%m-%d"))
+    }
+
+    /// Start of the period a child index covers; `None` for names that are not
+    /// partitions of `parent`.
+    fn child_start(&self, parent: &str, name: &str) -> Option<NaiveDate> {
+        let suffix = name.strip_prefix(parent)?.strip_prefix('.')?;
+        let start = NaiveDate::parse_from_str(suffix, "%Y-%m-%d").ok()?;
+        (self.granularity.start_of(start) == start).then_some(start)
+    }
+
+    /// Partitions among `names` that can hold vectors matching `filter`. Only
+    /// range and equality conditions on the timestamp key prune; anything else
+    /// keeps every partition.
+    pub fn prune(&self, parent: &str, names: Vec<String>, filter: Option<&Value>) -> Vec<String> {
+        let bounds = time_bounds(filter.and_then(|f| f.get(&self.timestamp_key)));
+        names
+            .into_iter()
+            .filter(|name| {
+                let Some(start) = self.child_start(parent, name) else { return false };
+                let from = start.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as f64;
+                let until = from + self.granularity.length().num_seconds() as f64;
+                bounds.admits(from, until)
+            })
+            .collect()
+    }
+}
+
+/// Bounds a filter condition places on a numeric field.
+#[derive(Default)]
+struct TimeBounds {
+    min: Option<f64>,
+    max: Option<f64>,
+    /// The upper bound came from `$lt`.
+    max_exclusive: bool,
+}
+
+impl TimeBounds {
+    /// Whether a value in `[from, until)` can satisfy the bounds.
+    fn admits(&self, from: f64, until: f64) -> bool {
+        self.min.is_none_or(|min| until > min)
+            && self.max.is_none_or(|max| from < max || (from == max && !self.max_exclusive))
+    }
+}
+
+fn time_bounds(condition: Option<&Value>) -> TimeBounds {
+    let Some(condition) = condition else { return TimeBounds::default() };
+    if let Some(at) = condition.as_f64() {
+        return TimeBounds { min: Some(at), max: Some(at), max_exclusive: false };
+    }
+    let bound = |op: &str| condition.get(op).and_then(|v| v.as_f64());
+    let inclusive_max = bound("$eq").or_else(|| bound("$lte"));
+    TimeBounds {
+        min: bound("$eq").or_else(|| bound("$gte")).or_else(|| bound("$gt")),
+        max: inclusive_max.or_else(|| bound("$lt")),
+        max_exclusive: inclusive_max.is_none() && bound("$lt").is_some(),
+    }
+}
+
+/// Config and partitioning of `index`, if it is a partitioned parent.
+pub async fn load(s3: &S3Client, index: &str) -> Option<(CreateIndex, Partitioning)> {
+    let data = s3.get_object(&format!("indexes/{}/config.json", index)).await.ok()?;
+    let config = CreateIndex::from_slice(&data).ok()?;
+    let partitioning = config.partitioning.clone()?;
+    Some((config, partitioning))
+}
+
+/// Existing partitions of `parent`, oldest first.
+pub async fn list(s3: &S3Client, parent: &str, partitioning: &Partitioning) -> Result<Vec<String>> {
+    let mut names: Vec<String> = s3
+        .list_objects(&format!("indexes/{}.", parent))
+        .await?
+        .iter()
+        .filter_map(|key| key.strip_prefix("indexes/").and_then(|k| k.strip_suffix("/config.json")))
+        .filter(|name| partitioning.child_start(parent, name).is_some())
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Create partition `child` with its parent's parameters unless it exists.
+pub async fn ensure_partition(s3: &S3Client, parent: &CreateIndex, child: &str) -> Result<()> {
+    let config_key = format!("indexes/{}/config.json", child);
+    if s3.get_object(&config_key).await.is_ok() {
+        return Ok(());
+    }
+    let config = CreateIndex {
+        schema_version: crate::schema::CONFIG_SCHEMA_VERSION,
+        name: child.to_string(),
+        dim: parent.dim,
+        metric: parent.metric.clone(),
+        nlist: parent.nlist,
+        m: parent.m,
+        nbits: parent.nbits,
+        default_nprobe: parent.default_nprobe,
+        ivfpq: parent.ivfpq,
+        non_filterable_metadata_keys: parent.non_filterable_metadata_keys.clone(),
+        vector_bucket_name: parent.vector_bucket_name.clone(),
+        store_raw_vectors: parent.store_raw_vectors,
+        partitioning: None,
+        dedup: parent.dedup,
+        versioning: parent.versioning,
+        algorithm: parent.algorithm.clone(),
+        reduction: parent.reduction,
+        truncation: parent.truncation,
+        flat_threshold: parent.flat_threshold,
+        hnsw_threshold: parent.hnsw_threshold,
+        indexing_priority: parent.indexing_priority,
+    };
+    s3.put_object(&config_key, serde_json::to_vec(&config)?.into())
+        .await
+        .with_context(|| format!("Failed to create partition {}", child))?;
+    tracing::info!(parent = %parent.name, partition = %child, "Created index partition");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn weekly() -> Partitioning {
+        Partitioning { granularity: Granularity::Week, timestamp_key: "ts".to_string() }
+    }
+
+    #[test]
+    fn test_partition_for_timestamp() {
+        let daily = Partitioning { granularity: Granularity::Day, timestamp_key: "ts".to_string() };
+        let now = DateTime::from_timestamp(0, 0).unwrap();
+        // 2025-01-15T12:00:00Z, a Wednesday.
+        let meta = json!({"ts": 1736942400});
+        assert_eq!(daily.partition_for("logs", &meta, now), "logs.2025-01-15");
+        assert_eq!(weekly().partition_for("logs", &meta, now), "logs.2025-01-13");
+        assert_eq!(daily.partition_for("logs", &json!({}), now), "logs.1970-01-01");
+    }
+
+    #[test]
+    fn test_prune_by_time_filter() {
+        let names = vec![
+            "logs.2025-01-06".to_string(),
+            "logs.2025-01-13".to_string(),
+            "logs.2025-01-20".to_string(),
+            "logs.v2".to_string(),
+        ];
+        // 2025-01-14T00:00:00Z up to 2025-01-20T00:00:00Z, exclusive.
+        let filter = json!({"ts": {"$gte": 1736812800, "$lt": 1737331200}, "level": "error"});
+        assert_eq!(weekly().prune("logs", names.clone(), Some(&filter)), vec!["logs.2025-01-13".to_string()]);
+        assert_eq!(weekly().prune("logs", names, None).len(), 3);
+    }
+}
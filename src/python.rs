@@ -0,0 +1,164 @@
+//! Python bindings for the embedded [`VectorDb`], built with `--features python`
+//! (`maturin develop --features python`). Dicts for index options, metadata
+//! and filters take the same shapes as the REST request bodies, and calls
+//! release the GIL while they wait on storage.
+
+use crate::api::S3CreateIndexRequest;
+use crate::embedded::{VectorDb, DEFAULT_BUCKET};
+use crate::minio::{S3Client, S3Credentials, S3Settings};
+use crate::model::{QueryRequest, VectorRecord};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::{json, Value};
+
+fn runtime_error(e: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", e))
+}
+
+/// Python values cross as JSON through the `json` module, so anything it can
+/// encode is accepted.
+fn to_json(py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let text: String = py.import_bound("json")?.call_method1("dumps", (value,))?.extract()?;
+    serde_json::from_str(&text).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(py.import_bound("json")?.call_method1("loads", (value.to_string(),))?.unbind())
+}
+
+/// `genai_vectors.VectorDb`: a handle on the indexes of one storage bucket.
+#[pyclass(name = "VectorDb", module = "genai_vectors")]
+struct PyVectorDb {
+    db: VectorDb,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[pymethods]
+impl PyVectorDb {
+    /// Connect as the server does, from `AWS_ENDPOINT_URL`, `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY`, `VEC_BUCKET` and `VEC_STORAGE_BACKEND`; the
+    /// arguments override the variables.
+    #[new]
+    #[pyo3(signature = (endpoint=None, bucket=None, access_key=None, secret_key=None))]
+    fn open(
+        py: Python<'_>,
+        endpoint: Option<String>,
+        bucket: Option<String>,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+    ) -> PyResult<Self> {
+        let mut settings = S3Settings::from_env();
+        settings.endpoint = endpoint.unwrap_or(settings.endpoint);
+        settings.bucket = bucket.unwrap_or(settings.bucket);
+        settings.credentials = match (settings.credentials, access_key, secret_key) {
+            (S3Credentials::Static { access_key: env_access, secret_key: env_secret }, access_key, secret_key) => {
+                S3Credentials::Static {
+                    access_key: access_key.unwrap_or(env_access),
+                    secret_key: secret_key.unwrap_or(env_secret),
+                }
+            }
+            (_, Some(access_key), Some(secret_key)) => S3Credentials::Static { access_key, secret_key },
+            (credentials, _, _) => credentials,
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        let s3 = py.allow_threads(|| runtime.block_on(S3Client::connect(settings))).map_err(runtime_error)?;
+        Ok(PyVectorDb { db: VectorDb::open(s3), runtime })
+    }
+
+    /// Create an index, or return the config of an identical existing one.
+    /// Keyword options are CreateIndex fields (`algorithm`, `flatThreshold`,
+    /// `dedup`, `metadataConfiguration`, ...). Returns the stored config.
+    #[pyo3(signature = (name, dimension, metric="cosine", vector_bucket=None, **options))]
+    fn create_index(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        dimension: u32,
+        metric: &str,
+        vector_bucket: Option<&str>,
+        options: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let mut body = match options {
+            Some(options) => to_json(py, options.as_any())?,
+            None => json!({}),
+        };
+        body["vectorBucketName"] = json!(vector_bucket.unwrap_or(DEFAULT_BUCKET));
+        body["indexName"] = json!(name);
+        body["dataType"] = json!("float32");
+        body["dimension"] = json!(dimension);
+        body["distanceMetric"] = json!(metric);
+        let request: S3CreateIndexRequest =
+            serde_json::from_value(body).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let config = py
+            .allow_threads(|| self.runtime.block_on(self.db.create_index(&request)))
+            .map_err(runtime_error)?;
+        to_python(py, &serde_json::to_value(&config).map_err(|e| PyValueError::new_err(e.to_string()))?)
+    }
+
+    /// Write one vector per key and build them into shards; `embeddings` is a
+    /// list of lists or a 2-D array, `metadata` an optional list of dicts.
+    /// Returns `{"written": n, "duplicates": [...]}`.
+    #[pyo3(signature = (index, keys, embeddings, metadata=None))]
+    fn upsert(
+        &self,
+        py: Python<'_>,
+        index: &str,
+        keys: Vec<String>,
+        embeddings: Vec<Vec<f32>>,
+        metadata: Option<Vec<Bound<'_, PyAny>>>,
+    ) -> PyResult<PyObject> {
+        if embeddings.len() != keys.len() || metadata.as_ref().is_some_and(|m| m.len() != keys.len()) {
+            return Err(PyValueError::new_err("keys, embeddings and metadata must have the same length"));
+        }
+        let mut metadata = metadata.unwrap_or_default().into_iter();
+        let mut records = Vec::with_capacity(keys.len());
+        for (id, embedding) in keys.into_iter().zip(embeddings) {
+            let meta = match metadata.next() {
+                Some(meta) if !meta.is_none() => to_json(py, &meta)?,
+                _ => json!({}),
+            };
+            records.push(VectorRecord { id, embedding, meta, created_at: chrono::Utc::now(), text: None });
+        }
+        let outcome = py
+            .allow_threads(|| self.runtime.block_on(self.db.put(index, records)))
+            .map_err(runtime_error)?;
+        to_python(py, &json!({"written": outcome.written, "duplicates": outcome.duplicates}))
+    }
+
+    /// The `top_k` nearest vectors to `embedding`, optionally only those whose
+    /// metadata matches `filter`. Returns `[{"id", "score", "metadata"}]`,
+    /// with `"data"` too on `return_data`.
+    #[pyo3(signature = (index, embedding, top_k=10, filter=None, nprobe=None, return_data=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn query(
+        &self,
+        py: Python<'_>,
+        index: &str,
+        embedding: Vec<f32>,
+        top_k: usize,
+        filter: Option<&Bound<'_, PyAny>>,
+        nprobe: Option<u32>,
+        return_data: bool,
+    ) -> PyResult<PyObject> {
+        let mut request = QueryRequest::new(index, embedding, top_k);
+        request.filter = filter.map(|filter| to_json(py, filter)).transpose()?;
+        request.nprobe = nprobe;
+        request.return_data = return_data;
+        let response = py
+            .allow_threads(|| self.runtime.block_on(self.db.query(index, request)))
+            .map_err(runtime_error)?;
+        to_python(py, response.get("results").unwrap_or(&json!([])))
+    }
+
+    /// Delete `keys` from `index`.
+    fn delete(&self, py: Python<'_>, index: &str, keys: Vec<String>) -> PyResult<()> {
+        py.allow_threads(|| self.runtime.block_on(self.db.delete(index, &keys))).map_err(runtime_error)
+    }
+}
+
+#[pymodule]
+fn genai_vectors(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyVectorDb>()?;
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use crate::faiss_utils::{calculate_optimal_nlist, train_ivfpq_index};
+use crate::integrity::sha256_file;
+use crate::minio::S3Client;
+use crate::tempfiles::TempFile;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Faiss needs this many training points per centroid for stable clusters; a
+/// first build smaller than that keeps training every shard on its own.
+const MIN_POINTS_PER_CENTROID: usize = 39;
+/// Training points per centroid used when the build has that many.
+const TRAINING_POINTS_PER_CENTROID: usize = 64;
+
+/// An index-level trained IVF-PQ quantizer: coarse centroids and PQ codebooks
+/// trained once, on a sample of the first large enough shard build, then
+/// reused by every later IVF-PQ shard, which only adds its vectors. Shards of
+/// one index share their centroids, so recall is the same across them, and
+/// builds skip training. Described by `indexes/<index>/quantizer.json`; the
+/// trained (empty) Faiss index is stored next to it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizerInfo {
+    /// Object under `indexes/<index>/`, relative so copies of the index keep it.
+    pub file: String,
+    pub dim: u32,
+    pub metric: String,
+    pub nlist: usize,
+    pub m: usize,
+    pub nbits: usize,
+    /// Vectors it was trained on.
+    pub trained_on: usize,
+    pub checksum: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Shared quantizers are on unless `VEC_SHARED_QUANTIZER=false`.
+pub fn enabled() -> bool {
+    std::env::var("VEC_SHARED_QUANTIZER").map(|v| v != "false" && v != "0").unwrap_or(true)
+}
+
+fn info_key(index: &str) -> String {
+    format!("indexes/{}/quantizer.json", index)
+}
+
+/// Cluster count for a quantizer shared by shards of up to `shard_size` vectors.
+fn nlist_for(vectors: usize, shard_size: usize) -> usize {
+    calculate_optimal_nlist(vectors.min(shard_size))
+}
+
+/// Whether `vectors` are enough to train `nlist` centroids worth sharing.
+fn can_train(vectors: usize, nlist: usize) -> bool {
+    vectors >= MIN_POINTS_PER_CENTROID * nlist
+}
+
+pub async fn load_info(s3: &S3Client, index: &str) -> Option<QuantizerInfo> {
+    let data = s3.get_object(&info_key(index)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// A trained quantizer downloaded for a build; each shard reads its own copy
+/// with `faiss::read_index`. The local file goes when this is dropped.
+pub struct SharedQuantizer {
+    pub info: QuantizerInfo,
+    pub local: TempFile,
+}
+
+/// Trained quantizer for new IVF-PQ shards of `index`: the stored one if it
+/// fits `dim` and `metric`, otherwise one trained now on a sample of the
+/// row-major `vectors`, with the `(m, nbits)` PQ codes recommended for them
+/// (see [`crate::tuning`]) and `nlist` lists if the index fixes them, and
+/// stored for later builds. `None` when disabled or when there
+/// are too few vectors to train one, in which case shards train their own.
+pub async fn ensure(
+    s3: &S3Client,
+    index: &str,
+    dim: u32,
+    metric: &str,
+    vectors: std::sync::Arc<Vec<f32>>,
+    shard_size: usize,
+    (nlist, m, nbits): (Option<usize>, usize, usize),
+) -> Result<Option<SharedQuantizer>> {
+    if !enabled() {
+        return Ok(None);
+    }
+    let local = TempFile::new("quantizer", ".faiss");
+    if let Some(info) = load_info(s3, index).await.filter(|info| info.dim == dim && info.metric == metric) {
+        s3.get_object_to_file(&format!("indexes/{}/{}", index, info.file), local.path()).await?;
+        let shared = SharedQuantizer { info, local };
+        let checksum = sha256_file(shared.local.path())?;
+        if checksum != shared.info.checksum {
+            tracing::warn!(index, file = %shared.info.file, "Shared quantizer failed its checksum, training shards separately");
+            return Ok(None);
+        }
+        return Ok(Some(shared));
+    }
+
+    let count = vectors.len() / dim as usize;
+    let nlist = nlist.unwrap_or_else(|| nlist_for(count, shard_size));
+    if !can_train(count, nlist) {
+        return Ok(None);
+    }
+    let training_size = (TRAINING_POINTS_PER_CENTROID * nlist).min(count);
+    // Training is build work, so it takes a thread of the indexing pool.
+    let train = {
+        let (metric, path) = (metric.to_string(), local.path().to_string());
+        move || -> Result<String> {
+            crate::faiss_utils::set_omp_threads(crate::governor::indexing_threads());
+            let trained = train_ivfpq_index(dim as usize, nlist, m, nbits, &metric, &vectors, training_size)?;
+            faiss::write_index(&trained, &path)?;
+            sha256_file(&path)
+        }
+    };
+    let checksum = crate::governor::run(train).await?;
+    let info = QuantizerInfo {
+        // A fresh object per training, so builds reading the previous one
+        // while this is written never see a mix of the two.
+        file: format!("quantizer/{}.faiss", uuid::Uuid::new_v4()),
+        dim,
+        metric: metric.to_string(),
+        nlist,
+        m,
+        nbits,
+        trained_on: training_size,
+        checksum,
+        created_at: Utc::now(),
+    };
+    let shared = SharedQuantizer { info, local };
+    s3.upload_file(&format!("indexes/{}/{}", index, shared.info.file), shared.local.path())
+        .await
+        .context("Failed to store shared quantizer")?;
+    s3.put_object(&info_key(index), serde_json::to_vec(&shared.info)?.into()).await?;
+    tracing::info!(index, nlist, m, nbits, trained_on = training_size, "Trained shared quantizer");
+    Ok(Some(shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_builds_train_per_shard() {
+        let nlist = nlist_for(1_000, 50_000);
+        assert!(!can_train(1_000, nlist));
+        let nlist = nlist_for(200_000, 50_000);
+        assert_eq!(nlist, calculate_optimal_nlist(50_000));
+        assert!(can_train(200_000, nlist));
+    }
+}
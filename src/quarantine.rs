@@ -0,0 +1,128 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Slices the indexer could not read are moved from `staged/<index>/<file>` to
+/// `quarantine/<index>/<file>`, next to a `<file>.error.json` report, so one
+/// bad slice doesn't hold back the rest of its index. Retrying moves a slice
+/// back under `staged/`.
+const QUARANTINE_PREFIX: &str = "quarantine/";
+const REPORT_SUFFIX: &str = ".error.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantineReport {
+    pub index_name: String,
+    /// File name of the slice, the same under `staged/` and `quarantine/`.
+    pub slice: String,
+    pub error: String,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+impl QuarantineReport {
+    pub fn staged_key(&self) -> String {
+        format!("staged/{}/{}", self.index_name, self.slice)
+    }
+
+    pub fn quarantined_key(&self) -> String {
+        format!("{}{}/{}", QUARANTINE_PREFIX, self.index_name, self.slice)
+    }
+
+    fn report_key(&self) -> String {
+        format!("{}{}", self.quarantined_key(), REPORT_SUFFIX)
+    }
+}
+
+/// Move the staged slice `slice_path` of `index` into quarantine with `error`
+/// as the reason. The report is written before the slice leaves `staged/`, so
+/// an interrupted move leaves it to be retried by the next indexer pass.
+pub async fn quarantine(s3: &S3Client, index: &str, slice_path: &str, error: &anyhow::Error) -> Result<QuarantineReport> {
+    let slice = slice_path
+        .strip_prefix(&format!("staged/{}/", index))
+        .with_context(|| format!("{} is not a staged slice of index {}", slice_path, index))?;
+    let report = QuarantineReport {
+        index_name: index.to_string(),
+        slice: slice.to_string(),
+        error: format!("{:#}", error),
+        quarantined_at: Utc::now(),
+    };
+    s3.copy_object(slice_path, &report.quarantined_key()).await?;
+    s3.put_object(&report.report_key(), serde_json::to_vec(&report)?.into()).await?;
+    s3.delete_object(slice_path).await?;
+    Ok(report)
+}
+
+/// Reports of the quarantined slices of `index`, or of every index, oldest
+/// first.
+pub async fn list(s3: &S3Client, index: Option<&str>) -> Result<Vec<QuarantineReport>> {
+    let prefix = match index {
+        Some(index) => format!("{}{}/", QUARANTINE_PREFIX, index),
+        None => QUARANTINE_PREFIX.to_string(),
+    };
+    let mut reports = Vec::new();
+    for key in s3.list_objects(&prefix).await? {
+        if !key.ends_with(REPORT_SUFFIX) {
+            continue;
+        }
+        let Some((index, slice)) = key[QUARANTINE_PREFIX.len()..key.len() - REPORT_SUFFIX.len()].split_once('/') else {
+            continue;
+        };
+        match s3.get_object(&key).await.map(|d| serde_json::from_slice::<QuarantineReport>(&d)) {
+            // Where the report is wins over what it says, which a copied or
+            // restored index carries over from its source.
+            Ok(Ok(report)) => reports.push(QuarantineReport { index_name: index.to_string(), slice: slice.to_string(), ..report }),
+            _ => tracing::warn!("Skipping unreadable quarantine report {}", key),
+        }
+    }
+    reports.sort_by_key(|report| report.quarantined_at);
+    Ok(reports)
+}
+
+/// Move quarantined slices of `index` back under `staged/` for the indexer to
+/// try again: those named in `slices`, or all of them. Returns the staged keys.
+pub async fn retry(s3: &S3Client, index: &str, slices: Option<&[String]>) -> Result<Vec<String>> {
+    let reports = list(s3, Some(index)).await?;
+    if let Some(unknown) = slices.into_iter().flatten().find(|slice| !reports.iter().any(|r| r.slice == **slice)) {
+        return Err(anyhow::anyhow!("Slice {} of index {} is not quarantined", unknown, index));
+    }
+    let mut staged = Vec::new();
+    for report in reports {
+        if slices.is_some_and(|slices| !slices.contains(&report.slice)) {
+            continue;
+        }
+        s3.copy_object(&report.quarantined_key(), &report.staged_key()).await?;
+        s3.delete_object(&report.quarantined_key()).await?;
+        // Report last, so an interrupted retry is still listed.
+        s3.delete_object(&report.report_key()).await?;
+        tracing::info!(index = index, slice = %report.slice, "Released slice from quarantine");
+        staged.push(report.staged_key());
+    }
+    Ok(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreadable_slices_are_quarantined_and_retried() {
+        let s3 = S3Client::in_memory("vectors");
+        s3.put_object("staged/docs/slice-1.jsonl", bytes::Bytes::from_static(b"not a record\n")).await.unwrap();
+        crate::indexer::run(&s3).await.unwrap();
+
+        assert!(s3.list_objects("staged/").await.unwrap().is_empty());
+        let reports = list(&s3, None).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].slice, "slice-1.jsonl");
+        assert!(reports[0].error.contains("staged/docs/slice-1.jsonl"), "{}", reports[0].error);
+        assert!(s3.get_object("quarantine/docs/slice-1.jsonl").await.is_ok());
+        assert!(list(&s3, Some("other")).await.unwrap().is_empty());
+
+        assert!(retry(&s3, "docs", Some(&["slice-2.jsonl".to_string()])).await.is_err());
+        assert_eq!(retry(&s3, "docs", None).await.unwrap(), vec!["staged/docs/slice-1.jsonl"]);
+        assert!(list(&s3, None).await.unwrap().is_empty());
+        assert!(s3.list_objects("quarantine/").await.unwrap().is_empty());
+        assert!(s3.get_object("staged/docs/slice-1.jsonl").await.is_ok());
+    }
+}
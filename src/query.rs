@@ -1,40 +1,81 @@
 use crate::{minio::S3Client, model::*};
-<<<<<<< HEAD
-use crate::faiss_utils::FaissIndex;
-// use genai_vectors::metadata_filter::MetadataFilter;  // Temporarily disabled for compilation
-=======
+use crate::cache;
+use crate::integrity;
 use crate::metadata_filter::MetadataFilter;
->>>>>>> b4defcdf9022a3cdb3403fbeb6bc66d9648e389d
-use crate::metrics::get_metrics_collector;
+use crate::metrics::{self, get_metrics_collector, LatencyBreakdown, OperationType, QueryMetrics};
+use crate::fusion::{self, ShardHits};
+use crate::raw_vectors::{self, RawVectors};
+use crate::text_index::{self, TextIndex};
 use faiss::{Index, Idx};
 use anyhow::{Context, Result};
+use futures::StreamExt;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Candidates fetched per requested result when reranking against raw vectors.
+const RERANK_OVERFETCH: usize = 4;
+
+const DEFAULT_SHARD_CONCURRENCY: usize = 16;
+
+/// Shards a query searches at once, from `VEC_QUERY_SHARD_CONCURRENCY`. Local
+/// searches also queue for the Faiss pool; this mostly bounds the remote
+/// round trips and downloads in flight.
+fn shard_concurrency() -> usize {
+    std::env::var("VEC_QUERY_SHARD_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_SHARD_CONCURRENCY)
+}
+
+/// Raised when a query's `timeout_ms` passes before every shard was searched
+/// and partial results weren't asked for.
+#[derive(Debug, thiserror::Error)]
+#[error("Query did not finish within {timeout_ms} ms")]
+pub struct QueryTimeout {
+    pub timeout_ms: u64,
+}
 
-pub async fn search(s3: S3Client, req: QueryRequest) -> Result<Value> {
+/// `future`'s output, or `None` once `deadline` passes. The future is dropped
+/// then, cancelling its downloads; Faiss searches don't yield, so one already
+/// running finishes first.
+async fn within<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+pub async fn search(s3: S3Client, mut req: QueryRequest) -> Result<Value> {
     let _measurement = crate::measure_operation!("query.search");
     let search_start = std::time::Instant::now();
+    let deadline = req.timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
 
     get_metrics_collector().track_metric("query.topk", req.topk as f64);
     get_metrics_collector().track_metric("query.vector_dimension", req.embedding.len() as f64);
     
-    // Load index configuration to validate metadata filtering
-    let index_config = if req.filter.is_some() {
+    // Load index configuration to validate metadata filtering and for the
+    // index's default nprobe
+    let index_config = if req.filter.is_some() || req.nprobe.is_none() {
         load_index_config(&s3, &req.index).await?
     } else {
         None
     };
+    req.nprobe = req.nprobe.or(index_config.as_ref().and_then(|config| config.default_nprobe));
     
-    // Validate metadata filter against configuration
+    // Validate metadata filter against the grammar and configuration
     if let Some(filter_value) = &req.filter {
+        MetadataFilter::try_from(filter_value.clone()).context("Invalid metadata filter")?;
         if let Some(ref config) = index_config {
             validate_metadata_filter(filter_value, &config.non_filterable_metadata_keys)?;
         }
     }
     
     // 1. Load index manifest to find active shards
-    let manifest_key = format!("indexes/{}/manifest.json", req.index);
-    let manifest_data = match s3.get_object(&manifest_key).await {
+    let manifest_data = match crate::replica::manifest(&s3, &req.index).await {
         Ok(data) => data,
         Err(_) => {
             get_metrics_collector().track_metric("query.index_not_found", 1.0);
@@ -45,79 +86,232 @@ pub async fn search(s3: S3Client, req: QueryRequest) -> Result<Value> {
         }
     };
 
-    let manifest: IndexManifest = serde_json::from_slice(&manifest_data)
+    let mut manifest = IndexManifest::from_slice(&manifest_data)
         .context("Failed to parse index manifest")?;
+    manifest.check_metrics()?;
 
     get_metrics_collector().track_metric("query.shards_count", manifest.shards.len() as f64);
+    let mut operation = get_metrics_collector().start_operation(
+        OperationType::VectorQuery,
+        req.index.clone(),
+        metrics::IndexConfig {
+            nlist: 0,
+            nprobe: req.nprobe,
+            m: 0,
+            nbits: 0,
+            metric: manifest.metric.clone(),
+            shard_size: manifest.total_vectors / manifest.shards.len().max(1),
+        },
+    );
+
+    // Deleted vectors stay in their shards until rebuilt; over-fetch so hiding
+    // them doesn't shrink the result below topk.
+    let deletions = crate::deletions::load(&s3, &req.index).await?;
+    let topk = req.topk;
+    let shard_req = if deletions.is_empty() {
+        req.clone()
+    } else {
+        QueryRequest { topk: req.topk * 2, ..req.clone() }
+    };
+    let req = &shard_req;
 
     let start = std::time::Instant::now();
-    let mut all_results = Vec::new();
-
-    for (shard_idx, shard) in manifest.shards.iter().enumerate() {
-        let shard_start = std::time::Instant::now();
-        let results = search_shard(&s3, &req, shard, &manifest).await?;
-        let shard_time = shard_start.elapsed();
+    let mut shard_hits = Vec::with_capacity(manifest.shards.len());
+    let mut breakdown = LatencyBreakdown::default();
+    let mut shard_explains = Vec::new();
+    let mut timed_out = false;
+    let mut failed_shards = Vec::new();
+
+    // Shards are searched at once, up to the configured bound; outcomes are
+    // taken in manifest order so merges don't depend on which finished first.
+    let manifest = &manifest;
+    let searches: Vec<_> = manifest.shards.iter().enumerate().map(|(shard_idx, shard)| {
+        let s3 = &s3;
+        async move {
+            let shard_start = std::time::Instant::now();
+            let mut explain = ShardExplain { shard_id: shard.shard_id.clone(), ..Default::default() };
+            let shard_search = async {
+                match crate::cluster::cluster().and_then(|c| c.remote_owner(&shard.shard_id)) {
+                    Some(owner) => match search_shard_remote(&owner, req, shard, deadline).await {
+                        Ok(results) => {
+                            // The owner's load/search split isn't reported back; the round trip is its own phase.
+                            explain.remote_search_ms = shard_start.elapsed().as_secs_f64() * 1000.0;
+                            explain.executed_on = Some(owner);
+                            Ok(results)
+                        }
+                        Err(e) => {
+                            // The owner may be restarting; fall back to searching the shard here.
+                            tracing::warn!("Remote search of shard {} on {} failed: {}", shard.shard_id, owner, e);
+                            get_metrics_collector().track_metric("query.remote_shard_fallback", 1.0);
+                            search_shard(s3, req, shard, manifest, &mut explain).await
+                        }
+                    },
+                    None => search_shard(s3, req, shard, manifest, &mut explain).await,
+                }
+            };
+            let outcome = within(deadline, shard_search).await;
+            (shard_idx, shard, explain, outcome, shard_start.elapsed())
+        }
+    }).collect();
+    let mut searches = futures::stream::iter(searches).buffered(shard_concurrency());
+    let mut searched = 0;
+    while let Some((shard_idx, shard, mut explain, outcome, shard_time)) = searches.next().await {
+        let results = match outcome {
+            Some(Ok(results)) => results,
+            Some(Err(e)) if req.allow_partial_results => {
+                let error = format!("{:#}", e);
+                tracing::warn!(index = %req.index, shard = %shard.shard_id, error = %error, "Skipping failed shard");
+                get_metrics_collector().track_metric("query.failed_shards", 1.0);
+                failed_shards.push(serde_json::json!({"shard_id": shard.shard_id, "error": error}));
+                continue;
+            }
+            Some(Err(e)) => return Err(e),
+            None => {
+                if !timed_out {
+                    get_metrics_collector().track_metric("query.timeouts", 1.0);
+                }
+                if !req.partial_on_timeout {
+                    return Err(QueryTimeout { timeout_ms: req.timeout_ms.unwrap_or_default() }.into());
+                }
+                timed_out = true;
+                continue;
+            }
+        };
+        searched += 1;
+        // Merging covers hiding deleted hits as each shard comes in, then
+        // fusing the shards' results below.
+        let merge_start = std::time::Instant::now();
+        let written_at = crate::deletions::parse_shard_time(&shard.created_at);
+        let results: Vec<SearchResult> = results
+            .into_iter()
+            .filter(|r| !deletions.is_deleted(&r.id, written_at))
+            .collect();
+        breakdown.index_load_ms += explain.index_load_ms;
+        breakdown.search_ms += explain.search_ms;
+        breakdown.remote_search_ms += explain.remote_search_ms;
+        explain.results = results.len();
+        shard_explains.push(explain);
         
         get_metrics_collector().track_metric(&format!("query.shard_{}_time_ms", shard_idx), shard_time.as_millis() as f64);
         get_metrics_collector().track_metric(&format!("query.shard_{}_results", shard_idx), results.len() as f64);
         
-        all_results.extend(results);
+        shard_hits.push(ShardHits {
+            // Shards written before the algorithm was recorded are IVF-PQ.
+            algorithm: if shard.algorithm.is_empty() { "ivfpq".to_string() } else { shard.algorithm.clone() },
+            hits: results.into_iter().map(|r| { let score = r.score; (r, score) }).collect(),
+        });
+        breakdown.result_merge_ms += merge_start.elapsed().as_secs_f64() * 1000.0;
+    }
+    if timed_out {
+        tracing::warn!(index = %req.index, timeout_ms = req.timeout_ms.unwrap_or_default(), searched, shards = manifest.shards.len(), "Query timed out, returning partial results");
     }
 
-    all_results.sort_by(|a, b| {
-        b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal)
-    });
-    all_results.truncate(req.topk);
+    let merge_start = std::time::Instant::now();
+    if req.text.is_some() {
+        blend_hybrid(&mut shard_hits, req.vector_weight);
+    }
+    let mut all_results: Vec<SearchResult> = fusion::fuse(req.fusion, shard_hits, |r| r.id.as_str())
+        .into_iter()
+        .map(|(result, score)| SearchResult { score, hybrid: None, ..result })
+        .collect();
+    all_results.truncate(topk);
+    breakdown.result_merge_ms += merge_start.elapsed().as_secs_f64() * 1000.0;
 
     let took_ms = start.elapsed().as_millis();
     let total_search_time = search_start.elapsed();
+    crate::governor::record_query(total_search_time);
     
     get_metrics_collector().track_metric("query.total_time_ms", total_search_time.as_millis() as f64);
+    breakdown.total_ms = total_search_time.as_secs_f64() * 1000.0;
+    get_metrics_collector().record_latency_breakdown(&breakdown);
     get_metrics_collector().track_metric("query.results_returned", all_results.len() as f64);
+    get_metrics_collector().record_recent_query(crate::metrics::QueryLogEntry {
+        timestamp: chrono::Utc::now(),
+        index_name: req.index.clone(),
+        topk,
+        results: all_results.len(),
+        took_ms: total_search_time.as_secs_f64() * 1000.0,
+    });
 
-    Ok(serde_json::json!({
+    operation.record_query_metrics(QueryMetrics {
+        topk,
+        nprobe_used: req.nprobe.unwrap_or(crate::faiss_utils::DEFAULT_NPROBE as u32),
+        shards_searched: manifest.shards.len(),
+        vectors_scanned: manifest.total_vectors,
+        result_count: all_results.len(),
+        latency_breakdown: breakdown.clone(),
+        recall_estimate: None,
+    });
+    operation.finish(HashMap::from([
+        ("vector_count".to_string(), manifest.total_vectors as f64),
+        ("dimension".to_string(), req.embedding.len() as f64),
+    ]));
+
+    let mut response = serde_json::json!({
         "results": all_results,
-        "took_ms": took_ms
-    }))
+        "took_ms": took_ms,
+        "latency_ms": breakdown
+    });
+    if req.explain {
+        response["explain"] = serde_json::json!({ "shards": shard_explains });
+    }
+    if timed_out {
+        response["timed_out"] = serde_json::json!(true);
+    }
+    if !failed_shards.is_empty() {
+        response["failed_shards"] = serde_json::json!(failed_shards);
+    }
+    Ok(response)
+}
+
+/// Score hybrid hits by blending their raw vector and BM25 scores over the
+/// candidates of every shard, then reorder each shard's hits by the blend.
+fn blend_hybrid(shard_hits: &mut [ShardHits<SearchResult>], vector_weight: f32) {
+    // Replicas that blend per shard send no raw scores; their score stands in
+    // for the vector score.
+    let (vector, lexical): (Vec<_>, Vec<_>) = shard_hits.iter()
+        .flat_map(|shard| &shard.hits)
+        .map(|(r, score)| r.hybrid.map_or((Some(*score), None), |h| (h.vector, h.lexical)))
+        .unzip();
+    let mut blended = text_index::blend(&vector, &lexical, vector_weight).into_iter();
+    for shard in shard_hits {
+        for (_, score) in &mut shard.hits {
+            *score = blended.next().unwrap_or_default();
+        }
+        shard.hits.sort_by(|a, b| fusion::rank(a.1, &a.0.id, b.1, &b.0.id));
+    }
 }
 
 async fn search_shard(
     s3: &S3Client,
     req: &QueryRequest,
     shard: &ShardInfo,
-    _manifest: &IndexManifest,
+    manifest: &IndexManifest,
+    explain: &mut ShardExplain,
 ) -> Result<Vec<SearchResult>> {
     let _measurement = crate::measure_operation!("query.search_shard");
-    
+    crate::warmup::usage_log().record(&req.index, &shard.shard_id);
+    let load_start = std::time::Instant::now();
+
     let metadata_start = std::time::Instant::now();
-    let metadata_bytes = s3.get_object(&shard.metadata_path).await
+    let (metadata_bytes, metadata_cache) = cache::get_object_with_status(s3, &shard.metadata_path).await
         .context("Failed to load shard metadata")?;
+    explain.cache.metadata = metadata_cache;
+    verify_checksum(&shard.metadata_path, shard.checksums.as_ref().map(|c| c.metadata.as_str()), &integrity::sha256_hex(&metadata_bytes))?;
+    let metadata_bytes = shard.content_encoding.decode(&metadata_bytes)?;
     let metadata_map: HashMap<String, Value> = serde_json::from_slice(&metadata_bytes)
         .context("Failed to parse shard metadata")?;
     let metadata_load_time = metadata_start.elapsed();
 
     // Apply metadata pre-filtering if specified
-<<<<<<< HEAD
-    let pre_filtered_ids: Option<Vec<String>> = if let Some(_filter_value) = &req.filter {
-        // TODO: Re-enable metadata filtering after fixing compilation
-        // match MetadataFilter::try_from(filter_value.clone()) {
-        //     Ok(filter) => {
-        //         let filtered = filter.pre_filter_ids(&metadata_map);
-        //         get_metrics_collector().track_metric("query.pre_filtered_candidates", filtered.len() as f64);
-        //         Some(filtered)
-        //     }
-        //     Err(e) => {
-        //         tracing::warn!("Invalid metadata filter: {}, proceeding without filter", e);
-        //         None
-        //     }
-        // }
-        None  // Temporarily disable filtering for compilation
-=======
     let pre_filtered_ids: Option<Vec<String>> = if let Some(filter_value) = &req.filter {
         match MetadataFilter::try_from(filter_value.clone()) {
             Ok(filter) => {
                 let filtered = filter.pre_filter_ids(&metadata_map);
                 get_metrics_collector().track_metric("query.pre_filtered_candidates", filtered.len() as f64);
+                if !metadata_map.is_empty() {
+                    explain.filter_selectivity = Some(filtered.len() as f64 / metadata_map.len() as f64);
+                }
                 Some(filtered)
             }
             Err(e) => {
@@ -125,28 +319,56 @@ async fn search_shard(
                 None
             }
         }
->>>>>>> b4defcdf9022a3cdb3403fbeb6bc66d9648e389d
     } else {
         None
     };
 
     let id_map_key = shard.index_path.replace("index.faiss", "id_map.json");
-    let id_map_bytes = s3.get_object(&id_map_key).await
+    let (id_map_bytes, id_map_cache) = cache::get_object_with_status(s3, &id_map_key).await
         .context("Failed to load id map")?;
+    explain.cache.id_map = id_map_cache;
+    verify_checksum(&id_map_key, shard.checksums.as_ref().map(|c| c.id_map.as_str()), &integrity::sha256_hex(&id_map_bytes))?;
+    let id_map_bytes = shard.content_encoding.decode(&id_map_bytes)?;
     let id_map: Vec<(i64, String)> = serde_json::from_slice(&id_map_bytes)
         .context("Failed to parse id map")?;
-    let id_lookup: HashMap<i64, String> = id_map.into_iter().collect();
+    // Rows of the raw vector column and text index are id map positions; the
+    // Faiss ids are key hashes, or the rows themselves in older shards.
+    let id_lookup: HashMap<i64, (i64, &String)> = id_map
+        .iter()
+        .enumerate()
+        .map(|(row, (faiss_id, key))| (*faiss_id, (row as i64, key)))
+        .collect();
+    let row_key = |row: i64| usize::try_from(row).ok().and_then(|row| id_map.get(row)).map(|(_, key)| key);
     
     get_metrics_collector().track_metric("query.metadata_load_time_ms", metadata_load_time.as_millis() as f64);
     get_metrics_collector().track_metric("query.id_map_size", id_lookup.len() as f64);
 
-    let index_bytes = s3.get_object(&shard.index_path).await
+    let local_index = cache::fetch_file(s3, &shard.index_path).await
         .context("Failed to download index file")?;
-    let local_index_path = format!("/tmp/{}.faiss", shard.shard_id);
-    std::fs::write(&local_index_path, &index_bytes)
-        .context("Failed to write temp index file")?;
+    explain.cache.index = local_index.cache_status();
+    let local_index_path = local_index.path().to_string_lossy().to_string();
+    // Cached index files were verified when they entered the cache.
+    let expected = shard.checksums.as_ref().map(|c| c.index.clone()).filter(|_| local_index.fresh);
+    let index_path = shard.index_path.clone();
+    let mut index = crate::faiss_pool::run("read", move || {
+        if let Some(expected) = expected {
+            verify_checksum(&index_path, Some(&expected), &integrity::sha256_file(&local_index_path)?)?;
+        }
+        Ok(faiss::read_index(&local_index_path)?)
+    })
+    .await?;
+    explain.index_load_ms = load_start.elapsed().as_secs_f64() * 1000.0;
+    explain.vectors_in_shard = index.ntotal() as usize;
+    let search_start = std::time::Instant::now();
 
-    let mut index = faiss::read_index(&local_index_path)?;
+    // Hybrid queries also use raw vectors to score lexical hits Faiss didn't return.
+    let hybrid = req.text.is_some();
+    let stored_vectors = if req.return_data || req.exact_rerank || hybrid {
+        load_shard_vectors(s3, shard, manifest.dim as usize).await?
+    } else {
+        None
+    };
+    let rerank = req.exact_rerank && stored_vectors.is_some();
 
     let search_k = if let Some(ref filtered_ids) = pre_filtered_ids {
         let expansion_factor = (metadata_map.len() as f64 / filtered_ids.len() as f64).ceil() as usize;
@@ -154,13 +376,37 @@ async fn search_shard(
     } else {
         req.topk
     };
+    // Quantization misorders close candidates; over-fetch so exact rescoring can
+    // promote the ones Faiss ranked just below the cut.
+    let search_k = if rerank {
+        (search_k * RERANK_OVERFETCH).min(index.ntotal() as usize)
+    } else {
+        search_k
+    };
 
-    let (distances, faiss_ids) = crate::faiss_utils::search_index(
-        &mut index,
-        &req.embedding,
-        search_k,
-        req.nprobe.map(|n| n as usize),
-    )?;
+    // Shards written before the algorithm was recorded are IVF-PQ.
+    let algorithm = if shard.algorithm.is_empty() { "ivfpq" } else { shard.algorithm.as_str() };
+    let nprobe = req.nprobe.map_or(crate::faiss_utils::DEFAULT_NPROBE, |n| n as usize);
+    // Shards of indexes that reduce dimensions hold projected vectors, so the
+    // query is projected the same way.
+    let projected = match &shard.projection {
+        Some(file) => {
+            // Projection files sit under the index directory the shard itself is in.
+            let index = shard.index_path.strip_prefix("indexes/").and_then(|rest| rest.split_once('/'));
+            let index = index.map_or(manifest.index_name.as_str(), |(index, _)| index);
+            Some(crate::reduction::load(s3, index, file, manifest.dim as usize).await?.apply(&req.embedding))
+        }
+        None => None,
+    };
+    let query = projected.unwrap_or_else(|| req.embedding.clone());
+    let search_algorithm = algorithm.to_string();
+    let (distances, faiss_ids) = crate::faiss_pool::run("search", move || {
+        crate::faiss_utils::search_index(&mut index, &search_algorithm, &query, search_k, nprobe)
+    })
+    .await?;
+    explain.candidates_requested = search_k;
+    explain.candidates_returned = faiss_ids.iter().filter(|id| **id != -1).count();
+    explain.nprobe = (algorithm == "ivfpq").then_some(nprobe as u32);
 
     let mut results = Vec::new();
     for (distance, faiss_id) in distances.iter().zip(faiss_ids.iter()) {
@@ -168,17 +414,21 @@ async fn search_shard(
             continue;
         }
         
-        if let Some(original_id) = id_lookup.get(faiss_id) {
+        if let Some(&(row, original_id)) = id_lookup.get(faiss_id) {
             if let Some(ref filtered_ids) = pre_filtered_ids {
                 if !filtered_ids.contains(original_id) {
                     continue;
                 }
             }
 
-            let score = match shard.metric.as_str() {
-                "cosine" => *distance,
-                "euclidean" => -distance,
-                _ => *distance,
+            let raw = stored_vectors.as_ref().and_then(|v| v.get(row, original_id));
+            let score = match (rerank, raw) {
+                (true, Some(raw)) => raw_vectors::exact_score(&shard.metric, &req.embedding, raw),
+                _ => match shard.metric.as_str() {
+                    "cosine" | "dotproduct" => *distance,
+                    "euclidean" | "manhattan" | "chebyshev" => -distance,
+                    _ => *distance,
+                },
             };
 
             let vector_meta = metadata_map.get(original_id)
@@ -189,49 +439,376 @@ async fn search_shard(
                 id: original_id.clone(),
                 score,
                 metadata: vector_meta,
+                data: raw.filter(|_| req.return_data).map(<[f32]>::to_vec),
+                hybrid: None,
             });
 
-            if results.len() >= req.topk {
+            if !rerank && !hybrid && results.len() >= req.topk {
                 break;
             }
         }
     }
 
-    let _ = std::fs::remove_file(&local_index_path);
+    if let Some(query_text) = &req.text {
+        // Hits keep their raw scores; they're blended once every shard's
+        // candidates are merged, so all of them are scaled together.
+        let lexical = match load_shard_text(s3, shard).await? {
+            Some(text) => text.search(query_text, search_k),
+            None => Vec::new(),
+        };
+        let lexical_scores: HashMap<&str, f32> = lexical.iter()
+            .filter_map(|(row, score)| Some((row_key(*row)?.as_str(), *score)))
+            .collect();
+        let mut vector_scores: Vec<Option<f32>> = results.iter().map(|r| Some(r.score)).collect();
+        for (row, _) in &lexical {
+            let Some(id) = row_key(*row) else { continue };
+            if results.iter().any(|r| &r.id == id) || pre_filtered_ids.as_ref().is_some_and(|ids| !ids.contains(id)) {
+                continue;
+            }
+            let raw = stored_vectors.as_ref().and_then(|v| v.get(*row, id));
+            vector_scores.push(raw.map(|raw| raw_vectors::exact_score(&shard.metric, &req.embedding, raw)));
+            results.push(SearchResult {
+                id: id.clone(),
+                score: 0.0,
+                metadata: metadata_map.get(id).cloned().unwrap_or_else(|| serde_json::json!({})),
+                data: raw.filter(|_| req.return_data).map(<[f32]>::to_vec),
+                hybrid: None,
+            });
+        }
+        for (result, vector) in results.iter_mut().zip(vector_scores) {
+            result.hybrid = Some(HybridScores { vector, lexical: lexical_scores.get(result.id.as_str()).copied() });
+        }
+        explain.lexical_candidates = Some(lexical_scores.len());
+    } else if rerank {
+        results.sort_by(|a, b| fusion::rank(a.score, &a.id, b.score, &b.id));
+        results.truncate(req.topk);
+    }
 
+    explain.search_ms = search_start.elapsed().as_secs_f64() * 1000.0;
     Ok(results)
 }
 
-#[derive(serde::Deserialize)]
-struct IndexManifest {
-    index_name: String,
-    dim: u32,
-    metric: String,
-    shards: Vec<ShardInfo>,
-    total_vectors: usize,
-    #[serde(default)]
-    algorithm: Option<String>,
-    #[serde(default)]
-    hnsw_threshold: Option<usize>,
+/// Per-shard statistics returned by explain mode. Shards searched on another
+/// replica only report the round-trip time and the replica that served them.
+#[derive(serde::Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ShardExplain {
+    shard_id: String,
+    /// Owner replica in cluster mode; `None` when searched here.
+    executed_on: Option<String>,
+    index_load_ms: f64,
+    search_ms: f64,
+    /// Round trip to `executed_on`, which covers its load and search.
+    remote_search_ms: f64,
+    vectors_in_shard: usize,
+    candidates_requested: usize,
+    candidates_returned: usize,
+    nprobe: Option<u32>,
+    /// Fraction of the shard's vectors that passed the metadata filter.
+    filter_selectivity: Option<f64>,
+    /// BM25 matches considered by a hybrid query.
+    lexical_candidates: Option<usize>,
+    cache: ShardCacheStatus,
+    /// Results kept after filtering and deletions.
+    results: usize,
 }
 
-#[derive(serde::Deserialize)]
-struct ShardInfo {
-    shard_id: String,
-    index_path: String,
-    metadata_path: String,
-    vector_count: usize,
-    metric: String,
-    created_at: String,
-    #[serde(default)]
-    algorithm: String,
+#[derive(serde::Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ShardCacheStatus {
+    metadata: cache::CacheStatus,
+    id_map: cache::CacheStatus,
+    index: cache::CacheStatus,
+}
+
+/// Body of `POST /internal/shards/search`, sent by the coordinating replica to the
+/// replica that owns a shard on the hash ring.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ShardSearchRequest {
+    pub shard_id: String,
+    pub query: QueryRequest,
+}
+
+/// The owner gets what is left of the query's time, so it stops with us.
+async fn search_shard_remote(owner: &str, req: &QueryRequest, shard: &ShardInfo, deadline: Option<Instant>) -> Result<Vec<SearchResult>> {
+    let cluster = crate::cluster::cluster().context("Cluster mode is not enabled")?;
+    let remaining_ms = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).as_millis() as u64);
+    let query = QueryRequest { timeout_ms: remaining_ms, partial_on_timeout: false, allow_partial_results: false, ..req.clone() };
+    let body = serde_json::json!({ "shard_id": shard.shard_id, "query": query });
+    let response = cluster.post_json(owner, "/internal/shards/search", &body).await?;
+    serde_json::from_value(response).context("Failed to parse remote shard results")
+}
+
+/// Search a single shard on this replica, on behalf of a coordinating replica.
+pub async fn search_local_shard(s3: S3Client, req: ShardSearchRequest) -> Result<Value> {
+    let manifest_data = crate::replica::manifest(&s3, &req.query.index).await?;
+    let mut manifest = IndexManifest::from_slice(&manifest_data)
+        .context("Failed to parse index manifest")?;
+    manifest.check_metrics()?;
+    let shard = manifest.shards.iter().find(|s| s.shard_id == req.shard_id)
+        .with_context(|| format!("Shard {} not found in index {}", req.shard_id, req.query.index))?;
+    let deadline = req.query.timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+    let results = within(deadline, search_shard(&s3, &req.query, shard, &manifest, &mut ShardExplain::default()))
+        .await
+        .ok_or(QueryTimeout { timeout_ms: req.query.timeout_ms.unwrap_or_default() })??;
+    Ok(serde_json::to_value(results)?)
+}
+
+/// Find vectors by id without their record objects: from slices the indexer
+/// hasn't consumed yet, then from shards, newest first. A shard's id map
+/// tells whether it holds a wanted id, so only those shards have their
+/// metadata read; embeddings come from the shard's raw vectors, or are
+/// reconstructed from its `IDMap2` index when it stores none. Returned
+/// documents use the record shape (`data.float32`, `metadata`).
+pub async fn lookup_vectors(s3: &S3Client, index: &str, ids: &[String]) -> Result<HashMap<String, Value>> {
+    let mut wanted: std::collections::HashSet<&str> = ids.iter().map(String::as_str).collect();
+    let mut found = HashMap::new();
+    let deletions = crate::deletions::load(s3, index).await?;
+
+    for slice_path in s3.list_objects(&format!("staged/{}/", index)).await? {
+        for record in crate::indexer::read_slice(s3, &slice_path).await? {
+            if !deletions.is_deleted(&record.id, record.created_at) && wanted.remove(record.id.as_str()) {
+                found.insert(record.id.clone(), crate::ingest::record_document(&record));
+            }
+        }
+    }
+
+    if wanted.is_empty() {
+        return Ok(found);
+    }
+    let Ok(manifest_data) = s3.get_object(&format!("indexes/{}/manifest.json", index)).await else {
+        return Ok(found);
+    };
+    let manifest = IndexManifest::from_slice(&manifest_data)
+        .context("Failed to parse index manifest")?;
+    // Shards are appended oldest first; the newest copy of a key wins.
+    for shard in manifest.shards.iter().rev() {
+        if wanted.is_empty() {
+            break;
+        }
+        let written_at = crate::deletions::parse_shard_time(&shard.created_at);
+        let hits: Vec<(usize, i64, String)> = load_id_map(s3, shard).await?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, id))| !deletions.is_deleted(id, written_at) && wanted.remove(id.as_str()))
+            .map(|(row, (faiss_id, id))| (row, faiss_id, id))
+            .collect();
+        if hits.is_empty() {
+            continue;
+        }
+        let metadata_bytes = cache::get_object(s3, &shard.metadata_path).await?;
+        verify_checksum(&shard.metadata_path, shard.checksums.as_ref().map(|c| c.metadata.as_str()), &integrity::sha256_hex(&metadata_bytes))?;
+        let mut metadata_map: HashMap<String, Value> = serde_json::from_slice(&shard.content_encoding.decode(&metadata_bytes)?)
+            .context("Failed to parse shard metadata")?;
+        let embeddings: Vec<Option<Vec<f32>>> = match load_shard_vectors(s3, shard, manifest.dim as usize).await? {
+            Some(raw) => hits.iter().map(|(row, _, id)| raw.get(*row as i64, id).map(<[f32]>::to_vec)).collect(),
+            None => reconstruct_vectors(s3, shard, hits.iter().map(|(_, faiss_id, _)| *faiss_id).collect()).await?,
+        };
+        for ((_, _, id), embedding) in hits.into_iter().zip(embeddings) {
+            let mut doc = serde_json::json!({ "key": id, "metadata": metadata_map.remove(&id).unwrap_or_else(|| serde_json::json!({})) });
+            if let Some(embedding) = embedding {
+                doc["data"] = serde_json::json!({ "float32": embedding });
+            }
+            found.insert(id, doc);
+        }
+    }
+    Ok(found)
+}
+
+/// Embeddings of `faiss_ids` decoded from a shard's index.
+async fn reconstruct_vectors(s3: &S3Client, shard: &ShardInfo, faiss_ids: Vec<i64>) -> Result<Vec<Option<Vec<f32>>>> {
+    let local_index = cache::fetch_file(s3, &shard.index_path).await
+        .context("Failed to download index file")?;
+    let local_index_path = local_index.path().to_string_lossy().to_string();
+    let expected = shard.checksums.as_ref().map(|c| c.index.clone()).filter(|_| local_index.fresh);
+    let index_path = shard.index_path.clone();
+    crate::faiss_pool::run("reconstruct", move || {
+        if let Some(expected) = expected {
+            verify_checksum(&index_path, Some(&expected), &integrity::sha256_file(&local_index_path)?)?;
+        }
+        let index = faiss::read_index(&local_index_path)?;
+        Ok(faiss_ids.into_iter().map(|id| crate::faiss_utils::reconstruct(&index, id)).collect())
+    })
+    .await
+}
+
+/// Metadata of every vector of `index` that matches `filter`, by key.
+pub async fn filter_vectors(s3: &S3Client, index: &str, filter: &Value) -> Result<BTreeMap<String, Value>> {
+    let metadata_filter = parse_filter(s3, index, filter).await?;
+    Ok(live_metadata(s3, index).await?.into_iter().filter(|(_, meta)| metadata_filter.matches(meta)).collect())
+}
+
+/// Exact number of vectors in `index`, optionally only those matching `filter`.
+pub async fn count_vectors(s3: &S3Client, index: &str, filter: Option<&Value>) -> Result<usize> {
+    let metadata_filter = match filter {
+        Some(filter) => Some(parse_filter(s3, index, filter).await?),
+        None => None,
+    };
+    let live = live_metadata(s3, index).await?;
+    Ok(match metadata_filter {
+        Some(metadata_filter) => live.values().filter(|meta| metadata_filter.matches(meta)).count(),
+        None => live.len(),
+    })
+}
+
+async fn parse_filter(s3: &S3Client, index: &str, filter: &Value) -> Result<MetadataFilter> {
+    if let Some(config) = load_index_config(s3, index).await? {
+        validate_metadata_filter(filter, &config.non_filterable_metadata_keys)?;
+    }
+    Ok(MetadataFilter::try_from(filter.clone())?)
+}
+
+/// Metadata of every live vector of `index`, by key. Reads shard metadata
+/// rather than record objects; slices the indexer hasn't consumed yet are
+/// included, and newer copies of a key replace older ones.
+async fn live_metadata(s3: &S3Client, index: &str) -> Result<HashMap<String, Value>> {
+    let deletions = crate::deletions::load(s3, index).await?;
+    let mut latest: HashMap<String, Value> = HashMap::new();
+
+    if let Ok(manifest_data) = s3.get_object(&format!("indexes/{}/manifest.json", index)).await {
+        let manifest = IndexManifest::from_slice(&manifest_data)
+            .context("Failed to parse index manifest")?;
+        // Shards are appended to the manifest as they are built, oldest first.
+        for shard in &manifest.shards {
+            let metadata_bytes = cache::get_object(s3, &shard.metadata_path).await?;
+            verify_checksum(&shard.metadata_path, shard.checksums.as_ref().map(|c| c.metadata.as_str()), &integrity::sha256_hex(&metadata_bytes))?;
+            let metadata_map: HashMap<String, Value> = serde_json::from_slice(&shard.content_encoding.decode(&metadata_bytes)?)
+                .context("Failed to parse shard metadata")?;
+            let written_at = crate::deletions::parse_shard_time(&shard.created_at);
+            latest.extend(metadata_map.into_iter().filter(|(id, _)| !deletions.is_deleted(id, written_at)));
+        }
+    }
+    let mut slices = s3.list_objects(&format!("staged/{}/", index)).await?;
+    slices.sort();
+    for slice_path in slices {
+        for record in crate::indexer::read_slice(s3, &slice_path).await? {
+            if !deletions.is_deleted(&record.id, record.created_at) {
+                latest.insert(record.id, record.meta);
+            }
+        }
+    }
+    Ok(latest)
+}
+
+/// Pull a shard's artifacts into the shard cache ahead of the first query.
+/// Returns `false` when the shard is no longer part of the index.
+pub(crate) async fn warm_shard(s3: &S3Client, index: &str, shard_id: &str) -> Result<bool> {
+    let manifest_data = s3.get_object(&format!("indexes/{}/manifest.json", index)).await?;
+    let manifest = IndexManifest::from_slice(&manifest_data)
+        .context("Failed to parse index manifest")?;
+    let Some(shard) = manifest.shards.iter().find(|s| s.shard_id == shard_id) else {
+        return Ok(false);
+    };
+
+    let id_map_key = shard.index_path.replace("index.faiss", "id_map.json");
+    for key in [&shard.metadata_path, &id_map_key] {
+        cache::get_object(s3, key).await?;
+    }
+    let local_index = cache::fetch_file(s3, &shard.index_path).await?;
+    if let Some(expected) = shard.checksums.as_ref().map(|c| c.index.as_str()).filter(|_| local_index.fresh) {
+        let actual = integrity::sha256_file(&local_index.path().to_string_lossy())?;
+        verify_checksum(&shard.index_path, Some(expected), &actual)?;
+    }
+    Ok(true)
+}
+
+/// Full-precision embeddings of a shard; `None` when its index doesn't store
+/// raw vectors or the shard predates them.
+async fn load_shard_vectors(s3: &S3Client, shard: &ShardInfo, dim: usize) -> Result<Option<RawVectors>> {
+    let Some(vectors_path) = &shard.vectors_path else {
+        return Ok(None);
+    };
+    let vectors_bytes = cache::get_object(s3, vectors_path).await
+        .context("Failed to load shard vectors")?;
+    verify_checksum(vectors_path, shard.checksums.as_ref().and_then(|c| c.vectors.as_deref()), &integrity::sha256_hex(&vectors_bytes))?;
+    // Only the legacy JSON form goes through the shard's content encoding.
+    let vectors = if vectors_path.ends_with(".json") {
+        RawVectors::parse(vectors_path, &shard.content_encoding.decode(&vectors_bytes)?, dim)?
+    } else {
+        RawVectors::parse(vectors_path, &vectors_bytes, dim)?
+    };
+    Ok(Some(vectors))
+}
+
+/// BM25 index of a shard; `None` when none of its vectors had text.
+async fn load_shard_text(s3: &S3Client, shard: &ShardInfo) -> Result<Option<TextIndex>> {
+    let Some(text_path) = &shard.text_path else {
+        return Ok(None);
+    };
+    let text_bytes = cache::get_object(s3, text_path).await
+        .context("Failed to load shard text index")?;
+    verify_checksum(text_path, shard.checksums.as_ref().and_then(|c| c.text.as_deref()), &integrity::sha256_hex(&text_bytes))?;
+    let text = serde_json::from_slice(&shard.content_encoding.decode(&text_bytes)?)
+        .context("Failed to parse shard text index")?;
+    Ok(Some(text))
+}
+
+async fn load_id_map(s3: &S3Client, shard: &ShardInfo) -> Result<Vec<(i64, String)>> {
+    let id_map_key = shard.index_path.replace("index.faiss", "id_map.json");
+    let id_map_bytes = cache::get_object(s3, &id_map_key).await
+        .context("Failed to load id map")?;
+    verify_checksum(&id_map_key, shard.checksums.as_ref().map(|c| c.id_map.as_str()), &integrity::sha256_hex(&id_map_bytes))?;
+    serde_json::from_slice(&shard.content_encoding.decode(&id_map_bytes)?)
+        .context("Failed to parse id map")
+}
+
+impl IndexManifest {
+    /// Check that every shard was built with the index's distance metric, and
+    /// settle all of them on its canonical name so scores are read the same
+    /// way. Scores of shards with different metrics can't be merged, so such
+    /// manifests are refused until `repair-metrics` has fixed them.
+    fn check_metrics(&mut self) -> Result<()> {
+        let metric = crate::faiss_utils::canonical_metric(&self.metric).with_context(|| {
+            format!("Index {} has unsupported distance metric '{}'", self.index_name, self.metric)
+        })?;
+        if let Some(shard) = self.shards.iter().find(|s| crate::faiss_utils::canonical_metric(&s.metric) != Some(metric)) {
+            get_metrics_collector().track_metric("query.mixed_metric_manifest", 1.0);
+            return Err(anyhow::anyhow!(
+                "Index {} uses the {} metric but shard {} was built with '{}'; run `repair-metrics --index {}`",
+                self.index_name,
+                metric,
+                shard.shard_id,
+                shard.metric,
+                self.index_name
+            ));
+        }
+        self.metric = metric.to_string();
+        for shard in &mut self.shards {
+            shard.metric = metric.to_string();
+        }
+        Ok(())
+    }
 }
 
-#[derive(serde::Serialize)]
+fn verify_checksum(key: &str, expected: Option<&str>, actual: &str) -> Result<()> {
+    integrity::verify(key, expected, actual).map_err(|e| {
+        get_metrics_collector().track_metric("query.checksum_mismatch", 1.0);
+        cache::invalidate(key);
+        tracing::error!("Shard artifact corrupted: {}", e);
+        anyhow::Error::new(e)
+    })
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 struct SearchResult {
     id: String,
     score: f32,
     metadata: Value,
+    /// Only set for `return_data` queries on shards that store raw vectors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<f32>>,
+    /// Only set by hybrid queries, whose `score` is blended from these.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hybrid: Option<HybridScores>,
+}
+
+/// Raw scores of a hybrid candidate. BM25 and vector scores are only scaled
+/// into a blend over the candidates of every shard, in [`search`].
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct HybridScores {
+    /// `None` for lexical matches of shards without raw vectors.
+    vector: Option<f32>,
+    lexical: Option<f32>,
 }
 
 async fn load_shard_metadata(s3: &S3Client, shard: &ShardInfo) -> Result<ShardMetadata> {
@@ -251,49 +828,17 @@ struct ShardMetadata {
     metadata: HashMap<String, Value>,
 }
 
-// Helper struct for index configuration
-#[derive(serde::Deserialize, Clone)]
-struct IndexConfig {
-    name: String,
-    dim: u32,
-    metric: String,
-    nlist: u32,
-    m: u32,
-    nbits: u32,
-    #[serde(default)]
-    non_filterable_metadata_keys: Vec<String>,
-}
-
-async fn load_index_config(s3: &S3Client, index_name: &str) -> Result<Option<IndexConfig>> {
+async fn load_index_config(s3: &S3Client, index_name: &str) -> Result<Option<CreateIndex>> {
     let config_key = format!("indexes/{}/config.json", index_name);
     
     match s3.get_object(&config_key).await {
-        Ok(data) => {
-            match serde_json::from_slice::<CreateIndex>(&data) {
-                Ok(create_index) => {
-                    // Convert CreateIndex to our IndexConfig
-                    Ok(Some(IndexConfig {
-                        name: create_index.name,
-                        dim: create_index.dim,
-                        metric: create_index.metric,
-                        nlist: create_index.nlist,
-                        m: create_index.m,
-                        nbits: create_index.nbits,
-                        non_filterable_metadata_keys: create_index.non_filterable_metadata_keys,
-                    }))
-                }
-                Err(_) => {
-                    // Try parsing as IndexConfig directly for backward compatibility
-                    match serde_json::from_slice::<IndexConfig>(&data) {
-                        Ok(config) => Ok(Some(config)),
-                        Err(e) => {
-                            tracing::warn!("Failed to parse index config: {}", e);
-                            Ok(None)
-                        }
-                    }
-                }
+        Ok(data) => match CreateIndex::from_slice(&data) {
+            Ok(config) => Ok(Some(config)),
+            Err(e) => {
+                tracing::warn!("Failed to parse index config: {}", e);
+                Ok(None)
             }
-        }
+        },
         Err(_) => Ok(None),
     }
 }
@@ -340,3 +885,72 @@ fn validate_filter_recursive(filter: &serde_json::Value, non_filterable_keys: &[
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(metric: &str, shard_metrics: &[&str]) -> IndexManifest {
+        let shards: Vec<Value> = shard_metrics
+            .iter()
+            .enumerate()
+            .map(|(i, metric)| serde_json::json!({
+                "shard_id": format!("s{}", i),
+                "index_path": "index.faiss",
+                "metadata_path": "metadata.json",
+                "vector_count": 1,
+                "metric": metric,
+                "created_at": "20250101T000000",
+            }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "index_name": "idx",
+            "dim": 2,
+            "metric": metric,
+            "shards": shards,
+            "total_vectors": shards.len(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_metrics_settles_spellings_and_rejects_mixes() {
+        let mut spellings = manifest("euclidean", &["L2", "Euclidean"]);
+        spellings.check_metrics().unwrap();
+        assert!(spellings.shards.iter().all(|s| s.metric == "euclidean"));
+
+        let error = manifest("cosine", &["cosine", "euclidean"]).check_metrics().unwrap_err();
+        assert!(error.to_string().contains("repair-metrics"));
+        assert!(manifest("hamming", &[]).check_metrics().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_work_past_the_deadline_is_dropped() {
+        assert_eq!(within(None, async { 7 }).await, Some(7));
+        let deadline = Some(Instant::now() + Duration::from_millis(20));
+        assert_eq!(within(deadline, async { 7 }).await, Some(7));
+        assert_eq!(within(deadline, std::future::pending::<()>()).await, None);
+    }
+
+    #[test]
+    fn test_hybrid_scores_blend_across_shards() {
+        let hit = |id: &str, vector: f32, lexical: f32| {
+            let hybrid = HybridScores { vector: Some(vector), lexical: Some(lexical) };
+            let result = SearchResult { id: id.to_string(), score: vector, metadata: Value::Null, data: None, hybrid: Some(hybrid) };
+            (result, vector)
+        };
+        // Scaled shard by shard, each shard's only hit would score 1.
+        let mut shard_hits = vec![
+            ShardHits { algorithm: "flat".to_string(), hits: vec![hit("weak", 0.1, 1.0)] },
+            ShardHits { algorithm: "flat".to_string(), hits: vec![hit("strong", 0.9, 8.0), hit("lexical", 0.5, 12.0)] },
+        ];
+        blend_hybrid(&mut shard_hits, 0.5);
+
+        let scores: Vec<(&str, f32)> = shard_hits.iter().flat_map(|s| &s.hits).map(|(r, score)| (r.id.as_str(), *score)).collect();
+        assert_eq!(scores[0], ("weak", 0.0));
+        assert_eq!(scores[1].0, "strong");
+        assert!((scores[1].1 - (0.5 + 0.5 * 7.0 / 11.0)).abs() < 1e-6);
+        assert_eq!(scores[2].0, "lexical");
+        assert!((scores[2].1 - 0.75).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Ranked QueryVectors results kept for paging are stored as
+/// `query-snapshots/<expiry epoch>-<id>.json`, so expired ones can be swept by
+/// name alone.
+const SNAPSHOT_PREFIX: &str = "query-snapshots/";
+
+/// Results beyond the first page of a paginated query, in rank order, exactly
+/// as they will be returned. Later pages are served from here instead of
+/// searching again, so they stay consistent with the first one.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QuerySnapshot {
+    /// Bucket and index names of the original request; continuations must
+    /// name the same ones, so access control applies to them as before.
+    pub bucket: Option<String>,
+    pub index_names: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub results: Vec<Value>,
+}
+
+/// How long snapshots stay readable, from `VEC_QUERY_CURSOR_TTL_SECS` (default 900).
+pub fn ttl_from_env() -> Duration {
+    let secs = std::env::var("VEC_QUERY_CURSOR_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(900);
+    Duration::seconds(secs.max(1))
+}
+
+fn snapshot_key(id: &str) -> String {
+    format!("{}{}.json", SNAPSHOT_PREFIX, id)
+}
+
+/// Store `snapshot` and return its id.
+pub async fn save(s3: &S3Client, snapshot: &QuerySnapshot) -> Result<String> {
+    let id = format!("{}-{}", snapshot.expires_at.timestamp(), uuid::Uuid::new_v4());
+    s3.put_object(&snapshot_key(&id), serde_json::to_vec(snapshot)?.into())
+        .await
+        .context("Failed to store query snapshot")?;
+    Ok(id)
+}
+
+/// Snapshot `id`, unless it expired or was swept.
+pub async fn load(s3: &S3Client, id: &str) -> Option<QuerySnapshot> {
+    if id.contains('/') {
+        return None;
+    }
+    let data = s3.get_object(&snapshot_key(id)).await.ok()?;
+    let snapshot: QuerySnapshot = serde_json::from_slice(&data).ok()?;
+    (snapshot.expires_at > Utc::now()).then_some(snapshot)
+}
+
+fn expiry_of(key: &str) -> Option<i64> {
+    key.strip_prefix(SNAPSHOT_PREFIX)?.split_once('-')?.0.parse().ok()
+}
+
+/// Delete expired snapshots, returning how many were removed.
+pub async fn sweep_expired(s3: &S3Client) -> Result<usize> {
+    let now = Utc::now().timestamp();
+    let mut swept = 0;
+    for key in s3.list_objects(SNAPSHOT_PREFIX).await? {
+        if expiry_of(&key).is_some_and(|expires| expires <= now) {
+            s3.delete_object(&key).await?;
+            swept += 1;
+        }
+    }
+    Ok(swept)
+}
+
+/// Sweep expired snapshots in the background, once per TTL.
+pub fn spawn_sweeper(s3: S3Client) {
+    let every = ttl_from_env().to_std().unwrap_or(std::time::Duration::from_secs(900));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            match sweep_expired(&s3).await {
+                Ok(0) => {}
+                Ok(swept) => tracing::info!(swept, "Swept expired query snapshots"),
+                Err(e) => tracing::warn!(error = %e, "Failed to sweep query snapshots"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_from_key() {
+        assert_eq!(expiry_of("query-snapshots/1736942400-0b7e.json"), Some(1736942400));
+        assert_eq!(expiry_of("query-snapshots/garbage.json"), None);
+    }
+}
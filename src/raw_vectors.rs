@@ -0,0 +1,95 @@
+use crate::distance;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+/// Object name of a shard's raw vector column, next to `index.faiss`.
+pub const COLUMN_FILE: &str = "vectors.f32";
+
+/// Full-precision embeddings of a shard, kept for indexes created with
+/// `storeRawVectors` so results can be returned with their data and reranked
+/// exactly even when the Faiss index only holds quantized codes.
+#[derive(Debug)]
+pub enum RawVectors {
+    /// Little-endian f32 rows in id map order, `dim` values per row.
+    Column { dim: usize, values: Vec<f32> },
+    /// JSON object keyed by vector id, written by earlier versions as `vectors.json`.
+    Keyed(HashMap<String, Vec<f32>>),
+}
+
+/// Encode row-major shard vectors as a column, in the order they were added to the index.
+pub fn encode_column(vectors: &[f32]) -> Vec<u8> {
+    vectors.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+impl RawVectors {
+    /// Parse a stored (already decoded) vectors object; the format follows its key.
+    pub fn parse(key: &str, data: &[u8], dim: usize) -> Result<Self> {
+        if key.ends_with(".json") {
+            let keyed = serde_json::from_slice(data).context("Failed to parse shard vectors")?;
+            return Ok(RawVectors::Keyed(keyed));
+        }
+        if dim == 0 || !data.len().is_multiple_of(dim * 4) {
+            return Err(anyhow::anyhow!(
+                "Vector column {} has {} bytes, not a whole number of {}-dimensional rows",
+                key, data.len(), dim
+            ));
+        }
+        let values = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok(RawVectors::Column { dim, values })
+    }
+
+    /// Embedding of the vector at id map row `row` with key `id`.
+    pub fn get(&self, row: i64, id: &str) -> Option<&[f32]> {
+        match self {
+            RawVectors::Column { dim, values } => {
+                let start = usize::try_from(row).ok()?.checked_mul(*dim)?;
+                values.get(start..start + dim)
+            }
+            RawVectors::Keyed(keyed) => keyed.get(id).map(Vec::as_slice),
+        }
+    }
+}
+
+/// Exact similarity on the same scale as the shard's Faiss scores: inner
+/// product for cosine and dotproduct, negated distance for the others
+/// (squared for euclidean).
+pub fn exact_score(metric: &str, query: &[f32], vector: &[f32]) -> f32 {
+    match metric {
+        "euclidean" => -distance::l2_squared(query, vector),
+        "manhattan" => -distance::l1(query, vector),
+        "chebyshev" => -distance::linf(query, vector),
+        _ => distance::dot(query, vector),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_round_trip() {
+        let vectors = [1.0, -2.5, 0.25, 3.0];
+        let raw = RawVectors::parse("shards/s1/vectors.f32", &encode_column(&vectors), 2).unwrap();
+        assert_eq!(raw.get(1, "b"), Some(&[0.25, 3.0][..]));
+        assert_eq!(raw.get(2, "c"), None);
+        assert_eq!(raw.get(-1, "a"), None);
+        assert!(RawVectors::parse("shards/s1/vectors.f32", &[0u8; 12], 2).is_err());
+    }
+
+    #[test]
+    fn test_keyed_legacy_format() {
+        let raw = RawVectors::parse("shards/s1/vectors.json", br#"{"a":[1.0,2.0]}"#, 2).unwrap();
+        assert_eq!(raw.get(7, "a"), Some(&[1.0, 2.0][..]));
+    }
+
+    #[test]
+    fn test_exact_score_matches_faiss_scale() {
+        assert_eq!(exact_score("cosine", &[1.0, 2.0], &[3.0, 4.0]), 11.0);
+        assert_eq!(exact_score("dotproduct", &[1.0, 2.0], &[3.0, 4.0]), 11.0);
+        assert_eq!(exact_score("euclidean", &[1.0, 2.0], &[3.0, 4.0]), -8.0);
+        assert_eq!(exact_score("manhattan", &[1.0, 2.0], &[3.0, 5.0]), -5.0);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::distance;
+use crate::faiss_utils::{reservoir_sample, training_seed, SplitMix64};
+use crate::integrity::sha256_hex;
+use crate::minio::S3Client;
+use crate::raw_vectors;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Most vectors PCA is trained on; each one costs `dim²` to accumulate.
+const PCA_TRAINING_VECTORS: usize = 8_192;
+/// Subspace iterations towards the leading principal directions.
+const PCA_ITERATIONS: usize = 24;
+/// Rows from which projecting is split over the current rayon pool; queries
+/// project one row, which isn't worth a hand-off.
+const PARALLEL_ROWS: usize = 1_024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReductionMethod {
+    /// The leading principal directions of a sample of the first build.
+    Pca,
+    /// Random Gaussian directions (Johnson-Lindenstrauss); nothing to train.
+    Random,
+}
+
+/// Dimensionality reduction of an index: Faiss indexes `dimension`-d
+/// projections of the embeddings and queries are projected the same way,
+/// trading a little recall for smaller, faster shards. Raw vectors keep every
+/// dimension, so returnData and exact reranking (which wins most of the recall
+/// back) are unaffected.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Reduction {
+    pub method: ReductionMethod,
+    pub dimension: u32,
+}
+
+impl Reduction {
+    pub fn validate(&self, dim: u32) -> Result<(), String> {
+        if self.dimension == 0 || self.dimension >= dim {
+            return Err(format!(
+                "reduction.dimension must be at least 1 and below the index dimension {}, got {}",
+                dim, self.dimension
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The projection an index's shards are built with, described by
+/// `indexes/<index>/projection.json`. The matrix, `dimOut` rows of `dimIn`
+/// little-endian f32s, is stored next to it under a fresh name per training,
+/// and shards record which one they used.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectionInfo {
+    /// Object under `indexes/<index>/`, relative so copies of the index keep it.
+    pub file: String,
+    pub method: ReductionMethod,
+    pub dim_in: u32,
+    pub dim_out: u32,
+    /// Vectors it was trained on; 0 for random projections.
+    pub trained_on: usize,
+    pub checksum: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A loaded projection matrix.
+pub struct Projection {
+    pub file: String,
+    dim_in: usize,
+    dim_out: usize,
+    matrix: Vec<f32>,
+}
+
+impl Projection {
+    pub fn dim_out(&self) -> usize {
+        self.dim_out
+    }
+
+    /// Project row-major `vectors` of `dim_in` values each.
+    pub fn apply(&self, vectors: &[f32]) -> Vec<f32> {
+        if vectors.len() >= PARALLEL_ROWS * self.dim_in {
+            return vectors
+                .par_chunks_exact(self.dim_in)
+                .flat_map_iter(|row| self.matrix.chunks_exact(self.dim_in).map(move |direction| distance::dot(direction, row)))
+                .collect();
+        }
+        let mut projected = Vec::with_capacity(vectors.len() / self.dim_in * self.dim_out);
+        for row in vectors.chunks_exact(self.dim_in) {
+            projected.extend(self.matrix.chunks_exact(self.dim_in).map(|direction| distance::dot(direction, row)));
+        }
+        projected
+    }
+
+    fn decode(file: &str, data: &[u8], dim_in: usize, dim_out: usize) -> Result<Self> {
+        if data.len() != dim_in * dim_out * 4 {
+            return Err(anyhow::anyhow!("Projection {} is {} bytes, expected {}x{} f32s", file, data.len(), dim_out, dim_in));
+        }
+        let matrix = data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        Ok(Projection { file: file.to_string(), dim_in, dim_out, matrix })
+    }
+}
+
+fn info_key(index: &str) -> String {
+    format!("indexes/{}/projection.json", index)
+}
+
+pub async fn load_info(s3: &S3Client, index: &str) -> Option<ProjectionInfo> {
+    let data = s3.get_object(&info_key(index)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Projection `file` of `index`, kept in memory once read; files are never
+/// rewritten, so cached copies can't go stale.
+pub async fn load(s3: &S3Client, index: &str, file: &str, dim_in: usize) -> Result<Arc<Projection>> {
+    static LOADED: OnceLock<Mutex<HashMap<String, Arc<Projection>>>> = OnceLock::new();
+    let key = format!("indexes/{}/{}", index, file);
+    let loaded = LOADED.get_or_init(Default::default);
+    if let Some(projection) = loaded.lock().unwrap().get(&key) {
+        return Ok(projection.clone());
+    }
+    let data = s3.get_object(&key).await.with_context(|| format!("Failed to load projection {}", key))?;
+    let dim_out = data.len() / 4 / dim_in.max(1);
+    let projection = Arc::new(Projection::decode(file, &data, dim_in, dim_out)?);
+    loaded.lock().unwrap().insert(key, projection.clone());
+    Ok(projection)
+}
+
+/// Projection for new shards of `index`: the stored one if it matches
+/// `reduction`, otherwise one trained now on the row-major `vectors` (PCA) or
+/// drawn at random, and stored for later builds. `None` when PCA has fewer
+/// vectors than output dimensions to learn from; those shards keep every
+/// dimension.
+pub async fn ensure(s3: &S3Client, index: &str, dim: usize, reduction: Reduction, vectors: &[f32]) -> Result<Option<Arc<Projection>>> {
+    let dim_out = reduction.dimension as usize;
+    let stored = load_info(s3, index)
+        .await
+        .filter(|info| info.dim_in as usize == dim && info.dim_out as usize == dim_out && info.method == reduction.method);
+    if let Some(info) = stored {
+        let projection = load(s3, index, &info.file, dim).await?;
+        return Ok(Some(projection));
+    }
+
+    let count = vectors.len() / dim;
+    let mut rng = SplitMix64::new(training_seed());
+    let (matrix, trained_on) = match reduction.method {
+        ReductionMethod::Pca if count < dim_out => return Ok(None),
+        ReductionMethod::Pca => {
+            let sample = reservoir_sample(count, PCA_TRAINING_VECTORS, &mut rng);
+            (principal_directions(vectors, dim, &sample, dim_out, &mut rng), sample.len())
+        }
+        ReductionMethod::Random => (random_directions(dim, dim_out, &mut rng), 0),
+    };
+    // A fresh object per training, so builds reading the previous one while
+    // this is written never see a mix of the two.
+    let file = format!("projection/{}.f32", uuid::Uuid::new_v4());
+    let data = raw_vectors::encode_column(&matrix);
+    let info = ProjectionInfo {
+        file: file.clone(),
+        method: reduction.method,
+        dim_in: dim as u32,
+        dim_out: dim_out as u32,
+        trained_on,
+        checksum: sha256_hex(&data),
+        created_at: Utc::now(),
+    };
+    s3.put_object(&format!("indexes/{}/{}", index, file), data.into())
+        .await
+        .context("Failed to store projection")?;
+    s3.put_object(&info_key(index), serde_json::to_vec(&info)?.into()).await?;
+    tracing::info!(index, method = ?reduction.method, dim, dim_out, trained_on, "Trained projection");
+    Ok(Some(Arc::new(Projection { file, dim_in: dim, dim_out, matrix })))
+}
+
+/// Standard normal sample (Box-Muller).
+pub(crate) fn gaussian(rng: &mut SplitMix64) -> f32 {
+    let uniform = |rng: &mut SplitMix64| ((rng.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    let (u, v) = (uniform(rng), uniform(rng));
+    ((-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()) as f32
+}
+
+/// `dim_out` random directions, scaled so projections keep distances and
+/// inner products in expectation.
+fn random_directions(dim: usize, dim_out: usize, rng: &mut SplitMix64) -> Vec<f32> {
+    let scale = 1.0 / (dim_out as f32).sqrt();
+    (0..dim * dim_out).map(|_| gaussian(rng) * scale).collect()
+}
+
+/// Orthonormalize `directions` (rows of `dim`) in place by modified
+/// Gram-Schmidt, replacing any that collapse with fresh random ones.
+fn orthonormalize(directions: &mut [f32], dim: usize, rng: &mut SplitMix64) {
+    for j in 0..directions.len() / dim {
+        loop {
+            let (done, rest) = directions.split_at_mut(j * dim);
+            let current = &mut rest[..dim];
+            for previous in done.chunks_exact(dim) {
+                let overlap = distance::dot(previous, current);
+                current.iter_mut().zip(previous).for_each(|(c, p)| *c -= overlap * p);
+            }
+            let norm = distance::dot(current, current).sqrt();
+            if norm > 1e-6 {
+                current.iter_mut().for_each(|c| *c /= norm);
+                break;
+            }
+            current.iter_mut().for_each(|c| *c = gaussian(rng));
+        }
+    }
+}
+
+/// The `dim_out` leading eigenvectors of the second moment of the `sample`
+/// rows, by subspace iteration. Uncentered, so inner products as well as
+/// distances are kept along the directions retained.
+fn principal_directions(vectors: &[f32], dim: usize, sample: &[usize], dim_out: usize, rng: &mut SplitMix64) -> Vec<f32> {
+    let mut moment = vec![0.0f32; dim * dim];
+    for &row in sample {
+        let x = &vectors[row * dim..(row + 1) * dim];
+        for (i, &xi) in x.iter().enumerate() {
+            moment[i * dim..(i + 1) * dim].iter_mut().zip(x).for_each(|(m, &xj)| *m += xi * xj);
+        }
+    }
+
+    let mut directions = random_directions(dim, dim_out, rng);
+    orthonormalize(&mut directions, dim, rng);
+    let mut next = vec![0.0f32; dim * dim_out];
+    for _ in 0..PCA_ITERATIONS {
+        for (direction, out) in directions.chunks_exact(dim).zip(next.chunks_exact_mut(dim)) {
+            for (o, m) in out.iter_mut().zip(moment.chunks_exact(dim)) {
+                *o = distance::dot(m, direction);
+            }
+        }
+        std::mem::swap(&mut directions, &mut next);
+        orthonormalize(&mut directions, dim, rng);
+    }
+    directions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pca_finds_the_spanned_plane() {
+        // Points in the plane of the first two axes of a 4-d space.
+        let mut rng = SplitMix64::new(7);
+        let vectors: Vec<f32> = (0..200).flat_map(|_| [gaussian(&mut rng), 3.0 * gaussian(&mut rng), 0.0, 0.0]).collect();
+        let sample: Vec<usize> = (0..200).collect();
+        let matrix = principal_directions(&vectors, 4, &sample, 2, &mut rng);
+        let projection = Projection { file: String::new(), dim_in: 4, dim_out: 2, matrix };
+
+        // The plane is kept whole, so distances within it survive projection.
+        let (a, b) = ([1.0, 2.0, 0.0, 0.0], [-2.0, 0.5, 0.0, 0.0]);
+        let (pa, pb) = (projection.apply(&a), projection.apply(&b));
+        assert_eq!(pa.len(), 2);
+        assert!((distance::l2_squared(&pa, &pb) - distance::l2_squared(&a, &b)).abs() < 1e-3);
+        assert!((distance::dot(&pa, &pb) - distance::dot(&a, &b)).abs() < 1e-3);
+
+        // Builds project in parallel, row for row as queries do.
+        let many: Vec<f32> = vectors.iter().copied().cycle().take(PARALLEL_ROWS * 2 * 4).collect();
+        let rows: Vec<f32> = many.chunks_exact(4).flat_map(|row| projection.apply(row)).collect();
+        assert_eq!(projection.apply(&many), rows);
+    }
+
+    #[test]
+    fn test_validate_dimension() {
+        let reduction = |dimension| Reduction { method: ReductionMethod::Random, dimension };
+        assert!(reduction(256).validate(1536).is_ok());
+        assert!(reduction(1536).validate(1536).is_err());
+        assert!(reduction(0).validate(1536).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::index_events::{IndexEvent, IndexEventKind};
+use crate::minio::S3Client;
+use anyhow::Result;
+use bytes::Bytes;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{OnceLock, RwLock};
+
+/// Read-replica mode: with `VEC_ROLE=follower` an API node rejects writes and
+/// serves queries from index manifests it polls, pre-loading new shards into
+/// the shard cache before switching to them. Followers share the leader's
+/// bucket and hold no state of their own, so they can be scaled freely.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Leader,
+    Follower,
+}
+
+pub fn role() -> Role {
+    static ROLE: OnceLock<Role> = OnceLock::new();
+    *ROLE.get_or_init(|| match std::env::var("VEC_ROLE").as_deref() {
+        Ok("follower") => Role::Follower,
+        _ => Role::Leader,
+    })
+}
+
+pub fn is_follower() -> bool {
+    role() == Role::Follower
+}
+
+/// Manifests as of the last poll, per index, on followers.
+fn manifests() -> &'static RwLock<HashMap<String, Bytes>> {
+    static MANIFESTS: OnceLock<RwLock<HashMap<String, Bytes>>> = OnceLock::new();
+    MANIFESTS.get_or_init(Default::default)
+}
+
+fn manifest_key(index: &str) -> String {
+    format!("indexes/{}/manifest.json", index)
+}
+
+/// Manifest of `index` to query against. The leader reads it from storage
+/// every time; followers use the copy from their last poll, fetching it on the
+/// first query of an index.
+pub async fn manifest(s3: &S3Client, index: &str) -> Result<Bytes> {
+    if !is_follower() {
+        return s3.get_object(&manifest_key(index)).await;
+    }
+    if let Some(data) = manifests().read().unwrap().get(index) {
+        return Ok(data.clone());
+    }
+    let data = s3.get_object(&manifest_key(index)).await?;
+    manifests().write().unwrap().insert(index.to_string(), data.clone());
+    Ok(data)
+}
+
+fn shard_ids(manifest: &[u8]) -> HashSet<String> {
+    let manifest: Value = serde_json::from_slice(manifest).unwrap_or_default();
+    manifest
+        .get("shards")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|shard| shard.get("shard_id")?.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Re-read the manifest of every index queried so far. Shards new in a
+/// changed manifest are cached before it replaces the old one, so queries
+/// never wait on them; indexes that disappeared are forgotten.
+pub async fn refresh(s3: &S3Client) -> usize {
+    let known: Vec<(String, Bytes)> = manifests().read().unwrap().iter().map(|(i, m)| (i.clone(), m.clone())).collect();
+    let mut changed = 0;
+    for (index, current) in known {
+        let latest = match s3.get_object(&manifest_key(&index)).await {
+            Ok(latest) => latest,
+            Err(_) => {
+                manifests().write().unwrap().remove(&index);
+                continue;
+            }
+        };
+        if latest == current {
+            continue;
+        }
+        let previous = shard_ids(&current);
+        let added: Vec<String> = shard_ids(&latest).difference(&previous).cloned().collect();
+        if crate::cache::shard_cache().is_some() {
+            for shard_id in &added {
+                if let Err(e) = crate::query::warm_shard(s3, &index, shard_id).await {
+                    tracing::warn!(index = %index, shard = %shard_id, error = %e, "Failed to pre-load shard");
+                }
+            }
+        }
+        let kind = if added.is_empty() { IndexEventKind::ManifestChanged } else { IndexEventKind::Indexed };
+        crate::index_events::publish(IndexEvent::new(kind, &index, &latest));
+        manifests().write().unwrap().insert(index, latest);
+        changed += 1;
+    }
+    changed
+}
+
+/// On followers, poll manifests every `VEC_MANIFEST_POLL_SECS` (default 10).
+pub fn spawn_poller(s3: S3Client) {
+    if !is_follower() {
+        return;
+    }
+    let secs = std::env::var("VEC_MANIFEST_POLL_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(10u64).max(1);
+    tracing::info!(poll_secs = secs, "Running as read replica");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(secs));
+        loop {
+            interval.tick().await;
+            let changed = refresh(&s3).await;
+            if changed > 0 {
+                tracing::info!(changed, "Picked up new index manifests");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_ids() {
+        let manifest = br#"{"shards": [{"shard_id": "s1"}, {"shard_id": "s2"}]}"#;
+        assert_eq!(shard_ids(manifest), HashSet::from(["s1".to_string(), "s2".to_string()]));
+        assert!(shard_ids(b"not json").is_empty());
+    }
+}
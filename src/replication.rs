@@ -0,0 +1,200 @@
+use crate::changelog::{self, Change, Mutation};
+use crate::metrics::get_metrics_collector;
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Changes younger than this are left for the next pass: replicas log with
+/// their own clocks, so a change can land slightly after a later-named one.
+const SETTLE_SECS: i64 = 5;
+
+/// What to do when a replicated put meets a key the target already has.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Apply every source mutation as is; the target ends up matching the source.
+    SourceWins,
+    /// Only create keys missing on the target (`ifNotExists`); existing ones are
+    /// left alone and counted as conflicts. Deletes still apply.
+    KeepTarget,
+}
+
+/// Tail the change log of a source deployment and replay it against the REST
+/// API of a target one. Indexes are not replicated; create them on the target
+/// first (e.g. with `migrate`).
+pub struct ReplicationOptions {
+    /// Base URL of the target API, e.g. `http://vectors-dr.internal:8080`.
+    pub target_url: String,
+    /// Sent as `x-api-key` when the target enforces access control.
+    pub target_api_key: Option<String>,
+    pub policy: ConflictPolicy,
+    /// Local file recording the last applied change so a restarted agent resumes.
+    pub state_file: String,
+    pub poll_interval: std::time::Duration,
+}
+
+/// Progress of the agent: the change log key applied last.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ReplicationState {
+    cursor: Option<String>,
+    applied: u64,
+}
+
+impl ReplicationState {
+    fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+        serde_json::from_slice(&data).context("Failed to parse replication state file")
+    }
+
+    /// Write-then-rename so a crash never leaves a truncated state file.
+    fn save(&self, path: &str) -> Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Target API call replaying `change`: the operation and its request body.
+fn request_for(change: &Change, policy: ConflictPolicy) -> (&'static str, Value) {
+    match &change.mutation {
+        Mutation::Put { vectors } => {
+            // Versions are assigned by the target on write.
+            let vectors: Vec<Value> = vectors
+                .iter()
+                .map(|doc| {
+                    let mut vector = doc.clone();
+                    if let Some(fields) = vector.as_object_mut() {
+                        fields.remove("version");
+                    }
+                    vector
+                })
+                .collect();
+            let mut body = json!({"vectorBucketName": change.bucket, "indexName": change.index, "vectors": vectors});
+            if policy == ConflictPolicy::KeepTarget {
+                body["condition"] = json!({"ifNotExists": true});
+            }
+            ("PutVectors", body)
+        }
+        Mutation::Delete { keys } => (
+            "DeleteVectors",
+            json!({"vectorBucketName": change.bucket, "indexName": change.index, "keys": keys}),
+        ),
+    }
+}
+
+struct Target {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl Target {
+    /// Replay `change`, returning how many keys conflicted.
+    async fn apply(&self, change: &Change, policy: ConflictPolicy) -> Result<usize> {
+        let (operation, body) = request_for(change, policy);
+        let mut request = self.http.post(format!("{}/{}", self.url.trim_end_matches('/'), operation)).json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.header("x-api-key", key);
+        }
+        let response = request.send().await.with_context(|| format!("{} to target failed", operation))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            anyhow::bail!("{} to target returned {}: {}", operation, status, message);
+        }
+        let response: Value = response.json().await.unwrap_or_default();
+        Ok(response.get("conflicts").and_then(|c| c.as_array()).map_or(0, Vec::len))
+    }
+}
+
+/// Replicate until the process is stopped. A change that fails to apply is
+/// retried every poll interval, so mutations are applied in order, at least
+/// once. Lag (`replication.lag_seconds`) is the age of the last applied
+/// change, or of the one being retried, and 0 when caught up.
+pub async fn run(source: &S3Client, opts: &ReplicationOptions) -> Result<()> {
+    let target = Target { http: reqwest::Client::new(), url: opts.target_url.clone(), api_key: opts.target_api_key.clone() };
+    let mut state = ReplicationState::load(&opts.state_file)?;
+    let metrics = get_metrics_collector();
+    tracing::info!(target = %opts.target_url, cursor = ?state.cursor, policy = ?opts.policy, "Replication agent started");
+    loop {
+        let settled = chrono::Utc::now() - chrono::Duration::seconds(SETTLE_SECS);
+        let pending = match changelog::list_after(source, state.cursor.as_deref()).await {
+            Ok(mut pending) => {
+                pending.retain(|key| changelog::logged_at(key).is_none_or(|at| at <= settled));
+                pending
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to list source changes");
+                tokio::time::sleep(opts.poll_interval).await;
+                continue;
+            }
+        };
+        metrics.track_metric("replication.pending_changes", pending.len() as f64);
+        if pending.is_empty() {
+            metrics.track_metric("replication.lag_seconds", 0.0);
+        }
+        for (i, key) in pending.iter().enumerate() {
+            let applied = match changelog::load(source, key).await {
+                Ok(change) => target.apply(&change, opts.policy).await.map(|conflicts| (change, conflicts)),
+                Err(e) => Err(e),
+            };
+            let (change, conflicts) = match applied {
+                Ok(applied) => applied,
+                Err(e) => {
+                    tracing::warn!(change = %key, error = %e, "Failed to replicate change, will retry");
+                    metrics.track_metric("replication.errors", 1.0);
+                    if let Some(at) = changelog::logged_at(key) {
+                        metrics.track_metric("replication.lag_seconds", (chrono::Utc::now() - at).num_seconds() as f64);
+                    }
+                    break;
+                }
+            };
+            if conflicts > 0 {
+                tracing::info!(change = %key, index = %change.index, conflicts, "Target kept existing vectors");
+                metrics.track_metric("replication.conflicts", conflicts as f64);
+            }
+            let lag = (chrono::Utc::now() - change.at).num_milliseconds() as f64 / 1000.0;
+            metrics.track_metric("replication.lag_seconds", lag);
+            metrics.track_metric("replication.pending_changes", (pending.len() - i - 1) as f64);
+            state.cursor = Some(key.clone());
+            state.applied += 1;
+            state.save(&opts.state_file)?;
+            tracing::debug!(change = %key, index = %change.index, lag_seconds = lag, "Replicated change");
+        }
+        if !pending.is_empty() {
+            tracing::info!(applied = state.applied, cursor = ?state.cursor, "Replication pass finished");
+        }
+        tokio::time::sleep(opts.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_for_change() {
+        let change = Change {
+            at: chrono::Utc::now(),
+            bucket: "b".to_string(),
+            index: "docs".to_string(),
+            mutation: Mutation::Put {
+                vectors: vec![json!({"key": "a", "data": {"float32": [1.0]}, "metadata": {}, "version": 7})],
+            },
+        };
+        let (operation, body) = request_for(&change, ConflictPolicy::KeepTarget);
+        assert_eq!(operation, "PutVectors");
+        assert!(body["vectors"][0].get("version").is_none());
+        assert_eq!(body["condition"], json!({"ifNotExists": true}));
+        assert!(request_for(&change, ConflictPolicy::SourceWins).1.get("condition").is_none());
+
+        let delete = Change { mutation: Mutation::Delete { keys: vec!["a".to_string()] }, ..change };
+        assert_eq!(request_for(&delete, ConflictPolicy::KeepTarget).0, "DeleteVectors");
+    }
+}
@@ -0,0 +1,70 @@
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use tracing::Instrument;
+
+/// Header carrying the request id, in both directions. Incoming values are kept
+/// so a query forwarded between replicas logs under the caller's id.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// boto3 reports this header as `ResponseMetadata.RequestId`.
+const AWS_REQUEST_ID_HEADER: &str = "x-amzn-requestid";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being served by the current task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn incoming_id(req: &Request) -> Option<String> {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map(str::to_string)
+}
+
+/// Assign every request an id, attach it to all log lines emitted while serving
+/// it (the JSON log format includes span fields) and echo it in the response.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let request_id = incoming_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path()
+    );
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span))
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+        response.headers_mut().insert(AWS_REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// `VEC_LOG_FORMAT=text` switches to human-readable logs; JSON is the default.
+pub fn init_logging() {
+    let builder = tracing_subscriber::fmt().with_max_level(tracing::Level::INFO);
+    match std::env::var("VEC_LOG_FORMAT").as_deref() {
+        Ok("text") => builder.init(),
+        _ => builder.json().with_current_span(true).with_span_list(false).init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    #[test]
+    fn test_incoming_request_id() {
+        let req = Request::builder().header(REQUEST_ID_HEADER, "abc-123").body(Body::empty()).unwrap();
+        assert_eq!(incoming_id(&req).as_deref(), Some("abc-123"));
+
+        let req = Request::builder().header(REQUEST_ID_HEADER, "x".repeat(200)).body(Body::empty()).unwrap();
+        assert_eq!(incoming_id(&req), None);
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Layout of `manifest.json` this build writes.
+pub const MANIFEST_SCHEMA_VERSION: u32 = 1;
+/// Layout of `config.json` this build writes.
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+/// Documents without it predate versioning and read as version 0.
+const VERSION_KEY: &str = "schema_version";
+
+/// Upgrades a document from one version to the next, in place.
+type Migration = fn(&mut Value);
+
+/// `MANIFEST_MIGRATIONS[v]` upgrades a version `v` manifest to `v + 1`; a
+/// layout change bumps the version and appends its migration here.
+const MANIFEST_MIGRATIONS: [Migration; MANIFEST_SCHEMA_VERSION as usize] = [manifest_v1];
+const CONFIG_MIGRATIONS: [Migration; CONFIG_SCHEMA_VERSION as usize] = [config_v1];
+
+/// Version 1 names every shard's algorithm; shards written before it was
+/// recorded are IVF-PQ.
+fn manifest_v1(manifest: &mut Value) {
+    for shard in manifest.get_mut("shards").and_then(Value::as_array_mut).into_iter().flatten() {
+        if shard.get("algorithm").and_then(Value::as_str).is_none_or(str::is_empty) {
+            shard["algorithm"] = json!("ivfpq");
+        }
+    }
+}
+
+/// Version 1 records whether raw vectors are stored; indexes created before
+/// it was configurable always stored them.
+fn config_v1(config: &mut Value) {
+    if config.get("store_raw_vectors").is_none() {
+        config["store_raw_vectors"] = json!(true);
+    }
+}
+
+fn upgrade(document: &mut Value, kind: &str, current: u32, migrations: &[Migration]) -> Result<u32> {
+    let object = document.as_object().with_context(|| format!("{} is not a JSON object", kind))?;
+    let version = match object.get(VERSION_KEY) {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .with_context(|| format!("{} has an invalid {}: {}", kind, VERSION_KEY, version))?,
+    };
+    if version > current {
+        return Err(anyhow::anyhow!(
+            "{} has schema version {}; this build reads up to {}",
+            kind,
+            version,
+            current
+        ));
+    }
+    for migrate in &migrations[version as usize..] {
+        migrate(document);
+    }
+    document[VERSION_KEY] = json!(current);
+    Ok(version)
+}
+
+/// Upgrade a parsed `manifest.json` to the current layout; returns the
+/// version it was at. Newer versions are refused rather than half understood.
+pub fn upgrade_manifest(manifest: &mut Value) -> Result<u32> {
+    upgrade(manifest, "Manifest", MANIFEST_SCHEMA_VERSION, &MANIFEST_MIGRATIONS)
+}
+
+/// Upgrade a parsed `config.json` to the current layout; returns the version
+/// it was at.
+pub fn upgrade_config(config: &mut Value) -> Result<u32> {
+    upgrade(config, "Index config", CONFIG_SCHEMA_VERSION, &CONFIG_MIGRATIONS)
+}
+
+/// Versions an index's documents were at before [`rewrite`]; `None` for one
+/// the index doesn't have.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaUpgrade {
+    pub manifest_version: Option<u32>,
+    pub config_version: Option<u32>,
+    /// Whether anything was written.
+    pub rewritten: bool,
+}
+
+/// Rewrite the manifest and config of `index` in the current layout, so
+/// readers no longer migrate them on every load. Documents already current
+/// are left alone, and nothing is written on a `dry_run`. Fields this build
+/// doesn't know are kept.
+pub async fn rewrite(s3: &S3Client, index: &str, dry_run: bool) -> Result<SchemaUpgrade> {
+    let manifest_version = rewrite_document(s3, &format!("indexes/{}/manifest.json", index), upgrade_manifest, dry_run).await?;
+    let config_version = rewrite_document(s3, &format!("indexes/{}/config.json", index), upgrade_config, dry_run).await?;
+    Ok(SchemaUpgrade {
+        manifest_version,
+        config_version,
+        rewritten: !dry_run
+            && (manifest_version.is_some_and(|v| v < MANIFEST_SCHEMA_VERSION)
+                || config_version.is_some_and(|v| v < CONFIG_SCHEMA_VERSION)),
+    })
+}
+
+/// Upgrade the document at `key` and write it back if that changed its
+/// version; returns the version it was at, `None` if there is none.
+async fn rewrite_document(
+    s3: &S3Client,
+    key: &str,
+    upgrade: fn(&mut Value) -> Result<u32>,
+    dry_run: bool,
+) -> Result<Option<u32>> {
+    let Ok(data) = s3.get_object(key).await else { return Ok(None) };
+    let mut document: Value = serde_json::from_slice(&data).with_context(|| format!("Failed to parse {}", key))?;
+    let version = upgrade(&mut document)?;
+    if document[VERSION_KEY] != json!(version) && !dry_run {
+        s3.put_object(key, serde_json::to_vec(&document)?.into()).await?;
+        tracing::info!(key, from = version, "Rewrote in the current schema");
+    }
+    Ok(Some(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_documents_upgrade_and_newer_ones_are_refused() {
+        let mut manifest = json!({
+            "index_name": "idx",
+            "shards": [{"shard_id": "a"}, {"shard_id": "b", "algorithm": "hnsw_flat"}],
+            "custom": 1,
+        });
+        assert_eq!(upgrade_manifest(&mut manifest).unwrap(), 0);
+        assert_eq!(manifest["schema_version"], json!(MANIFEST_SCHEMA_VERSION));
+        assert_eq!(manifest["shards"][0]["algorithm"], "ivfpq");
+        assert_eq!(manifest["shards"][1]["algorithm"], "hnsw_flat");
+        assert_eq!(manifest["custom"], 1);
+
+        // Upgrading a current document changes nothing.
+        let upgraded = manifest.clone();
+        assert_eq!(upgrade_manifest(&mut manifest).unwrap(), MANIFEST_SCHEMA_VERSION);
+        assert_eq!(manifest, upgraded);
+
+        let mut config = json!({"name": "idx"});
+        upgrade_config(&mut config).unwrap();
+        assert_eq!(config["store_raw_vectors"], true);
+        let mut kept = json!({"name": "idx", "store_raw_vectors": false});
+        upgrade_config(&mut kept).unwrap();
+        assert_eq!(kept["store_raw_vectors"], false);
+
+        let mut newer = json!({"schema_version": MANIFEST_SCHEMA_VERSION + 1});
+        assert!(upgrade_manifest(&mut newer).unwrap_err().to_string().contains("reads up to"));
+        assert!(upgrade_config(&mut json!({"schema_version": "one"})).is_err());
+        assert!(upgrade_config(&mut json!([])).is_err());
+    }
+}
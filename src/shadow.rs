@@ -0,0 +1,429 @@
+use crate::export::ExportRow;
+use crate::faiss_utils::{
+    build_flat_index, build_hnsw_flat_index, build_ivfpq_index, canonical_metric, ivfpq_supports, reservoir_sample,
+    search_index, training_seed, SplitMix64, DEFAULT_NPROBE,
+};
+use crate::indexer::DEFAULT_HNSW_THRESHOLD;
+use crate::jobs::CancelToken;
+use crate::minio::S3Client;
+use crate::model::CreateIndex;
+use crate::quantizer::QuantizerInfo;
+use crate::raw_vectors::exact_score;
+use crate::reduction;
+use crate::tempfiles::TempFile;
+use crate::tuning::{self, DataProfile, IndexParameters, IvfpqSettings, Thresholds};
+use anyhow::{Context, Result};
+use faiss::index::IndexImpl;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Instant;
+
+const MAX_QUERIES: usize = 1_000;
+const MAX_TOP_K: usize = 100;
+
+/// Parameters a shadow build overrides; anything unset keeps what the index
+/// is served with.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowParameters {
+    /// `auto`, `flat`, `ivfpq`, `hnsw_flat` or `hybrid`, resolved as the indexer does.
+    pub algorithm: Option<String>,
+    pub nlist: Option<usize>,
+    /// PQ subquantizers; must divide the indexed dimension.
+    pub m: Option<usize>,
+    pub nbits: Option<usize>,
+    pub nprobe: Option<usize>,
+    /// HNSW neighbours per node.
+    pub hnsw_m: Option<usize>,
+}
+
+/// Size of the benchmark run against both builds.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BenchmarkOptions {
+    /// Stored vectors reused as queries.
+    pub queries: usize,
+    pub top_k: usize,
+    /// Vectors sampled from the index to build from; bounds the job's memory.
+    pub max_vectors: usize,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        BenchmarkOptions { queries: 100, top_k: 10, max_vectors: 200_000 }
+    }
+}
+
+impl BenchmarkOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.queries == 0 || self.queries > MAX_QUERIES {
+            return Err(format!("queries must be between 1 and {}, got {}", MAX_QUERIES, self.queries));
+        }
+        if self.top_k == 0 || self.top_k > MAX_TOP_K {
+            return Err(format!("topK must be between 1 and {}, got {}", MAX_TOP_K, self.top_k));
+        }
+        if self.max_vectors == 0 {
+            return Err("maxVectors must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A fully resolved Faiss build and how it is searched.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum BuildParameters {
+    Flat,
+    HnswFlat { m: usize },
+    Ivfpq { nlist: usize, m: usize, nbits: usize, nprobe: usize },
+}
+
+/// The data a build indexes and how the index is served.
+pub struct BuildContext<'a> {
+    pub profile: DataProfile,
+    pub metric: &'a str,
+    pub thresholds: Thresholds,
+    pub nprobe: usize,
+    /// IVF-PQ parameters the index fixes.
+    pub ivfpq: Option<IvfpqSettings>,
+    /// The index's shared quantizer, whose parameters its IVF-PQ shards use.
+    pub quantizer: Option<&'a QuantizerInfo>,
+}
+
+impl BuildContext<'_> {
+    /// Parameters the indexer picks for this data under `algorithm`.
+    pub fn parameters(&self, algorithm: Option<&str>) -> BuildParameters {
+        let mut recommendation = tuning::recommend(&self.profile, self.metric, algorithm, self.thresholds);
+        if let Some(settings) = &self.ivfpq {
+            recommendation.pin(settings);
+        }
+        match recommendation.parameters {
+            IndexParameters::Flat => BuildParameters::Flat,
+            IndexParameters::HnswFlat { m } => BuildParameters::HnswFlat { m },
+            IndexParameters::Ivfpq { nlist, m, nbits } => {
+                let (nlist, m, nbits) = self.quantizer.map_or((nlist, m, nbits), |q| (q.nlist, q.m, q.nbits));
+                BuildParameters::Ivfpq { nlist, m, nbits, nprobe: self.nprobe }
+            }
+        }
+    }
+}
+
+impl ShadowParameters {
+    /// Checks that don't depend on the data; [`ShadowParameters::resolve`] does the rest.
+    pub fn validate(&self, metric: &str) -> Result<(), String> {
+        match self.algorithm.as_deref() {
+            None | Some("auto" | "flat" | "hnsw_flat") => {}
+            Some("ivfpq" | "hybrid") if !ivfpq_supports(metric) => {
+                return Err(format!("The {} metric needs hnsw_flat, flat or auto", metric));
+            }
+            Some("ivfpq" | "hybrid") => {}
+            Some(other) => return Err(format!("Unsupported algorithm: {}", other)),
+        }
+        if [self.nlist, self.m, self.nprobe, self.hnsw_m].contains(&Some(0)) {
+            return Err("nlist, m, nprobe and hnswM must be at least 1".to_string());
+        }
+        if self.nbits.is_some_and(|nbits| !(1..=16).contains(&nbits)) {
+            return Err("nbits must be between 1 and 16".to_string());
+        }
+        Ok(())
+    }
+
+    /// The build these overrides describe, on top of `context.parameters(serving)`.
+    pub fn resolve(&self, context: &BuildContext, serving: Option<&str>) -> Result<BuildParameters, String> {
+        let mut parameters = context.parameters(self.algorithm.as_deref().or(serving));
+        match &mut parameters {
+            BuildParameters::Ivfpq { nlist, m, nbits, nprobe } => {
+                *nlist = self.nlist.unwrap_or(*nlist);
+                *m = self.m.unwrap_or(*m);
+                *nbits = self.nbits.unwrap_or(*nbits);
+                *nprobe = self.nprobe.unwrap_or(*nprobe);
+                let (dim, count) = (context.profile.dimension, context.profile.vectors);
+                if dim % *m != 0 {
+                    return Err(format!("m must divide the indexed dimension {}, got {}", dim, m));
+                }
+                if *nlist > count {
+                    return Err(format!("nlist {} exceeds the {} vectors sampled", nlist, count));
+                }
+            }
+            BuildParameters::HnswFlat { m } => *m = self.hnsw_m.unwrap_or(*m),
+            BuildParameters::Flat => {}
+        }
+        Ok(parameters)
+    }
+}
+
+/// How one build fared on the benchmark.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildOutcome {
+    pub parameters: BuildParameters,
+    pub build_seconds: f64,
+    pub index_bytes: u64,
+    /// Mean recall@k against exact search over the full-dimension vectors.
+    pub recall: f64,
+    pub mean_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowReport {
+    pub index_name: String,
+    /// Vectors both builds hold: the index's, or a sample of them.
+    pub vectors: usize,
+    pub indexed_dimension: usize,
+    pub queries: usize,
+    pub top_k: usize,
+    /// The parameters the index is served with.
+    pub baseline: BuildOutcome,
+    pub shadow: BuildOutcome,
+    /// Shadow recall minus baseline recall.
+    pub recall_delta: f64,
+    /// Shadow mean latency over baseline mean latency.
+    pub latency_ratio: f64,
+}
+
+/// Build the vectors of `index` in `bucket` twice, as served and with
+/// `overrides`, and benchmark both against exact search. Builds live in
+/// memory and temp files only; nothing the index serves from is touched.
+pub async fn shadow_build(
+    s3: &S3Client,
+    bucket: &str,
+    index: &str,
+    overrides: ShadowParameters,
+    options: BenchmarkOptions,
+    cancel: &CancelToken,
+) -> Result<ShadowReport> {
+    let data = s3.get_object(&format!("indexes/{}/config.json", index)).await
+        .with_context(|| format!("Index not found: {}", index))?;
+    let config = CreateIndex::from_slice(&data).context("Failed to parse index config")?;
+    let metric = canonical_metric(&config.metric).with_context(|| format!("Unsupported metric: {}", config.metric))?;
+    let dim = config.dim as usize;
+
+    let mut keys = s3.list_bucket_objects(bucket, &format!("{}/vectors/", index)).await?;
+    keys.sort();
+    let mut rng = SplitMix64::new(training_seed());
+    let mut vectors = Vec::new();
+    for row in reservoir_sample(keys.len(), options.max_vectors, &mut rng) {
+        cancel.check()?;
+        let Ok(data) = s3.get_bucket_object(bucket, &keys[row]).await else { continue };
+        match serde_json::from_slice::<Value>(&data).ok().as_ref().and_then(ExportRow::from_record) {
+            Some(row) if row.embedding.len() == dim => vectors.extend_from_slice(&row.embedding),
+            _ => tracing::warn!(key = %keys[row], "Skipping unreadable vector record in shadow build"),
+        }
+    }
+    let count = vectors.len() / dim.max(1);
+    if count == 0 {
+        return Err(anyhow::anyhow!("Index {} has no vectors to build from", index));
+    }
+
+    // Shards index projections when the index reduces dimensions, so both
+    // builds do too; recall is still measured against the full vectors.
+    let projection = match (config.reduction, reduction::load_info(s3, index).await) {
+        (Some(_), Some(info)) if info.dim_in as usize == dim => Some(reduction::load(s3, index, &info.file, dim).await?),
+        _ => None,
+    };
+    let projected = projection.as_ref().map(|projection| projection.apply(&vectors));
+    let indexed_dim = projection.as_ref().map_or(dim, |projection| projection.dim_out());
+    let quantizer = crate::quantizer::load_info(s3, index)
+        .await
+        .filter(|info| info.dim as usize == indexed_dim && canonical_metric(&info.metric) == Some(metric));
+
+    let profile = DataProfile::measure(projected.as_deref().unwrap_or(&vectors), indexed_dim, keys.len(), &mut rng);
+    let context = BuildContext {
+        profile,
+        metric,
+        thresholds: Thresholds {
+            flat: config.flat_threshold.unwrap_or(tuning::DEFAULT_FLAT_THRESHOLD),
+            hnsw: config.hnsw_threshold.unwrap_or(DEFAULT_HNSW_THRESHOLD),
+        },
+        nprobe: config.default_nprobe.map_or(DEFAULT_NPROBE, |n| n as usize),
+        ivfpq: config.ivfpq,
+        quantizer: quantizer.as_ref(),
+    };
+    let baseline = context.parameters(config.algorithm.as_deref());
+    let shadow = overrides.resolve(&context, config.algorithm.as_deref()).map_err(anyhow::Error::msg)?;
+    let queries = reservoir_sample(count, options.queries, &mut rng);
+    let query_count = queries.len();
+    tracing::info!(index, vectors = count, ?baseline, ?shadow, "Starting shadow build");
+    crate::jobs::report_progress(json!({"phase": "benchmarking", "vectors": count, "buildsDone": 0, "builds": 2}));
+
+    let cancel = cancel.clone();
+    let reporter = crate::jobs::reporter();
+    let top_k = options.top_k;
+    let (baseline, shadow) = crate::governor::run(move || -> Result<(BuildOutcome, BuildOutcome)> {
+        let truth: Vec<Vec<i64>> = queries
+            .iter()
+            .map(|&row| exact_neighbours(metric, &vectors, dim, &vectors[row * dim..(row + 1) * dim], top_k))
+            .collect();
+        let indexed = projected.as_deref().unwrap_or(&vectors);
+        cancel.check()?;
+        let baseline = benchmark(baseline, metric, indexed, indexed_dim, &queries, &truth, top_k)?;
+        if let Some(reporter) = &reporter {
+            reporter.partial(json!({ "baseline": &baseline }));
+            reporter.progress(json!({"phase": "benchmarking", "vectors": count, "buildsDone": 1, "builds": 2}));
+        }
+        cancel.check()?;
+        let shadow = benchmark(shadow, metric, indexed, indexed_dim, &queries, &truth, top_k)?;
+        Ok((baseline, shadow))
+    })
+    .await?;
+
+    let report = ShadowReport {
+        index_name: index.to_string(),
+        vectors: count,
+        indexed_dimension: indexed_dim,
+        queries: query_count,
+        top_k,
+        recall_delta: shadow.recall - baseline.recall,
+        latency_ratio: shadow.mean_latency_ms / baseline.mean_latency_ms.max(f64::EPSILON),
+        baseline,
+        shadow,
+    };
+    tracing::info!(
+        index,
+        baseline_recall = report.baseline.recall,
+        shadow_recall = report.shadow.recall,
+        latency_ratio = report.latency_ratio,
+        "Finished shadow build"
+    );
+    Ok(report)
+}
+
+/// Build `parameters` over the row-major `vectors` (ids are row numbers)
+/// and time searches for the `queries` rows.
+fn benchmark(
+    parameters: BuildParameters,
+    metric: &str,
+    vectors: &[f32],
+    dim: usize,
+    queries: &[usize],
+    truth: &[Vec<i64>],
+    k: usize,
+) -> Result<BuildOutcome> {
+    let ids: Vec<i64> = (0..(vectors.len() / dim) as i64).collect();
+    let started = Instant::now();
+    let mut index: IndexImpl = match parameters {
+        BuildParameters::Flat => build_flat_index(dim, metric, vectors, &ids)?,
+        BuildParameters::HnswFlat { m } => build_hnsw_flat_index(dim, metric, vectors, &ids, m)?,
+        BuildParameters::Ivfpq { nlist, m, nbits, .. } => build_ivfpq_index(dim, nlist, m, nbits, metric, vectors, &ids)?,
+    };
+    let build_seconds = started.elapsed().as_secs_f64();
+
+    let local = TempFile::new("shadow", ".faiss");
+    faiss::write_index(&index, local.path())?;
+    let index_bytes = std::fs::metadata(local.path())?.len();
+    drop(local);
+
+    let (algorithm, nprobe) = match parameters {
+        BuildParameters::Flat => ("flat", 0),
+        BuildParameters::HnswFlat { .. } => ("hnsw_flat", 0),
+        BuildParameters::Ivfpq { nprobe, .. } => ("ivfpq", nprobe),
+    };
+    let mut latencies = Vec::with_capacity(queries.len());
+    let mut recall = 0.0;
+    for (&row, truth) in queries.iter().zip(truth) {
+        let query = &vectors[row * dim..(row + 1) * dim];
+        let started = Instant::now();
+        let (_, found) = search_index(&mut index, algorithm, query, k, nprobe)?;
+        latencies.push(started.elapsed().as_secs_f64() * 1000.0);
+        recall += recall_at_k(truth, &found);
+    }
+    latencies.sort_by(f64::total_cmp);
+    let searched = queries.len().max(1) as f64;
+    Ok(BuildOutcome {
+        parameters,
+        build_seconds,
+        index_bytes,
+        recall: recall / searched,
+        mean_latency_ms: latencies.iter().sum::<f64>() / searched,
+        p99_latency_ms: percentile(&latencies, 0.99),
+    })
+}
+
+/// Rows of the `k` vectors scoring best against `query`.
+fn exact_neighbours(metric: &str, vectors: &[f32], dim: usize, query: &[f32], k: usize) -> Vec<i64> {
+    let mut scored: Vec<(f32, i64)> = vectors
+        .chunks_exact(dim)
+        .enumerate()
+        .map(|(row, vector)| (exact_score(metric, query, vector), row as i64))
+        .collect();
+    let k = k.min(scored.len());
+    if k < scored.len() {
+        scored.select_nth_unstable_by(k, |a, b| b.0.total_cmp(&a.0));
+        scored.truncate(k);
+    }
+    scored.into_iter().map(|(_, row)| row).collect()
+}
+
+/// Share of the true neighbours found.
+fn recall_at_k(truth: &[i64], found: &[i64]) -> f64 {
+    if truth.is_empty() {
+        return 1.0;
+    }
+    found.iter().filter(|id| truth.contains(id)).count() as f64 / truth.len() as f64
+}
+
+/// Nearest-rank percentile of ascending `sorted` values; 0 when empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (sorted.len() as f64 * p).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_resolve_over_serving_parameters() {
+        let profile = DataProfile { vectors: 10_000, index_vectors: 200_000, dimension: 64, effective_dimension: 64.0 };
+        let context = BuildContext {
+            profile,
+            metric: "cosine",
+            thresholds: Thresholds { flat: 10_000, hnsw: 100_000 },
+            nprobe: 8,
+            ivfpq: None,
+            quantizer: None,
+        };
+        let serving = context.parameters(None);
+        assert!(matches!(serving, BuildParameters::Ivfpq { nprobe: 8, .. }));
+        assert_eq!(ShadowParameters::default().resolve(&context, None), Ok(serving));
+
+        let overrides = ShadowParameters { nlist: Some(64), m: Some(16), nprobe: Some(4), ..Default::default() };
+        assert!(matches!(
+            overrides.resolve(&context, None),
+            Ok(BuildParameters::Ivfpq { nlist: 64, m: 16, nprobe: 4, .. })
+        ));
+        assert!(ShadowParameters { m: Some(7), ..Default::default() }.resolve(&context, None).is_err());
+        assert!(ShadowParameters { nlist: Some(20_000), ..Default::default() }.resolve(&context, None).is_err());
+
+        let hnsw = ShadowParameters { algorithm: Some("hnsw_flat".to_string()), hnsw_m: Some(48), ..Default::default() };
+        assert_eq!(hnsw.resolve(&context, None), Ok(BuildParameters::HnswFlat { m: 48 }));
+        let flat = ShadowParameters { algorithm: Some("flat".to_string()), ..Default::default() };
+        assert_eq!(flat.resolve(&context, Some("ivfpq")), Ok(BuildParameters::Flat));
+
+        let manhattan = ShadowParameters { algorithm: Some("ivfpq".to_string()), ..Default::default() };
+        assert!(manhattan.validate("manhattan").is_err());
+        assert!(manhattan.validate("euclidean").is_ok());
+        assert!(ShadowParameters { nbits: Some(0), ..Default::default() }.validate("cosine").is_err());
+        assert_eq!(
+            serde_json::to_value(BuildParameters::HnswFlat { m: 32 }).unwrap(),
+            serde_json::json!({"algorithm": "hnsw_flat", "m": 32})
+        );
+    }
+
+    #[test]
+    fn test_recall_against_exact_neighbours() {
+        let vectors = [0.0, 0.0, 1.0, 0.0, 5.0, 5.0, 0.9, 0.1];
+        let mut nearest = exact_neighbours("euclidean", &vectors, 2, &[1.0, 0.0], 2);
+        nearest.sort();
+        assert_eq!(nearest, vec![1, 3]);
+        assert_eq!(recall_at_k(&nearest, &[3, 2]), 0.5);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.99), 4.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.0);
+        assert!(BenchmarkOptions { top_k: 0, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::model::VectorRecord;
+use crate::tempfiles::TempFile;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+
+/// Memory the indexer may hold loaded vectors in, from `VEC_INDEXER_MEMORY_MB`
+/// (default 1024). Shard builds need roughly twice the chunk they are given.
+pub fn memory_budget_from_env() -> usize {
+    let mb = std::env::var("VEC_INDEXER_MEMORY_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1024);
+    mb.max(1) * 1024 * 1024
+}
+
+/// Approximate memory a loaded record takes.
+fn record_bytes(record: &VectorRecord) -> usize {
+    record.embedding.len() * 4
+        + record.id.len()
+        + record.meta.to_string().len()
+        + record.text.as_ref().map_or(0, String::len)
+}
+
+/// Records on their way to shard builds: kept in memory up to a budget and
+/// spilled to a local JSONL file past it, then handed out in chunks that each
+/// fit the budget, in the order they were pushed.
+pub struct Spool {
+    budget: usize,
+    memory: Vec<VectorRecord>,
+    memory_bytes: usize,
+    spill: Option<(TempFile, BufWriter<File>)>,
+    len: usize,
+}
+
+impl Spool {
+    pub fn new(budget: usize) -> Self {
+        Spool { budget, memory: Vec::new(), memory_bytes: 0, spill: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Records written to disk so far.
+    pub fn spilled(&self) -> usize {
+        self.len - self.memory.len()
+    }
+
+    pub fn push(&mut self, record: VectorRecord) -> Result<()> {
+        self.len += 1;
+        let bytes = record_bytes(&record);
+        // Once spilling, everything goes to disk so the order is kept.
+        if self.spill.is_none() && self.memory_bytes + bytes <= self.budget {
+            self.memory_bytes += bytes;
+            self.memory.push(record);
+            return Ok(());
+        }
+        let (_, writer) = match &mut self.spill {
+            Some(spill) => spill,
+            None => {
+                let file = TempFile::new("spool", ".jsonl");
+                let writer = BufWriter::new(File::create(file.path()).context("Failed to create spill file")?);
+                self.spill.insert((file, writer))
+            }
+        };
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Stop taking records and start handing them out.
+    pub fn finish(self) -> Result<Chunks> {
+        let spill = match self.spill {
+            Some((file, mut writer)) => {
+                writer.flush()?;
+                drop(writer);
+                let lines = BufReader::new(File::open(file.path())?).lines();
+                Some((file, lines))
+            }
+            None => None,
+        };
+        Ok(Chunks { budget: self.budget, memory: Some(self.memory), spill })
+    }
+}
+
+/// The records of a finished [`Spool`], a budget's worth at a time.
+pub struct Chunks {
+    budget: usize,
+    memory: Option<Vec<VectorRecord>>,
+    spill: Option<(TempFile, Lines<BufReader<File>>)>,
+}
+
+impl Chunks {
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<VectorRecord>>> {
+        if let Some(memory) = self.memory.take().filter(|m| !m.is_empty()) {
+            return Ok(Some(memory));
+        }
+        let Some((_, lines)) = &mut self.spill else {
+            return Ok(None);
+        };
+        let mut chunk = Vec::new();
+        let mut bytes = 0;
+        while bytes < self.budget {
+            let Some(line) = lines.next() else { break };
+            let record: VectorRecord = serde_json::from_str(&line?).context("Failed to read spill file")?;
+            bytes += record_bytes(&record);
+            chunk.push(record);
+        }
+        Ok((!chunk.is_empty()).then_some(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: usize) -> VectorRecord {
+        VectorRecord {
+            id: format!("v{}", id),
+            embedding: vec![id as f32; 4],
+            meta: serde_json::json!({}),
+            created_at: chrono::Utc::now(),
+            text: None,
+        }
+    }
+
+    #[test]
+    fn test_spool_spills_past_budget_and_keeps_order() {
+        // Each record is 16 + 2 + 2 bytes, so three fit a 64 byte budget.
+        let mut spool = Spool::new(64);
+        for id in 0..10 {
+            spool.push(record(id)).unwrap();
+        }
+        assert_eq!((spool.len(), spool.spilled()), (10, 7));
+
+        let mut chunks = spool.finish().unwrap();
+        let mut sizes = Vec::new();
+        let mut ids = Vec::new();
+        while let Some(chunk) = chunks.next_chunk().unwrap() {
+            sizes.push(chunk.len());
+            ids.extend(chunk.into_iter().map(|r| r.id));
+        }
+        assert_eq!(sizes, vec![3, 4, 3]);
+        assert_eq!(ids, (0..10).map(|id| format!("v{}", id)).collect::<Vec<_>>());
+    }
+}
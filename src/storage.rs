@@ -0,0 +1,239 @@
+//! Non-S3 object storage backends (Google Cloud Storage, Azure Blob).
+//!
+//! `S3Client` stays the handle passed around the code base; when
+//! `VEC_STORAGE_BACKEND` selects `gcs` or `azure` it forwards every object
+//! operation to a [`BlobStore`] instead of the AWS SDK. Vector buckets map to
+//! GCS buckets or Azure containers, which must already exist. Tests use the
+//! same path over an in-memory store (`S3Client::in_memory`).
+
+use anyhow::Result;
+
+/// Storage backend selected via `VEC_STORAGE_BACKEND` (`s3`, `gcs`, `azure`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    S3,
+    Gcs,
+    Azure,
+    /// `object_store`'s in-memory store; never selected by the environment.
+    #[cfg(test)]
+    Memory,
+}
+
+impl StorageBackend {
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        match var("VEC_STORAGE_BACKEND").as_deref() {
+            None | Some("") | Some("s3") | Some("minio") => Ok(StorageBackend::S3),
+            Some("gcs") => Ok(StorageBackend::Gcs),
+            Some("azure") => Ok(StorageBackend::Azure),
+            Some(other) => Err(anyhow::anyhow!(
+                "Unknown VEC_STORAGE_BACKEND '{}', expected s3, gcs or azure",
+                other
+            )),
+        }
+    }
+}
+
+/// Builds without the `gcs` and `azure` features have no `BlobStore`; `S3Client`
+/// leaves out its blob field there and refuses those backends at startup.
+#[cfg(any(feature = "gcs", feature = "azure", test))]
+pub use blob::BlobStore;
+
+#[cfg(any(feature = "gcs", feature = "azure", test))]
+mod blob {
+    use super::*;
+    use anyhow::Context;
+    use bytes::Bytes;
+    use chrono::{DateTime, Utc};
+    use futures::TryStreamExt;
+    use object_store::{path::Path, ObjectStore};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncWriteExt;
+
+    /// GCS / Azure object access through the `object_store` crate. Credentials come
+    /// from the provider's standard environment variables (`GOOGLE_SERVICE_ACCOUNT`,
+    /// `AZURE_STORAGE_ACCOUNT_NAME`, `AZURE_STORAGE_ACCOUNT_KEY`, ...).
+    pub struct BlobStore {
+        backend: StorageBackend,
+        stores: Mutex<HashMap<String, Arc<dyn ObjectStore>>>,
+    }
+
+    impl BlobStore {
+        pub fn new(backend: StorageBackend) -> Result<Self> {
+            match backend {
+                #[cfg(feature = "gcs")]
+                StorageBackend::Gcs => {}
+                #[cfg(feature = "azure")]
+                StorageBackend::Azure => {}
+                #[cfg(test)]
+                StorageBackend::Memory => {}
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Storage backend {:?} is not enabled in this build",
+                        other
+                    ))
+                }
+            }
+            Ok(Self {
+                backend,
+                stores: Mutex::new(HashMap::new()),
+            })
+        }
+
+        /// One `ObjectStore` per bucket/container, built lazily and reused.
+        fn store(&self, bucket: &str) -> Result<Arc<dyn ObjectStore>> {
+            let mut stores = self.stores.lock().unwrap();
+            if let Some(store) = stores.get(bucket) {
+                return Ok(store.clone());
+            }
+            let store: Arc<dyn ObjectStore> = match self.backend {
+                #[cfg(feature = "gcs")]
+                StorageBackend::Gcs => Arc::new(
+                    object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()
+                        .with_context(|| format!("Failed to configure GCS bucket {}", bucket))?,
+                ),
+                #[cfg(feature = "azure")]
+                StorageBackend::Azure => Arc::new(
+                    object_store::azure::MicrosoftAzureBuilder::from_env()
+                        .with_container_name(bucket)
+                        .build()
+                        .with_context(|| format!("Failed to configure Azure container {}", bucket))?,
+                ),
+                #[cfg(test)]
+                StorageBackend::Memory => Arc::new(object_store::memory::InMemory::new()),
+                other => return Err(anyhow::anyhow!("Storage backend {:?} is not enabled", other)),
+            };
+            stores.insert(bucket.to_string(), store.clone());
+            Ok(store)
+        }
+
+        pub async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<()> {
+            self.store(bucket)?
+                .put(&Path::from(key), data.into())
+                .await
+                .with_context(|| format!("Failed to put object {}", key))?;
+            Ok(())
+        }
+
+        pub async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+            self.store(bucket)?
+                .get(&Path::from(key))
+                .await
+                .context("Failed to get object")?
+                .bytes()
+                .await
+                .context("Failed to read object body")
+        }
+
+        pub async fn get_to_file(&self, bucket: &str, key: &str, file_path: &str) -> Result<u64> {
+            let mut stream = self.store(bucket)?
+                .get(&Path::from(key))
+                .await
+                .context("Failed to get object")?
+                .into_stream();
+            let mut file = tokio::fs::File::create(file_path).await
+                .with_context(|| format!("Failed to create file {}", file_path))?;
+
+            let copied: Result<u64> = async {
+                let mut written = 0u64;
+                while let Some(chunk) = stream.try_next().await.context("Failed to read object body")? {
+                    file.write_all(&chunk).await.context("Failed to write object to file")?;
+                    written += chunk.len() as u64;
+                }
+                file.flush().await?;
+                Ok(written)
+            }
+            .await;
+
+            if copied.is_err() {
+                let _ = tokio::fs::remove_file(file_path).await;
+            }
+            copied
+        }
+
+        pub async fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<String>> {
+            Ok(self.list_modified(bucket, prefix).await?.into_iter().map(|(key, _)| key).collect())
+        }
+
+        pub async fn list_modified(&self, bucket: &str, prefix: &str) -> Result<Vec<(String, DateTime<Utc>)>> {
+            // object_store prefixes match whole path segments, while S3 prefixes are
+            // plain string prefixes; list the enclosing directory and filter.
+            let dir = prefix.rsplit_once('/').map(|(dir, _)| Path::from(dir));
+            let objects: Vec<_> = self.store(bucket)?
+                .list(dir.as_ref())
+                .try_collect()
+                .await
+                .context("Failed to list objects")?;
+            Ok(objects
+                .into_iter()
+                .map(|meta| (meta.location.to_string(), meta.last_modified))
+                .filter(|(key, _)| key.starts_with(prefix))
+                .collect())
+        }
+
+        pub async fn list_after(&self, bucket: &str, prefix: &str, start_after: Option<&str>, limit: usize) -> Result<Vec<String>> {
+            let mut keys: Vec<String> = self
+                .list(bucket, prefix)
+                .await?
+                .into_iter()
+                .filter(|key| start_after.is_none_or(|after| key.as_str() > after))
+                .collect();
+            keys.sort_unstable();
+            keys.truncate(limit);
+            Ok(keys)
+        }
+
+        pub async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+            match self.store(bucket)?.delete(&Path::from(key)).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(e) => Err(anyhow::anyhow!("Failed to delete object {}: {}", key, e)),
+            }
+        }
+
+        pub async fn copy(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<()> {
+            self.store(bucket)?
+                .copy(&Path::from(src_key), &Path::from(dst_key))
+                .await
+                .with_context(|| format!("Failed to copy object {} to {}", src_key, dst_key))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_backend_from_env() {
+        let backend = |value: Option<&str>| StorageBackend::from_vars(|_| value.map(str::to_string));
+        assert_eq!(backend(Some("gcs")).unwrap(), StorageBackend::Gcs);
+        assert!(backend(Some("ftp")).is_err());
+        assert_eq!(backend(None).unwrap(), StorageBackend::S3);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_lists_by_string_prefix() {
+        let store = BlobStore::new(StorageBackend::Memory).unwrap();
+        for key in ["indexes/a/config.json", "indexes/ab/config.json", "indexes/b/config.json"] {
+            store.put("vectors", key, Bytes::from_static(b"{}")).await.unwrap();
+        }
+        let mut keys = store.list("vectors", "indexes/a").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["indexes/a/config.json", "indexes/ab/config.json"]);
+        assert!(store.list("other", "indexes/").await.unwrap().is_empty());
+        let page = store.list_after("vectors", "indexes/", Some("indexes/a/config.json"), 1).await.unwrap();
+        assert_eq!(page, vec!["indexes/ab/config.json"]);
+
+        store.copy("vectors", "indexes/a/config.json", "indexes/c/config.json").await.unwrap();
+        store.delete("vectors", "indexes/a/config.json").await.unwrap();
+        assert!(store.get("vectors", "indexes/a/config.json").await.is_err());
+        assert_eq!(store.get("vectors", "indexes/c/config.json").await.unwrap(), Bytes::from_static(b"{}"));
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Directory for scratch files (slices on their way to storage, spilled
+/// records, shard builds, uncached shard downloads): `VEC_TMP_DIR`, or the
+/// system temp dir. Names are unique per call, so concurrent operations, and
+/// replicas sharing a volume, never write over each other's files.
+pub fn dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::var("VEC_TMP_DIR")
+            .ok()
+            .filter(|dir| !dir.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::warn!(dir = %dir.display(), error = %e, "Failed to create temp dir");
+        }
+        dir
+    })
+}
+
+/// A scratch file path, removed (if anything was written to it) when dropped.
+pub struct TempFile {
+    path: String,
+}
+
+impl TempFile {
+    /// `<prefix>-<uuid><suffix>` in [`dir`]; the file itself isn't created.
+    pub fn new(prefix: &str, suffix: &str) -> Self {
+        let name = format!("{}-{}{}", prefix, uuid::Uuid::new_v4(), suffix);
+        TempFile { path: dir().join(name).to_string_lossy().into_owned() }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Whether a file in [`dir`] is named like a [`TempFile`], so sweeping up
+/// ones left by crashed processes leaves anything else in a shared temp dir.
+pub fn is_temp_name(name: &str) -> bool {
+    let Some((prefix, rest)) = name.split_once('-') else { return false };
+    !prefix.is_empty() && rest.get(..36).is_some_and(|id| uuid::Uuid::parse_str(id).is_ok())
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_files_are_unique_and_removed() {
+        let (a, b) = (TempFile::new("shard", ".faiss"), TempFile::new("shard", ".faiss"));
+        assert_ne!(a.path(), b.path());
+        assert!(a.path().ends_with(".faiss"));
+        std::fs::write(a.path(), b"x").unwrap();
+        let path = a.path().to_string();
+        drop(a);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_temp_names() {
+        let file = TempFile::new("shard", ".faiss");
+        let name = std::path::Path::new(file.path()).file_name().unwrap().to_str().unwrap();
+        assert!(is_temp_name(name));
+        assert!(!is_temp_name("shard.faiss"));
+        assert!(!is_temp_name("systemd-private-abc"));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Soft-deleted indexes are moved under `deleted/<index>/<ts>/`, keeping their
+/// original keys below that prefix, until the retention window expires. Records
+/// move within their vector bucket, everything else within the service's.
+const TRASH_PREFIX: &str = "deleted/";
+const TOMBSTONE: &str = "tombstone.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub index_name: String,
+    pub deleted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub object_count: usize,
+    /// Vector bucket the index's records were trashed in; `None` for
+    /// tombstones written before records moved within their own bucket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_bucket: Option<String>,
+    /// The index's usage ledger entry when it was deleted, put back on restore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<crate::usage::IndexUsage>,
+}
+
+impl Tombstone {
+    fn prefix(&self) -> String {
+        trash_prefix(&self.index_name, self.deleted_at.timestamp())
+    }
+
+    /// Buckets holding the trashed copy.
+    fn buckets<'a>(&'a self, s3: &'a S3Client) -> Vec<&'a str> {
+        let mut buckets = vec![s3.bucket()];
+        buckets.extend(self.vector_bucket.as_deref().filter(|bucket| *bucket != s3.bucket()));
+        buckets
+    }
+}
+
+fn trash_prefix(index: &str, ts: i64) -> String {
+    format!("{}{}/{}/", TRASH_PREFIX, index, ts)
+}
+
+/// Retention window from `VEC_SOFT_DELETE_RETENTION_HOURS`. Soft delete is
+/// opt-in: unset or `0`, DeleteIndex removes objects immediately.
+pub fn retention_from_env() -> Option<Duration> {
+    let hours = std::env::var("VEC_SOFT_DELETE_RETENTION_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(0);
+    (hours > 0).then(|| Duration::hours(hours))
+}
+
+/// Prefixes of an index's objects in the service's bucket: config, manifest
+/// and shards, and slices still waiting for the indexer or quarantined by it.
+pub fn index_prefixes(index: &str) -> Vec<String> {
+    vec![
+        format!("indexes/{}/", index),
+        format!("staged/{}/", index),
+        format!("quarantine/{}/", index),
+    ]
+}
+
+/// Prefixes of an index's objects in its vector bucket: per-vector records
+/// and their history.
+pub fn record_prefixes(index: &str) -> Vec<String> {
+    vec![format!("{}/vectors/", index), format!("{}/versions/", index)]
+}
+
+/// Every object of `index` as `(bucket, key)`, records in `vector_bucket`.
+async fn index_objects<'a>(s3: &'a S3Client, index: &str, vector_bucket: &'a str) -> Result<Vec<(&'a str, String)>> {
+    let mut objects = Vec::new();
+    let locations = [(s3.bucket(), index_prefixes(index)), (vector_bucket, record_prefixes(index))];
+    for (bucket, prefixes) in locations {
+        for prefix in prefixes {
+            objects.extend(s3.list_bucket_objects(bucket, &prefix).await?.into_iter().map(|key| (bucket, key)));
+        }
+    }
+    Ok(objects)
+}
+
+/// Move an index, with its records in `vector_bucket`, into the trash.
+/// Objects are copied first and the originals deleted only once every copy
+/// succeeded, so a failure leaves the index intact.
+pub async fn soft_delete(
+    s3: &S3Client,
+    index: &str,
+    vector_bucket: &str,
+    retention: Duration,
+    usage: Option<crate::usage::IndexUsage>,
+) -> Result<Tombstone> {
+    let objects = index_objects(s3, index, vector_bucket).await?;
+    let deleted_at = Utc::now();
+    let tombstone = Tombstone {
+        index_name: index.to_string(),
+        deleted_at,
+        expires_at: deleted_at + retention,
+        object_count: objects.len(),
+        vector_bucket: Some(vector_bucket.to_string()),
+        usage,
+    };
+    let prefix = tombstone.prefix();
+
+    for (bucket, key) in &objects {
+        s3.copy_bucket_object(bucket, key, &format!("{}{}", prefix, key)).await?;
+    }
+    s3.put_object(&format!("{}{}", prefix, TOMBSTONE), serde_json::to_vec(&tombstone)?.into())
+        .await?;
+    for (bucket, key) in &objects {
+        s3.delete_bucket_object(bucket, key).await?;
+    }
+
+    tracing::info!(
+        "Moved index {} ({} objects) to trash until {}",
+        index, objects.len(), tombstone.expires_at
+    );
+    Ok(tombstone)
+}
+
+/// Delete an index and its records in `vector_bucket` for good, its config
+/// last so an interrupted delete can be repeated. Returns the objects deleted.
+pub async fn delete_now(s3: &S3Client, index: &str, vector_bucket: &str) -> Result<usize> {
+    let config_key = format!("indexes/{}/config.json", index);
+    let objects = index_objects(s3, index, vector_bucket).await?;
+    for (bucket, key) in objects.iter().filter(|(_, key)| *key != config_key) {
+        s3.delete_bucket_object(bucket, key).await?;
+    }
+    s3.delete_object(&config_key).await?;
+    Ok(objects.len())
+}
+
+/// Soft-deleted copies of an index, newest first.
+pub async fn list_deleted(s3: &S3Client, index: &str) -> Result<Vec<Tombstone>> {
+    let mut tombstones = Vec::new();
+    for key in s3.list_objects(&format!("{}{}/", TRASH_PREFIX, index)).await? {
+        if key.ends_with(&format!("/{}", TOMBSTONE)) {
+            let data = s3.get_object(&key).await?;
+            tombstones.push(serde_json::from_slice::<Tombstone>(&data).context("Failed to parse tombstone")?);
+        }
+    }
+    tombstones.sort_by_key(|t| std::cmp::Reverse(t.deleted_at));
+    Ok(tombstones)
+}
+
+/// Restore a soft-deleted index, by default its most recent deletion.
+/// Fails if an index with the same name exists again.
+pub async fn restore(s3: &S3Client, index: &str, deleted_at: Option<i64>) -> Result<Tombstone> {
+    if s3.get_object(&format!("indexes/{}/config.json", index)).await.is_ok() {
+        return Err(anyhow::anyhow!("Index {} already exists; delete or rename it before restoring", index));
+    }
+    let tombstone = list_deleted(s3, index)
+        .await?
+        .into_iter()
+        .find(|t| deleted_at.is_none_or(|ts| t.deleted_at.timestamp() == ts))
+        .with_context(|| format!("No deleted copy of index {} found", index))?;
+
+    let prefix = tombstone.prefix();
+    let tombstone_key = format!("{}{}", prefix, TOMBSTONE);
+    for bucket in tombstone.buckets(s3) {
+        for key in s3.list_bucket_objects(bucket, &prefix).await? {
+            if key != tombstone_key {
+                s3.copy_bucket_object(bucket, &key, &key[prefix.len()..]).await?;
+            }
+        }
+    }
+    purge(s3, &tombstone).await?;
+
+    tracing::info!("Restored index {} deleted at {}", index, tombstone.deleted_at);
+    Ok(tombstone)
+}
+
+async fn purge(s3: &S3Client, tombstone: &Tombstone) -> Result<()> {
+    let prefix = tombstone.prefix();
+    let tombstone_key = format!("{}{}", prefix, TOMBSTONE);
+    for bucket in tombstone.buckets(s3) {
+        for key in s3.list_bucket_objects(bucket, &prefix).await? {
+            if key != tombstone_key {
+                s3.delete_bucket_object(bucket, &key).await?;
+            }
+        }
+    }
+    // Tombstone last, so an interrupted purge is retried on the next run.
+    s3.delete_object(&tombstone_key).await
+}
+
+/// Permanently remove trashed indexes whose retention window has passed.
+pub async fn purge_expired(s3: &S3Client) -> Result<usize> {
+    let now = Utc::now();
+    let mut purged = 0;
+    for key in s3.list_objects(TRASH_PREFIX).await? {
+        if !key.ends_with(&format!("/{}", TOMBSTONE)) {
+            continue;
+        }
+        let tombstone: Tombstone = match s3.get_object(&key).await.map(|d| serde_json::from_slice(&d)) {
+            Ok(Ok(tombstone)) => tombstone,
+            _ => {
+                tracing::warn!("Skipping unreadable tombstone {}", key);
+                continue;
+            }
+        };
+        if tombstone.expires_at <= now {
+            purge(s3, &tombstone).await?;
+            tracing::info!("Purged deleted index {} (deleted at {})", tombstone.index_name, tombstone.deleted_at);
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trash_layout_keeps_original_keys() {
+        let deleted_at = DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap();
+        let tombstone = Tombstone {
+            index_name: "docs".to_string(),
+            deleted_at,
+            expires_at: deleted_at + Duration::hours(72),
+            object_count: 0,
+            vector_bucket: None,
+            usage: None,
+        };
+        let prefix = tombstone.prefix();
+        assert_eq!(prefix, "deleted/docs/1700000000/");
+        let trashed = format!("{}{}", prefix, "indexes/docs/manifest.json");
+        assert_eq!(&trashed[prefix.len()..], "indexes/docs/manifest.json");
+        assert!(index_prefixes("docs").iter().chain(&record_prefixes("docs")).all(|p| p.ends_with('/')));
+    }
+
+    #[tokio::test]
+    async fn test_records_are_trashed_in_their_vector_bucket() {
+        let s3 = S3Client::in_memory("vectors");
+        let (config, shard) = ("indexes/docs/config.json", "indexes/docs/shards/s1/index.faiss");
+        let records = ["docs/vectors/a key.json", "docs/versions/a key/1.json"];
+        for key in [config, shard] {
+            s3.put_object(key, "x".into()).await.unwrap();
+        }
+        for key in records {
+            s3.put_bucket_object("mine", key, "x".into()).await.unwrap();
+        }
+
+        let tombstone = soft_delete(&s3, "docs", "mine", Duration::hours(1), None).await.unwrap();
+        assert_eq!(tombstone.object_count, 4);
+        assert!(s3.get_bucket_object("mine", records[0]).await.is_err());
+        assert!(s3.get_object(shard).await.is_err());
+        let trashed = format!("{}{}", tombstone.prefix(), records[0]);
+        assert!(s3.get_bucket_object("mine", &trashed).await.is_ok());
+
+        restore(&s3, "docs", None).await.unwrap();
+        for key in records {
+            assert!(s3.get_bucket_object("mine", key).await.is_ok(), "{}", key);
+        }
+        assert!(s3.get_object(shard).await.is_ok());
+        assert!(s3.list_bucket_objects("mine", TRASH_PREFIX).await.unwrap().is_empty());
+
+        soft_delete(&s3, "docs", "mine", Duration::zero(), None).await.unwrap();
+        assert_eq!(purge_expired(&s3).await.unwrap(), 1);
+        assert!(s3.list_bucket_objects("mine", "").await.unwrap().is_empty());
+        assert!(s3.list_objects("").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::minio::S3Client;
+use crate::model::CreateIndex;
+use serde::{Deserialize, Serialize};
+
+/// Matryoshka truncation of an index: embeddings from a model trained so
+/// their prefixes are embeddings too (MRL) arrive at `sourceDimension` and are
+/// stored cut to the index dimension, so a cheap low-dimensional index can
+/// serve a high-dimensional model. Queries are cut the same way.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Truncation {
+    pub source_dimension: u32,
+    /// Rescale cut embeddings to unit length; prefixes of normalized
+    /// embeddings aren't, and cosine scores assume they are.
+    #[serde(default = "default_normalize")]
+    pub normalize: bool,
+}
+
+fn default_normalize() -> bool {
+    true
+}
+
+/// Truncation settings and dimension of `index`, from its config.
+pub async fn load(s3: &S3Client, index: &str) -> Option<(usize, Truncation)> {
+    let data = s3.get_object(&format!("indexes/{}/config.json", index)).await.ok()?;
+    let config = CreateIndex::from_slice(&data).ok()?;
+    config.truncation.map(|truncation| (config.dim as usize, truncation))
+}
+
+impl Truncation {
+    pub fn validate(&self, dim: u32, max_dimension: u32) -> Result<(), String> {
+        if self.source_dimension <= dim || self.source_dimension > max_dimension {
+            return Err(format!(
+                "truncation.sourceDimension must be above the index dimension {} and at most {}, got {}",
+                dim, max_dimension, self.source_dimension
+            ));
+        }
+        Ok(())
+    }
+
+    /// `embedding` as the index stores it: cut to `dim` values from
+    /// `sourceDimension`, or taken as already cut when it has `dim`, then
+    /// normalized if configured. Any other length is an error, so stored
+    /// vectors and queries can't end up truncated differently.
+    pub fn apply(&self, dim: usize, mut embedding: Vec<f32>) -> Result<Vec<f32>, String> {
+        if embedding.len() != self.source_dimension as usize && embedding.len() != dim {
+            return Err(format!(
+                "Vector has {} dimensions; this index takes {}, which it cuts to {}, or {} already cut",
+                embedding.len(),
+                self.source_dimension,
+                dim,
+                dim
+            ));
+        }
+        embedding.truncate(dim);
+        if self.normalize {
+            let norm = crate::distance::dot(&embedding, &embedding).sqrt();
+            if norm > 0.0 {
+                embedding.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+        Ok(embedding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_and_query_vectors_truncate_alike() {
+        let truncation = Truncation { source_dimension: 4, normalize: true };
+        let full = truncation.apply(2, vec![3.0, 4.0, 9.0, 9.0]).unwrap();
+        assert_eq!(full, vec![0.6, 0.8]);
+        assert_eq!(truncation.apply(2, vec![3.0, 4.0]).unwrap(), full);
+        assert!(truncation.apply(2, vec![3.0, 4.0, 9.0]).is_err());
+
+        let raw = Truncation { source_dimension: 4, normalize: false };
+        assert_eq!(raw.apply(2, vec![3.0, 4.0, 9.0, 9.0]).unwrap(), vec![3.0, 4.0]);
+        assert!(raw.validate(4, 4096).is_err());
+        assert!(raw.validate(2, 4096).is_ok());
+    }
+}
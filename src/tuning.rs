@@ -0,0 +1,413 @@
+use crate::distance;
+use crate::faiss_utils::{
+    calculate_optimal_nlist, calculate_optimal_training_size, ivfpq_supports, reservoir_sample, SplitMix64,
+    DEFAULT_HNSW_M,
+};
+use crate::indexer::MAX_VECTORS_PER_SHARD;
+use serde::{Deserialize, Serialize};
+
+/// Rows the effective dimension is estimated from; the estimate costs the
+/// square of this in dot products.
+const PROFILE_SAMPLE: usize = 512;
+/// Indexes up to this size are searched exhaustively unless configured
+/// otherwise: scanning them costs less than any index structure saves.
+pub const DEFAULT_FLAT_THRESHOLD: usize = 10_000;
+/// Largest configurable flat threshold; past it exact search is too slow to
+/// be a sensible default for every query.
+pub const MAX_FLAT_THRESHOLD: usize = 1_000_000;
+/// Faiss wants this many training points per centroid, for IVF lists and PQ
+/// codebooks alike.
+const POINTS_PER_CENTROID: usize = 39;
+/// Fewest vectors an IVF-PQ shard is trained on: enough for 16 PQ centroids.
+/// Smaller shards are built flat instead.
+pub const MIN_IVFPQ_VECTORS: usize = POINTS_PER_CENTROID << 4;
+/// Bounds on PQ subquantizers; more than 64 rarely pays for its code size.
+const MIN_PQ_M: usize = 4;
+const MAX_PQ_M: usize = 64;
+/// Largest configurable nlist.
+const MAX_NLIST: u32 = 65_536;
+
+/// What a build is about to index.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DataProfile {
+    /// Vectors in this build.
+    pub vectors: usize,
+    /// Vectors the index holds once the build is added.
+    pub index_vectors: usize,
+    pub dimension: usize,
+    /// Participation ratio of the covariance eigenvalues of a sample: how
+    /// many dimensions the variance is actually spread over, between 1 and
+    /// `dimension` (and at most the sample size).
+    pub effective_dimension: f64,
+}
+
+impl DataProfile {
+    /// Profile the row-major `vectors` of `dim` values each.
+    pub fn measure(vectors: &[f32], dim: usize, index_vectors: usize, rng: &mut SplitMix64) -> Self {
+        let count = vectors.len() / dim.max(1);
+        let sample = reservoir_sample(count, PROFILE_SAMPLE, rng);
+        DataProfile {
+            vectors: count,
+            index_vectors: index_vectors.max(count),
+            dimension: dim,
+            effective_dimension: effective_dimension(vectors, dim, &sample),
+        }
+    }
+}
+
+/// Participation ratio `(Σλ)² / Σλ²` of the covariance of the `sample`
+/// rows, from their centered Gram matrix so no eigendecomposition is needed:
+/// `Σλ` is its trace and `Σλ²` the sum of its squared entries.
+fn effective_dimension(vectors: &[f32], dim: usize, sample: &[usize]) -> f64 {
+    if sample.len() < 2 {
+        return dim as f64;
+    }
+    let mut mean = vec![0.0f32; dim];
+    for &row in sample {
+        mean.iter_mut().zip(&vectors[row * dim..(row + 1) * dim]).for_each(|(m, v)| *m += v);
+    }
+    mean.iter_mut().for_each(|m| *m /= sample.len() as f32);
+    let centered: Vec<Vec<f32>> = sample
+        .iter()
+        .map(|&row| vectors[row * dim..(row + 1) * dim].iter().zip(&mean).map(|(v, m)| v - m).collect())
+        .collect();
+
+    let (mut trace, mut squares) = (0.0f64, 0.0f64);
+    for (a, x) in centered.iter().enumerate() {
+        let diagonal = distance::dot(x, x) as f64;
+        trace += diagonal;
+        squares += diagonal * diagonal;
+        for y in &centered[a + 1..] {
+            let entry = distance::dot(x, y) as f64;
+            squares += 2.0 * entry * entry;
+        }
+    }
+    if squares == 0.0 {
+        return 1.0;
+    }
+    (trace * trace / squares).clamp(1.0, dim as f64)
+}
+
+/// Faiss structure and parameters for a build.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum IndexParameters {
+    /// Exhaustive search over the full vectors.
+    Flat,
+    HnswFlat { m: usize },
+    Ivfpq { nlist: usize, m: usize, nbits: usize },
+}
+
+impl IndexParameters {
+    /// Name recorded on shards and read by `faiss_utils::search_index`.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            IndexParameters::Flat => "flat",
+            IndexParameters::HnswFlat { .. } => "hnsw_flat",
+            IndexParameters::Ivfpq { .. } => "ivfpq",
+        }
+    }
+
+    /// Rough peak memory, in bytes, of Faiss building a shard of `rows`
+    /// vectors of `dim` dimensions: its copy of the vectors (or their codes),
+    /// graph links, id maps and, for IVF-PQ, the training sample and trained
+    /// centroids. For sizing how many shards build at once, not exact.
+    pub fn build_memory(&self, rows: usize, dim: usize) -> usize {
+        // `IDMap2` keeps each id in a list and in a hash map.
+        let ids = rows * 40;
+        ids + match *self {
+            IndexParameters::Flat => rows * dim * 4,
+            // Level 0 links every vector to 2 * m others; the levels above add little.
+            IndexParameters::HnswFlat { m } => rows * (dim * 4 + 2 * m * 4),
+            IndexParameters::Ivfpq { nlist, m, nbits } => {
+                let training = calculate_optimal_training_size(rows, nlist).min(rows) * dim * 4;
+                let centroids = nlist * dim * 4 + ((dim * 4) << nbits);
+                rows * ((m * nbits).div_ceil(8) + 8) + training + centroids
+            }
+        }
+    }
+}
+
+/// Index sizes at which a build moves to the next structure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    /// Up to this many vectors shards are flat; 0 never builds them flat.
+    pub flat: usize,
+    /// Below this many vectors hybrid and automatic indexes use HNSW.
+    pub hnsw: usize,
+}
+
+/// The parameters picked for a build and why; kept in the manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Recommendation {
+    pub profile: DataProfile,
+    pub parameters: IndexParameters,
+    pub rationale: Vec<String>,
+}
+
+/// IVF-PQ parameters fixed at CreateIndex; builds tune the ones left unset.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IvfpqSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nlist: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub m: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbits: Option<u32>,
+}
+
+impl IvfpqSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == IvfpqSettings::default()
+    }
+
+    /// `indexed_dim` is what Faiss indexes: the reduced dimension if any.
+    pub fn validate(&self, indexed_dim: u32) -> Result<(), String> {
+        if self.nlist.is_some_and(|nlist| nlist == 0 || nlist > MAX_NLIST) {
+            return Err(format!("nlist must be between 1 and {}", MAX_NLIST));
+        }
+        if let Some(m) = self.m {
+            if m == 0 || !indexed_dim.is_multiple_of(m) {
+                return Err(format!("m must divide the indexed dimension {}, got {}", indexed_dim, m));
+            }
+        }
+        if self.nbits.is_some_and(|nbits| !(1..=16).contains(&nbits)) {
+            return Err("nbits must be between 1 and 16".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// IVF lists for a shard of `vectors`: about their square root, but no more
+/// than can each be trained on enough points.
+pub fn nlist_for(vectors: usize) -> usize {
+    calculate_optimal_nlist(vectors).min((vectors / POINTS_PER_CENTROID).max(1))
+}
+
+/// PQ subquantizers for `dim` dimensions whose variance spans about
+/// `effective` of them: one per two effective dimensions, rounded up to a
+/// divisor of `dim` as Faiss requires.
+pub fn pq_m(dim: usize, effective: f64) -> usize {
+    let target = ((effective / 2.0).ceil() as usize).clamp(MIN_PQ_M, MAX_PQ_M);
+    let divisors: Vec<usize> = (1..=MAX_PQ_M.min(dim)).filter(|d| dim.is_multiple_of(*d)).collect();
+    divisors.iter().copied().find(|&d| d >= target).unwrap_or_else(|| divisors.last().copied().unwrap_or(1))
+}
+
+/// PQ code bits: 8 when `training_points` can train 256 centroids per
+/// subquantizer, fewer otherwise.
+pub fn pq_nbits(training_points: usize) -> usize {
+    [8, 6, 4].into_iter().find(|&nbits| training_points >= POINTS_PER_CENTROID << nbits).unwrap_or(4)
+}
+
+impl Recommendation {
+    /// Replace tuned IVF-PQ parameters with those the index fixes.
+    pub fn pin(&mut self, settings: &IvfpqSettings) {
+        let Recommendation { parameters, rationale, .. } = self;
+        if let IndexParameters::Ivfpq { nlist, m, nbits } = parameters {
+            for (name, value, setting) in [("nlist", nlist, settings.nlist), ("m", m, settings.m), ("nbits", nbits, settings.nbits)] {
+                if let Some(setting) = setting {
+                    *value = setting as usize;
+                    rationale.push(format!("{}={} is configured for the index", name, setting));
+                }
+            }
+        }
+    }
+}
+
+/// IVF-PQ parameters an index config records before any data is seen: those
+/// of a full shard whose variance spans every dimension.
+pub fn defaults(dim: usize) -> (usize, usize, usize) {
+    let nlist = nlist_for(MAX_VECTORS_PER_SHARD);
+    let nbits = pq_nbits(calculate_optimal_training_size(MAX_VECTORS_PER_SHARD, nlist));
+    (nlist, pq_m(dim, dim as f64), nbits)
+}
+
+/// Pick the structure and parameters for a build of `profile` under
+/// `metric`. A configured `flat` or `hnsw_flat` is honoured; otherwise the
+/// index is flat up to the flat threshold, and past it a configured `ivfpq`
+/// or `hybrid` is honoured where the metric allows it. Unset (or `auto`), the
+/// size of the index decides: HNSW below the HNSW threshold, IVF-PQ beyond.
+/// IVF parameters are sized for the shards the build is split into.
+pub fn recommend(profile: &DataProfile, metric: &str, algorithm: Option<&str>, thresholds: Thresholds) -> Recommendation {
+    let mut rationale = Vec::new();
+    let configured = algorithm.map(str::to_lowercase).filter(|algorithm| algorithm != "auto");
+    let size = profile.index_vectors;
+    let hnsw_threshold = thresholds.hnsw;
+    let mut choice = match configured.as_deref() {
+        Some(algorithm @ ("flat" | "hnsw_flat")) => {
+            rationale.push(format!("{} is configured for the index", algorithm));
+            algorithm.to_string()
+        }
+        _ if size <= thresholds.flat => {
+            rationale.push(format!("{} vectors are within the flat threshold of {}", size, thresholds.flat));
+            "flat".to_string()
+        }
+        Some("ivfpq") => {
+            rationale.push("ivfpq is configured for the index".to_string());
+            "ivfpq".to_string()
+        }
+        Some("hybrid") if size < hnsw_threshold => {
+            rationale.push(format!("hybrid index of {} vectors, below its HNSW threshold of {}", size, hnsw_threshold));
+            "hnsw_flat".to_string()
+        }
+        Some("hybrid") => {
+            rationale.push(format!("hybrid index of {} vectors, at or above its HNSW threshold of {}", size, hnsw_threshold));
+            "ivfpq".to_string()
+        }
+        _ if size < hnsw_threshold => {
+            rationale.push(format!("{} vectors fit HNSW graphs in memory (below {})", size, hnsw_threshold));
+            "hnsw_flat".to_string()
+        }
+        _ => {
+            rationale.push(format!("{} vectors need IVF-PQ's compressed codes (HNSW threshold {})", size, hnsw_threshold));
+            "ivfpq".to_string()
+        }
+    };
+    if choice == "ivfpq" && !ivfpq_supports(metric) {
+        rationale.push(format!("the {} metric has no PQ distance tables, so HNSW is used instead", metric));
+        choice = "hnsw_flat".to_string();
+    }
+    let shard = profile.vectors.min(MAX_VECTORS_PER_SHARD);
+    if choice == "ivfpq" && shard < MIN_IVFPQ_VECTORS {
+        rationale.push(format!("{} vectors are too few to train IVF-PQ (at least {})", shard, MIN_IVFPQ_VECTORS));
+        choice = "flat".to_string();
+    }
+
+    let effective = profile.effective_dimension;
+    let parameters = match choice.as_str() {
+        "flat" => IndexParameters::Flat,
+        "hnsw_flat" => {
+            let m = if effective < 16.0 {
+                DEFAULT_HNSW_M / 2
+            } else if effective > 256.0 {
+                DEFAULT_HNSW_M * 3 / 2
+            } else {
+                DEFAULT_HNSW_M
+            };
+            rationale.push(format!("HNSW M={} for an effective dimension of {:.0}", m, effective));
+            IndexParameters::HnswFlat { m }
+        }
+        _ => {
+            let nlist = nlist_for(shard);
+            let m = pq_m(profile.dimension, effective);
+            let nbits = pq_nbits(calculate_optimal_training_size(shard, nlist));
+            rationale.push(format!("{} IVF lists for shards of up to {} vectors", nlist, shard));
+            rationale.push(format!(
+                "PQ{}x{}: variance spans about {:.0} of {} dimensions",
+                m, nbits, effective, profile.dimension
+            ));
+            IndexParameters::Ivfpq { nlist, m, nbits }
+        }
+    };
+    Recommendation { profile: profile.clone(), parameters, rationale }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(index_vectors: usize, effective_dimension: f64) -> DataProfile {
+        DataProfile { vectors: index_vectors.min(50_000), index_vectors, dimension: 768, effective_dimension }
+    }
+
+    #[test]
+    fn test_effective_dimension_of_a_plane() {
+        // Equal variance along two of eight axes.
+        let mut rng = SplitMix64::new(3);
+        let vectors: Vec<f32> = (0..400)
+            .flat_map(|_| {
+                let (a, b) = ((rng.next_u64() % 1000) as f32 / 500.0 - 1.0, (rng.next_u64() % 1000) as f32 / 500.0 - 1.0);
+                [a, 0.0, b, 0.0, 0.0, 0.0, 0.0, 0.0]
+            })
+            .collect();
+        let measured = DataProfile::measure(&vectors, 8, 0, &mut rng);
+        assert_eq!((measured.vectors, measured.index_vectors), (400, 400));
+        assert!((measured.effective_dimension - 2.0).abs() < 0.2, "{}", measured.effective_dimension);
+        assert_eq!(effective_dimension(&[1.0, 1.0], 2, &[0]), 2.0);
+    }
+
+    #[test]
+    fn test_build_memory() {
+        assert_eq!(IndexParameters::Flat.build_memory(1_000, 4), 1_000 * (16 + 40));
+        let (rows, dim) = (MAX_VECTORS_PER_SHARD, 768);
+        let flat = IndexParameters::Flat.build_memory(rows, dim);
+        assert!(IndexParameters::HnswFlat { m: 32 }.build_memory(rows, dim) > flat);
+        let ivfpq = IndexParameters::Ivfpq { nlist: 256, m: 24, nbits: 8 }.build_memory(rows, dim);
+        assert!(ivfpq < flat, "{} >= {}", ivfpq, flat);
+    }
+
+    #[test]
+    fn test_recommendations_by_size_and_metric() {
+        let thresholds = Thresholds { flat: DEFAULT_FLAT_THRESHOLD, hnsw: 100_000 };
+        let recommend = |profile: &DataProfile, metric, algorithm| recommend(profile, metric, algorithm, thresholds);
+        assert_eq!(recommend(&profile(5_000, 300.0), "cosine", None).parameters, IndexParameters::Flat);
+        assert_eq!(recommend(&profile(50_000, 300.0), "cosine", None).parameters, IndexParameters::HnswFlat { m: 48 });
+
+        let large = recommend(&profile(1_000_000, 40.0), "cosine", Some("auto"));
+        assert_eq!(large.parameters, IndexParameters::Ivfpq { nlist: 256, m: 24, nbits: 8 });
+        assert!(large.rationale.iter().any(|line| line.contains("40 of 768")));
+
+        // Configured algorithms win, except IVF-PQ for metrics it can't index.
+        assert_eq!(recommend(&profile(5_000, 8.0), "cosine", Some("hnsw_flat")).parameters, IndexParameters::HnswFlat { m: 16 });
+        assert_eq!(recommend(&profile(1_000_000, 300.0), "manhattan", None).parameters.algorithm(), "hnsw_flat");
+        assert_eq!(recommend(&profile(50_000, 300.0), "cosine", Some("hybrid")).parameters.algorithm(), "hnsw_flat");
+    }
+
+    #[test]
+    fn test_small_indexes_are_flat_until_they_grow() {
+        let thresholds = Thresholds { flat: 20_000, hnsw: 0 };
+        let ivfpq = |index_vectors, vectors, thresholds| {
+            let profile = DataProfile { vectors, ..profile(index_vectors, 64.0) };
+            recommend(&profile, "cosine", Some("ivfpq"), thresholds).parameters
+        };
+        assert_eq!(ivfpq(15_000, 15_000, thresholds), IndexParameters::Flat);
+        assert_eq!(ivfpq(25_000, 15_000, thresholds).algorithm(), "ivfpq");
+        assert_eq!(ivfpq(15_000, 15_000, Thresholds { flat: 0, ..thresholds }).algorithm(), "ivfpq");
+
+        // Past the threshold, a build too small to train is still flat.
+        let small = recommend(
+            &DataProfile { vectors: 100, ..profile(25_000, 64.0) },
+            "cosine",
+            Some("ivfpq"),
+            thresholds,
+        );
+        assert_eq!(small.parameters, IndexParameters::Flat);
+        assert!(small.rationale.iter().any(|line| line.contains("too few")));
+
+        // A configured HNSW index is never flat.
+        let hnsw = recommend(&profile(100, 64.0), "cosine", Some("hnsw_flat"), thresholds);
+        assert_eq!(hnsw.parameters.algorithm(), "hnsw_flat");
+    }
+
+    #[test]
+    fn test_pq_parameters_fit_the_data() {
+        assert_eq!(pq_m(768, 768.0), 64);
+        assert_eq!(pq_m(100, 90.0), 50);
+        assert_eq!(pq_m(7, 1.0), 7);
+        assert_eq!(pq_nbits(10_000), 8);
+        assert_eq!(pq_nbits(3_000), 6);
+        assert_eq!(pq_nbits(100), 4);
+        assert_eq!(nlist_for(1_000), 25);
+        assert_eq!(nlist_for(50_000), calculate_optimal_nlist(50_000));
+        assert_eq!(defaults(1536), (256, 64, 8));
+    }
+
+    #[test]
+    fn test_configured_ivfpq_settings_override_tuning() {
+        let thresholds = Thresholds { flat: 0, hnsw: 0 };
+        let settings = IvfpqSettings { nlist: Some(100), m: Some(32), nbits: None };
+        assert!(settings.validate(768).is_ok());
+        assert!(settings.validate(100).unwrap_err().contains("divide"));
+        assert!(IvfpqSettings { nbits: Some(20), ..settings }.validate(768).is_err());
+
+        let mut ivfpq = recommend(&profile(1_000_000, 40.0), "cosine", None, thresholds);
+        ivfpq.pin(&settings);
+        assert_eq!(ivfpq.parameters, IndexParameters::Ivfpq { nlist: 100, m: 32, nbits: 8 });
+        assert!(ivfpq.rationale.iter().any(|line| line == "m=32 is configured for the index"));
+
+        // Settings only shape IVF-PQ builds.
+        let mut hnsw = recommend(&profile(1_000_000, 40.0), "cosine", Some("hnsw_flat"), thresholds);
+        hnsw.pin(&settings);
+        assert_eq!(hnsw.parameters.algorithm(), "hnsw_flat");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::minio::S3Client;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One ledger object per vector bucket, `usage/<bucket>.json`, holding what
+/// quotas are checked against. Updates are serialised within a replica; across
+/// replicas the last writer wins, so the counts are approximate under
+/// concurrent writes to the same bucket from several replicas.
+const USAGE_PREFIX: &str = "usage/";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexUsage {
+    pub vectors: u64,
+    /// Size of the vector-of-record documents written, a proxy for storage.
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketUsage {
+    pub indexes: BTreeMap<String, IndexUsage>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl BucketUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.indexes.values().map(|usage| usage.bytes).sum()
+    }
+}
+
+impl IndexUsage {
+    fn remove_vectors(&mut self, count: u64) {
+        // Deletes only report keys, so bytes are released at the average record size.
+        let freed = self.bytes.checked_div(self.vectors).unwrap_or(0) * count.min(self.vectors);
+        self.vectors = self.vectors.saturating_sub(count);
+        self.bytes = if self.vectors == 0 { 0 } else { self.bytes.saturating_sub(freed) };
+    }
+}
+
+fn ledger_key(bucket: &str) -> String {
+    format!("{}{}.json", USAGE_PREFIX, bucket)
+}
+
+fn ledger_lock() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: std::sync::OnceLock<tokio::sync::Mutex<()>> = std::sync::OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+/// Usage of `bucket`; empty if nothing was recorded yet.
+pub async fn load(s3: &S3Client, bucket: &str) -> Result<BucketUsage> {
+    match s3.get_object(&ledger_key(bucket)).await {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(_) => Ok(BucketUsage::default()),
+    }
+}
+
+async fn update(s3: &S3Client, bucket: &str, apply: impl FnOnce(&mut BucketUsage)) -> Result<()> {
+    let _guard = ledger_lock().lock().await;
+    let mut usage = load(s3, bucket).await?;
+    apply(&mut usage);
+    usage.updated_at = Some(Utc::now());
+    s3.put_object(&ledger_key(bucket), serde_json::to_vec(&usage)?.into()).await
+}
+
+/// Usage of one index. Indexes written before the ledger existed are seeded
+/// from a count of their vector-of-record objects.
+pub async fn index_usage(s3: &S3Client, bucket: &str, index: &str) -> Result<IndexUsage> {
+    if let Some(usage) = load(s3, bucket).await?.indexes.remove(index) {
+        return Ok(usage);
+    }
+    let vectors = s3.list_bucket_objects(bucket, &format!("{}/vectors/", index)).await.map(|keys| keys.len() as u64).unwrap_or(0);
+    Ok(IndexUsage { vectors, bytes: 0 })
+}
+
+pub async fn add_vectors(s3: &S3Client, bucket: &str, index: &str, base: IndexUsage, vectors: u64, bytes: u64) -> Result<()> {
+    update(s3, bucket, |usage| {
+        let entry = usage.indexes.entry(index.to_string()).or_insert(base);
+        entry.vectors += vectors;
+        entry.bytes += bytes;
+    })
+    .await
+}
+
+pub async fn remove_vectors(s3: &S3Client, bucket: &str, index: &str, count: u64) -> Result<()> {
+    update(s3, bucket, |usage| {
+        if let Some(entry) = usage.indexes.get_mut(index) {
+            entry.remove_vectors(count);
+        }
+    })
+    .await
+}
+
+pub async fn remove_index(s3: &S3Client, bucket: &str, index: &str) -> Result<()> {
+    update(s3, bucket, |usage| {
+        usage.indexes.remove(index);
+    })
+    .await
+}
+
+/// Put back the usage of a restored index.
+pub async fn restore_index(s3: &S3Client, bucket: &str, index: &str, restored: IndexUsage) -> Result<()> {
+    update(s3, bucket, |usage| {
+        usage.indexes.insert(index.to_string(), restored);
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_vectors_releases_average_bytes() {
+        let mut usage = IndexUsage { vectors: 4, bytes: 400 };
+        usage.remove_vectors(1);
+        assert_eq!(usage, IndexUsage { vectors: 3, bytes: 300 });
+        // Deleting keys that were never counted cannot go below zero.
+        usage.remove_vectors(10);
+        assert_eq!(usage, IndexUsage { vectors: 0, bytes: 0 });
+    }
+}
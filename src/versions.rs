@@ -0,0 +1,109 @@
+use crate::minio::S3Client;
+use crate::model::CreateIndex;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Versioned mode of an index: every write of a key also keeps a copy of the
+/// vector-of-record document, `<index>/versions/<key>/<version>.json` in the
+/// vector bucket, with the newest `max_versions` per key kept. History
+/// outlives DeleteVectors and goes with the index.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Versioning {
+    #[serde(default = "default_max_versions")]
+    pub max_versions: usize,
+}
+
+fn default_max_versions() -> usize {
+    10
+}
+
+pub fn history_prefix(index: &str, id: &str) -> String {
+    format!("{}/versions/{}/", index, id)
+}
+
+/// Versions are zero-padded so listing order is version order.
+pub fn version_object_key(index: &str, id: &str, version: i64) -> String {
+    format!("{}{:020}.json", history_prefix(index, id), version)
+}
+
+fn version_of(object: &str) -> Option<i64> {
+    object.rsplit('/').next()?.strip_suffix(".json")?.parse().ok()
+}
+
+/// Versioning settings of `index`, from its config.
+pub async fn load(s3: &S3Client, index: &str) -> Option<Versioning> {
+    let data = s3.get_object(&format!("indexes/{}/config.json", index)).await.ok()?;
+    CreateIndex::from_slice(&data).ok()?.versioning
+}
+
+/// Stored versions of `id`, oldest first.
+pub async fn list(s3: &S3Client, bucket: &str, index: &str, id: &str) -> Result<Vec<i64>> {
+    let mut versions: Vec<i64> = s3
+        .list_bucket_objects(bucket, &history_prefix(index, id))
+        .await?
+        .iter()
+        .filter_map(|object| version_of(object))
+        .collect();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+/// Document of `id` as written at `version`, if still kept.
+pub async fn get(s3: &S3Client, bucket: &str, index: &str, id: &str, version: i64) -> Option<Value> {
+    let data = s3.get_bucket_object(bucket, &version_object_key(index, id, version)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+impl Versioning {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_versions == 0 {
+            return Err("versioning.maxVersions must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Keep `records` (see [`crate::ingest::record_document`]) as new versions,
+    /// then compact each key's history to the newest `max_versions`.
+    pub async fn record(&self, s3: &S3Client, bucket: &str, index: &str, records: &[Value]) -> Result<()> {
+        for record in records {
+            let (Some(id), Some(version)) = (
+                record.get("key").and_then(|k| k.as_str()),
+                record.get("version").and_then(|v| v.as_i64()),
+            ) else {
+                continue;
+            };
+            s3.put_bucket_object(bucket, &version_object_key(index, id, version), serde_json::to_vec(record)?.into())
+                .await?;
+            self.compact(s3, bucket, index, id).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete versions of `id` beyond the newest `max_versions`, returning how many.
+    pub async fn compact(&self, s3: &S3Client, bucket: &str, index: &str, id: &str) -> Result<usize> {
+        let versions = list(s3, bucket, index, id).await?;
+        let excess = versions.len().saturating_sub(self.max_versions);
+        for version in &versions[..excess] {
+            s3.delete_bucket_object(bucket, &version_object_key(index, id, *version)).await?;
+        }
+        Ok(excess)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_keys_sort_by_version() {
+        let older = version_object_key("docs", "a", 999);
+        let newer = version_object_key("docs", "a", 1_736_942_400_000_000);
+        assert!(older < newer);
+        assert_eq!(version_of(&newer), Some(1_736_942_400_000_000));
+        assert_eq!(version_of("docs/versions/a/garbage.json"), None);
+        let parsed: Versioning = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(parsed.max_versions, 10);
+    }
+}
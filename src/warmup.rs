@@ -0,0 +1,153 @@
+use crate::minio::S3Client;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Where the shard usage log is persisted, shared by all API replicas.
+const USAGE_LOG_KEY: &str = "system/shard-usage.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShardUsage {
+    pub index: String,
+    pub shard_id: String,
+    pub last_used: DateTime<Utc>,
+}
+
+/// In-memory record of which shards queries touched, flushed periodically to
+/// object storage so the next deploy knows what to pre-load.
+#[derive(Default)]
+pub struct UsageLog {
+    entries: Mutex<HashMap<(String, String), DateTime<Utc>>>,
+}
+
+static USAGE_LOG: std::sync::OnceLock<UsageLog> = std::sync::OnceLock::new();
+
+pub fn usage_log() -> &'static UsageLog {
+    USAGE_LOG.get_or_init(UsageLog::default)
+}
+
+impl UsageLog {
+    pub fn record(&self, index: &str, shard_id: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((index.to_string(), shard_id.to_string()), Utc::now());
+    }
+
+    /// Merge local usage into the stored log, keeping the newest timestamp per shard
+    /// and at most `keep` entries, so concurrent replicas don't erase each other.
+    pub async fn flush(&self, s3: &S3Client, keep: usize) -> Result<()> {
+        let local: Vec<ShardUsage> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((index, shard_id), last_used)| ShardUsage {
+                index: index.clone(),
+                shard_id: shard_id.clone(),
+                last_used: *last_used,
+            })
+            .collect();
+        if local.is_empty() {
+            return Ok(());
+        }
+        let merged = merge_usage(load_usage(s3).await?, local, keep);
+        s3.put_object(USAGE_LOG_KEY, serde_json::to_vec(&merged)?.into()).await
+    }
+}
+
+async fn load_usage(s3: &S3Client) -> Result<Vec<ShardUsage>> {
+    match s3.get_object(USAGE_LOG_KEY).await {
+        Ok(data) => serde_json::from_slice(&data).context("Failed to parse shard usage log"),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Most recently used first, one entry per shard.
+fn merge_usage(stored: Vec<ShardUsage>, local: Vec<ShardUsage>, keep: usize) -> Vec<ShardUsage> {
+    let mut newest: HashMap<(String, String), DateTime<Utc>> = HashMap::new();
+    for usage in stored.into_iter().chain(local) {
+        let entry = newest
+            .entry((usage.index, usage.shard_id))
+            .or_insert(usage.last_used);
+        *entry = (*entry).max(usage.last_used);
+    }
+    let mut merged: Vec<ShardUsage> = newest
+        .into_iter()
+        .map(|((index, shard_id), last_used)| ShardUsage { index, shard_id, last_used })
+        .collect();
+    merged.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| a.shard_id.cmp(&b.shard_id)));
+    merged.truncate(keep);
+    merged
+}
+
+/// Pre-download the `limit` most recently used shards into the shard cache.
+/// Shards no longer referenced by their index manifest are skipped.
+pub async fn warm_up(s3: &S3Client, limit: usize) -> Result<usize> {
+    if crate::cache::shard_cache().is_none() {
+        tracing::info!("Skipping shard warm-up: VEC_CACHE_DIR is not set");
+        return Ok(0);
+    }
+    let usage = load_usage(s3).await?;
+    let mut warmed = 0;
+    for entry in usage.iter().take(limit) {
+        match crate::query::warm_shard(s3, &entry.index, &entry.shard_id).await {
+            Ok(true) => warmed += 1,
+            Ok(false) => tracing::debug!("Shard {}/{} is gone, not warming", entry.index, entry.shard_id),
+            Err(e) => tracing::warn!("Failed to warm shard {}/{}: {}", entry.index, entry.shard_id, e),
+        }
+    }
+    tracing::info!("Warmed {} of {} recently used shards", warmed, usage.len().min(limit));
+    Ok(warmed)
+}
+
+/// Run warm-up in the background and keep flushing the usage log.
+/// Configured via `VEC_WARMUP_SHARDS` (default 16, 0 disables warm-up) and
+/// `VEC_USAGE_FLUSH_SECS` (default 60).
+pub fn spawn(s3: S3Client) {
+    let env = |name: &str, default: u64| {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let limit = env("VEC_WARMUP_SHARDS", 16) as usize;
+    let flush_secs = env("VEC_USAGE_FLUSH_SECS", 60).max(1);
+    // Keep more history than we warm so shards can move up the ranking.
+    let keep = (limit * 4).max(64);
+
+    tokio::spawn(async move {
+        if limit > 0 {
+            if let Err(e) = warm_up(&s3, limit).await {
+                tracing::warn!("Shard warm-up failed: {}", e);
+            }
+        }
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(flush_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = usage_log().flush(&s3, keep).await {
+                tracing::warn!("Failed to flush shard usage log: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_newest_per_shard() {
+        let at = |secs| DateTime::<Utc>::from_timestamp(secs, 0).unwrap();
+        let usage = |shard: &str, secs| ShardUsage {
+            index: "idx".to_string(),
+            shard_id: shard.to_string(),
+            last_used: at(secs),
+        };
+        let merged = merge_usage(
+            vec![usage("a", 10), usage("b", 30)],
+            vec![usage("a", 40), usage("c", 20)],
+            2,
+        );
+        assert_eq!(merged, vec![usage("a", 40), usage("b", 30)]);
+    }
+}